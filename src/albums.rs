@@ -0,0 +1,109 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::db::write_transaction;
+use crate::sidecar_scan::sidecar_path_condition;
+
+/// A named album grouping indexed files independent of their tags
+#[derive(Debug, serde::Serialize)]
+pub struct Album {
+    pub id: i64,
+    pub name: String,
+    pub file_count: i64,
+}
+
+/// Creates an album and returns its id. Fails with a constraint violation when the name is taken.
+pub fn create_album(conn: &Connection, name: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO album (name, created_time) VALUES (?1, CAST(strftime('%s', 'now') AS INTEGER))",
+        params![name],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Returns the name of an album, None when there is no album with this id.
+pub fn album_name(conn: &Connection, album_id: i64) -> Result<Option<String>> {
+    conn.query_row("SELECT name FROM album WHERE id = ?1", params![album_id], |row| row.get(0))
+        .optional()
+}
+
+/// Lists all albums by name with their number of files.
+pub fn list_albums(conn: &Connection) -> Result<Vec<Album>> {
+    let mut stmt = conn.prepare(
+        "SELECT album.id, album.name, \
+         (SELECT COUNT(*) FROM album_file WHERE album_file.album_id = album.id) \
+         FROM album ORDER BY album.name COLLATE NOCASE, album.id",
+    )?;
+    let albums = stmt
+        .query_map([], |row| Ok(Album { id: row.get(0)?, name: row.get(1)?, file_count: row.get(2)? }))?
+        .collect();
+    albums
+}
+
+// Function to find the indexed file of an image path (or its sidecar path). An image with
+// several sidecars is represented by the first one indexed.
+fn file_id_for_image(conn: &Connection, image_path: &str) -> Result<Option<i64>> {
+    conn.query_row(
+        &format!("SELECT file.id FROM file WHERE {} ORDER BY file.id LIMIT 1", sidecar_path_condition(1)),
+        params![image_path],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Adds images to an album. Returns the number of files newly added and the paths that are not
+/// in the index; images already in the album are skipped.
+pub fn add_to_album(conn: &Connection, album_id: i64, image_paths: &[String]) -> Result<(usize, Vec<String>)> {
+    let tx = write_transaction(conn)?;
+    let mut added = 0;
+    let mut unknown = Vec::new();
+    for image_path in image_paths {
+        match file_id_for_image(&tx, image_path)? {
+            Some(file_id) => {
+                added += tx.execute(
+                    "INSERT OR IGNORE INTO album_file (album_id, file_id, added_time) \
+                     VALUES (?1, ?2, CAST(strftime('%s', 'now') AS INTEGER))",
+                    params![album_id, file_id],
+                )?;
+            }
+            None => unknown.push(image_path.clone()),
+        }
+    }
+    tx.commit()?;
+    Ok((added, unknown))
+}
+
+/// Removes images from an album. Returns the number of files removed.
+pub fn remove_from_album(conn: &Connection, album_id: i64, image_paths: &[String]) -> Result<usize> {
+    let tx = write_transaction(conn)?;
+    let mut removed = 0;
+    for image_path in image_paths {
+        removed += tx.execute(
+            &format!(
+                "DELETE FROM album_file WHERE album_id = ?2 AND file_id IN (SELECT file.id FROM file WHERE {})",
+                sidecar_path_condition(1)
+            ),
+            params![image_path, album_id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(removed)
+}
+
+/// Returns the image paths (as resolved by the last scan, else the sidecar paths) and
+/// source_present flags of an album's files in the order they were added. Files hidden by the exclusion filters (`--exclude-tag`/`--exclude-path-glob`) are left out.
+pub fn album_files(conn: &Connection, album_id: i64, exclude_tags: &[String], exclude_globs: &[String]) -> Result<Vec<(String, bool)>> {
+    let mut where_clause = String::from("WHERE album_file.album_id = CAST(?1 AS INTEGER)");
+    let mut parameters = vec![album_id.to_string()];
+    crate::routes::append_exclusion_filters(exclude_tags, exclude_globs, &mut where_clause, &mut parameters);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(file.image_path, file.path), file.source_present FROM album_file \
+         JOIN file ON album_file.file_id = file.id \
+         {} \
+         ORDER BY album_file.added_time, album_file.rowid",
+        where_clause
+    ))?;
+    let files = stmt
+        .query_map(rusqlite::params_from_iter(parameters.iter()), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect();
+    files
+}
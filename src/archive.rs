@@ -0,0 +1,162 @@
+// Minimal uncompressed tar (ustar) writer for exporting an image together with its sidecars
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+const BLOCK_SIZE: usize = 512;
+const NAME_FIELD_LEN: usize = 100;
+
+/// A file to put into an archive, read from `contents` while the archive is written
+pub struct ArchiveEntry<R> {
+    /// File name inside the archive
+    pub name: String,
+    /// Number of bytes archived from `contents`
+    pub size: u64,
+    /// Modification time as seconds since the Unix epoch
+    pub mtime: u64,
+    pub contents: R,
+}
+
+impl ArchiveEntry<File> {
+    /// Opens a file to be archived under its file name, with its current size and mtime
+    pub fn open(path: &Path) -> io::Result<ArchiveEntry<File>> {
+        let contents = File::open(path)?;
+        let metadata = contents.metadata()?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(ArchiveEntry { name, size: metadata.len(), mtime, contents })
+    }
+}
+
+/// Size of the tar archive of the entries, known before any of them is read
+pub fn tar_size<R>(entries: &[ArchiveEntry<R>]) -> u64 {
+    let data: u64 = entries
+        .iter()
+        .map(|entry| entry_headers(&entry.name, entry.size, entry.mtime).len() as u64 + padded_len(entry.size))
+        .sum();
+    data + 2 * BLOCK_SIZE as u64
+}
+
+/// Writes a tar archive of the entries in chunks of `chunk_size` bytes (the last one shorter),
+/// handing each to `write_chunk`, so no file is ever held in memory whole. Stops early when
+/// `write_chunk` returns false, e.g. because the client went away. Names longer than the ustar
+/// name field are stored in a PAX extended header, which current tar implementations read
+/// transparently. Fails when an entry ends before its size.
+pub fn write_tar<R: Read>(entries: Vec<ArchiveEntry<R>>, chunk_size: usize, write_chunk: impl FnMut(Vec<u8>) -> bool) -> io::Result<()> {
+    let mut chunks = Chunks { buffer: Vec::with_capacity(chunk_size), chunk_size, write_chunk, open: true };
+    let mut read_buffer = vec![0u8; chunk_size];
+    for mut entry in entries {
+        chunks.push(&entry_headers(&entry.name, entry.size, entry.mtime));
+        let mut remaining = entry.size;
+        while remaining > 0 && chunks.open {
+            let len = remaining.min(read_buffer.len() as u64) as usize;
+            entry.contents.read_exact(&mut read_buffer[..len])?;
+            chunks.push(&read_buffer[..len]);
+            remaining -= len as u64;
+        }
+        chunks.push(&[0; BLOCK_SIZE][..(padded_len(entry.size) - entry.size) as usize]);
+    }
+    // End of archive marker
+    chunks.push(&[0; 2 * BLOCK_SIZE]);
+    chunks.flush();
+    Ok(())
+}
+
+// Collects written bytes into chunks of a fixed size for write_tar
+struct Chunks<F> {
+    buffer: Vec<u8>,
+    chunk_size: usize,
+    write_chunk: F,
+    // Cleared once write_chunk refuses a chunk; later data is dropped
+    open: bool,
+}
+
+impl<F: FnMut(Vec<u8>) -> bool> Chunks<F> {
+    fn push(&mut self, mut data: &[u8]) {
+        while self.open && !data.is_empty() {
+            let len = (self.chunk_size - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..len]);
+            data = &data[len..];
+            if self.buffer.len() == self.chunk_size {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.open && !self.buffer.is_empty() {
+            let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.chunk_size));
+            self.open = (self.write_chunk)(chunk);
+        }
+    }
+}
+
+// The header blocks of an entry: its ustar header, preceded by a PAX header for long names
+fn entry_headers(name: &str, size: u64, mtime: u64) -> Vec<u8> {
+    let mut headers = Vec::new();
+    if name.len() > NAME_FIELD_LEN {
+        let record = pax_record("path", name);
+        headers.extend_from_slice(&header(&truncated_name(name), record.len() as u64, mtime, b'x'));
+        headers.extend_from_slice(&record);
+        headers.resize(padded_len(headers.len() as u64) as usize, 0);
+    }
+    headers.extend_from_slice(&header(&truncated_name(name), size, mtime, b'0'));
+    headers
+}
+
+// Length of data padded to whole blocks
+fn padded_len(len: u64) -> u64 {
+    len.div_ceil(BLOCK_SIZE as u64) * BLOCK_SIZE as u64
+}
+
+// Cuts a name down to the ustar name field on a character boundary
+fn truncated_name(name: &str) -> String {
+    let mut end = name.len().min(NAME_FIELD_LEN);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name[..end].to_string()
+}
+
+// A PAX record is "<length> <key>=<value>\n", where the length counts its own digits
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let body_len = key.len() + value.len() + 3;
+    let mut length = body_len + 1;
+    while (body_len + length.to_string().len()) != length {
+        length = body_len + length.to_string().len();
+    }
+    format!("{} {}={}\n", length, key, value).into_bytes()
+}
+
+fn header(name: &str, size: u64, mtime: u64, type_flag: u8) -> [u8; BLOCK_SIZE] {
+    let mut header = [0u8; BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], size);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = type_flag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with its own field filled with spaces
+    header[148..156].fill(b' ');
+    let checksum: u64 = header.iter().map(|&byte| u64::from(byte)).sum();
+    write_octal(&mut header[148..155], checksum);
+    header
+}
+
+// Writes a zero-padded octal number followed by a NUL terminator, filling the field
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    let digits = &digits.as_bytes()[digits.len() - (field.len() - 1)..];
+    field[..digits.len()].copy_from_slice(digits);
+    field[digits.len()] = 0;
+}
@@ -3,23 +3,156 @@ use std::thread;
 use std::time::Duration;
 use rusqlite::Connection;
 use crate::routes::USER_REQUEST_ACTIVE;
-use crate::cli::get_cli_args;
+use crate::cli::{get_cli_args, PreviewStrategy};
 use std::sync::atomic::{AtomicBool};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
 use once_cell::sync::Lazy;
 
+// Reset the progress gauges of a worker at the start of a pass over `total` files
+fn reset_worker_progress(worker: &str, total: usize) {
+    crate::metrics::METRICS.worker_total.with_label_values(&[worker]).set(total as i64);
+    crate::metrics::METRICS.worker_processed.with_label_values(&[worker]).set(0);
+}
+
 // Add a global flag to indicate thumbnail worker is exhausted
 pub static THUMBNAIL_WORKER_EXHAUSTED: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(false)));
 
-pub fn start_background_thumbnail_worker() {
+// Image paths the preview worker handles before continuing its sequential scan, most urgent first
+static PREVIEW_PRIORITY_QUEUE: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Moves image paths to the front of the preview worker's queue, keeping their order, e.g. the
+/// neighbours of the image open in the lightbox. Paths already queued are moved rather than
+/// duplicated; beyond --preview-priority-queue entries the least recently queued are dropped.
+/// Returns the number of paths queued, 0 when prioritizing is disabled.
+pub fn prioritize_previews(image_paths: &[String]) -> usize {
+    let capacity = get_cli_args().preview_priority_queue;
+    let mut queue = PREVIEW_PRIORITY_QUEUE.lock().unwrap();
+    for image_path in image_paths.iter().rev() {
+        queue.retain(|queued| queued != image_path);
+        queue.push_front(image_path.clone());
+    }
+    queue.truncate(capacity);
+    image_paths.len().min(capacity)
+}
+
+/// Takes the most urgent path off the preview priority queue
+pub fn next_priority_preview() -> Option<String> {
+    PREVIEW_PRIORITY_QUEUE.lock().unwrap().pop_front()
+}
+
+// Set while a thread outside of a worker pass generates the prioritized previews
+static PRIORITY_DRAIN_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Generates the prioritized previews on a thread of its own when no worker pass is underway to
+/// pick them up, e.g. after the pass started by the last rescan is done. The thread waits while
+/// user requests are served and exits once the queue is empty. Returns whether it was started.
+pub fn drain_priority_previews() -> bool {
+    if background_workers_running() || PRIORITY_DRAIN_RUNNING.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    let user_active = crate::routes::USER_REQUEST_ACTIVE.clone();
+    thread::spawn(move || loop {
+        while user_active.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(500));
+        }
+        if let Some(file_path) = next_priority_preview() {
+            log::debug!("Preview priority drain: handling {}", file_path);
+            generate_missing_preview(&file_path);
+            continue;
+        }
+        PRIORITY_DRAIN_RUNNING.store(false, Ordering::SeqCst);
+        // Paths queued after the queue was found empty are handled here unless another drain took over
+        if PREVIEW_PRIORITY_QUEUE.lock().unwrap().is_empty() || PRIORITY_DRAIN_RUNNING.swap(true, Ordering::SeqCst) {
+            break;
+        }
+    });
+    true
+}
+
+// Whether a thumbnail/preview pass is underway, and whether another one was asked for meanwhile
+struct WorkerState {
+    running: bool,
+    rerun: bool,
+}
+
+static WORKER_STATE: Mutex<WorkerState> = Mutex::new(WorkerState { running: false, rerun: false });
+
+/// Starts a pass of the background workers: thumbnails first, then previews. Both threads exit
+/// once the pass is complete. When a pass is already underway, another one is queued to run
+/// right after it, so files imported meanwhile are not missed. Returns false in that case.
+pub fn start_background_workers() -> bool {
+    let mut state = WORKER_STATE.lock().unwrap();
+    if state.running {
+        log::debug!("Background workers busy, queueing another pass");
+        state.rerun = true;
+        return false;
+    }
+    state.running = true;
+    drop(state);
+    start_background_thumbnail_worker();
+    true
+}
+
+/// Whether a background worker pass is underway or queued
+pub fn background_workers_running() -> bool {
+    WORKER_STATE.lock().unwrap().running
+}
+
+// Called by the last worker of a pass, including when it gives up on an error
+fn finish_background_workers() {
+    let mut state = WORKER_STATE.lock().unwrap();
+    if state.rerun {
+        state.rerun = false;
+        drop(state);
+        log::info!("Background workers starting queued pass");
+        start_background_thumbnail_worker();
+    } else {
+        state.running = false;
+        drop(state);
+        log::info!("Background workers idle until the next rescan");
+        // Paths prioritized after the preview worker's last look at the queue
+        if !PREVIEW_PRIORITY_QUEUE.lock().unwrap().is_empty() {
+            drain_priority_previews();
+        }
+    }
+}
+
+/// Starts the periodic cache integrity check of --cache-verify-interval-hours, if enabled. Each
+/// check decodes --cache-verify-sample percent of the cache files, pausing while user requests
+/// are active, and starts a worker pass to regenerate the corrupt files it removed.
+pub fn start_cache_verifier() {
+    let args = get_cli_args();
+    if args.cache_verify_interval_hours == 0 {
+        return;
+    }
+    let interval = Duration::from_secs(args.cache_verify_interval_hours.saturating_mul(3600));
+    let sample_percent = args.cache_verify_sample;
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        log::info!("Cache verifier checking {}% of the cache files", sample_percent);
+        match crate::processing::cache::verify_caches(sample_percent, &USER_REQUEST_ACTIVE) {
+            Some(summary) if summary.removed > 0 => {
+                log::warn!("Cache verifier removed {} corrupt cache files, regenerating", summary.removed);
+                start_background_workers();
+            }
+            Some(summary) => log::info!("Cache verifier found no corrupt files among {}", summary.checked),
+            None => log::info!("Cache verifier skipped, a check is already running"),
+        }
+    });
+}
+
+fn start_background_thumbnail_worker() {
     let user_active = USER_REQUEST_ACTIVE.clone();
     let exhausted_flag = THUMBNAIL_WORKER_EXHAUSTED.clone();
+    exhausted_flag.store(false, Ordering::SeqCst);
     thread::spawn(move || {
         let args = get_cli_args();
         let conn = match Connection::open(&args.db_path) {
             Ok(c) => c,
             Err(e) => {
                 log::error!("Background worker: failed to open DB: {}", e);
+                finish_background_workers();
                 return;
             }
         };
@@ -31,40 +164,116 @@ pub fn start_background_thumbnail_worker() {
                 thread::sleep(Duration::from_millis(500));
                 continue;
             }
-            // Query all file paths
-            let mut stmt = match conn.prepare("SELECT path FROM file") {
+            // Query all file paths along with their analyzed dominant color (if any) and whether
+            // their video properties have been probed
+            let mut stmt = match conn.prepare(&format!(
+                "SELECT id, path, dominant_color, \
+                 EXISTS(SELECT 1 FROM key_value WHERE key_value.file_id = file.id AND key = '{}'), \
+                 COALESCE(image_path, path) FROM file",
+                crate::processing::video::VIDEO_CODEC_KEY
+            )) {
                 Ok(s) => s,
                 Err(e) => {
                     log::error!("Background worker: failed to prepare statement: {}", e);
-                    break;
+                    finish_background_workers();
+                    return;
                 }
             };
-            let file_iter = stmt.query_map([], |row| row.get::<_, String>(0));
-            if let Ok(iter) = file_iter {
-                for file_path_res in iter {
+            // Read the whole list up front: an open read would block the writes made while
+            // generating, such as recording failures for /api/failures
+            let files = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, bool>(3)?,
+                        row.get::<_, String>(4)?,
+                    ))
+                })
+                .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>());
+            if let Ok(files) = files {
+                reset_worker_progress("thumbnail", files.len());
+                for (file_id, db_path, dominant_color, video_probed, image_path) in files {
                     if user_active.load(Ordering::SeqCst) {
                         interrupted = true;
                         break; // Pause if user becomes active
                     }
-                    if let Ok(file_path) = file_path_res {
-                        let file_path = file_path.strip_suffix(".xmp").unwrap_or(&file_path).to_string();
-                        let cache_key = crate::processing::cache::generate_cache_key(&file_path);
-                        if !crate::processing::cache::thumbnail_exists_in_cache(&cache_key) {
-                            log::info!("Background worker: generating thumbnail for {}", file_path);
-                            let result = crate::processing::image::generate_thumbnail(&file_path);
-                            if result.is_none() {
-                                log::error!("Failed to generate thumbnail for {}", file_path);
-                            } else {
-                                log::debug!("Successfully generated thumbnail for {}", file_path);
+                    // Generated thumbnails would not be cached; resume once there is room again
+                    if !crate::processing::cache::cache_has_free_space(&crate::processing::cache::get_cache_dir()) {
+                        interrupted = true;
+                        break;
+                    }
+                    crate::metrics::METRICS.worker_processed.with_label_values(&["thumbnail"]).inc();
+                    let file_path = crate::sidecar_scan::image_path_for_sidecar(&image_path).to_string();
+                    let cache_key = crate::processing::cache::thumbnail_cache_key(&file_path);
+                    // Sidecars that outlived their image: flag them instead of failing on every pass
+                    if !std::path::Path::new(&file_path).exists() {
+                        log::debug!("Background worker: image missing for sidecar {}", db_path);
+                        if let Err(e) = conn.execute(
+                            "UPDATE file SET source_present = 0 WHERE path = ?1 AND source_present = 1",
+                            rusqlite::params![db_path],
+                        ) {
+                            log::error!("Background worker: failed to flag missing image {}: {}", file_path, e);
+                        }
+                        continue;
+                    }
+                    let mut thumbnail = None;
+                    if !crate::processing::cache::thumbnail_exists_in_cache(&cache_key) {
+                        log::info!("Background worker: generating thumbnail for {}", file_path);
+                        thumbnail = crate::processing::image::generate_thumbnail(&file_path);
+                        if thumbnail.is_none() {
+                            log::error!("Failed to generate thumbnail for {}", file_path);
+                        } else {
+                            log::debug!("Successfully generated thumbnail for {}", file_path);
+                        }
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    // Higher densities for high-DPI screens (--thumbnail-scale-factors)
+                    for scale in crate::processing::cache::thumbnail_scale_factors().into_iter().filter(|&scale| scale > 1) {
+                        let scaled_key = crate::processing::cache::scaled_thumbnail_cache_key(&file_path, scale);
+                        if crate::processing::cache::thumbnail_exists_in_cache(&scaled_key) {
+                            continue;
+                        }
+                        log::info!("Background worker: generating {}x thumbnail for {}", scale, file_path);
+                        if crate::processing::image::generate_scaled_thumbnail(&file_path, scale, false).is_none() {
+                            log::error!("Failed to generate {}x thumbnail for {}", scale, file_path);
+                        }
+                        thread::sleep(Duration::from_millis(100));
+                    }
+
+                    // Analyze the dominant color once per file, reusing the cached thumbnail
+                    if dominant_color.is_none() {
+                        let thumbnail = thumbnail.or_else(|| crate::processing::cache::get_cached_thumbnail(&cache_key, &file_path));
+                        // AVIF thumbnails (--thumbnail-format avif) cannot be decoded here; the JPEG preview is analyzed once cached
+                        let thumbnail = thumbnail.and_then(|thumbnail| {
+                            if crate::processing::cache::thumbnail_content_type(&thumbnail) != "image/avif" {
+                                return Some(thumbnail);
+                            }
+                            let preview_key = crate::processing::cache::preview_cache_key(&file_path);
+                            crate::processing::cache::get_cached_preview(&preview_key, &file_path)
+                        });
+                        if let Some(color) = thumbnail.as_deref().and_then(crate::processing::color::dominant_color_from_base64) {
+                            log::trace!("Dominant color for {} is {}", file_path, color);
+                            if let Err(e) = conn.execute(
+                                "UPDATE file SET dominant_color = ?1 WHERE path = ?2",
+                                rusqlite::params![color, db_path],
+                            ) {
+                                log::error!("Background worker: failed to store dominant color for {}: {}", file_path, e);
                             }
-                            thread::sleep(Duration::from_millis(100));
                         }
                     }
+
+                    // Probe videos once for codec, size and frame rate; failures are retried next pass
+                    if !video_probed && is_video_file(&file_path) {
+                        store_video_info(&conn, file_id, &file_path);
+                    }
                 }
             }
             // Only set the flag if the scan was not interrupted
             if !interrupted {
                 exhausted_flag.store(true, Ordering::SeqCst);
+                start_background_preview_worker();
                 return;
             }
             // Sleep before next full scan
@@ -73,19 +282,45 @@ pub fn start_background_thumbnail_worker() {
     });
 }
 
-// Example: start a second worker when thumbnails are done
-pub fn start_background_preview_worker() {
+// Whether the path has one of the configured video extensions
+fn is_video_file(file_path: &str) -> bool {
+    std::path::Path::new(file_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| crate::processing::video::is_video_extension(&ext.to_ascii_lowercase()))
+}
+
+// Probes a video with ffprobe and stores its codec, size and frame rate as key values
+fn store_video_info(conn: &Connection, file_id: i64, file_path: &str) {
+    let Some(info) = crate::processing::video::probe_video(file_path) else {
+        return;
+    };
+    log::debug!("Background worker: video properties of {}: {:?}", file_path, info);
+    for (key, value) in info.key_values() {
+        if let Err(e) = conn.execute(
+            "INSERT INTO key_value (file_id, key, value) VALUES (?1, ?2, ?3)",
+            rusqlite::params![file_id, key, value],
+        ) {
+            log::error!("Background worker: failed to store {} for {}: {}", key, file_path, e);
+        }
+    }
+}
+
+// Second worker, started by the thumbnail worker once every thumbnail is done
+fn start_background_preview_worker() {
     let user_active = crate::routes::USER_REQUEST_ACTIVE.clone();
-    let exhausted_flag = THUMBNAIL_WORKER_EXHAUSTED.clone();
     std::thread::spawn(move || {
         log::info!("Background preview worker started");
-        loop {
-            // Wait until thumbnail worker is exhausted
-            if !exhausted_flag.load(Ordering::SeqCst) {
-                log::trace!("Preview worker waiting for thumbnail worker to finish...");
-                std::thread::sleep(std::time::Duration::from_secs(5));
-                continue;
+        let args = get_cli_args();
+        let conn = match rusqlite::Connection::open(&args.db_path) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Preview worker: failed to open DB: {}", e);
+                finish_background_workers();
+                return;
             }
+        };
+        loop {
             // Pause if user requests are active
             if user_active.load(Ordering::SeqCst) {
                 log::trace!("Preview worker pausing due to user activity");
@@ -93,56 +328,177 @@ pub fn start_background_preview_worker() {
                 continue;
             }
             log::debug!("Preview worker starting full-size preview scan");
-            let args = get_cli_args();
-            let conn = match rusqlite::Connection::open(&args.db_path) {
-                Ok(c) => c,
+            let file_iter = match preview_candidates(&conn, args.preview_strategy) {
+                Ok(paths) => paths,
                 Err(e) => {
-                    log::error!("Preview worker: failed to open DB: {}", e);
-                    std::thread::sleep(std::time::Duration::from_secs(30));
-                    continue;
+                    log::warn!("Preview worker: failed to query file paths: {}", e);
+                    break;
                 }
             };
-            let mut stmt = match conn.prepare("SELECT path FROM file") {
-                Ok(s) => s,
-                Err(e) => {
-                    log::error!("Preview worker: failed to prepare statement: {}", e);
-                    std::thread::sleep(std::time::Duration::from_secs(30));
-                    continue;
+            reset_worker_progress("preview", file_iter.len());
+            let mut interrupted = false;
+            for file_path in file_iter {
+                if user_active.load(Ordering::SeqCst) {
+                    log::trace!("Preview worker interrupted by user activity");
+                    interrupted = true;
+                    break;
                 }
-            };
-            let file_iter = stmt.query_map([], |row| row.get::<_, String>(0));
-            if let Ok(iter) = file_iter {
-                for file_path_res in iter {
-                    if user_active.load(Ordering::SeqCst) {
-                        log::trace!("Preview worker interrupted by user activity");
-                        break;
-                    }
-                    if let Ok(file_path) = file_path_res {
-                        let file_path = file_path.strip_suffix(".xmp").unwrap_or(&file_path);
-                        let cache_key = crate::processing::cache::generate_cache_key(file_path);
-                        // Only generate if not already cached
-                        if crate::processing::cache::get_cached_preview(&cache_key).is_none() {
-                            log::info!("Background worker: generating preview for {}", file_path);
-                            let result = crate::processing::image::generate_preview(&file_path);
-                            if result.is_none() {
-                                log::error!("Failed to generate preview for {}", file_path);
-                            } else {
-                                log::debug!("Successfully generated preview for {}", file_path);
-                            }
-                            thread::sleep(Duration::from_millis(100));
-                        } else {
-                            log::trace!("Preview already cached for {}", file_path);
-                        }
-                    }
+                if !crate::processing::cache::cache_has_free_space(&crate::processing::cache::get_preview_cache_dir()) {
+                    interrupted = true;
+                    break;
                 }
-                log::warn!("Preview worker: Done with full scan.");
-                return;
-            } else {
-                log::warn!("Preview worker: failed to query file paths");
+                crate::metrics::METRICS.worker_processed.with_label_values(&["preview"]).inc();
+                // Prioritized previews first, e.g. those the lightbox is about to show
+                generate_priority_previews(&user_active);
+                generate_missing_preview(crate::sidecar_scan::image_path_for_sidecar(&file_path));
+            }
+            if !interrupted {
+                generate_priority_previews(&user_active);
+                log::info!("Preview worker: Done with full scan.");
+                break;
             }
-            log::debug!("Preview worker sleeping before next scan");
-            std::thread::sleep(std::time::Duration::from_secs(60));
+            // Sleep before resuming the interrupted scan
+            thread::sleep(Duration::from_secs(10));
         }
+        finish_background_workers();
     });
 }
 
+/// Paths the preview worker generates previews for in a pass with the given --preview-strategy,
+/// as stored in the file table (map them with `image_path_for_sidecar`). Viewed files come most
+/// recently viewed first; `on-demand` leaves every preview to the requests that open the image.
+/// Read up front, like the thumbnail worker's list.
+pub fn preview_candidates(conn: &Connection, strategy: PreviewStrategy) -> rusqlite::Result<Vec<String>> {
+    let sql = match strategy {
+        PreviewStrategy::All => "SELECT COALESCE(image_path, path) FROM file",
+        PreviewStrategy::Viewed => {
+            "SELECT COALESCE(image_path, path) FROM file WHERE viewed_at IS NOT NULL ORDER BY viewed_at DESC, id"
+        }
+        PreviewStrategy::OnDemand => return Ok(Vec::new()),
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let paths = stmt.query_map([], |row| row.get(0))?.collect();
+    paths
+}
+
+/// Marks the files of an image as viewed for `--preview-strategy viewed`, keeping the time of
+/// the first view. Returns the number of files newly marked.
+pub fn mark_viewed(conn: &Connection, image_path: &str, now: i64) -> rusqlite::Result<usize> {
+    let tx = crate::db::write_transaction(conn)?;
+    let marked = tx.execute(
+        &format!(
+            "UPDATE file SET viewed_at = ?2 WHERE {} AND viewed_at IS NULL",
+            crate::sidecar_scan::sidecar_path_condition(1)
+        ),
+        rusqlite::params![image_path, now],
+    )?;
+    tx.commit()?;
+    Ok(marked)
+}
+
+// Drains the preview priority queue, leaving the rest for later when a user request comes in
+fn generate_priority_previews(user_active: &AtomicBool) {
+    while !user_active.load(Ordering::SeqCst) {
+        let Some(file_path) = next_priority_preview() else {
+            break;
+        };
+        log::debug!("Preview worker: handling prioritized {}", file_path);
+        generate_missing_preview(&file_path);
+    }
+}
+
+/// Assets rebuilt by `POST /api/regenerate-search` or cleared by `POST /cache/clear`
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegenerateTarget {
+    #[serde(alias = "thumbnail")]
+    Thumbnails,
+    #[serde(alias = "preview")]
+    Previews,
+    All,
+}
+
+/// Outcome of a regeneration, see [`regenerate_files`]
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct RegenerateSummary {
+    pub total: usize,
+    pub regenerated: usize,
+    pub failed: usize,
+}
+
+// Only one regeneration runs at a time, so they never compete for the same cache files
+static REGENERATE_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Held by the running regeneration; the next one may start once it is dropped
+pub struct RegenerationGuard(());
+
+impl Drop for RegenerationGuard {
+    fn drop(&mut self) {
+        REGENERATE_IN_PROGRESS.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Claims the regeneration slot, None while another regeneration is running
+pub fn start_regeneration() -> Option<RegenerationGuard> {
+    (!REGENERATE_IN_PROGRESS.swap(true, Ordering::SeqCst)).then_some(RegenerationGuard(()))
+}
+
+/// Deletes and regenerates the thumbnails (at every --thumbnail-scale-factors density) and/or
+/// full-size previews of the given images one at a time, pausing while user requests are active.
+/// Sized previews and rating badges are deleted and left to be regenerated on request.
+/// `on_file` is called after each file with its path and whether it was regenerated.
+pub fn regenerate_files(
+    _guard: RegenerationGuard,
+    file_paths: &[String],
+    target: RegenerateTarget,
+    user_active: &AtomicBool,
+    mut on_file: impl FnMut(&str, bool),
+) -> RegenerateSummary {
+    let thumbnails = target != RegenerateTarget::Previews;
+    let previews = target != RegenerateTarget::Thumbnails;
+    let mut summary = RegenerateSummary { total: file_paths.len(), ..Default::default() };
+    for file_path in file_paths {
+        while user_active.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(500));
+        }
+        let removed = crate::processing::cache::remove_cached_files(file_path, thumbnails, previews);
+        log::info!("Regenerating {} (removed {} cache files)", file_path, removed);
+        let mut ok = true;
+        if thumbnails {
+            for scale in crate::processing::cache::thumbnail_scale_factors() {
+                if crate::processing::image::generate_scaled_thumbnail(file_path, scale, false).is_none() {
+                    log::error!("Failed to regenerate {}x thumbnail for {}", scale, file_path);
+                    ok = false;
+                }
+            }
+        }
+        if previews && crate::processing::image::generate_preview(file_path).is_none() {
+            log::error!("Failed to regenerate preview for {}", file_path);
+            ok = false;
+        }
+        if ok {
+            summary.regenerated += 1;
+        } else {
+            summary.failed += 1;
+        }
+        on_file(file_path, ok);
+    }
+    summary
+}
+
+// Generates and caches the preview of an image unless it is already cached
+fn generate_missing_preview(file_path: &str) {
+    let cache_key = crate::processing::cache::preview_cache_key(file_path);
+    if crate::processing::cache::get_cached_preview(&cache_key, file_path).is_none() {
+        log::info!("Background worker: generating preview for {}", file_path);
+        let result = crate::processing::image::generate_preview(file_path);
+        if result.is_none() {
+            log::error!("Failed to generate preview for {}", file_path);
+        } else {
+            log::debug!("Successfully generated preview for {}", file_path);
+        }
+        thread::sleep(Duration::from_millis(100));
+    } else {
+        log::trace!("Preview already cached for {}", file_path);
+    }
+}
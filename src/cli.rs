@@ -1,4 +1,4 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::sync::OnceLock;
 
 /// Log level enum for CLI
@@ -23,6 +23,148 @@ impl LogLevel {
     }
 }
 
+/// Log output format enum for CLI
+#[derive(Debug, Clone, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable env_logger lines
+    Text,
+    /// One JSON object per line with timestamp, level, target and message
+    Json,
+}
+
+/// Metadata keys imported by default: tags, title, description, keywords, camera, lens, rating, color/pick labels and capture date
+pub const DEFAULT_IMPORT_KEYS: &str = "digiKam:TagsList,dc:title/rdf:Alt,dc:description,ImageDescription,dc:subject,tiff:Make,tiff:Model,exif:Model,LensModel,aux:Lens,xmp:Rating,digiKam:ColorLabel,digiKam:PickLabel,exif:DateTimeOriginal,xmp:CreateDate,exif:GPSLatitude,exif:GPSLongitude";
+
+/// Thumbnail shape enum for CLI
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum ThumbnailCrop {
+    /// Keep the aspect ratio, fitting the image inside the thumbnail square
+    Fit,
+    /// Center-crop to fill the thumbnail square exactly
+    Fill,
+}
+
+/// On-disk encoding of cached thumbnails for CLI
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum ThumbnailCacheFormat {
+    /// Raw image bytes, JPEG or the --thumbnail-format
+    Jpeg,
+    /// The base64 text served in JSON, about a third larger but returned without encoding
+    Base64,
+}
+
+/// Encoding of generated thumbnails for CLI
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ThumbnailFormat {
+    /// JPEG, readable by every client and by earlier caches
+    Jpeg,
+    /// AVIF, lossy and typically about half the size of the JPEG
+    Avif,
+    /// WebP; the image crate only encodes it lossless, so it is usually larger than the JPEG
+    Webp,
+}
+
+impl ThumbnailFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpeg",
+            ThumbnailFormat::Avif => "avif",
+            ThumbnailFormat::Webp => "webp",
+        }
+    }
+
+    /// Extension of the thumbnail cache files in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpg",
+            ThumbnailFormat::Avif => "avif",
+            ThumbnailFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Which files the background preview worker generates previews for, for CLI
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PreviewStrategy {
+    /// Every indexed file
+    All,
+    /// None; previews are generated when an image is opened or prioritized
+    OnDemand,
+    /// Files whose thumbnail has been requested
+    Viewed,
+}
+
+/// Preview encodings offered besides JPEG for CLI
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PreviewFormat {
+    /// AVIF, lossy and typically about half the size of the JPEG
+    Avif,
+    /// WebP; the image crate only encodes it lossless
+    Webp,
+}
+
+impl PreviewFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            PreviewFormat::Avif => "image/avif",
+            PreviewFormat::Webp => "image/webp",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PreviewFormat::Avif => "avif",
+            PreviewFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Thumbnail/preview encoding settings for the files under a path prefix (--quality-override)
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityOverride {
+    pub prefix: String,
+    pub thumbnail_quality: Option<u8>,
+    pub thumbnail_size: Option<u32>,
+    pub preview_quality: Option<u8>,
+}
+
+/// What thumbnail/preview cache keys identify the source file by, for CLI
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CacheKey {
+    /// Its path; an image edited in place keeps its cached files
+    Path,
+    /// Its size and modification time; an edited image gets new cache files and a moved one keeps them
+    Content,
+}
+
+/// Handling of partially parsed XMP sidecars for CLI
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum MalformedXmp {
+    /// Import the metadata read up to the error
+    Accept,
+    /// Skip the sidecar until it is fixed
+    Skip,
+}
+
+/// What to run for CLI; without a subcommand the server is started as with `serve`
+#[derive(Debug, Clone, Copy, PartialEq, Subcommand)]
+pub enum Command {
+    /// Scan the sidecars into the index, then start the web server
+    Serve,
+    /// Scan the sidecars into the index and exit, e.g. from cron
+    Index,
+    /// Remove index entries whose sidecar (or exiftool-indexed image) no longer exists and exit
+    Prune,
+    /// Delete corrupt files from the thumbnail/preview caches (--cache-verify-sample percent of them) and exit
+    Verify,
+}
+
+/// Video file extensions thumbnailed with ffmpeg and played through /video by default
+pub const DEFAULT_VIDEO_EXTENSIONS: &str = "mp4,avi,mov,wmv,flv,webm,mkv,m4v,3gp,ogv";
+
+/// Multi-valued XMP properties whose rdf:li items are joined into one value by default
+pub const DEFAULT_LIST_KEYS: &str = "digiKam:TagsList,dc:title,dc:subject,dc:description,tiff:ImageDescription,exif:ImageDescription,dc:creator,lr:hierarchicalSubject";
+
 /// Command line arguments for ImageFind
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -47,13 +189,307 @@ pub struct CliArgs {
     #[arg(long, required = true)]
     pub scan_dir: String,
 
+    /// Seconds between progress lines (percentage done and estimated time left) while importing sidecars (0 disables)
+    #[arg(long, default_value_t = 10)]
+    pub scan_progress_interval_secs: u64,
+
+    /// Comma-separated sidecar file extensions to scan; "json" reads Google Takeout style JSON sidecars
+    #[arg(long, value_delimiter = ',', default_value = "xmp")]
+    pub sidecar_extensions: Vec<String>,
+
+    /// Also index media files without a sidecar, reading their embedded metadata with exiftool
+    #[arg(long)]
+    pub use_exiftool: bool,
+
+    /// exiftool executable used by --use-exiftool
+    #[arg(long, default_value = "exiftool")]
+    pub exiftool_path: String,
+
+    /// Comma-separated video file extensions handled with ffmpeg; "default" stands for the built-in set (e.g. "default,mts,m2ts")
+    #[arg(long, value_delimiter = ',', default_value = "default", value_parser = parse_video_extension)]
+    pub video_extensions: Vec<String>,
+
     /// Set the logging level
     #[arg(long, value_enum, default_value = "info")]
     pub log_level: LogLevel,
 
+    /// Set the log output format
+    #[arg(long, value_enum, default_value = "text")]
+    pub log_format: LogFormat,
+
     /// Port for the webserver (default: 8080)
     #[arg(long, default_value_t = 8080)]
     pub port: u16,
+
+    /// Number of HTTP worker threads, each with its own event loop (0 starts one per CPU core)
+    #[arg(long, default_value_t = 0)]
+    pub http_workers: usize,
+
+    /// Seconds an idle HTTP connection is kept open for further requests (0 closes it after each response)
+    #[arg(long, default_value_t = 5)]
+    pub keep_alive_secs: u64,
+
+    /// Number of recently served thumbnails kept in memory in front of the disk cache (0 disables)
+    #[arg(long, default_value_t = 1000)]
+    pub memory_cache_entries: usize,
+
+    /// How thumbnails are stored on disk: "jpeg" bytes, or the "base64" text returned by the API so reads skip the encoding
+    #[arg(long, value_enum, default_value = "jpeg")]
+    pub thumbnail_cache_format: ThumbnailCacheFormat,
+
+    /// Thumbnail shape: "fit" keeps the aspect ratio, "fill" center-crops to a square
+    #[arg(long, value_enum, default_value = "fit")]
+    pub thumbnail_crop: ThumbnailCrop,
+
+    /// Answer thumbnail requests that fail for a known file type (document, video, RAW, image) with a generic SVG icon
+    #[arg(long)]
+    pub thumbnail_fallback_icons: bool,
+
+    /// Apply a light unsharp mask to thumbnails after the final resize
+    #[arg(long)]
+    pub thumbnail_sharpen: bool,
+
+    /// Blur radius (sigma) of the --thumbnail-sharpen unsharp mask; larger values sharpen coarser detail
+    #[arg(long, default_value_t = 0.5, value_parser = parse_sharpen_sigma)]
+    pub thumbnail_sharpen_sigma: f32,
+
+    /// Minimum difference (0-255) from the blurred image before a pixel is sharpened, to leave noise alone
+    #[arg(long, default_value_t = 3)]
+    pub thumbnail_sharpen_threshold: i32,
+
+    /// Encoding of generated thumbnails: "jpeg", "avif" (about half the size) or "webp" (lossless); JSON responses name it in content_type
+    #[arg(long, value_enum, default_value = "jpeg")]
+    pub thumbnail_format: ThumbnailFormat,
+
+    /// JPEG quality of thumbnails, 1-100
+    #[arg(long, default_value = "50", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub thumbnail_quality: u8,
+
+    /// JPEG quality of previews, 1-100
+    #[arg(long, default_value = "60", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub preview_quality: u8,
+
+    /// Longest edge (or side, with --thumbnail-crop fill) of thumbnails in pixels at the base pixel density, 16-1000
+    #[arg(long, default_value = "200", value_parser = clap::value_parser!(u32).range(16..=1000))]
+    pub thumbnail_size: u32,
+
+    /// Comma-separated pixel densities thumbnails are generated at (e.g. "1,2" adds 400px thumbnails for high-DPI screens, served with ?dpr=2)
+    #[arg(long, value_delimiter = ',', default_value = "1", value_parser = clap::value_parser!(u32).range(1..=4))]
+    pub thumbnail_scale_factors: Vec<u32>,
+
+    /// Comma-separated preview formats served instead of JPEG to clients whose Accept header lists them, in order of preference (e.g. "avif,webp")
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub preview_formats: Vec<PreviewFormat>,
+
+    /// Encoding settings for the files under a path prefix, as "<prefix>:<setting>=<value>,..." with the settings thumbnail_quality, thumbnail_size and preview_quality (e.g. "/photos/documents:thumbnail_quality=85"); repeatable, the longest matching prefix wins
+    #[arg(long, value_parser = parse_quality_override)]
+    pub quality_override: Vec<QualityOverride>,
+
+    /// Longest edge in pixels of source images that are decoded for thumbnails/previews; larger ones are only shown from an embedded reduced-resolution version (0 disables)
+    #[arg(long, default_value_t = 0)]
+    pub max_source_dimension: u32,
+
+    /// Seconds a thumbnail/preview request waits for generation before answering 503 (0 waits indefinitely)
+    #[arg(long, default_value_t = 0)]
+    pub generation_timeout_secs: u64,
+
+    /// Number of prioritized paths the preview worker handles before continuing its scan (0 disables prioritizing)
+    #[arg(long, default_value_t = 100)]
+    pub preview_priority_queue: usize,
+
+    /// Which previews the background worker generates ahead of time: "all", "viewed" (files whose thumbnail was requested) or "on-demand" (none)
+    #[arg(long, value_enum, default_value = "all")]
+    pub preview_strategy: PreviewStrategy,
+
+    /// Regenerate cached thumbnails/previews whose source image was modified after they were cached
+    #[arg(long)]
+    pub revalidate_cache: bool,
+
+    /// What cache keys identify the source by: "path" or "content" (its size and modification time)
+    #[arg(long, value_enum, default_value = "path")]
+    pub cache_key: CacheKey,
+
+    /// Store thumbnail/preview cache files in subdirectories named by this many leading characters of their key (0 keeps them in one directory)
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub cache_shard_chars: u8,
+
+    /// Store byte-identical thumbnails/previews once and hardlink further cache entries to them
+    #[arg(long)]
+    pub dedupe_cache: bool,
+
+    /// Stop writing thumbnail/preview cache files while the cache's file system has less free space than this, e.g. "2GB" (0 disables)
+    #[arg(long, default_value = "0", value_parser = parse_min_free_bytes)]
+    pub min_free_bytes: u64,
+
+    /// Evict the least recently used thumbnails once the thumbnail cache grows beyond this size, e.g. "1GB" (0 disables)
+    #[arg(long, default_value = "0", value_parser = parse_max_cache_size)]
+    pub max_thumbnail_cache_size: u64,
+
+    /// Evict the least recently used previews once the full-image cache grows beyond this size, e.g. "20GB" (0 disables)
+    #[arg(long, default_value = "0", value_parser = parse_max_cache_size)]
+    pub max_full_image_cache_size: u64,
+
+    /// Hours between checks of the thumbnail/preview caches for corrupt files, which are deleted and regenerated (0 disables)
+    #[arg(long, default_value_t = 0)]
+    pub cache_verify_interval_hours: u64,
+
+    /// Percentage of cache files decoded per check; each check continues with the files after the previous one's
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub cache_verify_sample: u8,
+
+    /// Comma-separated metadata key substrings to import into the index (e.g. "tiff:Model"), or "all"
+    #[arg(long, value_delimiter = ',', default_value = DEFAULT_IMPORT_KEYS)]
+    pub import_keys: Vec<String>,
+
+    /// Encode thumbnails and previews as progressive JPEG (requires the progressive-jpeg feature)
+    #[arg(long)]
+    pub progressive_jpeg: bool,
+
+    /// Comma-separated multi-valued XMP properties whose rdf:Seq/rdf:Bag/rdf:Alt items are joined by semicolon
+    #[arg(long, value_delimiter = ',', default_value = DEFAULT_LIST_KEYS)]
+    pub list_keys: Vec<String>,
+
+    /// What to do with an XMP sidecar whose XML is malformed beyond recovery: "accept" imports the metadata read up to the error, "skip" leaves the file out of the scan
+    #[arg(long, value_enum, default_value = "accept")]
+    pub malformed_xmp: MalformedXmp,
+
+    /// Apply XMP develop crop/orientation (crs:Crop*, crs:Orientation) to previews of standard images
+    #[arg(long)]
+    pub apply_develop_settings: bool,
+
+    /// Comma-separated origins allowed to embed the UI in a frame ("self", "none", "*" or e.g. "https://portal.example.com")
+    #[arg(long, value_delimiter = ',', default_value = "self", value_parser = parse_frame_ancestor)]
+    pub frame_ancestors: Vec<String>,
+
+    /// File of search synonyms, one group of equivalent terms per line separated by "=" (e.g. "NYC = New York City")
+    #[arg(long)]
+    pub synonyms_file: Option<String>,
+
+    /// Reject requests that change the database, such as album edits, with 403 Forbidden
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Keep a history of search terms with their result counts for /api/history; off by default as terms are stored in the database
+    #[arg(long)]
+    pub track_search_history: bool,
+
+    /// Hide files carrying this tag (or one of its subtags) from search and listing endpoints; repeatable
+    #[arg(long)]
+    pub exclude_tag: Vec<String>,
+
+    /// Hide files whose path matches this glob (e.g. "*/Private/*") from search and listing endpoints; repeatable
+    #[arg(long)]
+    pub exclude_path_glob: Vec<String>,
+
+    /// Bearer token that lets a request pass include_excluded=true to see excluded files; unset disables the override
+    #[arg(long)]
+    pub exclude_override_token: Option<String>,
+
+    /// Base URL the server is reachable at (e.g. "https://photos.example.com"), for the absolute links of /feed.json and /feed.xml; taken from each request's Host header when unset
+    #[arg(long)]
+    pub public_url: Option<String>,
+
+    /// Number of recently imported files listed in /feed.json and /feed.xml
+    #[arg(long, default_value_t = 50)]
+    pub feed_items: usize,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl CliArgs {
+    /// The subcommand to run, `serve` when none was given
+    pub fn command(&self) -> Command {
+        self.command.unwrap_or(Command::Serve)
+    }
+}
+
+/// Parses the --thumbnail-sharpen-sigma value, which has to be a positive number.
+pub fn parse_sharpen_sigma(value: &str) -> Result<f32, String> {
+    match value.trim().parse::<f32>() {
+        Ok(sigma) if sigma.is_normal() && sigma > 0.0 => Ok(sigma),
+        _ => Err(format!("sigma must be a positive number: {}", value)),
+    }
+}
+
+/// Parses a --min-free-bytes value, a byte count with an optional unit such as "500MB" or "2GB"
+pub fn parse_min_free_bytes(value: &str) -> Result<u64, String> {
+    crate::routes::parse_size(value).ok_or_else(|| format!("expected a size such as 500MB or 2GB: {}", value))
+}
+
+/// Parses a --max-thumbnail-cache-size or --max-full-image-cache-size value, such as "20GB"
+pub fn parse_max_cache_size(value: &str) -> Result<u64, String> {
+    crate::routes::parse_size(value).ok_or_else(|| format!("expected a size such as 500MB or 20GB: {}", value))
+}
+
+/// Parses one --quality-override value, "<prefix>:<setting>=<value>,...". The prefix is split
+/// off at the last colon, so it may contain colons itself (e.g. "C:\Photos:preview_quality=90").
+pub fn parse_quality_override(value: &str) -> Result<QualityOverride, String> {
+    let (prefix, settings) = value
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected <prefix>:<setting>=<value>,...: {}", value))?;
+    if prefix.is_empty() {
+        return Err(format!("empty path prefix: {}", value));
+    }
+    let mut quality_override = QualityOverride {
+        prefix: prefix.to_string(),
+        thumbnail_quality: None,
+        thumbnail_size: None,
+        preview_quality: None,
+    };
+    for setting in settings.split(',').map(str::trim) {
+        let (name, setting_value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("expected <setting>=<value>: {}", setting))?;
+        let setting_value = setting_value.trim();
+        match name.trim() {
+            "thumbnail_quality" => quality_override.thumbnail_quality = Some(parse_jpeg_quality(setting_value)?),
+            "preview_quality" => quality_override.preview_quality = Some(parse_jpeg_quality(setting_value)?),
+            "thumbnail_size" => match setting_value.parse::<u32>() {
+                Ok(size) if (16..=1000).contains(&size) => quality_override.thumbnail_size = Some(size),
+                _ => return Err(format!("thumbnail_size must be 16-1000: {}", setting_value)),
+            },
+            other => return Err(format!("unknown setting: {}", other)),
+        }
+    }
+    Ok(quality_override)
+}
+
+// Parses a JPEG quality setting of --quality-override
+fn parse_jpeg_quality(value: &str) -> Result<u8, String> {
+    match value.parse::<u8>() {
+        Ok(quality) if (1..=100).contains(&quality) => Ok(quality),
+        _ => Err(format!("quality must be 1-100: {}", value)),
+    }
+}
+
+/// Parses one --video-extensions entry into a lowercase extension without the leading dot.
+///
+/// Extensions are also embedded in the search page script, so only ASCII letters and digits are accepted.
+pub fn parse_video_extension(value: &str) -> Result<String, String> {
+    let extension = value.trim().trim_start_matches('.').to_ascii_lowercase();
+    if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("invalid video extension: {}", value));
+    }
+    Ok(extension)
+}
+
+/// Parses one --frame-ancestors entry into a CSP source expression.
+///
+/// The keywords `self` and `none` may be given without the quotes CSP requires. Anything that
+/// could end the directive or the header (whitespace, `;`, quotes) is rejected.
+pub fn parse_frame_ancestor(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    match value {
+        "self" | "'self'" => return Ok("'self'".to_string()),
+        "none" | "'none'" => return Ok("'none'".to_string()),
+        "" => return Err("empty frame ancestor".to_string()),
+        _ => {}
+    }
+    if value.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, ';' | ',' | '\'' | '"')) {
+        return Err(format!("invalid frame ancestor: {}", value));
+    }
+    Ok(value.to_string())
 }
 
 pub static CLI_ARGS: OnceLock<CliArgs> = OnceLock::new();
@@ -64,9 +500,32 @@ pub fn get_cli_args() -> &'static CliArgs {
 
 /// Initialize logging based on CLI arguments
 pub fn init_logging(args: &CliArgs) {
-    env_logger::Builder::from_default_env()
-        .filter_level(args.log_level.to_log_level_filter())
-        .init();
+    let mut builder = env_logger::Builder::from_default_env();
+    builder.filter_level(args.log_level.to_log_level_filter());
+    if let LogFormat::Json = args.log_format {
+        builder.format(|buf, record| {
+            use std::io::Write;
+            let line = json_log_line(
+                &buf.timestamp_millis().to_string(),
+                record.level(),
+                record.target(),
+                &record.args().to_string(),
+            );
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.init();
     
-    log::info!("Logging initialized at level: {:?}", args.log_level);
+    log::info!("Logging initialized at level: {:?}, format: {:?}", args.log_level, args.log_format);
+}
+
+/// Formats a single log record as a JSON object for log aggregators
+pub fn json_log_line(timestamp: &str, level: log::Level, target: &str, message: &str) -> String {
+    serde_json::json!({
+        "timestamp": timestamp,
+        "level": level.as_str(),
+        "target": target,
+        "message": message,
+    })
+    .to_string()
 }
\ No newline at end of file
@@ -0,0 +1,220 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OpenFlags, Result, Transaction, TransactionBehavior};
+
+/// Ordered list of schema migrations as (description, SQL).
+///
+/// Each migration is applied exactly once, in order, and its 1-based position in this
+/// list is recorded as the schema version. Never edit or reorder existing entries;
+/// append new ones to change the schema.
+const MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "create file and key_value tables",
+        // IF NOT EXISTS so databases created before migrations existed are adopted as-is
+        "CREATE TABLE IF NOT EXISTS file (
+            id INTEGER PRIMARY KEY,
+            path TEXT NOT NULL,
+            hash BIGINT NOT NULL,
+            UNIQUE(path, hash)
+        );
+        CREATE TABLE IF NOT EXISTS key_value (
+            id INTEGER PRIMARY KEY,
+            file_id INTEGER NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            FOREIGN KEY(file_id) REFERENCES file(id)
+        );",
+    ),
+    (
+        "add file.dominant_color",
+        "ALTER TABLE file ADD COLUMN dominant_color TEXT;",
+    ),
+    (
+        "add file.capture_time",
+        "ALTER TABLE file ADD COLUMN capture_time INTEGER;
+        CREATE INDEX IF NOT EXISTS idx_file_capture_time ON file(capture_time);",
+    ),
+    (
+        "add file.size_bytes",
+        "ALTER TABLE file ADD COLUMN size_bytes INTEGER;",
+    ),
+    (
+        "add file.source_present",
+        "ALTER TABLE file ADD COLUMN source_present INTEGER NOT NULL DEFAULT 1;",
+    ),
+    (
+        "add file.import_time",
+        // Existing rows keep NULL, their import time is unknown; ordering by id keeps their insertion order
+        "ALTER TABLE file ADD COLUMN import_time INTEGER;
+        CREATE INDEX IF NOT EXISTS idx_file_import_time ON file(import_time);",
+    ),
+    (
+        "create album and album_file tables",
+        "CREATE TABLE IF NOT EXISTS album (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            created_time INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS album_file (
+            album_id INTEGER NOT NULL,
+            file_id INTEGER NOT NULL,
+            added_time INTEGER NOT NULL,
+            PRIMARY KEY(album_id, file_id),
+            FOREIGN KEY(album_id) REFERENCES album(id),
+            FOREIGN KEY(file_id) REFERENCES file(id)
+        );",
+    ),
+    (
+        "create search_history table",
+        "CREATE TABLE IF NOT EXISTS search_history (
+            query TEXT PRIMARY KEY,
+            last_searched INTEGER NOT NULL,
+            result_count INTEGER NOT NULL,
+            search_count INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_search_history_last_searched ON search_history(last_searched);",
+    ),
+    (
+        "create failure table",
+        "CREATE TABLE IF NOT EXISTS failure (
+            path TEXT NOT NULL,
+            stage TEXT NOT NULL,
+            error TEXT NOT NULL,
+            failed_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL,
+            PRIMARY KEY(path, stage)
+        );
+        CREATE INDEX IF NOT EXISTS idx_failure_failed_at ON failure(failed_at);",
+    ),
+    (
+        "add file image_path column",
+        "ALTER TABLE file ADD COLUMN image_path TEXT;
+        CREATE INDEX IF NOT EXISTS idx_file_image_path ON file(image_path);",
+    ),
+    (
+        "add file viewed_at column",
+        "ALTER TABLE file ADD COLUMN viewed_at INTEGER;",
+    ),
+    (
+        "add file hash index",
+        // Moved files are found by the hash of their sidecar
+        "CREATE INDEX IF NOT EXISTS idx_file_hash ON file(hash);",
+    ),
+];
+
+/// Returns the schema version of the database, 0 if no migration has been applied yet.
+pub fn current_schema_version(conn: &Connection) -> Result<i64> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+}
+
+/// Applies all pending migrations in order, each in its own transaction.
+pub fn run_migrations(conn: &mut Connection) -> Result<()> {
+    let current = current_schema_version(conn)?;
+    log::debug!("Database schema version: {} (latest: {})", current, MIGRATIONS.len());
+
+    for (index, (description, sql)) in MIGRATIONS.iter().enumerate() {
+        let version = index as i64 + 1;
+        if version <= current {
+            continue;
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, strftime('%s', 'now'))",
+            params![version, description],
+        )?;
+        tx.commit()?;
+        log::info!("Applied database migration {}: {}", version, description);
+    }
+
+    Ok(())
+}
+
+// How long an edit from a request waits for the background workers to release the database
+const WRITE_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts a transaction holding the write lock from the start. Reads and writes of the background
+/// workers can hold the database briefly, so the commit waits for them instead of failing right away.
+pub fn write_transaction(conn: &Connection) -> Result<Transaction<'_>> {
+    conn.busy_timeout(WRITE_BUSY_TIMEOUT)?;
+    Transaction::new_unchecked(conn, TransactionBehavior::Immediate)
+}
+
+// How long a search waits for a commit of the background workers or a scan to finish
+const READ_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the database for the read-only request handlers, such as searches. The connection
+/// cannot write, so it never takes the write lock and cannot change the index by accident; it
+/// waits for a running commit instead of failing with "database is locked". Unlike
+/// `Connection::open`, a missing database is an error rather than created empty.
+pub fn open_read_only(db_path: &str) -> Result<Connection> {
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(READ_BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+// Full-text index of key_value.value for search. Not a migration: it needs SQLite's FTS5
+// module with the trigram tokenizer (3.34+), and search falls back to LIKE without it. The
+// triggers keep it in sync with every write to key_value, from the scan or elsewhere.
+const FTS_SCHEMA: &str = "CREATE VIRTUAL TABLE key_value_fts USING fts5(value, content='key_value', content_rowid='id', tokenize='trigram');
+    CREATE TRIGGER key_value_fts_insert AFTER INSERT ON key_value BEGIN
+        INSERT INTO key_value_fts(rowid, value) VALUES (new.id, new.value);
+    END;
+    CREATE TRIGGER key_value_fts_delete AFTER DELETE ON key_value BEGIN
+        INSERT INTO key_value_fts(key_value_fts, rowid, value) VALUES ('delete', old.id, old.value);
+    END;
+    CREATE TRIGGER key_value_fts_update AFTER UPDATE ON key_value BEGIN
+        INSERT INTO key_value_fts(key_value_fts, rowid, value) VALUES ('delete', old.id, old.value);
+        INSERT INTO key_value_fts(rowid, value) VALUES (new.id, new.value);
+    END;
+    INSERT INTO key_value_fts(key_value_fts) VALUES ('rebuild');";
+
+// Whether search terms are matched through key_value_fts, set once the index is known to exist
+static FTS_SEARCH: AtomicBool = AtomicBool::new(false);
+
+/// Creates the full-text index of metadata values if it is missing, indexing the existing rows,
+/// e.g. on the first start after an upgrade. Returns whether the index is available; false when
+/// SQLite lacks FTS5 or its trigram tokenizer, in which case the database is left unchanged.
+pub fn ensure_fts_index(conn: &Connection) -> Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'key_value_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(true);
+    }
+
+    log::info!("Building the full-text search index");
+    let tx = write_transaction(conn)?;
+    if let Err(e) = tx.execute_batch(FTS_SCHEMA) {
+        log::warn!("Full-text search is not available, searching with LIKE: {}", e);
+        return Ok(false);
+    }
+    tx.commit()?;
+    log::info!("Built the full-text search index");
+    Ok(true)
+}
+
+/// Whether search terms are matched through the full-text index
+pub fn fts_search_enabled() -> bool {
+    FTS_SEARCH.load(Ordering::Relaxed)
+}
+
+/// Switches search between the full-text index and plain LIKE matching
+pub fn set_fts_search(enabled: bool) {
+    FTS_SEARCH.store(enabled, Ordering::Relaxed);
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Files handed to one exiftool run; its start-up time dominates for small batches
+const EXIFTOOL_BATCH_SIZE: usize = 100;
+
+// Image formats read by the app. Videos are matched by --video-extensions.
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "bmp", "webp", "tif", "tiff", "heic", "heif",
+    "nef", "cr2", "cr3", "arw", "orf", "rw2", "raf", "dng", "pef", "srw",
+];
+
+// exiftool tags mapped onto the keys used for XMP metadata, so they are imported, searched and
+// displayed the same way. Earlier entries win when several tags map onto one key.
+const MAPPED_TAGS: &[(&str, &str)] = &[
+    ("Make", "tiff:Make"),
+    ("Model", "tiff:Model"),
+    ("LensModel", "exifEX:LensModel"),
+    ("Lens", "aux:Lens"),
+    ("DateTimeOriginal", "exif:DateTimeOriginal"),
+    ("CreateDate", "xmp:CreateDate"),
+    ("ModifyDate", "xmp:ModifyDate"),
+    ("Rating", "xmp:Rating"),
+    ("TagsList", "digiKam:TagsList/rdf:Seq"),
+    ("ColorLabel", "digiKam:ColorLabel"),
+    ("PickLabel", "digiKam:PickLabel"),
+    ("Title", "dc:title/rdf:Alt"),
+    ("Description", "dc:description/rdf:Alt"),
+    ("ImageDescription", "dc:description/rdf:Alt"),
+    ("Subject", "dc:subject/rdf:Bag"),
+    ("Keywords", "dc:subject/rdf:Bag"),
+    ("Creator", "dc:creator/rdf:Seq"),
+    ("Artist", "dc:creator/rdf:Seq"),
+    ("HierarchicalSubject", "lr:hierarchicalSubject/rdf:Bag"),
+    ("GPSLatitude", "exif:GPSLatitude"),
+    ("GPSLongitude", "exif:GPSLongitude"),
+];
+
+// Groups describing the file system entry or exiftool itself rather than the image
+const SKIPPED_GROUPS: &[&str] = &["ExifTool", "System"];
+
+/// Whether a path looks like a media file exiftool can describe: an image format the app
+/// reads or one of the configured video extensions.
+pub fn is_media_file(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    let extension = extension.to_ascii_lowercase();
+    IMAGE_EXTENSIONS.contains(&extension.as_str()) || crate::processing::video::is_video_extension(&extension)
+}
+
+/// Checks that exiftool can be run, returning its version.
+pub fn exiftool_version(exiftool_path: &str) -> Result<String, String> {
+    let output = Command::new(exiftool_path)
+        .arg("-ver")
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", exiftool_path, e))?;
+    if !output.status.success() {
+        return Err(format!("{} -ver failed: {}", exiftool_path, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Runs `exiftool -j` on the files in batches and returns the key/value pairs found per file,
+/// see `exiftool_key_values`. Files exiftool reports nothing for are left out. A batch that
+/// fails is logged and skipped, so one unreadable file does not stop the import.
+pub fn read_metadata(exiftool_path: &str, files: &[PathBuf]) -> Vec<(PathBuf, HashMap<String, String>)> {
+    let mut metadata = Vec::new();
+    for batch in files.chunks(EXIFTOOL_BATCH_SIZE) {
+        log::debug!("Running exiftool on {} files", batch.len());
+        // -G1: group names as key prefix, -n: numeric values (ratings, labels, orientation)
+        let output = match Command::new(exiftool_path).args(["-j", "-G1", "-n", "-q", "-q", "--"]).args(batch).output() {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("Failed to run {}: {}", exiftool_path, e);
+                return metadata;
+            }
+        };
+        // exiftool exits with 1 when a single file fails but still prints the others
+        let entries: Vec<serde_json::Map<String, serde_json::Value>> = match serde_json::from_slice(&output.stdout) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!(
+                    "Unreadable exiftool output for a batch of {} files: {} ({})",
+                    batch.len(),
+                    e,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                continue;
+            }
+        };
+        for entry in entries {
+            let Some(source_file) = entry.get("SourceFile").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            metadata.push((PathBuf::from(source_file), exiftool_key_values(&entry)));
+        }
+    }
+    metadata
+}
+
+/// Maps one file of `exiftool -j -G1` output onto metadata keys: well-known tags become the keys
+/// used for XMP metadata (`IFD0:Model` to `tiff:Model`, `XMP-dc:Subject` to `dc:subject/rdf:Bag`,
+/// ...), taking the first group in name order when a tag is found in several. Other XMP tags keep
+/// their namespace (`XMP-crs:CropTop` to `crs:CropTop`) and all others their group (`ExifIFD:ISO`).
+/// Lists are joined by semicolon like XMP lists; binary data is skipped.
+pub fn exiftool_key_values(entry: &serde_json::Map<String, serde_json::Value>) -> HashMap<String, String> {
+    let mut entries: Vec<(&String, String)> = entry
+        .iter()
+        .filter(|(key, _)| key.as_str() != "SourceFile")
+        .filter(|(key, _)| !key.split_once(':').is_some_and(|(group, _)| SKIPPED_GROUPS.contains(&group)))
+        .filter_map(|(key, value)| Some((key, exiftool_value(value)?)))
+        .collect();
+    entries.sort();
+
+    let tag_of = |key: &str| key.rsplit(':').next().unwrap_or(key).to_string();
+    let mut kv = HashMap::new();
+    for (tag, mapped_key) in MAPPED_TAGS {
+        if kv.contains_key(*mapped_key) {
+            continue;
+        }
+        if let Some((_, value)) = entries.iter().find(|(key, _)| tag_of(key) == *tag) {
+            kv.insert(mapped_key.to_string(), value.clone());
+        }
+    }
+    for (key, value) in &entries {
+        let tag = tag_of(key);
+        if MAPPED_TAGS.iter().any(|(mapped_tag, _)| *mapped_tag == tag) {
+            continue;
+        }
+        let stored_key = match key.split_once(':') {
+            Some((group, _)) => match group.strip_prefix("XMP-") {
+                Some(namespace) => format!("{}:{}", namespace, tag),
+                None => key.to_string(),
+            },
+            None => tag,
+        };
+        kv.entry(stored_key).or_insert_with(|| value.clone());
+    }
+    kv
+}
+
+fn exiftool_value(value: &serde_json::Value) -> Option<String> {
+    let text = match value {
+        serde_json::Value::String(s) => s.trim().to_string(),
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::Bool(b) => b.to_string(),
+        serde_json::Value::Array(items) => items.iter().filter_map(exiftool_value).collect::<Vec<_>>().join(";"),
+        serde_json::Value::Null | serde_json::Value::Object(_) => return None,
+    };
+    (!text.is_empty() && !text.starts_with("(Binary data")).then_some(text)
+}
@@ -0,0 +1,104 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::db::write_transaction;
+
+// Longest error message stored; ffmpeg and decoder errors can run to many lines
+const MAX_ERROR_LENGTH: usize = 1000;
+
+/// Processing stage in which a file failed
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureStage {
+    /// Reading the sidecar into the index
+    Scan,
+    /// Generating the thumbnail
+    Thumbnail,
+    /// Generating the full-size preview
+    Preview,
+}
+
+impl FailureStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureStage::Scan => "scan",
+            FailureStage::Thumbnail => "thumbnail",
+            FailureStage::Preview => "preview",
+        }
+    }
+}
+
+/// A file that failed a processing stage, with the error of its most recent attempt
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct Failure {
+    pub path: String,
+    pub stage: String,
+    pub error: String,
+    // Unix timestamp of the most recent failure
+    pub failed_at: i64,
+    // Failed attempts since the failure was first recorded or last cleared
+    pub attempts: i64,
+}
+
+/// Records that `path` failed `stage`. A file failing the same stage again keeps one entry with
+/// the latest error and time, and its attempts counted up.
+pub fn record_failure(conn: &Connection, path: &str, stage: FailureStage, error: &str, now: i64) -> Result<()> {
+    let error: String = error.trim().chars().take(MAX_ERROR_LENGTH).collect();
+    let tx = write_transaction(conn)?;
+    tx.execute(
+        "INSERT INTO failure (path, stage, error, failed_at, attempts) VALUES (?1, ?2, ?3, ?4, 1) \
+         ON CONFLICT(path, stage) DO UPDATE SET error = excluded.error, \
+         failed_at = excluded.failed_at, attempts = attempts + 1",
+        params![path, stage.as_str(), error, now],
+    )?;
+    tx.commit()
+}
+
+/// Records a failure from code that has no database connection at hand, such as thumbnail and
+/// preview generation. Only logs when the failure cannot be stored, e.g. before the database
+/// is set up, as the failure itself is logged by the caller.
+pub fn report_failure(path: &str, stage: FailureStage, error: &str) {
+    let Some(args) = crate::cli::CLI_ARGS.get() else {
+        return;
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let result = Connection::open(&args.db_path).and_then(|conn| record_failure(&conn, path, stage, error, now));
+    if let Err(e) = result {
+        log::warn!("Failed to record {} failure of {}: {}", stage.as_str(), path, e);
+    }
+}
+
+/// Deletes the failure of one file in one stage, e.g. once it succeeded.
+pub fn clear_failure(conn: &Connection, path: &str, stage: FailureStage) -> Result<usize> {
+    conn.execute("DELETE FROM failure WHERE path = ?1 AND stage = ?2", params![path, stage.as_str()])
+}
+
+/// Lists up to `limit` failures, of one stage or all, most recent first.
+pub fn list_failures(conn: &Connection, stage: Option<FailureStage>, limit: usize) -> Result<Vec<Failure>> {
+    let mut stmt = conn.prepare(
+        "SELECT path, stage, error, failed_at, attempts FROM failure \
+         WHERE ?1 IS NULL OR stage = ?1 ORDER BY failed_at DESC, rowid DESC LIMIT ?2",
+    )?;
+    let failures = stmt
+        .query_map(params![stage.map(|s| s.as_str()), limit as i64], |row| {
+            Ok(Failure {
+                path: row.get(0)?,
+                stage: row.get(1)?,
+                error: row.get(2)?,
+                failed_at: row.get(3)?,
+                attempts: row.get(4)?,
+            })
+        })?
+        .collect();
+    failures
+}
+
+/// Deletes the failures of one stage or all, returning the number of entries removed.
+pub fn clear_failures(conn: &Connection, stage: Option<FailureStage>) -> Result<usize> {
+    let tx = write_transaction(conn)?;
+    let removed = tx.execute("DELETE FROM failure WHERE ?1 IS NULL OR stage = ?1", params![stage.map(|s| s.as_str())])?;
+    tx.commit()?;
+    Ok(removed)
+}
@@ -0,0 +1,139 @@
+use rusqlite::{params_from_iter, Connection, Result};
+
+use crate::routes::append_exclusion_filters;
+use crate::sidecar_scan::{format_timestamp, image_path_for_sidecar};
+
+const FEED_TITLE: &str = "ImageFind: recent additions";
+
+// Width of the previews linked as item images, see /image/{path}?w=
+const FEED_IMAGE_WIDTH: u32 = 640;
+
+/// A recently imported file listed in /feed.json and /feed.xml
+#[derive(Debug, PartialEq)]
+pub struct FeedItem {
+    pub file_path: String,
+    /// The file's dc:title, or its file name when it has none
+    pub title: String,
+    pub import_time: i64,
+}
+
+/// Returns the `limit` most recently imported files, newest first. Files whose image is missing
+/// and those hidden by --exclude-tag/--exclude-path-glob are left out.
+pub fn recent_feed_items(conn: &Connection, limit: usize, exclude_tags: &[String], exclude_globs: &[String]) -> Result<Vec<FeedItem>> {
+    let mut where_clause = String::from("WHERE import_time IS NOT NULL AND source_present = 1");
+    let mut parameters = Vec::new();
+    append_exclusion_filters(exclude_tags, exclude_globs, &mut where_clause, &mut parameters);
+    parameters.push(limit.to_string());
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(image_path, path), import_time, \
+         (SELECT value FROM key_value title WHERE title.file_id = file.id AND title.key LIKE 'dc:title%' LIMIT 1) \
+         FROM file \
+         {} \
+         ORDER BY import_time DESC, id DESC \
+         LIMIT CAST(?{} AS INTEGER)",
+        where_clause,
+        parameters.len()
+    ))?;
+    let rows = stmt.query_map(params_from_iter(parameters.iter()), |row| {
+        let file_path = image_path_for_sidecar(&row.get::<_, String>(0)?).to_string();
+        let title = row
+            .get::<_, Option<String>>(2)?
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| file_path.rsplit(['/', '\\']).next().unwrap_or(&file_path).to_string());
+        Ok(FeedItem { file_path, title, import_time: row.get(1)? })
+    })?;
+    rows.collect()
+}
+
+/// Absolute URL of a file under one of the path routes (e.g. "image"), each path component
+/// percent-encoded. The file path keeps its leading slash, as those routes expect.
+pub fn feed_file_url(base_url: &str, route: &str, file_path: &str) -> String {
+    let encoded: Vec<String> = file_path.split('/').map(|component| urlencoding::encode(component).into_owned()).collect();
+    format!("{}/{}/{}", base_url.trim_end_matches('/'), route, encoded.join("/"))
+}
+
+// Links of an item: the page to open (the detail fragment) and the preview image
+fn item_links(base_url: &str, item: &FeedItem) -> (String, String) {
+    let page = format!("{}/detail?path={}", base_url.trim_end_matches('/'), urlencoding::encode(&item.file_path));
+    let image = format!("{}?w={}", feed_file_url(base_url, "image", &item.file_path), FEED_IMAGE_WIDTH);
+    (page, image)
+}
+
+/// Builds a JSON Feed 1.1 (https://jsonfeed.org) of the items, with absolute links under `base_url`
+pub fn json_feed(base_url: &str, items: &[FeedItem]) -> serde_json::Value {
+    let base_url = base_url.trim_end_matches('/');
+    let items: Vec<serde_json::Value> = items
+        .iter()
+        .map(|item| {
+            let (page, image) = item_links(base_url, item);
+            serde_json::json!({
+                "id": feed_file_url(base_url, "image", &item.file_path),
+                "url": page,
+                "title": item.title,
+                "image": image,
+                "content_html": format!("<img src=\"{}\" alt=\"{}\" />", xml_escape(&image), xml_escape(&item.title)),
+                "date_published": format_timestamp(item.import_time),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": FEED_TITLE,
+        "home_page_url": format!("{}/", base_url),
+        "feed_url": format!("{}/feed.json", base_url),
+        "items": items,
+    })
+}
+
+/// Builds an Atom feed (RFC 4287) of the items, with absolute links under `base_url`. The feed is
+/// as recent as its newest item, or `now` when there is none.
+pub fn atom_feed(base_url: &str, items: &[FeedItem], now: i64) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let updated = items.iter().map(|item| item.import_time).max().unwrap_or(now);
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         \x20 <title>{}</title>\n\
+         \x20 <id>{}/feed.xml</id>\n\
+         \x20 <link rel=\"self\" href=\"{}/feed.xml\" />\n\
+         \x20 <link href=\"{}/\" />\n\
+         \x20 <updated>{}</updated>\n\
+         \x20 <author><name>ImageFind</name></author>\n",
+        FEED_TITLE,
+        xml_escape(base_url),
+        xml_escape(base_url),
+        xml_escape(base_url),
+        format_timestamp(updated)
+    );
+    for item in items {
+        let (page, image) = item_links(base_url, item);
+        let content = format!("<img src=\"{}\" alt=\"{}\" />", xml_escape(&image), xml_escape(&item.title));
+        xml.push_str(&format!(
+            "  <entry>\n\
+             \x20   <title>{}</title>\n\
+             \x20   <id>{}</id>\n\
+             \x20   <link href=\"{}\" />\n\
+             \x20   <link rel=\"enclosure\" type=\"image/jpeg\" href=\"{}\" />\n\
+             \x20   <updated>{}</updated>\n\
+             \x20   <content type=\"html\">{}</content>\n\
+             \x20 </entry>\n",
+            xml_escape(&item.title),
+            xml_escape(&feed_file_url(base_url, "image", &item.file_path)),
+            xml_escape(&page),
+            xml_escape(&image),
+            format_timestamp(item.import_time),
+            xml_escape(&content)
+        ));
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
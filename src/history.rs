@@ -0,0 +1,67 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::db::write_transaction;
+
+// Distinct queries kept; the least recently searched are dropped beyond this
+const MAX_HISTORY_ENTRIES: i64 = 1000;
+
+/// A distinct search term with when it was last searched and what that search found
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct HistoryEntry {
+    pub query: String,
+    // Unix timestamp of the most recent search
+    pub last_searched: i64,
+    // Files found by the most recent search
+    pub result_count: i64,
+    // How often the term was searched
+    pub search_count: i64,
+}
+
+/// Records a search in the history. Blank terms are ignored; terms differing only in surrounding
+/// whitespace count as one. A repeated term moves to the top with its latest result count.
+pub fn record_search(conn: &Connection, query: &str, result_count: usize, now: i64) -> Result<()> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(());
+    }
+    let tx = write_transaction(conn)?;
+    tx.execute(
+        "INSERT INTO search_history (query, last_searched, result_count, search_count) VALUES (?1, ?2, ?3, 1) \
+         ON CONFLICT(query) DO UPDATE SET last_searched = excluded.last_searched, \
+         result_count = excluded.result_count, search_count = search_count + 1",
+        params![query, now, result_count as i64],
+    )?;
+    tx.execute(
+        "DELETE FROM search_history WHERE query NOT IN \
+         (SELECT query FROM search_history ORDER BY last_searched DESC, rowid DESC LIMIT ?1)",
+        params![MAX_HISTORY_ENTRIES],
+    )?;
+    tx.commit()
+}
+
+/// Lists up to `limit` distinct search terms, most recently searched first.
+pub fn recent_searches(conn: &Connection, limit: usize) -> Result<Vec<HistoryEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT query, last_searched, result_count, search_count FROM search_history \
+         ORDER BY last_searched DESC, rowid DESC LIMIT ?1",
+    )?;
+    let entries = stmt
+        .query_map(params![limit as i64], |row| {
+            Ok(HistoryEntry {
+                query: row.get(0)?,
+                last_searched: row.get(1)?,
+                result_count: row.get(2)?,
+                search_count: row.get(3)?,
+            })
+        })?
+        .collect();
+    entries
+}
+
+/// Deletes the whole search history, returning the number of terms removed.
+pub fn clear_history(conn: &Connection) -> Result<usize> {
+    let tx = write_transaction(conn)?;
+    let removed = tx.execute("DELETE FROM search_history", [])?;
+    tx.commit()?;
+    Ok(removed)
+}
@@ -1,4 +1,16 @@
+pub mod albums;
+pub mod archive;
+pub mod background;
 pub mod cli;
+pub mod db;
+pub mod exiftool;
+pub mod failures;
+pub mod feed;
+pub mod history;
+pub mod metrics;
 pub mod processing;
+pub mod relocate;
 pub mod routes;
-
+pub mod sidecar_scan;
+pub mod suggest;
+pub mod synonyms;
@@ -1,10 +1,21 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{dev::Service, middleware::DefaultHeaders, web, App, HttpServer};
 use clap::Parser;
 mod routes;
 mod cli;
+mod db;
+mod exiftool;
+mod failures;
+mod feed;
+mod history;
+mod metrics;
 mod sidecar_scan;
 mod processing;
+mod relocate;
+mod albums;
 mod background;
+mod archive;
+mod suggest;
+mod synonyms;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -12,28 +23,125 @@ async fn main() -> std::io::Result<()> {
     let args = cli::CliArgs::parse();
     cli::init_logging(&args);
     cli::CLI_ARGS.set(args).expect("CLI_ARGS already set");
-    
+
+    if let Some(path) = &cli::CLI_ARGS.get().unwrap().synonyms_file {
+        if let Err(e) = synonyms::load_synonyms(path) {
+            eprintln!("Error loading synonyms: {}", e);
+        }
+    }
+
+    match cli::CLI_ARGS.get().unwrap().command() {
+        cli::Command::Serve => {}
+        cli::Command::Index => {
+            if let Err(e) = sidecar_scan::scan_and_import_sidecars() {
+                eprintln!("Error importing sidecars: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        cli::Command::Prune => {
+            match sidecar_scan::prune_missing_files() {
+                Ok(removed) => println!("Removed {} missing files from the index", removed),
+                Err(e) => {
+                    eprintln!("Error pruning the index: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        cli::Command::Verify => {
+            processing::cache::migrate_cache_dirs();
+            let sample_percent = cli::CLI_ARGS.get().unwrap().cache_verify_sample;
+            // Nothing else runs, so the check never has to pause
+            let user_active = std::sync::atomic::AtomicBool::new(false);
+            if let Some(summary) = processing::cache::verify_caches(sample_percent, &user_active) {
+                println!(
+                    "Checked {} cache files: {} corrupt, {} removed",
+                    summary.checked, summary.corrupt, summary.removed
+                );
+            }
+            return Ok(());
+        }
+    }
+
+    // Caches written with another layout (e.g. unsharded) are moved once before anything reads them
+    processing::cache::migrate_cache_dirs();
 
     if let Err(e) = sidecar_scan::scan_and_import_sidecars() {
         eprintln!("Error importing sidecars: {}", e);
     }
 
     let port = cli::CLI_ARGS.get().unwrap().port;
+    let http_workers = cli::CLI_ARGS.get().unwrap().http_workers;
+    let keep_alive = std::time::Duration::from_secs(cli::CLI_ARGS.get().unwrap().keep_alive_secs);
+
+    // Framing is controlled by --frame-ancestors; headers set by a handler take precedence
+    let frame_ancestors = &cli::CLI_ARGS.get().unwrap().frame_ancestors;
+    let content_security_policy = routes::content_security_policy(frame_ancestors);
+    let x_frame_options = routes::x_frame_options(frame_ancestors);
 
-    background::start_background_thumbnail_worker();
-    background::start_background_preview_worker();
+    background::start_background_workers();
+    background::start_cache_verifier();
+
+    let server = HttpServer::new(move || {
+        let mut security_headers = DefaultHeaders::new()
+            .add(("Content-Security-Policy", content_security_policy.clone()));
+        if let Some(x_frame_options) = x_frame_options {
+            security_headers = security_headers.add(("X-Frame-Options", x_frame_options));
+        }
 
-    HttpServer::new(|| {
         App::new()
+            .wrap(security_headers)
+            // Count requests per route pattern for /metrics
+            .wrap_fn(|req, srv| {
+                let endpoint = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+                metrics::METRICS.http_requests.with_label_values(&[endpoint.as_str()]).inc();
+                srv.call(req)
+            })
             .route("/", web::get().to(routes::index))
             .route("/health_check", web::get().to(routes::health_check))
+            .route("/metrics", web::get().to(routes::metrics))
+            .route("/rescan", web::post().to(routes::rescan))
             .route("/search", web::get().to(routes::search_page))
+            .route("/detail", web::get().to(routes::detail_fragment))
             .route("/api", web::get().to(routes::api_search))
+            .route("/api/count", web::get().to(routes::api_count))
+            .route("/api/window", web::get().to(routes::api_window))
+            .route("/api/suggest", web::get().to(routes::api_suggest))
+            .route("/api/by-color", web::get().to(routes::api_by_color))
+            .route("/api/recent", web::get().to(routes::api_recent))
+            .route("/feed.json", web::get().to(routes::feed_json))
+            .route("/feed.xml", web::get().to(routes::feed_atom))
+            .route("/api/browse", web::get().to(routes::api_browse))
+            .route("/api/history", web::get().to(routes::api_history))
+            .route("/api/history", web::delete().to(routes::api_clear_history))
+            .route("/api/failures", web::get().to(routes::api_failures))
+            .route("/api/failures", web::delete().to(routes::api_clear_failures))
+            .route("/api/failures/retry", web::post().to(routes::api_retry_failures))
+            .route("/api/download-with-sidecar", web::get().to(routes::download_with_sidecar))
+            .route("/api/albums", web::get().to(routes::api_list_albums))
+            .route("/api/albums", web::post().to(routes::api_create_album))
+            .route("/api/albums/{id}", web::get().to(routes::api_album))
+            .route("/api/albums/{id}/add", web::post().to(routes::api_album_add))
+            .route("/api/albums/{id}/remove", web::delete().to(routes::api_album_remove))
+            .route("/api/metadata/batch", web::post().to(routes::api_metadata_batch))
+            .route("/api/thumbnail-status", web::post().to(routes::api_thumbnail_status))
+            .route("/api/prioritize-previews", web::post().to(routes::api_prioritize_previews))
+            .route("/api/move", web::post().to(routes::api_move))
+            .route("/api/cache/verify", web::post().to(routes::api_cache_verify))
+            .route("/api/cache/stats", web::get().to(routes::api_cache_stats))
+            .route("/api/cache/clear", web::post().to(routes::api_cache_clear))
+            .route("/cache/stats", web::get().to(routes::api_cache_stats))
+            .route("/cache/clear", web::post().to(routes::api_cache_clear))
+            .route("/api/regenerate-search", web::post().to(routes::api_regenerate_search))
             .route("/image/{path:.*}", web::get().to(routes::get_preview))
             .route("/thumbnail/{path:.*}", web::get().to(routes::get_thumbnail))
             .route("/video/{path:.*}", web::get().to(routes::serve_video))
     })
-    .bind(("0.0.0.0", port))?
-    .run()
-    .await
+    // A zero duration disables keep-alive
+    .keep_alive(keep_alive);
+
+    // Without --http-workers actix starts one worker per CPU core
+    let server = if http_workers > 0 { server.workers(http_workers) } else { server };
+    server.bind(("0.0.0.0", port))?.run().await
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+/// Prometheus metrics exposed on GET /metrics.
+pub struct Metrics {
+    pub registry: Registry,
+    /// Requests per route pattern (e.g. "/thumbnail/{path:.*}")
+    pub http_requests: IntCounterVec,
+    /// Cache lookups by cache ("memory", "thumbnail", "preview") and result ("hit", "miss")
+    pub cache_lookups: IntCounterVec,
+    /// Generated thumbnails/previews by kind and result ("success", "failure")
+    pub generated: IntCounterVec,
+    /// Time spent generating uncached thumbnails/previews, by kind
+    pub generation_seconds: HistogramVec,
+    /// Files processed in the current pass of each background worker
+    pub worker_processed: IntGaugeVec,
+    /// Files to process in the current pass of each background worker
+    pub worker_total: IntGaugeVec,
+}
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(|| {
+    let registry = Registry::new();
+
+    let http_requests = IntCounterVec::new(
+        Opts::new("imagefind_http_requests_total", "HTTP requests per endpoint"),
+        &["endpoint"],
+    )
+    .unwrap();
+    let cache_lookups = IntCounterVec::new(
+        Opts::new("imagefind_cache_lookups_total", "Cache lookups by cache and result"),
+        &["cache", "result"],
+    )
+    .unwrap();
+    let generated = IntCounterVec::new(
+        Opts::new("imagefind_generated_total", "Thumbnails and previews generated, by kind and result"),
+        &["kind", "result"],
+    )
+    .unwrap();
+    let generation_seconds = HistogramVec::new(
+        HistogramOpts::new("imagefind_generation_duration_seconds", "Time spent generating thumbnails and previews")
+            .buckets(vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+        &["kind"],
+    )
+    .unwrap();
+    let worker_processed = IntGaugeVec::new(
+        Opts::new("imagefind_worker_processed_files", "Files processed in the current background worker pass"),
+        &["worker"],
+    )
+    .unwrap();
+    let worker_total = IntGaugeVec::new(
+        Opts::new("imagefind_worker_total_files", "Files in the current background worker pass"),
+        &["worker"],
+    )
+    .unwrap();
+
+    registry.register(Box::new(http_requests.clone())).unwrap();
+    registry.register(Box::new(cache_lookups.clone())).unwrap();
+    registry.register(Box::new(generated.clone())).unwrap();
+    registry.register(Box::new(generation_seconds.clone())).unwrap();
+    registry.register(Box::new(worker_processed.clone())).unwrap();
+    registry.register(Box::new(worker_total.clone())).unwrap();
+
+    Metrics {
+        registry,
+        http_requests,
+        cache_lookups,
+        generated,
+        generation_seconds,
+        worker_processed,
+        worker_total,
+    }
+});
+
+// Count a cache lookup as hit or miss
+pub fn record_cache_lookup(cache: &str, hit: bool) {
+    METRICS
+        .cache_lookups
+        .with_label_values(&[cache, if hit { "hit" } else { "miss" }])
+        .inc();
+}
+
+// Time the generation of an uncached thumbnail/preview and count its outcome
+pub fn time_generation<T>(kind: &str, generate: impl FnOnce() -> Option<T>) -> Option<T> {
+    let timer = METRICS.generation_seconds.with_label_values(&[kind]).start_timer();
+    let result = generate();
+    timer.observe_duration();
+    METRICS
+        .generated
+        .with_label_values(&[kind, if result.is_some() { "success" } else { "failure" }])
+        .inc();
+    result
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+pub fn render() -> Result<String, String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .map_err(|e| format!("Failed to encode metrics: {}", e))?;
+    String::from_utf8(buffer).map_err(|e| format!("Metrics are not valid UTF-8: {}", e))
+}
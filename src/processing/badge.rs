@@ -0,0 +1,104 @@
+use image::{Rgb, RgbImage};
+
+/// Highest rating drawn; ratings above are drawn as this many stars
+pub const MAX_RATING_STARS: u8 = 5;
+
+// Star size, margin to the thumbnail edge and gap between stars, in pixels at the base density
+const STAR_SIZE: u32 = 14;
+const STAR_MARGIN: u32 = 4;
+const STAR_GAP: u32 = 2;
+
+// Gold fill of the stars, and how much the strip behind them is darkened (of 255)
+const STAR_COLOR: [u8; 3] = [255, 200, 40];
+const BACKDROP_ALPHA: u32 = 140;
+
+// Inner radius of the star points relative to the outer radius
+const STAR_INNER_RATIO: f32 = 0.4;
+
+// Subsamples per pixel edge for antialiased star edges
+const SUPERSAMPLING: u32 = 4;
+
+/// Draws `rating` filled stars onto a darkened strip in the bottom-left corner of a thumbnail.
+/// `scale` is the thumbnail's pixel density (--thumbnail-scale-factors), so the stars cover the
+/// same area on screen at every density. Stars shrink to fit narrow thumbnails; none are drawn
+/// when they would be too small to tell apart.
+pub fn draw_rating_stars(img: &mut RgbImage, rating: u8, scale: u32) {
+    let count = u32::from(rating.min(MAX_RATING_STARS));
+    if count == 0 {
+        return;
+    }
+    let scale = scale.max(1);
+    let (margin, gap) = (STAR_MARGIN * scale, STAR_GAP * scale);
+    let fitting = img.width().saturating_sub(2 * margin + (count - 1) * gap) / count;
+    let size = (STAR_SIZE * scale).min(fitting).min(img.height().saturating_sub(2 * margin));
+    if size < 6 {
+        return;
+    }
+
+    // Darken a strip behind the stars so they stay visible on bright images
+    let strip_width = count * size + (count - 1) * gap + 2 * margin;
+    let strip_top = img.height() - size - 2 * margin;
+    for y in strip_top..img.height() {
+        for x in 0..strip_width {
+            let pixel = img.get_pixel_mut(x, y);
+            pixel.0 = pixel.0.map(|c| (u32::from(c) * (255 - BACKDROP_ALPHA) / 255) as u8);
+        }
+    }
+
+    let top = img.height() - size - margin;
+    for star in 0..count {
+        let left = margin + star * (size + gap);
+        draw_star(img, left, top, size);
+    }
+}
+
+// Function to fill a five-pointed star, point up, in the `size` square at (`left`, `top`)
+fn draw_star(img: &mut RgbImage, left: u32, top: u32, size: u32) {
+    let outer = size as f32 / 2.0;
+    let center = (left as f32 + outer, top as f32 + outer);
+    let vertices: Vec<(f32, f32)> = (0..10)
+        .map(|i| {
+            let radius = if i % 2 == 0 { outer } else { outer * STAR_INNER_RATIO };
+            let angle = (i as f32 * 36.0 - 90.0).to_radians();
+            (center.0 + radius * angle.cos(), center.1 + radius * angle.sin())
+        })
+        .collect();
+
+    let samples = SUPERSAMPLING * SUPERSAMPLING;
+    for y in top..top + size {
+        for x in left..left + size {
+            let covered = (0..samples)
+                .filter(|sample| {
+                    let sx = x as f32 + ((sample % SUPERSAMPLING) as f32 + 0.5) / SUPERSAMPLING as f32;
+                    let sy = y as f32 + ((sample / SUPERSAMPLING) as f32 + 0.5) / SUPERSAMPLING as f32;
+                    inside_polygon(&vertices, sx, sy)
+                })
+                .count() as u32;
+            if covered == 0 {
+                continue;
+            }
+            let pixel = img.get_pixel_mut(x, y);
+            let mut blended = [0u8; 3];
+            for (channel, (&background, &star)) in blended.iter_mut().zip(pixel.0.iter().zip(STAR_COLOR.iter())) {
+                *channel = ((u32::from(background) * (samples - covered) + u32::from(star) * covered) / samples) as u8;
+            }
+            *pixel = Rgb(blended);
+        }
+    }
+}
+
+// Even-odd test of whether a point lies inside a polygon
+fn inside_polygon(vertices: &[(f32, f32)], x: f32, y: f32) -> bool {
+    let mut inside = false;
+    let mut previous = vertices[vertices.len() - 1];
+    for &current in vertices {
+        if (current.1 > y) != (previous.1 > y) {
+            let crossing = previous.0 + (y - previous.1) / (current.1 - previous.1) * (current.0 - previous.0);
+            if x < crossing {
+                inside = !inside;
+            }
+        }
+        previous = current;
+    }
+    inside
+}
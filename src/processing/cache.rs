@@ -1,17 +1,63 @@
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use lru::LruCache;
+use once_cell::sync::Lazy;
 use sha2::{Sha256, Digest};
 
+use crate::cli::{PreviewFormat, ThumbnailFormat};
+
+// In-memory LRU of recently served base64 thumbnails, consulted before the disk cache.
+// None when disabled with --memory-cache-entries 0.
+static MEMORY_THUMBNAIL_CACHE: Lazy<Mutex<Option<LruCache<String, String>>>> = Lazy::new(|| {
+    let entries = crate::cli::CLI_ARGS.get().map(|a| a.memory_cache_entries).unwrap_or(0);
+    log::debug!("In-memory thumbnail cache capacity: {} entries", entries);
+    Mutex::new(NonZeroUsize::new(entries).map(LruCache::new))
+});
+
+// Function to get a thumbnail from the in-memory cache
+pub fn get_memory_thumbnail(cache_key: &str) -> Option<String> {
+    let mut cache = MEMORY_THUMBNAIL_CACHE.lock().unwrap();
+    let hit = cache.as_mut()?.get(cache_key).cloned();
+    crate::metrics::record_cache_lookup("memory", hit.is_some());
+    log::trace!("In-memory thumbnail cache {} for key: {}", if hit.is_some() { "hit" } else { "miss" }, cache_key);
+    hit
+}
+
+// Function to store a base64 thumbnail in the in-memory cache
+pub fn put_memory_thumbnail(cache_key: &str, thumbnail_base64: &str) {
+    if let Some(cache) = MEMORY_THUMBNAIL_CACHE.lock().unwrap().as_mut() {
+        cache.put(cache_key.to_string(), thumbnail_base64.to_string());
+    }
+}
+
+// Function to drop a thumbnail from the in-memory cache, e.g. when it is regenerated
+pub fn invalidate_memory_thumbnail(cache_key: &str) {
+    if let Some(cache) = MEMORY_THUMBNAIL_CACHE.lock().unwrap().as_mut() {
+        cache.pop(cache_key);
+    }
+}
+
+// Function to empty the in-memory cache, e.g. when the thumbnail cache directory is cleared
+pub fn clear_memory_thumbnails() {
+    if let Some(cache) = MEMORY_THUMBNAIL_CACHE.lock().unwrap().as_mut() {
+        cache.clear();
+    }
+}
+
 // Function to get thumbnail cache directory path
 pub fn get_cache_dir() -> std::path::PathBuf {
     // Try to get from CLI args if available, otherwise use temp directory for tests
-    match std::panic::catch_unwind(|| crate::cli::get_cli_args()) {
+    match std::panic::catch_unwind(crate::cli::get_cli_args) {
         Ok(args) => {
             let cache_dir = Path::new(&args.thumbnail_cache);
             if !cache_dir.exists() {
                 log::info!("Creating thumbnail cache directory: {}", cache_dir.display());
-                fs::create_dir_all(&cache_dir).expect("Failed to create cache directory");
+                fs::create_dir_all(cache_dir).expect("Failed to create cache directory");
             } else {
                 log::trace!("Thumbnail cache directory exists: {}", cache_dir.display());
             }
@@ -31,12 +77,12 @@ pub fn get_cache_dir() -> std::path::PathBuf {
 // Function to get cache directory path for full images
 pub fn get_preview_cache_dir() -> std::path::PathBuf {
     // Try to get from CLI args if available, otherwise use temp directory for tests
-    match std::panic::catch_unwind(|| crate::cli::get_cli_args()) {
+    match std::panic::catch_unwind(crate::cli::get_cli_args) {
         Ok(args) => {
             let cache_dir = Path::new(&args.full_image_cache);
             if !cache_dir.exists() {
                 log::info!("Creating preview cache directory: {}", cache_dir.display());
-                fs::create_dir_all(&cache_dir).expect("Failed to create full image cache directory");
+                fs::create_dir_all(cache_dir).expect("Failed to create full image cache directory");
             } else {
                 log::trace!("Preview cache directory exists: {}", cache_dir.display());
             }
@@ -62,19 +108,382 @@ pub fn generate_cache_key(file_path: &str) -> String {
     key
 }
 
-// Function to get cached thumbnail from disk
-pub fn get_cached_thumbnail(cache_key: &str) -> Option<String> {
+// Function to get what cache keys identify a source file by: its path, or with --cache-key content
+// its size and modification time, so an image edited in place gets new keys and a moved one keeps
+// them. Files that cannot be stat'd, such as missing ones, fall back to the path.
+fn key_source(file_path: &str) -> String {
+    let by_content = crate::cli::CLI_ARGS.get().is_some_and(|a| a.cache_key == crate::cli::CacheKey::Content);
+    if !by_content {
+        return file_path.to_string();
+    }
+    let modified = fs::metadata(file_path).and_then(|m| Ok((m.len(), m.modified()?)));
+    match modified {
+        Ok((size, modified)) => {
+            let mtime = modified.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+            format!("#content{}@{}.{:09}", size, mtime.as_secs(), mtime.subsec_nanos())
+        }
+        Err(e) => {
+            log::trace!("Keying cache files of {} by path, it cannot be stat'd: {}", file_path, e);
+            file_path.to_string()
+        }
+    }
+}
+
+// Function to generate the thumbnail cache key. Square (fill) thumbnails are cached under a
+// different key than aspect-preserving ones, so switching --thumbnail-crop never serves
+// the other variant; fit keeps the plain path key used by earlier versions.
+pub fn thumbnail_cache_key(file_path: &str) -> String {
+    thumbnail_key(file_path, thumbnail_fill(), 1)
+}
+
+// Function to generate the cache key of a thumbnail at a pixel density (--thumbnail-scale-factors).
+// The base density keeps the key of `thumbnail_cache_key`; others add e.g. "@2x" to the path.
+pub fn scaled_thumbnail_cache_key(file_path: &str, scale: u32) -> String {
+    thumbnail_key(file_path, thumbnail_fill(), scale)
+}
+
+// Function to generate the cache key of a thumbnail with a rating badge (`?badge=rating`). The
+// rating is part of the key, so a changed rating draws a new badge instead of serving the old one.
+pub fn badged_thumbnail_cache_key(file_path: &str, scale: u32, rating: u8) -> String {
+    generate_cache_key(&format!("{}#rating{}", scaled_thumbnail_cache_key(file_path, scale), rating))
+}
+
+// Function to generate the cache key of a thumbnail of either shape at a pixel density. Thumbnails
+// encoded with other than the default settings, from --thumbnail-quality, --thumbnail-size or
+// --quality-override, add them to the path (e.g. "#q85s300"), so changing them never serves
+// thumbnails of the old ones. The same goes for a --thumbnail-format other than JPEG ("#avif").
+fn thumbnail_key(file_path: &str, fill: bool, scale: u32) -> String {
+    let fill = if fill { "#fill" } else { "" };
+    let format = match super::image::thumbnail_format() {
+        ThumbnailFormat::Jpeg => String::new(),
+        format => format!("#{}", format.name()),
+    };
+    let settings = super::image::quality_settings(file_path);
+    let quality = if settings.thumbnail_quality == super::image::THUMBNAIL_QUALITY
+        && settings.thumbnail_size == super::image::THUMBNAIL_SIZE
+    {
+        String::new()
+    } else {
+        format!("#q{}s{}", settings.thumbnail_quality, settings.thumbnail_size)
+    };
+    let source = key_source(file_path);
+    if scale <= 1 {
+        generate_cache_key(&format!("{}{}{}{}", source, fill, quality, format))
+    } else {
+        generate_cache_key(&format!("{}{}{}{}@{}x", source, fill, quality, format, scale))
+    }
+}
+
+// Function to get the part of preview cache keys that sets apart previews encoded with another
+// quality from --preview-quality or --quality-override (e.g. "#q90"); empty with the default quality
+fn preview_quality_key(file_path: &str) -> String {
+    let quality = super::image::quality_settings(file_path).preview_quality;
+    if quality == super::image::PREVIEW_QUALITY {
+        String::new()
+    } else {
+        format!("#q{}", quality)
+    }
+}
+
+// Function to generate the cache key of the full-size preview
+pub fn preview_cache_key(file_path: &str) -> String {
+    generate_cache_key(&format!("{}{}", key_source(file_path), preview_quality_key(file_path)))
+}
+
+// Function to get the pixel densities thumbnails are generated at, in ascending order and always
+// including the base density
+pub fn thumbnail_scale_factors() -> Vec<u32> {
+    let mut factors = vec![1];
+    if let Some(args) = crate::cli::CLI_ARGS.get() {
+        factors.extend(&args.thumbnail_scale_factors);
+    }
+    factors.sort_unstable();
+    factors.dedup();
+    factors
+}
+
+// Function to pick the thumbnail density for a device pixel ratio (?dpr=): the lowest configured
+// one covering it, or the highest when none does
+pub fn thumbnail_scale_for_dpr(dpr: f32) -> u32 {
+    let factors = thumbnail_scale_factors();
+    factors
+        .iter()
+        .copied()
+        .find(|&scale| scale as f32 >= dpr)
+        .unwrap_or_else(|| factors.last().copied().unwrap_or(1))
+}
+
+// Function to generate the cache key of a preview scaled to a requested width (/image/{path}?w=)
+pub fn sized_preview_cache_key(file_path: &str, width: u32) -> String {
+    generate_cache_key(&format!("{}{}#w{}", key_source(file_path), preview_quality_key(file_path), width))
+}
+
+// Function to generate the cache key of a preview encoded in another format than JPEG
+// (--preview-formats), at full size or a requested width
+pub fn preview_variant_cache_key(file_path: &str, width: Option<u32>, format: PreviewFormat) -> String {
+    let source = key_source(file_path);
+    let quality = preview_quality_key(file_path);
+    match width {
+        Some(width) => generate_cache_key(&format!("{}{}#w{}#{}", source, quality, width, format.name())),
+        None => generate_cache_key(&format!("{}{}#{}", source, quality, format.name())),
+    }
+}
+
+// Function to get the number of leading cache key characters that name a cache file's
+// subdirectory (--cache-shard-chars), so no directory holds more than a fraction of the files
+fn cache_shard_chars() -> usize {
+    crate::cli::CLI_ARGS.get().map(|a| usize::from(a.cache_shard_chars)).unwrap_or(2)
+}
+
+// Extension of preview cache files. Previews in another --preview-formats format keep it too, as
+// they fall back to the JPEG bytes when those are smaller.
+pub const PREVIEW_FILE_EXTENSION: &str = "jpg";
+
+// Extensions of cache files: JPEG thumbnails and previews, and AVIF or WebP thumbnails
+const CACHE_FILE_EXTENSIONS: &[&str] = &["jpg", "avif", "webp"];
+
+// Function to get the extension of thumbnail cache files, that of the --thumbnail-format
+fn thumbnail_file_extension() -> &'static str {
+    super::image::thumbnail_format().extension()
+}
+
+// Function to get the path of a cache file, e.g. "<dir>/ab/abcdef....jpg" for the "jpg" extension,
+// or directly in the cache directory with --cache-shard-chars 0
+pub fn cache_file_path(cache_dir: &Path, cache_key: &str, extension: &str) -> PathBuf {
+    let file_name = format!("{}.{}", cache_key, extension);
+    match cache_key.get(..cache_shard_chars()) {
+        Some(shard) if !shard.is_empty() => cache_dir.join(shard).join(file_name),
+        _ => cache_dir.join(file_name),
+    }
+}
+
+/// Moves the files of both image caches that were written under another --cache-shard-chars
+/// setting, such as the flat layout of earlier versions, to where they are looked up now.
+pub fn migrate_cache_dirs() {
+    for cache_dir in [get_cache_dir(), get_preview_cache_dir()] {
+        let moved = migrate_cache_layout(&cache_dir);
+        if moved > 0 {
+            log::info!("Moved {} cache files in {} to the current layout", moved, cache_dir.display());
+        }
+    }
+}
+
+/// Moves cache files in a cache directory and its subdirectories to their current location.
+/// Returns the number of files moved; emptied subdirectories are removed.
+pub fn migrate_cache_layout(cache_dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return 0;
+    };
+    // Collected first, as moving files creates new subdirectories
+    let entries: Vec<fs::DirEntry> = entries.flatten().collect();
+    let mut moved = 0;
+    for entry in entries {
+        let path = entry.path();
+        if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            moved += usize::from(migrate_cache_file(cache_dir, &path));
+        } else if entry.file_name() == CONTENT_DIR {
+            moved += migrate_cache_layout(&path);
+        } else if let Ok(files) = fs::read_dir(&path) {
+            for file in files.flatten() {
+                moved += usize::from(migrate_cache_file(cache_dir, &file.path()));
+            }
+            // Only succeeds once the subdirectory is empty
+            let _ = fs::remove_dir(&path);
+        }
+    }
+    moved
+}
+
+// Function to split the name of a cache file into its key and extension, None for other files
+fn cache_file_key(path: &Path) -> Option<(&str, &str)> {
+    let (cache_key, extension) = path.file_name()?.to_str()?.rsplit_once('.')?;
+    CACHE_FILE_EXTENSIONS.contains(&extension).then_some((cache_key, extension))
+}
+
+// Function to move one "<key>.jpg" (or .avif, .webp) cache file to the path of its key. An existing
+// file at the new path wins, the misplaced one is dropped. Other files are left alone.
+fn migrate_cache_file(cache_dir: &Path, path: &Path) -> bool {
+    let Some((cache_key, extension)) = cache_file_key(path) else {
+        return false;
+    };
+    let target = cache_file_path(cache_dir, cache_key, extension);
+    if cache_key.is_empty() || target == path || !path.is_file() {
+        return false;
+    }
+    let result = if target.exists() {
+        fs::remove_file(path)
+    } else {
+        target.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::rename(path, &target))
+    };
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("Failed to move cache file {} to {}: {}", path.display(), target.display(), e);
+            false
+        }
+    }
+}
+
+// Function to list the thumbnail cache keys of an image: both shapes at every density
+fn thumbnail_keys(file_path: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    for scale in thumbnail_scale_factors() {
+        for fill in [false, true] {
+            keys.push(thumbnail_key(file_path, fill, scale));
+        }
+    }
+    keys
+}
+
+// Function to list the preview cache keys of an image: the full-size preview and every width and
+// format of sized previews, in the same order for any path
+fn preview_keys(file_path: &str) -> Vec<String> {
+    let mut widths: Vec<u32> = (super::image::PREVIEW_MIN_WIDTH..super::image::PREVIEW_MAX_DIMENSION)
+        .filter_map(super::image::preview_width)
+        .collect();
+    widths.dedup();
+
+    let mut keys = vec![preview_cache_key(file_path)];
+    let formats = crate::cli::CLI_ARGS.get().map(|a| a.preview_formats.clone()).unwrap_or_default();
+    for width in std::iter::once(None).chain(widths.iter().copied().map(Some)) {
+        for &format in &formats {
+            keys.push(preview_variant_cache_key(file_path, width, format));
+        }
+    }
+    keys.extend(widths.into_iter().map(|width| sized_preview_cache_key(file_path, width)));
+    keys
+}
+
+/// Renames the cache files of an image that moved to another path, so its thumbnails and
+/// previews are served without regenerating them: both thumbnail shapes, the full-size preview
+/// and every width of sized previews. Files encoded with other --quality-override settings than
+/// the new path's are left to be regenerated. Returns the number of files renamed.
+pub fn rename_cached_files(old_path: &str, new_path: &str) -> usize {
+    let thumbnail_dir = get_cache_dir();
+    let preview_dir = get_preview_cache_dir();
+    let old_settings = super::image::quality_settings(old_path);
+    let new_settings = super::image::quality_settings(new_path);
+    let thumbnail_extension = thumbnail_file_extension();
+    let mut keys: Vec<(&Path, &str, String, String)> = Vec::new();
+    if (old_settings.thumbnail_quality, old_settings.thumbnail_size) == (new_settings.thumbnail_quality, new_settings.thumbnail_size) {
+        keys.extend(
+            thumbnail_keys(old_path)
+                .into_iter()
+                .zip(thumbnail_keys(new_path))
+                .map(|(old_key, new_key)| (thumbnail_dir.as_path(), thumbnail_extension, old_key, new_key)),
+        );
+    }
+    if old_settings.preview_quality == new_settings.preview_quality {
+        keys.extend(
+            preview_keys(old_path)
+                .into_iter()
+                .zip(preview_keys(new_path))
+                .map(|(old_key, new_key)| (preview_dir.as_path(), PREVIEW_FILE_EXTENSION, old_key, new_key)),
+        );
+    }
+
+    let mut renamed = 0;
+    for (cache_dir, extension, old_key, new_key) in keys {
+        invalidate_memory_thumbnail(&old_key);
+        let old_file = cache_file_path(cache_dir, &old_key, extension);
+        if !old_file.is_file() {
+            continue;
+        }
+        let new_file = cache_file_path(cache_dir, &new_key, extension);
+        // Hardlinks of --dedupe-cache stay linked to their shared file when renamed
+        let result = new_file.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::rename(&old_file, &new_file));
+        match result {
+            Ok(()) => renamed += 1,
+            Err(e) => log::warn!("Failed to rename cache file {} to {}: {}", old_file.display(), new_file.display(), e),
+        }
+    }
+    renamed
+}
+
+/// Deletes the cached thumbnails (both shapes, every density, rating badges) and/or previews
+/// (full-size, every width and format) of an image, also from the in-memory cache, so they are
+/// regenerated with the current settings. Returns the number of files deleted.
+pub fn remove_cached_files(file_path: &str, thumbnails: bool, previews: bool) -> usize {
+    let thumbnail_dir = get_cache_dir();
+    let preview_dir = get_preview_cache_dir();
+    let thumbnail_extension = thumbnail_file_extension();
+    let mut keys: Vec<(&Path, &str, String)> = Vec::new();
+    if thumbnails {
+        keys.extend(thumbnail_keys(file_path).into_iter().map(|key| (thumbnail_dir.as_path(), thumbnail_extension, key)));
+        for scale in thumbnail_scale_factors() {
+            keys.extend((0..=5).map(|rating| {
+                (thumbnail_dir.as_path(), thumbnail_extension, badged_thumbnail_cache_key(file_path, scale, rating))
+            }));
+        }
+    }
+    if previews {
+        keys.extend(preview_keys(file_path).into_iter().map(|key| (preview_dir.as_path(), PREVIEW_FILE_EXTENSION, key)));
+    }
+
+    let mut removed = 0;
+    for (cache_dir, extension, key) in keys {
+        invalidate_memory_thumbnail(&key);
+        let cache_file = cache_file_path(cache_dir, &key, extension);
+        match fs::remove_file(&cache_file) {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to remove cache file {}: {}", cache_file.display(), e),
+        }
+    }
+    removed
+}
+
+// Function to tell whether thumbnails are center-cropped to a square (--thumbnail-crop fill)
+pub fn thumbnail_fill() -> bool {
+    crate::cli::CLI_ARGS
+        .get()
+        .map(|a| a.thumbnail_crop == crate::cli::ThumbnailCrop::Fill)
+        .unwrap_or(false)
+}
+
+// Function to check whether the source image was modified after its cache file was written.
+// Only done with --revalidate-cache; a missing source or cache file is never stale.
+fn is_cache_stale(cache_file: &Path, source_path: &str) -> bool {
+    if !crate::cli::CLI_ARGS.get().map(|a| a.revalidate_cache).unwrap_or(false) {
+        return false;
+    }
+    let source_modified = fs::metadata(source_path).and_then(|m| m.modified());
+    let cache_modified = fs::metadata(cache_file).and_then(|m| m.modified());
+    match (source_modified, cache_modified) {
+        (Ok(source_modified), Ok(cache_modified)) if source_modified > cache_modified => {
+            log::debug!("Source {} is newer than cache file {}", source_path, cache_file.display());
+            true
+        }
+        _ => false,
+    }
+}
+
+// Function to get cached thumbnail, from memory first and then from disk
+pub fn get_cached_thumbnail(cache_key: &str, source_path: &str) -> Option<String> {
     let cache_dir = get_cache_dir();
-    let cache_file = cache_dir.join(format!("{}.jpg", cache_key));
+    let cache_file = cache_file_path(&cache_dir, cache_key, thumbnail_file_extension());
+
+    if is_cache_stale(&cache_file, source_path) {
+        invalidate_memory_thumbnail(cache_key);
+        crate::metrics::record_cache_lookup("thumbnail", false);
+        return None;
+    }
+
+    if let Some(cached) = get_memory_thumbnail(cache_key) {
+        return Some(cached);
+    }
     
     log::trace!("Checking thumbnail cache for key: {}", cache_key);
     
+    crate::metrics::record_cache_lookup("thumbnail", cache_file.exists());
     if cache_file.exists() {
         log::debug!("Found cached thumbnail: {}", cache_file.display());
         match fs::read(&cache_file) {
             Ok(bytes) => {
                 log::trace!("Successfully read cached thumbnail, size: {} bytes", bytes.len());
-                Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &bytes))
+                let thumbnail_base64 = cached_thumbnail_base64(bytes);
+                put_memory_thumbnail(cache_key, &thumbnail_base64);
+                Some(thumbnail_base64)
             },
             Err(e) => {
                 log::warn!("Failed to read cached thumbnail {}: {}", cache_file.display(), e);
@@ -87,14 +496,79 @@ pub fn get_cached_thumbnail(cache_key: &str) -> Option<String> {
     }
 }
 
-// Function to save thumbnail to disk cache
+// Function to turn the content of a thumbnail cache file into base64. Files written with
+// --thumbnail-cache-format base64 hold the text already, so caches written in either format
+// are read alike.
+fn cached_thumbnail_base64(bytes: Vec<u8>) -> String {
+    if !is_base64_image(&bytes) {
+        return base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &bytes);
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => base64::Engine::encode(&base64::engine::general_purpose::STANDARD, e.as_bytes()),
+    }
+}
+
+// Function to check whether cache file content is base64 text of an image, by decoding its first
+// characters: "/9j/" is the JPEG SOI marker, and AVIF and WebP are recognized by their headers.
+// Raw image bytes never decode, as they start with characters outside the base64 alphabet.
+fn is_base64_image(bytes: &[u8]) -> bool {
+    if bytes.starts_with(b"/9j/") {
+        return true;
+    }
+    bytes
+        .get(..16)
+        .and_then(|head| base64::Engine::decode(&base64::engine::general_purpose::STANDARD, head).ok())
+        .is_some_and(|head| cached_image_content_type(&head) != "image/jpeg")
+}
+
+/// Tells the content type of a cached thumbnail or preview from its leading bytes: AVIF or WebP,
+/// else JPEG. Preview variants that were not smaller than the JPEG are cached as the JPEG, and
+/// thumbnails keep the encoding they were generated with.
+pub fn cached_image_content_type(bytes: &[u8]) -> &'static str {
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        PreviewFormat::Webp.mime_type()
+    } else if bytes.len() >= 12 && &bytes[4..12] == b"ftypavif" {
+        PreviewFormat::Avif.mime_type()
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// Content type of a base64 thumbnail as returned by the thumbnail functions, read from its
+/// first bytes; video thumbnails that ffmpeg wrote stay JPEG whatever --thumbnail-format says
+pub fn thumbnail_content_type(thumbnail_base64: &str) -> &'static str {
+    let head = thumbnail_base64.get(..16).unwrap_or(thumbnail_base64);
+    match base64::Engine::decode(&base64::engine::general_purpose::STANDARD, head) {
+        Ok(bytes) => cached_image_content_type(&bytes),
+        Err(_) => "image/jpeg",
+    }
+}
+
+// Function to save thumbnail to disk cache. Stored as raw image bytes, or as base64 text with
+// --thumbnail-cache-format base64; gzip saves only ~1% on JPEG, so cache files are deliberately
+// left uncompressed.
 pub fn save_thumbnail_to_cache(cache_key: &str, jpeg_bytes: &[u8]) -> io::Result<()> {
     let cache_dir = get_cache_dir();
-    let cache_file = cache_dir.join(format!("{}.jpg", cache_key));
+    let cache_file = cache_file_path(&cache_dir, cache_key, thumbnail_file_extension());
     
     log::debug!("Saving thumbnail to cache: {} ({} bytes)", cache_file.display(), jpeg_bytes.len());
+    // A (re)generated thumbnail replaces whatever is held in memory for this key
+    invalidate_memory_thumbnail(cache_key);
+
+    let store_base64 = crate::cli::CLI_ARGS
+        .get()
+        .is_some_and(|a| a.thumbnail_cache_format == crate::cli::ThumbnailCacheFormat::Base64);
+    let encoded;
+    let file_bytes = if store_base64 {
+        encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, jpeg_bytes);
+        encoded.as_bytes()
+    } else {
+        jpeg_bytes
+    };
     
-    match fs::write(&cache_file, jpeg_bytes) {
+    let max_size = crate::cli::CLI_ARGS.get().map(|a| a.max_thumbnail_cache_size).unwrap_or(0);
+    match write_cache_file(&cache_dir, &cache_file, file_bytes, max_size) {
         Ok(_) => {
             log::trace!("Successfully saved thumbnail to cache: {}", cache_file.display());
             Ok(())
@@ -107,12 +581,18 @@ pub fn save_thumbnail_to_cache(cache_key: &str, jpeg_bytes: &[u8]) -> io::Result
 }
 
 // Function to get cached full image from disk
-pub fn get_cached_preview(cache_key: &str) -> Option<String> {
+pub fn get_cached_preview(cache_key: &str, source_path: &str) -> Option<String> {
     let cache_dir = get_preview_cache_dir();
-    let cache_file = cache_dir.join(format!("{}.jpg", cache_key));
+    let cache_file = cache_file_path(&cache_dir, cache_key, PREVIEW_FILE_EXTENSION);
+
+    if is_cache_stale(&cache_file, source_path) {
+        crate::metrics::record_cache_lookup("preview", false);
+        return None;
+    }
     
     log::trace!("Checking if preview is cached using key: {}", cache_key);
     
+    crate::metrics::record_cache_lookup("preview", cache_file.exists());
     if cache_file.exists() {
         log::debug!("Found cached preview: {}", cache_file.display());
         match fs::read(&cache_file) {
@@ -134,11 +614,12 @@ pub fn get_cached_preview(cache_key: &str) -> Option<String> {
 // Function to save full image to disk cache
 pub fn save_preview_to_cache(cache_key: &str, image_bytes: &[u8]) -> io::Result<()> {
     let cache_dir = get_preview_cache_dir();
-    let cache_file = cache_dir.join(format!("{}.jpg", cache_key));
+    let cache_file = cache_file_path(&cache_dir, cache_key, PREVIEW_FILE_EXTENSION);
 
     log::debug!("Saving preview to cache: {} ({} bytes)", cache_file.display(), image_bytes.len());
 
-    match fs::write(&cache_file, image_bytes) {
+    let max_size = crate::cli::CLI_ARGS.get().map(|a| a.max_full_image_cache_size).unwrap_or(0);
+    match write_cache_file(&cache_dir, &cache_file, image_bytes, max_size) {
         Ok(_) => {
             log::trace!("Successfully saved preview to cache: {}", cache_file.display());
             Ok(())
@@ -150,9 +631,368 @@ pub fn save_preview_to_cache(cache_key: &str, image_bytes: &[u8]) -> io::Result<
     }
 }
 
+/// Free space available to this process on the file system holding the path, which need not
+/// exist yet (its closest existing ancestor is checked). None when it cannot be determined.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    let c_path = std::ffi::CString::new(existing.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // The field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Whether the file system holding the path has at least `min_free_bytes` available.
+/// Assumes it does when the free space cannot be determined.
+pub fn has_free_space(path: &Path, min_free_bytes: u64) -> bool {
+    min_free_bytes == 0 || available_space(path).is_none_or(|available| available >= min_free_bytes)
+}
+
+// Cache directories currently below --min-free-bytes, so the change is logged once, not per write
+static LOW_SPACE_DIRS: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// Function to check a cache directory against --min-free-bytes, logging when it runs low and recovers
+pub fn cache_has_free_space(cache_dir: &Path) -> bool {
+    let min_free_bytes = crate::cli::CLI_ARGS.get().map(|a| a.min_free_bytes).unwrap_or(0);
+    let has_space = has_free_space(cache_dir, min_free_bytes);
+    let mut low_space_dirs = LOW_SPACE_DIRS.lock().unwrap();
+    if !has_space && low_space_dirs.insert(cache_dir.to_path_buf()) {
+        log::warn!(
+            "Less than {} bytes free for cache directory {}, serving thumbnails/previews without caching them",
+            min_free_bytes,
+            cache_dir.display()
+        );
+    } else if has_space && low_space_dirs.remove(cache_dir) {
+        log::info!("Free space recovered for cache directory {}, caching again", cache_dir.display());
+    }
+    has_space
+}
+
+// Subdirectory of a cache directory holding the shared files of --dedupe-cache
+const CONTENT_DIR: &str = "by-content";
+
+// Distinguishes concurrent temporary files in the content directory
+static CONTENT_TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Function to write a cache file. With --dedupe-cache, identical outputs share one file: the
+// bytes are stored once under by-content/ (named by their sha256) and each cache key is a hardlink to it.
+// Falls back to a plain copy when the file system does not support hardlinks. With a `max_size`
+// (--max-thumbnail-cache-size, --max-full-image-cache-size), older files are evicted to stay below it.
+fn write_cache_file(cache_dir: &Path, cache_file: &Path, bytes: &[u8], max_size: u64) -> io::Result<()> {
+    let replaced_len = fs::metadata(cache_file).map(|m| m.len()).unwrap_or(0);
+    // Never write through an existing file, it may be a hardlink shared with other keys
+    match fs::remove_file(cache_file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    if max_size > 0 {
+        reserve_cache_space(cache_dir, cache_file, replaced_len, bytes.len() as u64, max_size);
+    }
+    // Below --min-free-bytes the output is only served; the replaced entry is gone either way
+    if !cache_has_free_space(cache_dir) {
+        log::debug!("Not caching {}, low on disk space", cache_file.display());
+        return Ok(());
+    }
+    if let Some(shard_dir) = cache_file.parent() {
+        fs::create_dir_all(shard_dir)?;
+    }
+    if crate::cli::CLI_ARGS.get().map(|a| a.dedupe_cache).unwrap_or(false) {
+        match link_to_content(cache_dir, cache_file, bytes) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                log::debug!("Could not deduplicate {}, writing a copy: {}", cache_file.display(), e);
+                let _ = fs::remove_file(cache_file);
+            }
+        }
+    }
+    fs::write(cache_file, bytes)
+}
+
+// Tracked size of the cache files in each size-capped cache directory, counted once on its first
+// write and then kept up to date by the writes and evictions. Files removed otherwise make it too
+// high, which only brings the next eviction, and its recount, forward.
+static CACHE_DIR_SIZES: Lazy<Mutex<HashMap<PathBuf, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Eviction frees space down to this share of the cap, so it does not run again on the next write
+const EVICTION_TARGET_PERCENT: u64 = 90;
+
+// Function to account for a cache file about to be written in place of `replaced_len` bytes,
+// evicting the least recently used files of the cache directory first when it would outgrow
+// `max_size`. Best effort: files that cannot be removed are logged and skipped.
+fn reserve_cache_space(cache_dir: &Path, cache_file: &Path, replaced_len: u64, len: u64, max_size: u64) {
+    let mut sizes = CACHE_DIR_SIZES.lock().unwrap();
+    let size = sizes
+        .entry(cache_dir.to_path_buf())
+        .or_insert_with(|| cache_files_by_age(cache_dir).iter().map(|(_, _, len)| len).sum());
+    *size = size.saturating_sub(replaced_len) + len;
+    if *size <= max_size {
+        return;
+    }
+
+    let target = max_size / 100 * EVICTION_TARGET_PERCENT;
+    let files = cache_files_by_age(cache_dir);
+    let mut remaining: u64 = files.iter().map(|(_, _, len)| len).sum::<u64>() + len;
+    let (mut evicted, mut freed) = (0, 0);
+    for (path, _, file_len) in files {
+        if remaining <= target {
+            break;
+        }
+        if path == cache_file {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                if let Some(cache_key) = path.file_stem().and_then(|n| n.to_str()) {
+                    invalidate_memory_thumbnail(cache_key);
+                }
+                remaining -= file_len;
+                freed += file_len;
+                evicted += 1;
+            }
+            Err(e) => log::warn!("Failed to evict cache file {}: {}", path.display(), e),
+        }
+    }
+    *size = remaining;
+    log::info!(
+        "Evicted {} files ({} bytes) from cache directory {} to stay below {} bytes",
+        evicted,
+        freed,
+        cache_dir.display(),
+        max_size
+    );
+}
+
+// Function to list the "<key>.jpg" (or .avif, .webp) files of a cache directory with their last access (or, where
+// access times are not kept, modification) time and size, least recently used first. The shared
+// files of --dedupe-cache are left out; they stay until the cache directory is cleared.
+fn cache_files_by_age(cache_dir: &Path) -> Vec<(PathBuf, std::time::SystemTime, u64)> {
+    let mut files: Vec<(PathBuf, std::time::SystemTime, u64)> = walkdir::WalkDir::new(cache_dir)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() != 1 || entry.file_name() != CONTENT_DIR)
+        .flatten()
+        .filter(|entry| entry.file_type().is_file() && cache_file_key(entry.path()).is_some())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let used = metadata.accessed().or_else(|_| metadata.modified()).ok()?;
+            Some((entry.into_path(), used, metadata.len()))
+        })
+        .collect();
+    files.sort_by_key(|(_, used, _)| *used);
+    files
+}
+
+// Function to hardlink a cache file to the shared file with the same content, storing it first if new
+fn link_to_content(cache_dir: &Path, cache_file: &Path, bytes: &[u8]) -> io::Result<()> {
+    let digest = format!("{:x}", Sha256::digest(bytes));
+    let extension = cache_file.extension().and_then(|e| e.to_str()).unwrap_or(PREVIEW_FILE_EXTENSION);
+    let content_file = cache_file_path(&cache_dir.join(CONTENT_DIR), &digest, extension);
+    let content_dir = content_file.parent().unwrap_or(cache_dir).to_path_buf();
+    fs::create_dir_all(&content_dir)?;
+
+    if content_file.exists() {
+        log::debug!("Identical cache content already stored, linking {}", cache_file.display());
+        // Linked keys share one modification time; keep it recent for --revalidate-cache
+        fs::File::options().write(true).open(&content_file)?.set_modified(std::time::SystemTime::now())?;
+    } else {
+        // Renamed into place so a concurrent writer never links a partially written file
+        let counter = CONTENT_TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let tmp_file = content_dir.join(format!("{}.{}.{}.tmp", digest, std::process::id(), counter));
+        if let Err(e) = fs::write(&tmp_file, bytes).and_then(|_| fs::rename(&tmp_file, &content_file)) {
+            let _ = fs::remove_file(&tmp_file);
+            return Err(e);
+        }
+    }
+    fs::hard_link(&content_file, cache_file)
+}
+
+/// Files and bytes deleted by [`clear_cache_dir`]
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct CacheClearSummary {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Deletes every file below a cache directory, including the shared files of --dedupe-cache,
+/// and the emptied subdirectories, keeping the directory itself. Symbolic links are deleted,
+/// never followed, so nothing outside the directory is touched. Hardlinked entries count their
+/// bytes once per link. Files that cannot be deleted are logged and left.
+pub fn clear_cache_dir(cache_dir: &Path) -> CacheClearSummary {
+    let mut summary = CacheClearSummary::default();
+    // Subdirectories are visited after their files, so they are empty by then
+    for entry in walkdir::WalkDir::new(cache_dir).min_depth(1).contents_first(true).into_iter().flatten() {
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            // Only fails when a file in it could not be deleted
+            let _ = fs::remove_dir(path);
+            continue;
+        }
+        let len = if entry.file_type().is_file() { entry.metadata().map(|m| m.len()).unwrap_or(0) } else { 0 };
+        match fs::remove_file(path) {
+            Ok(()) => {
+                summary.files += 1;
+                summary.bytes += len;
+            }
+            Err(e) => log::warn!("Failed to delete cache file {}: {}", path.display(), e),
+        }
+    }
+    // Counted again on the next write with a size cap
+    CACHE_DIR_SIZES.lock().unwrap().remove(cache_dir);
+    summary
+}
+
+/// File count, size and age range of a cache directory, see [`cache_dir_stats`]
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct CacheStats {
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+    /// Modification time of the oldest file as a UTC Unix timestamp, None when empty
+    pub oldest: Option<i64>,
+    /// Modification time of the newest file as a UTC Unix timestamp, None when empty
+    pub newest: Option<i64>,
+}
+
+/// Counts the files below a cache directory with their total size and the modification times
+/// of the oldest and newest. Symbolic links are neither followed nor counted, and hardlinked
+/// --dedupe-cache entries count their bytes once per link. A missing directory is empty.
+pub fn cache_dir_stats(cache_dir: &Path) -> CacheStats {
+    let mut stats = CacheStats { path: cache_dir.display().to_string(), ..CacheStats::default() };
+    let files = walkdir::WalkDir::new(cache_dir).into_iter().flatten().filter(|entry| entry.file_type().is_file());
+    for metadata in files.filter_map(|entry| entry.metadata().ok()) {
+        stats.files += 1;
+        stats.bytes += metadata.len();
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs() as i64);
+        if let Some(modified) = modified {
+            stats.oldest = Some(stats.oldest.map_or(modified, |oldest| oldest.min(modified)));
+            stats.newest = Some(stats.newest.map_or(modified, |newest| newest.max(modified)));
+        }
+    }
+    stats
+}
+
 // Function to check if a thumbnail exists in the cache
 pub fn thumbnail_exists_in_cache(cache_key: &str) -> bool {
     let cache_dir = get_cache_dir();
-    let cache_file = cache_dir.join(format!("{}.jpg", cache_key));
+    let cache_file = cache_file_path(&cache_dir, cache_key, thumbnail_file_extension());
     cache_file.exists()
-}
\ No newline at end of file
+}
+
+/// Outcome of a check of the cache files for corruption
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct CacheVerifySummary {
+    /// Cache files read and decoded
+    pub checked: usize,
+    /// Cache files that are empty or fail to decode as an image
+    pub corrupt: usize,
+    /// Corrupt cache files deleted, to be regenerated on the next request or worker pass
+    pub removed: usize,
+}
+
+// Guards against overlapping checks from the periodic verifier and /api/cache/verify
+static CACHE_VERIFY_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+// Where the sample of the next check starts, so repeated partial checks cover the whole cache
+static CACHE_VERIFY_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+/// Checks `sample_percent` of the files of both image caches, see [`verify_cache_dir`]. Each
+/// check samples the files following those of the previous one, so checks of 10% cover the
+/// whole cache every ten runs. Returns None when another check is already running.
+pub fn verify_caches(sample_percent: u8, user_active: &AtomicBool) -> Option<CacheVerifySummary> {
+    if CACHE_VERIFY_IN_PROGRESS.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    let sample_percent = sample_percent.clamp(1, 100);
+    let offset = CACHE_VERIFY_OFFSET.fetch_add(usize::from(sample_percent), Ordering::SeqCst) % 100;
+    let mut summary = CacheVerifySummary::default();
+    for cache_dir in [get_cache_dir(), get_preview_cache_dir()] {
+        let dir_summary = verify_cache_dir(&cache_dir, sample_percent, offset as u8, user_active);
+        log::info!(
+            "Verified {} cache files in {}: {} corrupt, {} removed",
+            dir_summary.checked,
+            cache_dir.display(),
+            dir_summary.corrupt,
+            dir_summary.removed
+        );
+        summary.checked += dir_summary.checked;
+        summary.corrupt += dir_summary.corrupt;
+        summary.removed += dir_summary.removed;
+    }
+    CACHE_VERIFY_IN_PROGRESS.store(false, Ordering::SeqCst);
+    Some(summary)
+}
+
+/// Decodes the cache files of a cache directory, including its shard and --dedupe-cache
+/// subdirectories, and deletes those that are empty or not a readable image, e.g. after a disk
+/// error or an interrupted write. Only a sample of `sample_percent` of the files is checked:
+/// those whose key falls into the buckets `offset..offset + sample_percent` (modulo 100). Keys
+/// are hashes, so buckets are filled evenly. Waits while `user_active` is set.
+pub fn verify_cache_dir(cache_dir: &Path, sample_percent: u8, offset: u8, user_active: &AtomicBool) -> CacheVerifySummary {
+    let mut summary = CacheVerifySummary::default();
+    let cache_files = walkdir::WalkDir::new(cache_dir)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .map(walkdir::DirEntry::into_path);
+    for cache_file in cache_files {
+        let Some((cache_key, _)) = cache_file_key(&cache_file) else {
+            continue;
+        };
+        let bucket = cache_key.get(..4).and_then(|prefix| u16::from_str_radix(prefix, 16).ok()).unwrap_or(0) % 100;
+        if (usize::from(bucket) + 100 - usize::from(offset)) % 100 >= usize::from(sample_percent) {
+            continue;
+        }
+        while user_active.load(Ordering::SeqCst) {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+
+        let bytes = match fs::read(&cache_file) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::warn!("Failed to read cache file {}: {}", cache_file.display(), e);
+                continue;
+            }
+        };
+        summary.checked += 1;
+        if let Err(e) = check_cached_image(&bytes) {
+            log::warn!("Corrupt cache file {} ({} bytes): {}", cache_file.display(), bytes.len(), e);
+            summary.corrupt += 1;
+            invalidate_memory_thumbnail(cache_key);
+            match fs::remove_file(&cache_file) {
+                Ok(()) => summary.removed += 1,
+                Err(e) => log::error!("Failed to remove corrupt cache file {}: {}", cache_file.display(), e),
+            }
+        }
+    }
+    summary
+}
+
+// Function to check that the bytes of a cache file are a readable image. AVIF preview variants
+// (--preview-formats) are only checked for their file type box, as no AVIF decoder is built in;
+// base64 thumbnails (--thumbnail-cache-format base64) are decoded first.
+fn check_cached_image(bytes: &[u8]) -> Result<(), String> {
+    let decoded;
+    let bytes = if is_base64_image(bytes) {
+        decoded = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, bytes).map_err(|e| e.to_string())?;
+        decoded.as_slice()
+    } else {
+        bytes
+    };
+    if bytes.get(4..12) == Some(b"ftypavif".as_slice()) {
+        return Ok(());
+    }
+    image::load_from_memory(bytes).map(|_| ()).map_err(|e| e.to_string())
+}
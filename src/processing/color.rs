@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use image::DynamicImage;
+
+// Pixels are quantized to this many bits per channel when looking for the dominant color
+const QUANTIZE_BITS: u8 = 4;
+
+// Estimate the dominant color of an image: downsample, bucket pixels into a coarse
+// RGB histogram and average the pixels of the most populated bucket
+pub fn dominant_color(img: &DynamicImage) -> [u8; 3] {
+    let small = img.thumbnail(64, 64).to_rgb8();
+    let shift = 8 - QUANTIZE_BITS;
+
+    let mut buckets: HashMap<(u8, u8, u8), (u64, [u64; 3])> = HashMap::new();
+    for pixel in small.pixels() {
+        let [r, g, b] = pixel.0;
+        let entry = buckets.entry((r >> shift, g >> shift, b >> shift)).or_insert((0, [0; 3]));
+        entry.0 += 1;
+        entry.1[0] += r as u64;
+        entry.1[1] += g as u64;
+        entry.1[2] += b as u64;
+    }
+
+    match buckets.values().max_by_key(|(count, _)| *count) {
+        Some((count, sums)) => [
+            (sums[0] / count) as u8,
+            (sums[1] / count) as u8,
+            (sums[2] / count) as u8,
+        ],
+        None => [0, 0, 0],
+    }
+}
+
+// Compute the dominant color of a base64-encoded thumbnail as a "#rrggbb" hex string
+pub fn dominant_color_from_base64(thumbnail_base64: &str) -> Option<String> {
+    let bytes = BASE64.decode(thumbnail_base64).ok()?;
+    let img = image::load_from_memory(&bytes)
+        .map_err(|e| log::warn!("Failed to decode thumbnail for color analysis: {:?}", e))
+        .ok()?;
+    Some(to_hex(dominant_color(&img)))
+}
+
+pub fn to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+// Parse "#rrggbb" or "rrggbb" into RGB components
+pub fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+    Some([r, g, b])
+}
+
+// Euclidean distance in RGB space (0.0 ..= ~441.7)
+pub fn color_distance(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let dr = a[0] as f64 - b[0] as f64;
+    let dg = a[1] as f64 - b[1] as f64;
+    let db = a[2] as f64 - b[2] as f64;
+    (dr * dr + dg * dg + db * db).sqrt()
+}
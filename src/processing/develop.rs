@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use image::DynamicImage;
+use rusqlite::{params, Connection};
+
+use crate::cli::get_cli_args;
+
+// Develop setting keys read from the XMP sidecar. They are stored in key_value under
+// these normalized names regardless of whether they were attributes or elements.
+pub const DEVELOP_KEYS: &[&str] = &[
+    "crs:HasCrop",
+    "crs:CropTop",
+    "crs:CropLeft",
+    "crs:CropBottom",
+    "crs:CropRight",
+    "crs:Orientation",
+    "tiff:Orientation",
+];
+
+// Crop and orientation from the editor's develop settings (crs namespace)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DevelopSettings {
+    // Crop rectangle as fractions (0.0-1.0) of the original image: top, left, bottom, right
+    pub crop: Option<(f64, f64, f64, f64)>,
+    // EXIF style orientation (1-8)
+    pub orientation: Option<u8>,
+}
+
+impl DevelopSettings {
+    // Build settings from normalized key-values; returns None when nothing applies
+    pub fn from_key_values(kv: &HashMap<String, String>) -> Option<DevelopSettings> {
+        let number = |key: &str| kv.get(key).and_then(|v| v.trim().parse::<f64>().ok());
+
+        let has_crop = kv
+            .get("crs:HasCrop")
+            .map(|v| !v.eq_ignore_ascii_case("false"))
+            .unwrap_or(true);
+        let crop = match (number("crs:CropTop"), number("crs:CropLeft"), number("crs:CropBottom"), number("crs:CropRight")) {
+            (Some(top), Some(left), Some(bottom), Some(right)) if has_crop => {
+                let clamp = |v: f64| v.clamp(0.0, 1.0);
+                let (top, left, bottom, right) = (clamp(top), clamp(left), clamp(bottom), clamp(right));
+                if bottom > top && right > left {
+                    Some((top, left, bottom, right))
+                } else {
+                    log::warn!("Ignoring degenerate develop crop {} {} {} {}", top, left, bottom, right);
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        let orientation = kv
+            .get("crs:Orientation")
+            .or_else(|| kv.get("tiff:Orientation"))
+            .and_then(|v| v.trim().parse::<u8>().ok())
+            .filter(|o| (1..=8).contains(o));
+
+        if crop.is_none() && orientation.is_none() {
+            return None;
+        }
+        Some(DevelopSettings { crop, orientation })
+    }
+
+    // Crop first (crop coordinates refer to the unrotated image), then orient
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let mut img = img;
+        if let Some((top, left, bottom, right)) = self.crop {
+            let (width, height) = (img.width() as f64, img.height() as f64);
+            let x = (left * width).round() as u32;
+            let y = (top * height).round() as u32;
+            let crop_width = (((right - left) * width).round() as u32).max(1);
+            let crop_height = (((bottom - top) * height).round() as u32).max(1);
+            log::debug!("Applying develop crop {}x{} at {},{}", crop_width, crop_height, x, y);
+            img = img.crop_imm(x, y, crop_width, crop_height);
+        }
+        match self.orientation {
+            Some(2) => img.fliph(),
+            Some(3) => img.rotate180(),
+            Some(4) => img.flipv(),
+            Some(5) => img.rotate90().fliph(),
+            Some(6) => img.rotate90(),
+            Some(7) => img.rotate270().fliph(),
+            Some(8) => img.rotate270(),
+            _ => img,
+        }
+    }
+}
+
+// Load the stored develop settings for an image from the database
+pub fn load_develop_settings(file_path: &str) -> Option<DevelopSettings> {
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::warn!("Failed to open database for develop settings: {}", e);
+            return None;
+        }
+    };
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT key_value.key, key_value.value FROM key_value \
+             JOIN file ON key_value.file_id = file.id \
+             WHERE {} AND (key_value.key LIKE 'crs:%' OR key_value.key = 'tiff:Orientation')",
+            crate::sidecar_scan::sidecar_path_condition(1)
+        ))
+        .map_err(|e| log::warn!("Failed to prepare develop settings query: {}", e))
+        .ok()?;
+    let kv: HashMap<String, String> = stmt
+        .query_map(params![file_path], |row| Ok((row.get(0)?, row.get(1)?)))
+        .ok()?
+        .flatten()
+        .collect();
+
+    let settings = DevelopSettings::from_key_values(&kv);
+    log::trace!("Develop settings for {}: {:?}", file_path, settings);
+    settings
+}
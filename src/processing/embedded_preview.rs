@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+use super::exif::parse_jpeg_header;
+use super::raw::{smallest_preview_covering, EmbeddedPreview};
+
+// Upper bound on the IFDs visited in one file, so a malformed or looping IFD chain ends quickly
+const MAX_IFDS: usize = 64;
+
+// Fujifilm RAF files start with this magic and keep the offset and length of their JPEG preview
+// as big-endian 32-bit values at these positions
+const RAF_MAGIC: &[u8] = b"FUJIFILMCCD-RAW";
+const RAF_JPEG_OFFSET_POSITION: usize = 84;
+
+// TIFF magic numbers: standard TIFF (NEF, CR2, ARW, DNG, PEF, ...), Olympus ORF and Panasonic RW2
+const TIFF_MAGICS: &[u16] = &[42, 0x4F52, 0x5352, 0x55];
+
+// Tags pointing to embedded JPEG streams or to further IFDs that may hold them
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_MAKER_NOTE: u16 = 0x927C;
+// Panasonic RW2 stores its full-size preview as the value of this tag
+const TAG_RW2_JPEG_FROM_RAW: u16 = 0x002E;
+// Nikon maker notes link an IFD describing the medium-sized preview
+const TAG_NIKON_PREVIEW_IFD: u16 = 0x0011;
+
+// Old-style and new-style JPEG compression of image strips
+const JPEG_COMPRESSIONS: &[u32] = &[6, 7];
+
+/// Extracts the best JPEG preview embedded in a RAW file without running exiv2, reading only the
+/// TIFF structure (IFD0 chain, SubIFDs, EXIF IFD and Nikon maker note preview) or the RAF header.
+/// Previews are picked as by [`super::raw::exiv2_extract_best_preview`]: the most pixels, or with
+/// a `min_dimension` hint the smallest one covering it. Lossless JPEG sensor data is skipped.
+/// Fails for other containers, such as CR3, and for files without a usable preview.
+pub fn extract_embedded_preview(file_path: &str, min_dimension: Option<u32>) -> Result<Vec<u8>, String> {
+    let mut file = File::open(file_path).map_err(|e| format!("Failed to open {}: {}", file_path, e))?;
+    let file_len = file.metadata().map_err(|e| format!("Failed to stat {}: {}", file_path, e))?.len();
+    let ranges = jpeg_ranges(&mut file).ok_or_else(|| "Not a TIFF based RAW or RAF file".to_string())?;
+
+    let mut seen = HashSet::new();
+    let mut previews = Vec::new();
+    let mut preview_ranges = Vec::new();
+    for (offset, length) in ranges {
+        if length == 0 || offset.checked_add(length).is_none_or(|end| end > file_len) || !seen.insert(offset) {
+            continue;
+        }
+        if file.seek(SeekFrom::Start(offset)).is_err() {
+            continue;
+        }
+        let Some(header) = parse_jpeg_header(BufReader::new((&mut file).take(length))) else {
+            continue;
+        };
+        if header.lossless || header.width == 0 || header.height == 0 {
+            continue;
+        }
+        log::trace!("Embedded JPEG in {} at {}: {}x{}, {} bytes", file_path, offset, header.width, header.height, length);
+        previews.push(EmbeddedPreview { number: preview_ranges.len() as u32, width: header.width, height: header.height });
+        preview_ranges.push((offset, length));
+    }
+
+    let selected = min_dimension
+        .and_then(|min_dimension| smallest_preview_covering(&previews, min_dimension))
+        .or_else(|| previews.iter().max_by_key(|preview| u64::from(preview.width) * u64::from(preview.height)))
+        .ok_or_else(|| "No embedded JPEG preview found".to_string())?;
+    log::debug!("Using embedded preview {}x{} of {}", selected.width, selected.height, file_path);
+
+    let (offset, length) = preview_ranges[selected.number as usize];
+    let mut jpeg = vec![0u8; length as usize];
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(&mut jpeg))
+        .map_err(|e| format!("Failed to read embedded preview of {}: {}", file_path, e))?;
+    Ok(jpeg)
+}
+
+// An IFD to visit: its offset, the position its offsets are relative to (the start of a Nikon
+// maker note's own TIFF header, else the file) and its byte order
+struct Ifd {
+    base: u64,
+    offset: u64,
+    big_endian: bool,
+    maker_note: bool,
+}
+
+// Function to list the (offset, length) of every JPEG stream a RAW file points to; None when it
+// is neither TIFF based nor a RAF file
+fn jpeg_ranges(file: &mut File) -> Option<Vec<(u64, u64)>> {
+    let head = read_at(file, 0, RAF_JPEG_OFFSET_POSITION + 8)
+        .or_else(|| read_at(file, 0, 8))?;
+    if head.starts_with(RAF_MAGIC) {
+        let offset = u32::from_be_bytes(head.get(RAF_JPEG_OFFSET_POSITION..RAF_JPEG_OFFSET_POSITION + 4)?.try_into().ok()?);
+        let length = u32::from_be_bytes(head.get(RAF_JPEG_OFFSET_POSITION + 4..RAF_JPEG_OFFSET_POSITION + 8)?.try_into().ok()?);
+        return Some(vec![(offset.into(), length.into())]);
+    }
+    let (big_endian, ifd0) = tiff_header(&head)?;
+
+    let mut ranges = Vec::new();
+    let mut queue = vec![Ifd { base: 0, offset: ifd0, big_endian, maker_note: false }];
+    let mut visited = HashSet::new();
+    while let Some(ifd) = queue.pop() {
+        if visited.len() >= MAX_IFDS || ifd.offset == 0 || !visited.insert(ifd.base + ifd.offset) {
+            continue;
+        }
+        let Some(next) = read_ifd(file, &ifd, &mut ranges, &mut queue) else {
+            continue;
+        };
+        queue.push(Ifd { offset: next, ..ifd });
+    }
+    Some(ranges)
+}
+
+// Function to read the byte order and first IFD offset of a TIFF header
+fn tiff_header(head: &[u8]) -> Option<(bool, u64)> {
+    let big_endian = match head.get(..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let magic: [u8; 2] = head.get(2..4)?.try_into().ok()?;
+    let magic = if big_endian { u16::from_be_bytes(magic) } else { u16::from_le_bytes(magic) };
+    if !TIFF_MAGICS.contains(&magic) {
+        return None;
+    }
+    let offset: [u8; 4] = head.get(4..8)?.try_into().ok()?;
+    Some((big_endian, u64::from(if big_endian { u32::from_be_bytes(offset) } else { u32::from_le_bytes(offset) })))
+}
+
+// Function to read one IFD: adds the JPEG streams it points to to `ranges` and the IFDs it links
+// to `queue`, returning the offset of the next IFD in its chain
+fn read_ifd(file: &mut File, ifd: &Ifd, ranges: &mut Vec<(u64, u64)>, queue: &mut Vec<Ifd>) -> Option<u64> {
+    let u16_of = |bytes: &[u8]| -> Option<u16> {
+        let bytes: [u8; 2] = bytes.get(..2)?.try_into().ok()?;
+        Some(if ifd.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_of = |bytes: &[u8]| -> Option<u32> {
+        let bytes: [u8; 4] = bytes.get(..4)?.try_into().ok()?;
+        Some(if ifd.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+
+    let start = ifd.base + ifd.offset;
+    let count = usize::from(u16_of(&read_at(file, start, 2)?)?);
+    let entries = read_at(file, start + 2, count * 12 + 4)?;
+
+    let (mut jpeg_offset, mut jpeg_length) = (None, None);
+    let (mut compression, mut strip_offset, mut strip_length) = (None, None, None);
+    for entry in entries.chunks_exact(12) {
+        let tag = u16_of(entry)?;
+        let kind = u16_of(&entry[2..])?;
+        let values = u32_of(&entry[4..])?;
+        // SHORT values sit in the first two bytes of the value field
+        let value = if kind == 3 { u32::from(u16_of(&entry[8..])?) } else { u32_of(&entry[8..])? };
+        match tag {
+            TAG_JPEG_OFFSET => jpeg_offset = Some(value),
+            TAG_JPEG_LENGTH => jpeg_length = Some(value),
+            TAG_COMPRESSION => compression = Some(value),
+            TAG_STRIP_OFFSETS if values == 1 => strip_offset = Some(value),
+            TAG_STRIP_BYTE_COUNTS if values == 1 => strip_length = Some(value),
+            TAG_RW2_JPEG_FROM_RAW if !ifd.maker_note => ranges.push((ifd.base + u64::from(value), u64::from(values))),
+            TAG_SUB_IFDS => {
+                // A single offset is stored in the entry itself, several in an array it points to
+                let offsets = if values == 1 {
+                    vec![value]
+                } else {
+                    let array = read_at(file, ifd.base + u64::from(value), values.min(MAX_IFDS as u32) as usize * 4)?;
+                    array.chunks_exact(4).filter_map(u32_of).collect()
+                };
+                for offset in offsets {
+                    queue.push(Ifd { offset: offset.into(), ..*ifd });
+                }
+            }
+            TAG_EXIF_IFD => queue.push(Ifd { offset: value.into(), ..*ifd }),
+            TAG_MAKER_NOTE if !ifd.maker_note => {
+                if let Some(maker_note) = nikon_maker_note(file, ifd.base + u64::from(value)) {
+                    queue.push(maker_note);
+                }
+            }
+            TAG_NIKON_PREVIEW_IFD if ifd.maker_note => queue.push(Ifd { offset: value.into(), ..*ifd }),
+            _ => {}
+        }
+    }
+    if let (Some(offset), Some(length)) = (jpeg_offset, jpeg_length) {
+        ranges.push((ifd.base + u64::from(offset), u64::from(length)));
+    }
+    if let (Some(compression), Some(offset), Some(length)) = (compression, strip_offset, strip_length) {
+        if JPEG_COMPRESSIONS.contains(&compression) {
+            ranges.push((ifd.base + u64::from(offset), u64::from(length)));
+        }
+    }
+    u32_of(&entries[count * 12..]).map(u64::from)
+}
+
+// Function to locate the IFD of a Nikon maker note, which is "Nikon\0" and a version followed by
+// a TIFF header of its own that its offsets are relative to
+fn nikon_maker_note(file: &mut File, position: u64) -> Option<Ifd> {
+    let head = read_at(file, position, 18)?;
+    if !head.starts_with(b"Nikon\0") {
+        return None;
+    }
+    let (big_endian, offset) = tiff_header(&head[10..])?;
+    Some(Ifd { base: position + 10, offset, big_endian, maker_note: true })
+}
+
+// Function to read `length` bytes at an offset of the file
+fn read_at(file: &mut File, offset: u64, length: usize) -> Option<Vec<u8>> {
+    let mut bytes = vec![0u8; length];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut bytes).ok()?;
+    Some(bytes)
+}
@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+// Header segments are small (APP1 is at most 64 KB); giving up after this many bytes keeps
+// malformed files from being read in full
+const MAX_HEADER_BYTES: u64 = 1024 * 1024;
+
+/// EXIF block and frame size read from the header segments of a JPEG file
+pub struct JpegHeader {
+    /// TIFF structured EXIF data (starting at the byte order mark), if the file has any
+    pub exif: Option<Vec<u8>>,
+    pub width: u32,
+    pub height: u32,
+    /// Lossless JPEG (SOF3 and its variants), which RAW files use for sensor data and the image crate cannot decode
+    pub lossless: bool,
+}
+
+/// Reads the segments of a JPEG up to its frame header, without touching the compressed image data.
+pub fn read_jpeg_header(file_path: &str) -> Option<JpegHeader> {
+    let file = File::open(file_path).ok()?;
+    parse_jpeg_header(BufReader::new(file))
+}
+
+/// Reads the segments of a JPEG stream up to its frame header, e.g. one embedded in a RAW file.
+pub fn parse_jpeg_header(reader: impl Read) -> Option<JpegHeader> {
+    let mut reader = reader.take(MAX_HEADER_BYTES);
+
+    let mut marker = [0u8; 2];
+    reader.read_exact(&mut marker).ok()?;
+    if marker != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut exif = None;
+    loop {
+        reader.read_exact(&mut marker).ok()?;
+        if marker[0] != 0xFF {
+            return None;
+        }
+        // Markers may be preceded by any number of 0xFF fill bytes
+        let mut kind = marker[1];
+        while kind == 0xFF {
+            let mut next = [0u8; 1];
+            reader.read_exact(&mut next).ok()?;
+            kind = next[0];
+        }
+        // Standalone markers carry no length
+        if kind == 0x01 || (0xD0..=0xD7).contains(&kind) {
+            continue;
+        }
+        // Image data or end of image before a frame header
+        if kind == 0xDA || kind == 0xD9 {
+            return None;
+        }
+
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length).ok()?;
+        let length = usize::from(u16::from_be_bytes(length)).checked_sub(2)?;
+        let mut segment = vec![0u8; length];
+        reader.read_exact(&mut segment).ok()?;
+
+        match kind {
+            0xE1 if exif.is_none() && segment.starts_with(b"Exif\0\0") => {
+                exif = Some(segment.split_off(6));
+            }
+            // SOF0-SOF15, except DHT, JPG and DAC which share the range
+            0xC0..=0xCF if !matches!(kind, 0xC4 | 0xC8 | 0xCC) => {
+                let height = u16::from_be_bytes([*segment.get(1)?, *segment.get(2)?]);
+                let width = u16::from_be_bytes([*segment.get(3)?, *segment.get(4)?]);
+                let lossless = matches!(kind, 0xC3 | 0xC7 | 0xCB | 0xCF);
+                return Some(JpegHeader { exif, width: width.into(), height: height.into(), lossless });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the JPEG thumbnail embedded in IFD1 of an EXIF block, if there is one.
+pub fn embedded_thumbnail(exif: &[u8]) -> Option<&[u8]> {
+    let big_endian = match exif.get(..4)? {
+        [b'I', b'I', 42, 0] => false,
+        [b'M', b'M', 0, 42] => true,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<usize> {
+        let bytes = [*exif.get(offset)?, *exif.get(offset + 1)?];
+        Some(usize::from(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }))
+    };
+    let u32_at = |offset: usize| -> Option<usize> {
+        let bytes: [u8; 4] = exif.get(offset..offset + 4)?.try_into().ok()?;
+        usize::try_from(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }).ok()
+    };
+
+    // IFD0 holds the main image tags, the thumbnail is described by the IFD linked after it
+    let ifd0 = u32_at(4)?;
+    let ifd1 = u32_at(ifd0 + 2 + u16_at(ifd0)? * 12)?;
+    if ifd1 == 0 {
+        return None;
+    }
+
+    let (mut offset, mut length) = (None, None);
+    for index in 0..u16_at(ifd1)? {
+        let entry = ifd1 + 2 + index * 12;
+        // SHORT values sit in the first two bytes of the value field
+        let value = if u16_at(entry + 2)? == 3 { u16_at(entry + 8)? } else { u32_at(entry + 8)? };
+        match u16_at(entry)? {
+            0x0201 => offset = Some(value),
+            0x0202 => length = Some(value),
+            _ => {}
+        }
+    }
+    let (offset, length) = (offset?, length?);
+    let thumbnail = exif.get(offset..offset.checked_add(length)?)?;
+    thumbnail.starts_with(&[0xFF, 0xD8]).then_some(thumbnail)
+}
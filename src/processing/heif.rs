@@ -0,0 +1,149 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::failures::{report_failure, FailureStage};
+use super::cache::{generate_cache_key, preview_cache_key, save_preview_to_cache, save_thumbnail_to_cache, scaled_thumbnail_cache_key, thumbnail_fill};
+use super::raw::{best_preview_file, scale_jpeg_bytes};
+
+/// HEIF containers (iPhone HEIC photos, Fujifilm HIF), decoded with libheif's command line tool
+pub const HEIF_EXTENSIONS: &[&str] = &["heic", "heif", "hif"];
+
+// libheif decoders, in order of preference: heif-dec from libheif 1.17 on, heif-convert before it
+const HEIF_DECODERS: &[&str] = &["heif-dec", "heif-convert"];
+
+// Quality of the intermediate JPEG, which is scaled and re-encoded afterwards
+const HEIF_DECODE_QUALITY: &str = "95";
+
+// Set once a missing decoder has been reported, so the log is not flooded with one line per photo
+static DECODER_MISSING_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Why a HEIF file could not be decoded
+#[derive(Debug, PartialEq)]
+pub enum HeifError {
+    /// Neither heif-dec nor heif-convert is installed, which is logged once rather than per file
+    DecoderMissing,
+    /// The decoder ran but failed, or its output could not be read
+    Failed(String),
+}
+
+impl std::fmt::Display for HeifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeifError::DecoderMissing => write!(f, "No HEIF decoder found, install {} (libheif)", HEIF_DECODERS.join(" or ")),
+            HeifError::Failed(e) => f.write_str(e),
+        }
+    }
+}
+
+/// Whether a (lowercase) extension is one of the HEIF_EXTENSIONS
+pub fn is_heif_extension(extension: &str) -> bool {
+    HEIF_EXTENSIONS.contains(&extension)
+}
+
+/// Decodes the primary image of a HEIF file into JPEG bytes with heif-dec (or heif-convert).
+/// Files with several top-level images, such as bursts, are written as one JPEG each; the largest
+/// is returned. Fails with [`HeifError::DecoderMissing`] when neither tool is installed.
+pub fn heif_decode_to_jpeg(file_path: &str) -> Result<Vec<u8>, HeifError> {
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let tmp_dir: PathBuf = std::env::temp_dir().join(format!("imagefind_heif_{}_{}", generate_cache_key(file_path), ts));
+    fs::create_dir_all(&tmp_dir).map_err(|e| HeifError::Failed(format!("Temp dir create failed: {}", e)))?;
+    let output_file = tmp_dir.join("decoded.jpg");
+
+    let mut result = None;
+    for decoder in HEIF_DECODERS {
+        let output = Command::new(decoder)
+            .arg("-q")
+            .arg(HEIF_DECODE_QUALITY)
+            .arg(file_path)
+            .arg(&output_file)
+            .output();
+        result = match output {
+            Ok(output) if output.status.success() => Some(Ok(())),
+            Ok(output) => Some(Err(HeifError::Failed(format!(
+                "{} failed: {}",
+                decoder,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => Some(Err(HeifError::Failed(format!("{} exec failed: {}", decoder, e)))),
+        };
+        break;
+    }
+    let result = result.unwrap_or_else(|| {
+        if !DECODER_MISSING_LOGGED.swap(true, Ordering::Relaxed) {
+            log::warn!("{}; HEIC/HEIF files get no thumbnails or previews", HeifError::DecoderMissing);
+        }
+        Err(HeifError::DecoderMissing)
+    });
+
+    let result = result.and_then(|_| {
+        // A multi-image file is written as decoded-1.jpg, decoded-2.jpg, ...
+        let candidates = fs::read_dir(&tmp_dir)
+            .map(|entries| entries.flatten().map(|entry| entry.path()).filter(|path| path.is_file()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let decoded = best_preview_file(candidates)
+            .ok_or_else(|| HeifError::Failed("HEIF decoder produced no image".to_string()))?;
+        fs::read(&decoded).map_err(|e| HeifError::Failed(format!("Failed to read decoded HEIF {}: {}", decoded.display(), e)))
+    });
+    let _ = fs::remove_dir_all(&tmp_dir);
+    result
+}
+
+pub fn generate_heif_preview(file_path: &str) -> Option<String> {
+    log::info!("Generating HEIF preview for: {}", file_path);
+
+    let cache_key = preview_cache_key(file_path);
+    let quality = super::image::quality_settings(file_path).preview_quality;
+    // Without libheif there is nothing to report per file; the missing tool was logged once
+    let decoded = match heif_decode_to_jpeg(file_path) {
+        Err(HeifError::DecoderMissing) => return None,
+        decoded => decoded.map_err(|e| e.to_string()),
+    };
+    match decoded
+        .and_then(|bytes| scale_jpeg_bytes(&bytes, super::image::PREVIEW_MAX_DIMENSION, quality, false, false))
+    {
+        Ok(jpeg_bytes) => {
+            if let Err(e) = save_preview_to_cache(&cache_key, &jpeg_bytes) {
+                log::warn!("Failed to cache HEIF preview: {}", e);
+            }
+            Some(BASE64.encode(&jpeg_bytes))
+        }
+        Err(e) => {
+            log::error!("HEIF preview failed for {}: {}", file_path, e);
+            report_failure(file_path, FailureStage::Preview, &e);
+            None
+        }
+    }
+}
+
+pub fn generate_heif_thumbnail(file_path: &str, scale: u32) -> Option<String> {
+    log::info!("Generating HEIF thumbnail for: {}", file_path);
+
+    let cache_key = scaled_thumbnail_cache_key(file_path, scale);
+    let settings = super::image::quality_settings(file_path);
+    let max_dimension = settings.thumbnail_size * scale;
+    let decoded = match heif_decode_to_jpeg(file_path) {
+        Err(HeifError::DecoderMissing) => return None,
+        decoded => decoded.map_err(|e| e.to_string()),
+    };
+    match decoded
+        .and_then(|bytes| scale_jpeg_bytes(&bytes, max_dimension, settings.thumbnail_quality, thumbnail_fill(), true))
+    {
+        Ok(jpeg_bytes) => {
+            if let Err(e) = save_thumbnail_to_cache(&cache_key, &jpeg_bytes) {
+                log::warn!("Failed to cache HEIF thumbnail: {}", e);
+            }
+            Some(BASE64.encode(&jpeg_bytes))
+        }
+        Err(e) => {
+            log::error!("HEIF thumbnail failed for {}: {}", file_path, e);
+            report_failure(file_path, FailureStage::Thumbnail, &e);
+            None
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+// Document formats indexed through their sidecars but never thumbnailed
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "epub", "xls", "xlsx", "ods", "ppt", "pptx", "odp",
+];
+
+// Image formats, including those without a decoder here such as Photoshop files, or HEIC without libheif
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "bmp", "webp", "tif", "tiff", "heic", "heif", "avif", "jxl", "psd", "svg",
+];
+
+/// Generic glyph standing in for a thumbnail that cannot be generated, with --thumbnail-fallback-icons
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileTypeIcon {
+    Document,
+    Video,
+    Raw,
+    Image,
+}
+
+impl FileTypeIcon {
+    /// Picks the icon from the file extension (case-insensitive); None for unknown types
+    pub fn for_path(path: &str) -> Option<FileTypeIcon> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        if crate::processing::video::is_video_extension(&extension) {
+            Some(FileTypeIcon::Video)
+        } else if crate::sidecar_scan::RAW_EXTENSIONS.contains(&extension.as_str()) {
+            Some(FileTypeIcon::Raw)
+        } else if DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+            Some(FileTypeIcon::Document)
+        } else if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            Some(FileTypeIcon::Image)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileTypeIcon::Document => "document",
+            FileTypeIcon::Video => "video",
+            FileTypeIcon::Raw => "raw",
+            FileTypeIcon::Image => "image",
+        }
+    }
+
+    /// The bundled 200x200 SVG
+    pub fn svg(&self) -> &'static str {
+        match self {
+            FileTypeIcon::Document => include_str!("../../templates/icons/document.svg"),
+            FileTypeIcon::Video => include_str!("../../templates/icons/video.svg"),
+            FileTypeIcon::Raw => include_str!("../../templates/icons/raw.svg"),
+            FileTypeIcon::Image => include_str!("../../templates/icons/image.svg"),
+        }
+    }
+}
@@ -4,13 +4,132 @@ use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
 use crate::processing::raw::generate_raw_preview;
 
-use super::cache::{generate_cache_key, get_cached_thumbnail, get_cached_preview, save_thumbnail_to_cache};
+use super::develop::load_develop_settings;
+use super::cache::{
+    get_cached_preview, preview_cache_key, get_cached_thumbnail, save_preview_to_cache, save_thumbnail_to_cache,
+    badged_thumbnail_cache_key, preview_variant_cache_key, scaled_thumbnail_cache_key, sized_preview_cache_key, thumbnail_content_type,
+    thumbnail_fill,
+};
+use crate::cli::{PreviewFormat, ThumbnailFormat};
+use crate::failures::{report_failure, FailureStage};
+use super::heif::{generate_heif_preview, generate_heif_thumbnail, is_heif_extension};
 use super::raw::generate_raw_thumbnail;
 use super::tiff::{generate_tiff_thumbnail,generate_tiff_preview};
-use super::video::generate_video_thumbnail;
+use super::video::{generate_video_thumbnail, is_video_extension};
+
+/// Longest edge of full-size previews
+pub const PREVIEW_MAX_DIMENSION: u32 = 1980;
+
+/// Smallest width served for a `?w=` preview request
+pub const PREVIEW_MIN_WIDTH: u32 = 64;
+
+/// Longest edge (fit) or side (fill) of thumbnails at the base pixel density, unless set with --thumbnail-size
+pub const THUMBNAIL_SIZE: u32 = 200;
+
+/// JPEG quality of thumbnails, unless set with --thumbnail-quality or overridden for their directory (--quality-override)
+pub const THUMBNAIL_QUALITY: u8 = 50;
+
+/// JPEG quality of previews, unless set with --preview-quality or overridden for their directory (--quality-override)
+pub const PREVIEW_QUALITY: u8 = 60;
+
+// AVIF quality of --preview-formats avif previews, which keep it whatever their JPEG's quality
+const AVIF_PREVIEW_QUALITY: u8 = 60;
+
+/// Thumbnail sources with both edges within this are encoded as they are instead of scaled to 200px (fit mode)
+pub const THUMBNAIL_PASSTHROUGH_DIMENSION: u32 = 400;
+
+// Requested preview widths are rounded up to a multiple of this, bounding the cached variants per image
+const PREVIEW_WIDTH_STEP: u32 = 32;
+
+/// Maps a requested preview width onto the width that is generated and cached: rounded up to
+/// a multiple of 32 and clamped to 64..=1980. None means the full-size preview.
+pub fn preview_width(requested: u32) -> Option<u32> {
+    let width = requested.div_ceil(PREVIEW_WIDTH_STEP).saturating_mul(PREVIEW_WIDTH_STEP).max(PREVIEW_MIN_WIDTH);
+    (width < PREVIEW_MAX_DIMENSION).then_some(width)
+}
+
+/// Encoding settings of the thumbnails and previews of one file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualitySettings {
+    pub thumbnail_quality: u8,
+    /// Thumbnail size at the base pixel density
+    pub thumbnail_size: u32,
+    pub preview_quality: u8,
+}
+
+/// Returns the encoding settings of a file: those of the --quality-override with the longest
+/// prefix matching its path, where settings the override leaves out keep their defaults from
+/// --thumbnail-quality, --thumbnail-size and --preview-quality. A
+/// prefix matches whole path components, so "/photos/doc" does not match "/photos/docs/a.jpg".
+/// Of several overrides with the same prefix, the last one given wins.
+pub fn quality_settings(file_path: &str) -> QualitySettings {
+    let mut settings = QualitySettings {
+        thumbnail_quality: THUMBNAIL_QUALITY,
+        thumbnail_size: THUMBNAIL_SIZE,
+        preview_quality: PREVIEW_QUALITY,
+    };
+    let Some(args) = crate::cli::CLI_ARGS.get() else {
+        return settings;
+    };
+    settings.thumbnail_quality = args.thumbnail_quality;
+    settings.thumbnail_size = args.thumbnail_size;
+    settings.preview_quality = args.preview_quality;
+    let matching = args
+        .quality_override
+        .iter()
+        .filter(|o| path_has_prefix(file_path, &o.prefix))
+        .rev()
+        .max_by_key(|o| o.prefix.trim_end_matches('/').len());
+    if let Some(quality_override) = matching {
+        settings.thumbnail_quality = quality_override.thumbnail_quality.unwrap_or(settings.thumbnail_quality);
+        settings.thumbnail_size = quality_override.thumbnail_size.unwrap_or(settings.thumbnail_size);
+        settings.preview_quality = quality_override.preview_quality.unwrap_or(settings.preview_quality);
+    }
+    settings
+}
+
+// Function to check whether a path lies under a prefix, comparing whole path components
+fn path_has_prefix(file_path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    match file_path.strip_prefix(prefix) {
+        Some(rest) => prefix.is_empty() || rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+// Function to get the --max-source-dimension limit, None when disabled
+pub fn max_source_dimension() -> Option<u32> {
+    crate::cli::CLI_ARGS.get().map(|a| a.max_source_dimension).filter(|&limit| limit > 0)
+}
+
+/// Returns the dimensions of a source image that is too large to decode (--max-source-dimension),
+/// probed from its header without decoding it. TIFFs count as their largest overview within the
+/// limit, if they have one. None when the image is within the limit, the limit is disabled or
+/// the format is not probed: RAW files are read through their embedded previews and videos
+/// through ffmpeg.
+pub fn oversized_source(file_path: &str) -> Option<(u32, u32)> {
+    let limit = max_source_dimension()?;
+    let extension = Path::new(file_path).extension()?.to_string_lossy().to_lowercase();
+    let (width, height) = match extension.as_str() {
+        "tiff" | "tif" => super::tiff::decodable_tiff_dimensions(file_path, limit)?,
+        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" => {
+            image::ImageReader::open(file_path).ok()?.with_guessed_format().ok()?.into_dimensions().ok()?
+        }
+        _ => return None,
+    };
+    (width.max(height) > limit).then_some((width, height))
+}
 
 // Function to generate a JPEG thumbnail from an image file
 pub fn generate_thumbnail(file_path: &str) -> Option<String> {
+    generate_scaled_thumbnail(file_path, 1, false)
+}
+
+/// Generates (or reads from cache) the thumbnail at a pixel density of --thumbnail-scale-factors,
+/// e.g. 400px for a scale of 2. Each density is cached under its own key. With `prefer_exif`, a
+/// JPEG on a cold cache is first thumbnailed from its embedded EXIF thumbnail, skipping the
+/// decode of the full image; it falls back to the regular path when there is no usable one.
+pub fn generate_scaled_thumbnail(file_path: &str, scale: u32, prefer_exif: bool) -> Option<String> {
     let path = Path::new(file_path);
     
     log::debug!("Generating thumbnail for: {}", file_path);
@@ -22,17 +141,160 @@ pub fn generate_thumbnail(file_path: &str) -> Option<String> {
     }
     
     // Generate cache key
-    let cache_key = generate_cache_key(file_path);
+    let cache_key = scaled_thumbnail_cache_key(file_path, scale);
     log::trace!("Generated cache key for thumbnail: {}", cache_key);
     
     // Check disk cache first
-    if let Some(cached) = get_cached_thumbnail(&cache_key) {
+    if let Some(cached) = get_cached_thumbnail(&cache_key, file_path) {
         log::debug!("Using cached thumbnail for: {}", file_path);
         return Some(cached);
     }
     
     log::debug!("No cached thumbnail found, generating new one for: {}", file_path);
-    
+    crate::metrics::time_generation("thumbnail", || {
+        prefer_exif
+            .then(|| exif_thumbnail(path, file_path, &cache_key, scale))
+            .flatten()
+            .or_else(|| generate_uncached_thumbnail(path, file_path, &cache_key, scale))
+    })
+}
+
+/// Generates (or reads from cache) the thumbnail at `scale` with `rating` stars drawn in its
+/// bottom-left corner (`?badge=rating`). The badge is drawn on the plain thumbnail, which is
+/// generated first if needed, and the result is cached under its own key next to it.
+pub fn generate_badged_thumbnail(file_path: &str, scale: u32, prefer_exif: bool, rating: u8) -> Option<String> {
+    let cache_key = badged_thumbnail_cache_key(file_path, scale, rating);
+    if let Some(cached) = get_cached_thumbnail(&cache_key, file_path) {
+        log::debug!("Using cached rating badge thumbnail for: {}", file_path);
+        return Some(cached);
+    }
+
+    let thumbnail = generate_scaled_thumbnail(file_path, scale, prefer_exif)?;
+    // There is no AVIF decoder here, so AVIF thumbnails are served without the badge
+    if thumbnail_content_type(&thumbnail) == "image/avif" {
+        log::debug!("Serving the AVIF thumbnail of {} without its rating badge", file_path);
+        return Some(thumbnail);
+    }
+    let mut img = match BASE64.decode(thumbnail).ok().and_then(|bytes| image::load_from_memory(&bytes).ok()) {
+        Some(img) => img.to_rgb8(),
+        None => {
+            log::error!("Failed to decode thumbnail of {} for its rating badge", file_path);
+            return None;
+        }
+    };
+    super::badge::draw_rating_stars(&mut img, rating, scale);
+    match encode_thumbnail(&image::DynamicImage::ImageRgb8(img), quality_settings(file_path).thumbnail_quality) {
+        Ok(thumbnail_bytes) => {
+            if let Err(e) = save_thumbnail_to_cache(&cache_key, &thumbnail_bytes) {
+                log::warn!("Failed to cache rating badge thumbnail: {}", e);
+            }
+            log::debug!("Drew {} rating stars on thumbnail of {}", rating, file_path);
+            Some(BASE64.encode(&thumbnail_bytes))
+        }
+        Err(e) => {
+            log::warn!("Encoding failed for rating badge thumbnail of {}: {}", file_path, e);
+            None
+        }
+    }
+}
+
+// Embedded thumbnails whose aspect ratio differs more than this from the image's are letterboxed
+// (black bars added by the camera) and not used
+const EXIF_THUMBNAIL_ASPECT_TOLERANCE: f64 = 0.02;
+
+// Function to build a thumbnail from the EXIF thumbnail embedded in a JPEG. None when there is
+// none, or it is too small for the thumbnail size or letterboxed to a different aspect ratio.
+// Like the regular path it keeps the stored pixel orientation, which the embedded thumbnail shares.
+fn exif_thumbnail(path: &Path, file_path: &str, cache_key: &str, scale: u32) -> Option<String> {
+    let settings = quality_settings(file_path);
+    let size = settings.thumbnail_size * scale;
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    if extension != "jpg" && extension != "jpeg" {
+        return None;
+    }
+    let header = super::exif::read_jpeg_header(file_path)?;
+    let embedded = super::exif::embedded_thumbnail(header.exif.as_deref()?)?;
+    let embedded = match image::load_from_memory(embedded) {
+        Ok(img) => img,
+        Err(e) => {
+            log::debug!("Embedded EXIF thumbnail of {} does not decode: {}", file_path, e);
+            return None;
+        }
+    };
+    let (width, height) = (embedded.width(), embedded.height());
+
+    let covered = if thumbnail_fill() { width.min(height) } else { width.max(height) };
+    if covered < size {
+        log::debug!("Embedded EXIF thumbnail of {} is too small ({}x{})", file_path, width, height);
+        return None;
+    }
+    if height == 0 || header.width == 0 || header.height == 0 {
+        return None;
+    }
+    let embedded_aspect = f64::from(width) / f64::from(height);
+    let image_aspect = f64::from(header.width) / f64::from(header.height);
+    if (embedded_aspect / image_aspect - 1.0).abs() > EXIF_THUMBNAIL_ASPECT_TOLERANCE {
+        log::debug!(
+            "Embedded EXIF thumbnail of {} ({}x{}) does not match the image ({}x{})",
+            file_path, width, height, header.width, header.height
+        );
+        return None;
+    }
+
+    let thumbnail = if thumbnail_fill() {
+        embedded.resize_to_fill(size, size, image::imageops::FilterType::CatmullRom)
+    } else {
+        embedded.resize(size, size, image::imageops::FilterType::CatmullRom)
+    };
+    let thumbnail = sharpen_thumbnail(thumbnail);
+    match encode_thumbnail(&thumbnail, settings.thumbnail_quality) {
+        Ok(thumbnail_bytes) => {
+            let _ = save_thumbnail_to_cache(cache_key, &thumbnail_bytes);
+            log::info!("Generated thumbnail from embedded EXIF thumbnail ({}x{}): {}", width, height, file_path);
+            Some(BASE64.encode(&thumbnail_bytes))
+        }
+        Err(e) => {
+            log::warn!("Encoding failed for EXIF thumbnail of {}: {}", file_path, e);
+            None
+        }
+    }
+}
+
+// Function to apply the --thumbnail-sharpen unsharp mask to a resized thumbnail, if enabled
+pub fn sharpen_thumbnail(thumbnail: image::DynamicImage) -> image::DynamicImage {
+    match crate::cli::CLI_ARGS.get().filter(|a| a.thumbnail_sharpen) {
+        Some(args) => {
+            log::trace!("Sharpening thumbnail (sigma {}, threshold {})", args.thumbnail_sharpen_sigma, args.thumbnail_sharpen_threshold);
+            thumbnail.unsharpen(args.thumbnail_sharpen_sigma, args.thumbnail_sharpen_threshold)
+        }
+        None => thumbnail,
+    }
+}
+
+// Function to get the --thumbnail-format, JPEG when unset
+pub fn thumbnail_format() -> ThumbnailFormat {
+    crate::cli::CLI_ARGS.get().map(|a| a.thumbnail_format).unwrap_or(ThumbnailFormat::Jpeg)
+}
+
+/// Encodes a thumbnail in the --thumbnail-format; `quality` applies to JPEG and AVIF, as WebP
+/// is written lossless
+pub fn encode_thumbnail(img: &image::DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let format = match thumbnail_format() {
+        ThumbnailFormat::Jpeg => return super::jpeg::encode_jpeg(img, quality),
+        ThumbnailFormat::Avif => PreviewFormat::Avif,
+        ThumbnailFormat::Webp => PreviewFormat::Webp,
+    };
+    encode_preview_variant(img, format, quality).map_err(|e| format!("Failed to encode {}: {}", format.name(), e))
+}
+
+// Function to generate a thumbnail that is not in the cache yet
+fn generate_uncached_thumbnail(path: &Path, file_path: &str, cache_key: &str, scale: u32) -> Option<String> {
+    let settings = quality_settings(file_path);
+    let size = settings.thumbnail_size * scale;
+    if let Some((width, height)) = oversized_source(file_path) {
+        log::warn!("{} is {}x{}, beyond --max-source-dimension; only its embedded thumbnail is used", file_path, width, height);
+        return exif_thumbnail(path, file_path, cache_key, scale);
+    }
     // Check file extension for supported formats
     if let Some(extension) = path.extension() {
         let ext_str = extension.to_string_lossy().to_lowercase();
@@ -43,12 +305,12 @@ pub fn generate_thumbnail(file_path: &str) -> Option<String> {
             "nef" | "cr2" | "cr3" | "arw" | "orf" | "rw2" | "raf" | "dng" => {
                 log::info!("Processing RAW file thumbnail: {}", file_path);
                 
-                if let Some(result) = generate_raw_thumbnail(file_path) {
+                if let Some(result) = generate_raw_thumbnail(file_path, scale) {
                     log::info!("Successfully generated RAW thumbnail using rawloader");
-                    return Some(result);
+                    Some(result)
                 } else {
                     log::error!("RAW thumbna processing failed: {}", file_path);
-                    return None;
+                    None
                 }
             }
             // TIFF files - use specialized tiff crate
@@ -56,7 +318,7 @@ pub fn generate_thumbnail(file_path: &str) -> Option<String> {
                 log::info!("Processing TIFF file thumbnail: {}", file_path);
                 
                 // Try the specialized TIFF handler first
-                if let Some(result) = generate_tiff_thumbnail(file_path) {
+                if let Some(result) = generate_tiff_thumbnail(file_path, scale) {
                     log::info!("Successfully generated TIFF thumbnail using specialized handler");
                     return Some(result);
                 }
@@ -79,58 +341,58 @@ pub fn generate_thumbnail(file_path: &str) -> Option<String> {
                         log::debug!("Original image dimensions: {}x{}", original_width, original_height);
                         
                         // Early check: if image is very small, use it directly
-                        if original_width <= 400 && original_height <= 400 {
+                        if original_width <= THUMBNAIL_PASSTHROUGH_DIMENSION && original_height <= THUMBNAIL_PASSTHROUGH_DIMENSION && !thumbnail_fill() {
                             log::trace!("Very small image, using direct conversion");
                             // Very small image: convert to base64
-                            let mut jpeg_bytes = Vec::new();
-                            if img.write_with_encoder(
-                                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_bytes, 50)
-                            ).is_ok() {
-                                let base64_result = BASE64.encode(&jpeg_bytes);
-                                let _ = save_thumbnail_to_cache(&cache_key, &jpeg_bytes);
+                            if let Ok(thumbnail_bytes) = encode_thumbnail(&img, settings.thumbnail_quality) {
+                                let base64_result = BASE64.encode(&thumbnail_bytes);
+                                let _ = save_thumbnail_to_cache(cache_key, &thumbnail_bytes);
                                 log::debug!("Successfully processed small image thumbnail");
                                 return Some(base64_result);
                             }
                         }
 
                         // Optimize thumbnail generation based on image size
-                        let thumbnail = if original_width > 2000 || original_height > 2000 {
+                        let thumbnail = if thumbnail_fill() {
+                            log::trace!("Center-cropping thumbnail to a square");
+                            img.resize_to_fill(size, size, image::imageops::FilterType::CatmullRom)
+                        } else if original_width > 2000 || original_height > 2000 {
                             log::trace!("Large image, using progressive scaling");
                             // Large image: use progressive scaling for better performance
                             let intermediate = img.resize(
-                                800, 
-                                800, 
+                                size * 4, 
+                                size * 4, 
                                 image::imageops::FilterType::Triangle // Fast first pass
                             );
                             intermediate.resize(
-                                200, 
-                                200, 
+                                size, 
+                                size, 
                                 image::imageops::FilterType::CatmullRom // High quality final pass
                             )
                         } else {
                             log::trace!("Medium image, using direct scaling");
                             // Smaller image: direct scaling with high quality
                             img.resize(
-                                200, 
-                                200, 
+                                size, 
+                                size, 
                                 image::imageops::FilterType::CatmullRom
                             )
                         };
 
-                        // Convert to JPEG and encode as base64
-                        let mut jpeg_bytes = Vec::new();
-                        if thumbnail.write_with_encoder(
-                            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_bytes, 50)
-                        ).is_ok() {
-                            let base64_result = BASE64.encode(&jpeg_bytes);
+                        let thumbnail = sharpen_thumbnail(thumbnail);
+
+                        // Encode in the --thumbnail-format and as base64
+                        if let Ok(thumbnail_bytes) = encode_thumbnail(&thumbnail, settings.thumbnail_quality) {
+                            let base64_result = BASE64.encode(&thumbnail_bytes);
                             // Save to disk cache
-                            let _ = save_thumbnail_to_cache(&cache_key, &jpeg_bytes);
+                            let _ = save_thumbnail_to_cache(cache_key, &thumbnail_bytes);
                             log::info!("Successfully generated standard image thumbnail");
                             return Some(base64_result);
                         }
                         
-                        log::error!("JPEG encoding failed for thumbnail: {}", file_path);
-                        // If JPEG encoding failed, return None
+                        log::error!("Thumbnail encoding failed for: {}", file_path);
+                        report_failure(file_path, FailureStage::Thumbnail, "Thumbnail encoding failed");
+                        // If encoding failed, return None
                         None
                     }
                     Err(e) => {
@@ -150,7 +412,7 @@ pub fn generate_thumbnail(file_path: &str) -> Option<String> {
                                     "k25" | "kdc" | "mdc" | "mos" | "mrw" | "pef" | "ptx" | "pxn" | 
                                     "r3d" | "rwl" | "sr2" | "srf" | "srw" | "x3f" => {
                                         log::debug!("Attempting rawloader fallback for unsupported RAW format");
-                                        if let Some(result) = generate_raw_thumbnail(file_path) {
+                                        if let Some(result) = generate_raw_thumbnail(file_path, scale) {
                                             log::info!("Successfully generated thumbnail using rawloader fallback");
                                             return Some(result);
                                         }
@@ -163,26 +425,33 @@ pub fn generate_thumbnail(file_path: &str) -> Option<String> {
                                 
                                 // If rawloader failed, no other options
                                 log::error!("All processing methods failed for: {}", file_path);
-                                return None;
+                                report_failure(file_path, FailureStage::Thumbnail, &e.to_string());
+                                None
                             }
                             _ => {
                                 // For other errors, no fallback available
                                 log::error!("Image processing error for {}: {:?}", file_path, e);
+                                report_failure(file_path, FailureStage::Thumbnail, &e.to_string());
                                 None
                             }
                         }
                     }
                 }
             }
-            // Video formats - generate thumbnail from first frame
-            "mp4" | "avi" | "mov" | "wmv" | "flv" | "webm" | "mkv" | "m4v" | "3gp" | "ogv" => {
+            // HEIC/HEIF - decoded with libheif's command line tool
+            ext if is_heif_extension(ext) => {
+                log::info!("Processing HEIF file thumbnail: {}", file_path);
+                generate_heif_thumbnail(file_path, scale)
+            }
+            // Video formats (--video-extensions) - generate thumbnail from first frame
+            ext if is_video_extension(ext) => {
                 log::info!("Processing video thumbnail: {}", file_path);
                 
-                if let Some(thumbnail_base64) = generate_video_thumbnail(file_path) {
+                if let Some(thumbnail_base64) = generate_video_thumbnail(file_path, scale) {
                     // Decode base64 to get JPEG bytes for caching
                     if let Ok(jpeg_bytes) = BASE64.decode(&thumbnail_base64) {
                         // Save to disk cache
-                        if let Err(e) = save_thumbnail_to_cache(&cache_key, &jpeg_bytes) {
+                        if let Err(e) = save_thumbnail_to_cache(cache_key, &jpeg_bytes) {
                             log::warn!("Failed to cache video thumbnail: {}", e);
                         } else {
                             log::trace!("Successfully cached video thumbnail");
@@ -218,17 +487,126 @@ pub fn generate_preview(file_path: &str) -> Option<String> {
     }
     
     // Generate cache key
-    let cache_key = generate_cache_key(file_path);
+    let cache_key = preview_cache_key(file_path);
     log::trace!("The cache key: {}", cache_key);
     
     // Check disk cache first
-    if let Some(cached) = get_cached_preview(&cache_key) {
+    if let Some(cached) = get_cached_preview(&cache_key, file_path) {
         log::debug!("Using cached preview for: {}", file_path);
         return Some(cached);
     }
     
     log::debug!("No cached preview found, generating new one for: {}", file_path);
-    
+    crate::metrics::time_generation("preview", || generate_uncached_preview(path, file_path, &cache_key))
+}
+
+/// Generates a preview scaled down to `width` (see [`preview_width`]), derived from the full-size
+/// preview and cached separately per width. Previews already narrower are returned unchanged.
+pub fn generate_sized_preview(file_path: &str, width: u32) -> Option<String> {
+    let cache_key = sized_preview_cache_key(file_path, width);
+    if let Some(cached) = get_cached_preview(&cache_key, file_path) {
+        log::debug!("Using cached {}px preview for: {}", width, file_path);
+        return Some(cached);
+    }
+
+    let full_preview = generate_preview(file_path)?;
+    crate::metrics::time_generation("preview", || {
+        let jpeg_bytes = BASE64.decode(&full_preview).ok()?;
+        let img = match image::load_from_memory(&jpeg_bytes) {
+            Ok(img) => img,
+            Err(e) => {
+                log::error!("Failed to decode preview of {} for resizing: {}", file_path, e);
+                return None;
+            }
+        };
+        if img.width() <= width {
+            log::debug!("Preview of {} is already {}px wide, serving it for {}px", file_path, img.width(), width);
+            return Some(full_preview.clone());
+        }
+        let scaled = img.resize(width, PREVIEW_MAX_DIMENSION, image::imageops::FilterType::CatmullRom);
+        match super::jpeg::encode_jpeg(&scaled, quality_settings(file_path).preview_quality) {
+            Ok(scaled_bytes) => {
+                if let Err(e) = save_preview_to_cache(&cache_key, &scaled_bytes) {
+                    log::warn!("Failed to save {}px preview to cache: {}", width, e);
+                }
+                log::info!("Generated {}x{} preview for: {}", scaled.width(), scaled.height(), file_path);
+                Some(BASE64.encode(&scaled_bytes))
+            }
+            Err(e) => {
+                log::error!("JPEG encoding failed for {}px preview {}: {:?}", width, file_path, e);
+                None
+            }
+        }
+    })
+}
+
+/// Encodes the JPEG preview (full size, or `width` wide as in [`generate_sized_preview`]) in
+/// another format (--preview-formats) and caches it per format, returning the encoded bytes.
+/// When the result is not smaller than the JPEG, as lossless WebP of a photo usually is not,
+/// the JPEG is cached and returned in its place, so tell the two apart by their content.
+pub fn generate_preview_variant(file_path: &str, width: Option<u32>, format: PreviewFormat) -> Option<Vec<u8>> {
+    let cache_key = preview_variant_cache_key(file_path, width, format);
+    if let Some(cached) = get_cached_preview(&cache_key, file_path) {
+        log::debug!("Using cached {} preview for: {}", format.name(), file_path);
+        return BASE64.decode(cached).ok();
+    }
+
+    let jpeg_preview = match width {
+        Some(width) => generate_sized_preview(file_path, width)?,
+        None => generate_preview(file_path)?,
+    };
+    let jpeg_bytes = BASE64.decode(jpeg_preview).ok()?;
+    crate::metrics::time_generation("preview", || {
+        let img = match image::load_from_memory(&jpeg_bytes) {
+            Ok(img) => img,
+            Err(e) => {
+                log::error!("Failed to decode preview of {} for {} encoding: {}", file_path, format.name(), e);
+                return None;
+            }
+        };
+        let bytes = match encode_preview_variant(&img, format, AVIF_PREVIEW_QUALITY) {
+            Ok(bytes) if bytes.len() < jpeg_bytes.len() => bytes,
+            Ok(bytes) => {
+                log::debug!(
+                    "{} preview of {} is {} bytes, not smaller than the {} byte JPEG; keeping the JPEG",
+                    format.name(), file_path, bytes.len(), jpeg_bytes.len()
+                );
+                jpeg_bytes.clone()
+            }
+            Err(e) => {
+                log::error!("{} encoding failed for preview {}: {}", format.name(), file_path, e);
+                return None;
+            }
+        };
+        if let Err(e) = save_preview_to_cache(&cache_key, &bytes) {
+            log::warn!("Failed to save {} preview to cache: {}", format.name(), e);
+        }
+        log::info!("Generated {} preview ({} bytes, JPEG {} bytes) for: {}", format.name(), bytes.len(), jpeg_bytes.len(), file_path);
+        Some(bytes)
+    })
+}
+
+// Function to encode a preview or thumbnail as AVIF (with the given quality) or lossless WebP
+fn encode_preview_variant(img: &image::DynamicImage, format: PreviewFormat, quality: u8) -> image::ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    // Previews have no alpha channel; grayscale ones are encoded as RGB, which both encoders take
+    let rgb = image::DynamicImage::ImageRgb8(img.to_rgb8());
+    match format {
+        PreviewFormat::Avif => {
+            // Speed 8 of 1-10 keeps encoding a 1980px preview well under a second
+            rgb.write_with_encoder(image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut bytes, 8, quality))?
+        }
+        PreviewFormat::Webp => rgb.write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut bytes))?,
+    }
+    Ok(bytes)
+}
+
+// Function to generate a preview that is not in the cache yet
+fn generate_uncached_preview(path: &Path, file_path: &str, cache_key: &str) -> Option<String> {
+    if let Some((width, height)) = oversized_source(file_path) {
+        log::warn!("Not previewing {}: {}x{} is beyond --max-source-dimension", file_path, width, height);
+        return None;
+    }
     // Check file extension for supported formats
     if let Some(extension) = path.extension() {
         let ext_str = extension.to_string_lossy().to_lowercase();
@@ -240,10 +618,10 @@ pub fn generate_preview(file_path: &str) -> Option<String> {
                 
                 if let Some(result) = generate_raw_preview(file_path) {
                     log::info!("Successfully generated RAW preview using rawloader");
-                    return Some(result);
+                    Some(result)
                 } else {
                     log::error!("RAW preview processing failed: {}", file_path);
-                    return None;
+                    None
                 }
             }
             // TIFF files - use specialized tiff crate
@@ -271,29 +649,38 @@ pub fn generate_preview(file_path: &str) -> Option<String> {
                     Ok(img) => {
                         let (original_width, original_height) = (img.width(), img.height());
                         log::debug!("Preview processing - original dimensions: {}x{}", original_width, original_height);
+
+                        // Apply the editor's crop/orientation so the preview matches the edited image
+                        let apply_develop = crate::cli::CLI_ARGS.get().map(|a| a.apply_develop_settings).unwrap_or(false);
+                        let develop_settings = if apply_develop { load_develop_settings(file_path) } else { None };
+                        let img = match develop_settings {
+                            Some(settings) => {
+                                log::debug!("Applying develop settings to preview of {}: {:?}", file_path, settings);
+                                settings.apply(img)
+                            }
+                            None => img,
+                        };
                         
-                        let max_dimension = 1980u32;
+                        let max_dimension = PREVIEW_MAX_DIMENSION;
                         log::trace!("Scaling image to fit {}x{}", max_dimension, max_dimension);
                         let scaled_img = img.thumbnail(max_dimension, max_dimension);
                         
-                        let mut jpeg_bytes = Vec::new();
-                        match scaled_img.write_with_encoder(
-                            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_bytes, 60)
-                        ) {
-                            Ok(_) => {
+                        match super::jpeg::encode_jpeg(&scaled_img, quality_settings(file_path).preview_quality) {
+                            Ok(jpeg_bytes) => {
                                 log::debug!("Successfully processed preview, size: {} bytes", jpeg_bytes.len());
                                 
-                                if let Err(e) = super::cache::save_preview_to_cache(&cache_key, &jpeg_bytes) {
+                                if let Err(e) = super::cache::save_preview_to_cache(cache_key, &jpeg_bytes) {
                                     log::warn!("Failed to save preview to cache: {}", e);
                                 } else {
                                     log::trace!("Successfully cached preview");
                                 }
                                 let base64_result = BASE64.encode(&jpeg_bytes);
                                 log::info!("Successfully generated preview for: {}", file_path);
-                                return Some(base64_result);
+                                Some(base64_result)
                             }
                             Err(e) => {
                                 log::error!("JPEG encoding failed for preview {}: {:?}", file_path, e);
+                                report_failure(file_path, FailureStage::Preview, &format!("JPEG encoding failed: {}", e));
                                 None
                             }
                         }
@@ -328,17 +715,29 @@ pub fn generate_preview(file_path: &str) -> Option<String> {
                                 
                                 // If rawloader failed, no other options
                                 log::error!("All processing methods failed for: {}", file_path);
-                                return None;
+                                report_failure(file_path, FailureStage::Preview, &e.to_string());
+                                None
                             }
                             _ => {
                                 // For other errors, no fallback available
                                 log::error!("Image processing error for {}: {:?}", file_path, e);
+                                report_failure(file_path, FailureStage::Preview, &e.to_string());
                                 None
                             }
                         }
                     }
                 }
             }
+            // HEIC/HEIF - decoded with libheif's command line tool
+            ext if is_heif_extension(ext) => {
+                log::info!("Processing HEIF file preview: {}", file_path);
+                generate_heif_preview(file_path)
+            }
+            // Videos are played from their transcoded preview through /video instead
+            ext if is_video_extension(ext) => {
+                log::debug!("No image preview for video: {}", file_path);
+                None
+            }
             _ => {
                 log::debug!("Unsupported file extension for preview: {}", ext_str);
                 None
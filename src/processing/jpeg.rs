@@ -0,0 +1,53 @@
+use image::DynamicImage;
+
+// Encode an image as JPEG for thumbnails and previews. With --progressive-jpeg the
+// image is written as a progressive JPEG, which browsers can render coarse-to-fine
+// while it is still downloading. Falls back to a baseline JPEG when the
+// progressive-jpeg feature is not compiled in or the progressive encoder fails.
+// Grayscale images (e.g. scanned documents) stay single-channel in both encoders,
+// which makes them noticeably smaller than the same image written as RGB.
+pub fn encode_jpeg(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let progressive = crate::cli::CLI_ARGS.get().map(|a| a.progressive_jpeg).unwrap_or(false);
+    if progressive {
+        match encode_progressive(img, quality) {
+            Ok(jpeg_bytes) => {
+                log::trace!("Encoded progressive JPEG, size: {} bytes, quality: {}", jpeg_bytes.len(), quality);
+                return Ok(jpeg_bytes);
+            }
+            Err(e) => log::warn!("Progressive JPEG encoding unavailable, using baseline: {}", e),
+        }
+    }
+    encode_baseline(img, quality)
+}
+
+pub fn encode_baseline(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let mut jpeg_bytes = Vec::new();
+    img.write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_bytes, quality))
+        .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+    Ok(jpeg_bytes)
+}
+
+#[cfg(feature = "progressive-jpeg")]
+pub fn encode_progressive(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let (width, height) = (
+        u16::try_from(img.width()).map_err(|_| format!("Image width {} too large for JPEG", img.width()))?,
+        u16::try_from(img.height()).map_err(|_| format!("Image height {} too large for JPEG", img.height()))?,
+    );
+    let (samples, color_type) = if img.color().has_color() {
+        (img.to_rgb8().into_raw(), jpeg_encoder::ColorType::Rgb)
+    } else {
+        (img.to_luma8().into_raw(), jpeg_encoder::ColorType::Luma)
+    };
+    let mut jpeg_bytes = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut jpeg_bytes, quality);
+    encoder.set_progressive(true);
+    encoder
+        .encode(&samples, width, height, color_type)
+        .map_err(|e| format!("Failed to encode progressive JPEG: {}", e))?;
+    Ok(jpeg_bytes)
+}
+
+#[cfg(not(feature = "progressive-jpeg"))]
+pub fn encode_progressive(_img: &DynamicImage, _quality: u8) -> Result<Vec<u8>, String> {
+    Err("built without the progressive-jpeg feature".to_string())
+}
@@ -1,5 +1,13 @@
+pub mod badge;
 pub mod cache;
+pub mod color;
+pub mod develop;
+pub mod embedded_preview;
+pub mod exif;
+pub mod heif;
+pub mod icon;
 pub mod image;
+pub mod jpeg;
 pub mod raw;
 pub mod tiff;
 pub mod video;
@@ -5,13 +5,34 @@ use std::fs;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::cache::{generate_cache_key, save_thumbnail_to_cache, save_preview_to_cache};
+use crate::failures::{report_failure, FailureStage};
+use super::embedded_preview::extract_embedded_preview;
+use super::cache::{generate_cache_key, preview_cache_key, save_thumbnail_to_cache, save_preview_to_cache, scaled_thumbnail_cache_key, thumbnail_fill};
+use super::exif::read_jpeg_header;
+use super::image::THUMBNAIL_PASSTHROUGH_DIMENSION;
 
-// Try to extract the best available preview from a RAW file using exiv2
-// Returns raw JPEG bytes of the largest extracted preview.
-fn exiv2_extract_best_preview(file_path: &str) -> Result<Vec<u8>, String> {
+/// Tries to extract the best available preview from a RAW file (or a TIFF) using exiv2.
+/// Returns raw JPEG bytes of the extracted preview with the most pixels, or with a `min_dimension`
+/// hint (thumbnails), of the smallest one whose shorter side is at least that large. Falls back
+/// to the largest preview when none is large enough or the previews cannot be listed.
+pub fn exiv2_extract_best_preview(file_path: &str, min_dimension: Option<u32>) -> Result<Vec<u8>, String> {
     log::info!("Attempting exiv2 preview extraction for: {}", file_path);
 
+    // Only the selected preview is written out, instead of every preview exiv2 finds
+    let preview_number = min_dimension.and_then(|min_dimension| {
+        let previews = exiv2_list_previews(file_path)?;
+        let selected = smallest_preview_covering(&previews, min_dimension)?;
+        log::debug!(
+            "Using exiv2 preview {} ({}x{}) for a {}px target: {}",
+            selected.number, selected.width, selected.height, min_dimension, file_path
+        );
+        Some(selected.number)
+    });
+    let extract_arg = match preview_number {
+        Some(number) => format!("-ep{}", number),
+        None => "-ep".to_string(),
+    };
+
     // Create a unique temporary directory for extraction
     let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
     let tmp_dir: PathBuf = std::env::temp_dir().join(format!(
@@ -24,13 +45,13 @@ fn exiv2_extract_best_preview(file_path: &str) -> Result<Vec<u8>, String> {
     }
     log::trace!("Created temp dir for exiv2: {}", tmp_dir.display());
 
-    // Run: exiv2 -ep <file>
+    // Run: exiv2 -ep[N] <file>
     // We set current_dir to tmp_dir so the previews are written there.
     let output = Command::new("exiv2")
         .arg("-f")
         .arg("-l")
         .arg(&tmp_dir)
-        .arg("-ep")
+        .arg(&extract_arg)
         .arg(file_path)
         .current_dir(&tmp_dir)
         .output();
@@ -56,8 +77,8 @@ fn exiv2_extract_best_preview(file_path: &str) -> Result<Vec<u8>, String> {
     log::trace!("exiv2 preview extraction completed for: {}", file_path);
 
 
-    // Find the largest preview file produced (usually *-preview*.jpg/jpeg)
-    let mut best_file: Option<(PathBuf, u64)> = None;
+    // Collect the preview files produced (usually *-preview*.jpg/jpeg)
+    let mut candidates = Vec::new();
     if let Ok(entries) = fs::read_dir(&tmp_dir) {
         for entry in entries.flatten() {
             let path = entry.path();
@@ -72,26 +93,16 @@ fn exiv2_extract_best_preview(file_path: &str) -> Result<Vec<u8>, String> {
                 log::warn!("Skipping non-JPEG entry: {}", path.display());
                 continue; 
             }
-            if !(name.contains("preview") || name.contains("thumb") || name.contains("jpg")) {
-                log::trace!("Skipping non-JPEG entry: {}", path.display());
-                // Keep generic jpgs too, but prioritize preview-ish names by size anyway
-            }
-            if let Ok(meta) = fs::metadata(&path) {
-                let size = meta.len();
-                match &best_file {
-                    Some((_, best_size)) if size <= *best_size => {}
-                    _ => { best_file = Some((path.clone(), size)); }
-                }
-            }
+            candidates.push(path);
         }
     } else {
         log::warn!("Failed to read temp dir for exiv2 outputs: {}", tmp_dir.display());
     }
 
     // Read best preview and cleanup temp directory
-    let result = match best_file {
-        Some((path, size)) => {
-            log::info!("exiv2 preview selected: {} ({} bytes)", path.display(), size);
+    let result = match best_preview_file(candidates) {
+        Some(path) => {
+            log::info!("exiv2 preview selected: {}", path.display());
             fs::read(&path).map_err(|e| format!("Failed to read exiv2 output {}: {}", path.display(), e))
         }
         None => Err("No exiv2 preview files produced".to_string()),
@@ -100,60 +111,177 @@ fn exiv2_extract_best_preview(file_path: &str) -> Result<Vec<u8>, String> {
     result
 }
 
-// Scale JPEG bytes to max_dimension and re-encode with given quality
-fn scale_jpeg_bytes(jpeg: &[u8], max_dimension: u32, jpeg_quality: u8) -> Result<Vec<u8>, String> {
+/// A preview embedded in a RAW file, as listed by `exiv2 -pp`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedPreview {
+    /// Number to extract it with, as in `exiv2 -ep<number>`
+    pub number: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Function to list the JPEG previews embedded in a file; None when exiv2 fails
+fn exiv2_list_previews(file_path: &str) -> Option<Vec<EmbeddedPreview>> {
+    let output = match Command::new("exiv2").arg("-pp").arg(file_path).output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            log::debug!("exiv2 -pp failed for {}: {}", file_path, String::from_utf8_lossy(&output.stderr));
+            return None;
+        }
+        Err(e) => {
+            log::debug!("Failed to execute exiv2 -pp for {}: {}", file_path, e);
+            return None;
+        }
+    };
+    Some(parse_preview_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses the output of `exiv2 -pp`, lines such as "Preview 2: image/jpeg, 570x375 pixels, 46098 bytes".
+/// Previews in other formats (e.g. TIFF) are left out, as only JPEG previews are extracted.
+pub fn parse_preview_list(output: &str) -> Vec<EmbeddedPreview> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (number, rest) = line.trim().strip_prefix("Preview ")?.split_once(':')?;
+            let mut fields = rest.split(',').map(str::trim);
+            if fields.next()? != "image/jpeg" {
+                return None;
+            }
+            let (width, height) = fields.next()?.strip_suffix(" pixels")?.split_once('x')?;
+            Some(EmbeddedPreview { number: number.parse().ok()?, width: width.parse().ok()?, height: height.parse().ok()? })
+        })
+        .collect()
+}
+
+/// Picks the preview with the fewest pixels whose shorter side is at least `min_dimension`, so it
+/// also fills a square-cropped thumbnail. None when every preview is smaller.
+pub fn smallest_preview_covering(previews: &[EmbeddedPreview], min_dimension: u32) -> Option<&EmbeddedPreview> {
+    previews
+        .iter()
+        .filter(|preview| preview.width.min(preview.height) >= min_dimension)
+        .min_by_key(|preview| u64::from(preview.width) * u64::from(preview.height))
+}
+
+/// Picks the sharpest of the preview files extracted from a RAW file: the one with the most pixels
+/// according to its JPEG frame header. A small, highly compressed high resolution preview thus wins
+/// over a larger low resolution one. Byte size only decides between previews of equal pixel count,
+/// so a file whose header cannot be read is chosen only when no header can be.
+pub fn best_preview_file(candidates: impl IntoIterator<Item = PathBuf>) -> Option<PathBuf> {
+    candidates
+        .into_iter()
+        .filter_map(|path| {
+            let size = fs::metadata(&path).ok()?.len();
+            let pixels = path
+                .to_str()
+                .and_then(read_jpeg_header)
+                .map(|header| u64::from(header.width) * u64::from(header.height))
+                .unwrap_or(0);
+            log::trace!("exiv2 preview candidate {}: {} pixels, {} bytes", path.display(), pixels, size);
+            Some(((pixels, size), path))
+        })
+        .max_by_key(|(rank, _)| *rank)
+        .map(|(_, path)| path)
+}
+
+/// Scales JPEG bytes down to max_dimension (or center-crops to a max_dimension square) and re-encodes
+/// them with the given quality. Extracted previews are never upscaled: one already within the target
+/// is only re-encoded (or cropped), as is a thumbnail source within THUMBNAIL_PASSTHROUGH_DIMENSION,
+/// matching standard images. Thumbnails get the optional --thumbnail-sharpen pass after scaling
+/// and are encoded in the --thumbnail-format, previews always as JPEG.
+pub fn scale_jpeg_bytes(jpeg: &[u8], max_dimension: u32, jpeg_quality: u8, crop_to_square: bool, thumbnail: bool) -> Result<Vec<u8>, String> {
     let img = image::load_from_memory(jpeg).map_err(|e| format!("Failed to load JPEG bytes: {}", e))?;
-    let scaled = img.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom);
-    let mut out = Vec::new();
-    scaled
-        .write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, jpeg_quality))
-        .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
-    Ok(out)
+    let encode = |img: &image::DynamicImage| {
+        if thumbnail {
+            super::image::encode_thumbnail(img, jpeg_quality)
+        } else {
+            super::jpeg::encode_jpeg(img, jpeg_quality)
+        }
+    };
+    let (width, height) = (img.width(), img.height());
+    let passthrough_dimension = if thumbnail { THUMBNAIL_PASSTHROUGH_DIMENSION } else { max_dimension };
+
+    if crop_to_square && width.min(height) <= max_dimension {
+        log::debug!("Extracted preview {}x{} is within {}px, cropping without scaling", width, height, max_dimension);
+        let side = width.min(height);
+        return encode(&img.crop_imm((width - side) / 2, (height - side) / 2, side, side));
+    }
+    if !crop_to_square && width <= passthrough_dimension && height <= passthrough_dimension {
+        log::debug!("Extracted preview {}x{} is within {}px, re-encoding without scaling", width, height, passthrough_dimension);
+        return encode(&img);
+    }
+
+    let scaled = if crop_to_square {
+        img.resize_to_fill(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
+    } else {
+        img.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
+    };
+    let scaled = if thumbnail { super::image::sharpen_thumbnail(scaled) } else { scaled };
+    encode(&scaled)
+}
+
+/// Extracts the best embedded preview of a RAW file (see [`exiv2_extract_best_preview`]) and
+/// scales it with `scale`. The preview is read in-process first; exiv2 is only run when that
+/// fails, e.g. for CR3 files, or the preview found does not decode.
+fn scale_raw_preview(
+    file_path: &str,
+    min_dimension: Option<u32>,
+    scale: impl Fn(&[u8]) -> Result<Vec<u8>, String>,
+) -> Result<Vec<u8>, String> {
+    match extract_embedded_preview(file_path, min_dimension).and_then(|bytes| scale(&bytes)) {
+        Ok(jpeg_bytes) => Ok(jpeg_bytes),
+        Err(e) => {
+            log::debug!("In-process preview extraction failed for {}, trying exiv2: {}", file_path, e);
+            exiv2_extract_best_preview(file_path, min_dimension).and_then(|bytes| scale(&bytes))
+        }
+    }
 }
 
 pub fn generate_raw_preview(file_path: &str) -> Option<String> {
     log::info!("Generating RAW preview for: {}", file_path);
 
-    let cache_key = generate_cache_key(file_path);
+    let cache_key = preview_cache_key(file_path);
+    let quality = super::image::quality_settings(file_path).preview_quality;
 
-    // First try exiv2-based extraction
-    match exiv2_extract_best_preview(file_path)
-        .and_then(|bytes| scale_jpeg_bytes(&bytes, 1980, 60))
-    {
+    match scale_raw_preview(file_path, None, |bytes| {
+        scale_jpeg_bytes(bytes, super::image::PREVIEW_MAX_DIMENSION, quality, false, false)
+    }) {
         Ok(jpeg_bytes) => {
             if let Err(e) = save_preview_to_cache(&cache_key, &jpeg_bytes) {
-                log::warn!("Failed to cache exiv2 preview: {}", e);
+                log::warn!("Failed to cache RAW preview: {}", e);
             }
             let base64_result = BASE64.encode(&jpeg_bytes);
-            log::info!("Successfully generated RAW preview via exiv2, base64 length: {}", base64_result.len());
-            return Some(base64_result);
+            log::info!("Successfully generated RAW preview, base64 length: {}", base64_result.len());
+            Some(base64_result)
         }
         Err(e) => {
-            log::error!("exiv2 preview failed for {}: {}", file_path, e);
+            log::error!("RAW preview extraction failed for {}: {}", file_path, e);
+            report_failure(file_path, FailureStage::Preview, &e);
             None
         }
     }
 }
 
-pub fn generate_raw_thumbnail(file_path: &str) -> Option<String> {
+pub fn generate_raw_thumbnail(file_path: &str, scale: u32) -> Option<String> {
     log::info!("Generating RAW thumbnail for: {}", file_path);
 
-    let cache_key = generate_cache_key(file_path);
+    let cache_key = scaled_thumbnail_cache_key(file_path, scale);
+    let settings = super::image::quality_settings(file_path);
 
-    // First try exiv2-based extraction
-    match exiv2_extract_best_preview(file_path)
-        .and_then(|bytes| scale_jpeg_bytes(&bytes, 200, 50))
-    {
+    let max_dimension = settings.thumbnail_size * scale;
+    match scale_raw_preview(file_path, Some(max_dimension), |bytes| {
+        scale_jpeg_bytes(bytes, max_dimension, settings.thumbnail_quality, thumbnail_fill(), true)
+    }) {
         Ok(jpeg_bytes) => {
             if let Err(e) = save_thumbnail_to_cache(&cache_key, &jpeg_bytes) {
-                log::warn!("Failed to cache exiv2 thumbnail: {}", e);
+                log::warn!("Failed to cache RAW thumbnail: {}", e);
             }
             let base64_result = BASE64.encode(&jpeg_bytes);
-            log::info!("Successfully generated RAW thumbnail via exiv2, base64 length: {}", base64_result.len());
-            return Some(base64_result);
+            log::info!("Successfully generated RAW thumbnail, base64 length: {}", base64_result.len());
+            Some(base64_result)
         }
         Err(e) => {
-            log::error!("exiv2 thumbnail failed for {}: {}", file_path, e);
+            log::error!("RAW thumbnail extraction failed for {}: {}", file_path, e);
+            report_failure(file_path, FailureStage::Thumbnail, &e);
             None
         }
     }
@@ -1,7 +1,24 @@
 use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use image::{DynamicImage, RgbImage};
 use tiff;
+use tiff::decoder::{ChunkType, Decoder, DecodingResult};
+use tiff::tags::Tag;
 
+// Callback used to persist the encoded JPEG into one of the disk caches
+pub type CacheSaveFn = fn(&str, &[u8]) -> std::io::Result<()>;
+
+// Images with more pixels than this are downsampled chunk by chunk instead of being decoded whole
+pub const FULL_DECODE_MAX_PIXELS: u64 = 64 * 1024 * 1024;
+
+// Upper bound on the number of IFDs inspected when looking for a reduced-resolution overview
+const MAX_OVERVIEW_IFDS: usize = 64;
+
+// Old-style JPEG compression, which the tiff crate does not decode, and the tags pointing to the
+// complete JPEG stream such files carry
+const OLD_JPEG_COMPRESSION: u16 = 6;
+const JPEG_INTERCHANGE_FORMAT: Tag = Tag::Unknown(513);
+const JPEG_INTERCHANGE_FORMAT_LENGTH: Tag = Tag::Unknown(514);
 
 // Shared function for TIFF to RGB JPEG (for both thumbnail and preview)
 pub fn convert_tiff_to_rgb_jpeg(
@@ -9,219 +26,394 @@ pub fn convert_tiff_to_rgb_jpeg(
     max_dimension: u32,
     jpeg_quality: u8,
     cache_key: Option<&str>,
-    save_to_cache: Option<fn(&str, &[u8]) -> std::io::Result<()>>,
+    save_to_cache: Option<CacheSaveFn>,
+    crop_to_square: bool,
+    sharpen: bool,
 ) -> Result<Vec<u8>, String> {
     log::info!("Processing TIFF file with tiff crate: {}", file_path);
-    
+
+    let (rgb_img, color_type) = match decode_tiff_rgb(file_path, max_dimension, crop_to_square, FULL_DECODE_MAX_PIXELS) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            // Compressions the tiff crate cannot decode may still come with an embedded JPEG
+            // preview, extracted with exiv2 as for RAW files; thumbnails (sharpened) pick the smallest sufficient one
+            log::info!("Trying the embedded previews of undecodable TIFF {}", file_path);
+            let jpeg_bytes = super::raw::exiv2_extract_best_preview(file_path, sharpen.then_some(max_dimension))
+                .and_then(|bytes| super::raw::scale_jpeg_bytes(&bytes, max_dimension, jpeg_quality, crop_to_square, sharpen))
+                .map_err(|fallback| format!("{}; no embedded preview either: {}", e, fallback))?;
+            if let (Some(key), Some(save_fn)) = (cache_key, save_to_cache) {
+                if let Err(e) = save_fn(key, &jpeg_bytes) {
+                    log::warn!("Failed to save TIFF result to cache: {}", e);
+                }
+            }
+            return Ok(jpeg_bytes);
+        }
+    };
+    let (width, height) = rgb_img.dimensions();
+    log::trace!("Created RGB image from TIFF data");
+
+    // Grayscale scans are encoded as grayscale JPEG again
+    let dynamic_img = match color_type {
+        tiff::ColorType::Gray(_) | tiff::ColorType::GrayA(_) => {
+            log::debug!("Grayscale TIFF, keeping a single channel: {}", file_path);
+            DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(rgb_img).to_luma8())
+        }
+        _ => DynamicImage::ImageRgb8(rgb_img),
+    };
+    let scaled_img = if crop_to_square {
+        log::debug!("Center-cropping TIFF image ({}x{}) to {}x{}", width, height, max_dimension, max_dimension);
+        dynamic_img.resize_to_fill(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
+    } else if width > max_dimension || height > max_dimension {
+        log::debug!("Large TIFF image ({}x{}), using progressive scaling to {}", width, height, max_dimension);
+        let intermediate = dynamic_img.resize(800, 800, image::imageops::FilterType::Triangle);
+        intermediate.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
+    } else {
+        log::debug!("Small TIFF image ({}x{}), direct scaling to {}", width, height, max_dimension);
+        dynamic_img.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
+    };
+
+    log::trace!("Image scaling completed");
+    let scaled_img = if sharpen { super::image::sharpen_thumbnail(scaled_img) } else { scaled_img };
+
+    // Thumbnails (sharpened) are encoded in the --thumbnail-format, previews as JPEG
+    let encoded = if sharpen {
+        super::image::encode_thumbnail(&scaled_img, jpeg_quality)
+    } else {
+        super::jpeg::encode_jpeg(&scaled_img, jpeg_quality)
+    };
+    match encoded {
+        Ok(jpeg_bytes) => {
+            log::debug!("Successfully encoded TIFF, size: {} bytes, quality: {}", jpeg_bytes.len(), jpeg_quality);
+
+            if let (Some(key), Some(save_fn)) = (cache_key, save_to_cache) {
+                match save_fn(key, &jpeg_bytes) {
+                    Ok(_) => log::trace!("Saved TIFF result to cache"),
+                    Err(e) => log::warn!("Failed to save TIFF result to cache: {}", e),
+                }
+            }
+            Ok(jpeg_bytes)
+        },
+        Err(e) => {
+            log::error!("Encoding failed for TIFF {}: {:?}", file_path, e);
+            Err("Encoding failed".to_string())
+        }
+    }
+}
+
+/// Decodes a TIFF into an 8-bit RGB image, large enough to scale to `min_dimension` on its long
+/// edge (short edge when `crop_to_square`) without decoding more than needed.
+///
+/// A reduced-resolution overview IFD (`NewSubfileType` 1, as written by pyramid/COG tools) is used
+/// when one is still large enough. Images above `max_full_decode_pixels` are read one strip or tile
+/// at a time and box-downsampled on the fly, so memory stays bounded by the chunk and output size.
+/// The color type the decoded IFD is stored in is returned alongside, to tell grayscale sources apart.
+///
+/// JPEG compression is decoded in both styles: "new style" (7) by the tiff crate, strip by strip or
+/// tile by tile, and "old style" (6) from the complete JPEG stream its JPEGInterchangeFormat tag
+/// points to.
+pub fn decode_tiff_rgb(
+    file_path: &str,
+    min_dimension: u32,
+    crop_to_square: bool,
+    max_full_decode_pixels: u64,
+) -> Result<(RgbImage, tiff::ColorType), String> {
     let file = File::open(file_path)
         .map_err(|e| {
             log::error!("Failed to open TIFF file {}: {:?}", file_path, e);
             format!("Failed to open TIFF file {}: {:?}", file_path, e)
         })?;
-    
+
     log::debug!("Successfully opened TIFF file: {}", file_path);
-    
-    let mut decoder = tiff::decoder::Decoder::new(file)
+
+    let mut decoder = Decoder::new(file)
         .map_err(|e| {
             log::error!("Failed to create TIFF decoder for {}: {:?}", file_path, e);
             format!("Failed to create TIFF decoder for {}: {:?}", file_path, e)
         })?
         .with_limits(tiff::decoder::Limits::unlimited());
-    
+
     log::trace!("Created TIFF decoder with unlimited limits");
-    
+
+    let max_source_dimension = super::image::max_source_dimension();
+    seek_to_overview(&mut decoder, min_dimension, crop_to_square, max_source_dimension)
+        .map_err(|e| format!("Failed to read TIFF directories for {}: {:?}", file_path, e))?;
+
     let (width, height) = decoder.dimensions()
         .map_err(|e| {
             log::error!("Failed to get TIFF dimensions for {}: {:?}", file_path, e);
             format!("Failed to get TIFF dimensions for {}: {:?}", file_path, e)
         })?;
-    
+
     log::info!("TIFF dimensions: {}x{}", width, height);
-    
-    match decoder.read_image() {
-        Ok(tiff::decoder::DecodingResult::U8(data)) => {
-            // Detect color type
-            let color_type = decoder.colortype().unwrap_or(tiff::ColorType::RGB(8));
-            log::debug!("TIFF color type: {:?}", color_type);
-
-            let rgb_data = match color_type {
-                tiff::ColorType::Gray(nbits) => {
-                    log::info!("TIFF is greyscale ({} bits), converting to RGB", nbits);
-                    // Convert grayscale to RGB by duplicating each value
-                    data.iter().flat_map(|v| std::iter::repeat(*v).take(3)).collect::<Vec<u8>>()
-                }
-                tiff::ColorType::RGB(_) => {
-                    data
-                }
-                tiff::ColorType::YCbCr(_) => {
-                    log::info!("TIFF is YCbCr, converting to RGB");
-                    let mut rgb_data = Vec::with_capacity(data.len());
-                    for chunk in data.chunks_exact(3) {
-                        let y = chunk[0] as f32;
-                        let cb = chunk[1] as f32 - 128.0;
-                        let cr = chunk[2] as f32 - 128.0;
-
-                        let r = (y + 1.402 * cr).clamp(0.0, 255.0) as u8;
-                        let g = (y - 0.344136 * cb - 0.714136 * cr).clamp(0.0, 255.0) as u8;
-                        let b = (y + 1.772 * cb).clamp(0.0, 255.0) as u8;
-
-                        rgb_data.push(r);
-                        rgb_data.push(g);
-                        rgb_data.push(b);
-                    }
-                    rgb_data
-                }
-                _ => {
-                    log::warn!("TIFF color type not handled: {:?}", color_type);
-                    data
-                }
-            };
-
-            let rgb_width = width;
-            let rgb_height = height;
-            let rgb_img = RgbImage::from_raw(rgb_width, rgb_height, rgb_data);
-
-            if let Some(rgb_img) = rgb_img {
-                log::trace!("Created RGB image from raw data");
-                
-                let dynamic_img = DynamicImage::ImageRgb8(rgb_img);
-                let scaled_img = if width > max_dimension || height > max_dimension {
-                    log::debug!("Large TIFF image ({}x{}), using progressive scaling to {}", width, height, max_dimension);
-                    let intermediate = dynamic_img.resize(800, 800, image::imageops::FilterType::Triangle);
-                    intermediate.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
-                } else {
-                    log::debug!("Small TIFF image ({}x{}), direct scaling to {}", width, height, max_dimension);
-                    dynamic_img.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
-                };
-                
-                log::trace!("Image scaling completed");
-                
-                let mut jpeg_bytes = Vec::new();
-                match scaled_img.write_with_encoder(
-                    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_bytes, jpeg_quality)
-                ) {
-                    Ok(_) => {
-                        log::debug!("Successfully encoded TIFF as JPEG, size: {} bytes, quality: {}", jpeg_bytes.len(), jpeg_quality);
-                        
-                        if let (Some(key), Some(save_fn)) = (cache_key, save_to_cache) {
-                            match save_fn(key, &jpeg_bytes) {
-                                Ok(_) => log::trace!("Saved TIFF result to cache"),
-                                Err(e) => log::warn!("Failed to save TIFF result to cache: {}", e),
-                            }
-                        }
-                        Ok(jpeg_bytes)
-                    },
-                    Err(e) => {
-                        log::error!("JPEG encoding failed for TIFF {}: {:?}", file_path, e);
-                        Err("JPEG encoding failed".to_string())
-                    }
-                }
-            } else {
-                log::error!("Failed to create RGB image from TIFF data for {}", file_path);
-                Err("Failed to create RGB image from TIFF data for {}".to_string())
-            }
+    if let Some(limit) = max_source_dimension.filter(|&limit| width.max(height) > limit) {
+        log::warn!("TIFF {} is {}x{}, beyond --max-source-dimension {}, and has no smaller overview", file_path, width, height, limit);
+        return Err(format!("TIFF {} is too large to decode ({}x{})", file_path, width, height));
+    }
+
+    if decoder.find_tag_unsigned::<u16>(Tag::Compression).ok().flatten() == Some(OLD_JPEG_COMPRESSION) {
+        log::debug!("TIFF uses old-style JPEG compression: {}", file_path);
+        return decode_old_jpeg(&mut decoder, file_path).map_err(|e| {
+            log::error!("Failed to read TIFF image data for {}: {}", file_path, e);
+            format!("Failed to read TIFF image data for {}: {}", file_path, e)
+        });
+    }
+
+    let color_type = decoder.colortype().unwrap_or(tiff::ColorType::RGB(8));
+    log::debug!("TIFF color type: {:?}", color_type);
+
+    let pixels = u64::from(width) * u64::from(height);
+    // Chunks of planar TIFFs only hold one sample plane, so those are always decoded whole
+    let planar = decoder
+        .find_tag_unsigned::<u16>(Tag::PlanarConfiguration)
+        .ok()
+        .flatten()
+        == Some(2);
+    if pixels > max_full_decode_pixels && !planar {
+        log::info!("Large TIFF ({} pixels), decoding chunk by chunk", pixels);
+        return decode_downsampled(&mut decoder, width, height, color_type, min_dimension, crop_to_square)
+            .map(|img| (img, color_type))
+            .map_err(|e| {
+                log::error!("Failed to read TIFF image data for {}: {}", file_path, e);
+                format!("Failed to read TIFF image data for {}: {}", file_path, e)
+            });
+    }
+    if pixels > max_full_decode_pixels {
+        log::warn!("Large planar TIFF ({} pixels) has to be decoded whole: {}", pixels, file_path);
+    }
+
+    let data = decoder.read_image()
+        .map_err(|e| {
+            log::error!("Failed to read TIFF image data for {}: {:?}", file_path, e);
+            format!("Failed to read TIFF image data for {}: {:?}", file_path, e)
+        })?;
+    let rgb_data = to_rgb8(data, color_type)
+        .map_err(|e| {
+            log::error!("{} for {}", e, file_path);
+            format!("{} for {}", e, file_path)
+        })?;
+
+    let rgb_img = RgbImage::from_raw(width, height, rgb_data).ok_or_else(|| {
+        log::error!("Failed to create RGB image from TIFF data for {}", file_path);
+        format!("Failed to create RGB image from TIFF data for {}", file_path)
+    })?;
+    Ok((rgb_img, color_type))
+}
+
+// Function to decode an old-style JPEG-compressed IFD from the JPEG stream its
+// JPEGInterchangeFormat tags point to. Old-style files without that stream, split over strips with
+// separately stored tables, are not supported.
+fn decode_old_jpeg<R: Read + Seek>(decoder: &mut Decoder<R>, file_path: &str) -> Result<(RgbImage, tiff::ColorType), String> {
+    let offset = decoder.find_tag_unsigned::<u64>(JPEG_INTERCHANGE_FORMAT).ok().flatten();
+    let length = decoder.find_tag_unsigned::<u64>(JPEG_INTERCHANGE_FORMAT_LENGTH).ok().flatten();
+    let (Some(offset), Some(length)) = (offset, length) else {
+        return Err("old-style JPEG compression without a JPEGInterchangeFormat stream is not supported".to_string());
+    };
+
+    let mut file = File::open(file_path).map_err(|e| format!("Failed to reopen TIFF file: {}", e))?;
+    let file_size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if offset.checked_add(length).is_none_or(|end| end > file_size) {
+        return Err(format!("JPEG stream at {} ({} bytes) extends past the end of the file", offset, length));
+    }
+    let mut jpeg = vec![0u8; length as usize];
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(&mut jpeg))
+        .map_err(|e| format!("Failed to read the JPEG stream: {}", e))?;
+
+    let img = image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg)
+        .map_err(|e| format!("Failed to decode the JPEG stream: {}", e))?;
+    log::debug!("Decoded old-style JPEG stream of {} bytes to {}x{}", length, img.width(), img.height());
+    let color_type = match img.color() {
+        image::ColorType::L8 | image::ColorType::L16 => tiff::ColorType::Gray(8),
+        _ => tiff::ColorType::RGB(8),
+    };
+    Ok((img.to_rgb8(), color_type))
+}
+
+/// Dimensions of the largest image of a TIFF that can be decoded within `max_dimension`: the main
+/// image when it is within the limit, else its largest overview that is, else the main image.
+/// Read from the directories only, without decoding image data.
+pub fn decodable_tiff_dimensions(file_path: &str, max_dimension: u32) -> Option<(u32, u32)> {
+    let file = File::open(file_path).ok()?;
+    let mut decoder = Decoder::new(file).ok()?.with_limits(tiff::decoder::Limits::unlimited());
+    // No overview covers u32::MAX, so this only falls back to one when the main image is too large
+    seek_to_overview(&mut decoder, u32::MAX, false, Some(max_dimension)).ok()?;
+    decoder.dimensions().ok()
+}
+
+// Function to move the decoder to the smallest overview that still covers min_dimension. When
+// the main image is larger than max_source_dimension, only overviews within it are considered,
+// falling back to the largest of them if none covers min_dimension. Stays on (or returns to) the
+// main image when there is no suitable overview.
+fn seek_to_overview<R: Read + Seek>(
+    decoder: &mut Decoder<R>,
+    min_dimension: u32,
+    crop_to_square: bool,
+    max_source_dimension: Option<u32>,
+) -> tiff::TiffResult<()> {
+    let (width, height) = decoder.dimensions()?;
+    let covered = |w: u32, h: u32| if crop_to_square { w.min(h) } else { w.max(h) } >= min_dimension;
+    let within_limit = |w: u32, h: u32| max_source_dimension.is_none_or(|limit| w.max(h) <= limit);
+
+    let mut best: Option<(usize, u64)> = None;
+    // Largest overview within the limit, used when none covers min_dimension
+    let mut largest: Option<(usize, u64)> = None;
+    let mut index = 0;
+    while decoder.more_images() && index < MAX_OVERVIEW_IFDS {
+        index += 1;
+        if let Err(e) = decoder.next_image() {
+            log::debug!("Stopped looking for TIFF overviews at IFD {}: {:?}", index, e);
+            break;
         }
-        Ok(tiff::decoder::DecodingResult::U16(data)) => {
-            let color_type = decoder.colortype().unwrap_or(tiff::ColorType::RGB(16));
-            log::debug!("TIFF color type: {:?}", color_type);
-
-            let rgb_data: Vec<u8> = match color_type {
-                tiff::ColorType::Gray(_nbits) => {
-                    log::info!("TIFF is 16-bit greyscale, converting to 8-bit RGB");
-                    // Convert grayscale to RGB by duplicating each value
-                    data.iter().flat_map(|x| {
-                        let v = (x >> 8) as u8;
-                        [v, v, v]
-                    }).collect()
-                }
-                tiff::ColorType::RGB(_) => {
-                    data.iter().map(|&x| (x >> 8) as u8).collect()
-                }
-                tiff::ColorType::YCbCr(_) => {
-                    log::info!("TIFF is 16-bit YCbCr, converting to RGB");
-                    let mut rgb_data = Vec::with_capacity(data.len());
-                    for chunk in data.chunks_exact(3) {
-                        let y = (chunk[0] >> 8) as f32;
-                        let cb = (chunk[1] >> 8) as f32 - 128.0;
-                        let cr = (chunk[2] >> 8) as f32 - 128.0;
-
-                        let r = (y + 1.402 * cr).clamp(0.0, 255.0) as u8;
-                        let g = (y - 0.344136 * cb - 0.714136 * cr).clamp(0.0, 255.0) as u8;
-                        let b = (y + 1.772 * cb).clamp(0.0, 255.0) as u8;
-
-                        rgb_data.push(r);
-                        rgb_data.push(g);
-                        rgb_data.push(b);
-                    }
-                    rgb_data
-                }
-                _ => {
-                    log::warn!("TIFF color type not handled: {:?}", color_type);
-                    data.iter().map(|&x| (x >> 8) as u8).collect()
-                }
-            };
-
-            let rgb_img = RgbImage::from_raw(width, height, rgb_data);
-            if let Some(rgb_img) = rgb_img {
-                log::trace!("Created RGB image from 16-bit converted data");
-                
-                let dynamic_img = DynamicImage::ImageRgb8(rgb_img);
-                let scaled_img = if width > max_dimension || height > max_dimension {
-                    log::debug!("Large 16-bit TIFF image ({}x{}), using progressive scaling", width, height);
-                    let intermediate = dynamic_img.resize(800, 800, image::imageops::FilterType::Triangle);
-                    intermediate.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
-                } else {
-                    log::debug!("Small 16-bit TIFF image ({}x{}), direct scaling", width, height);
-                    dynamic_img.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
-                };
-                
-                let mut jpeg_bytes = Vec::new();
-                match scaled_img.write_with_encoder(
-                    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_bytes, jpeg_quality)
-                ) {
-                    Ok(_) => {
-                        log::debug!("Successfully encoded 16-bit TIFF as JPEG, size: {} bytes", jpeg_bytes.len());
-                        
-                        if let (Some(key), Some(save_fn)) = (cache_key, save_to_cache) {
-                            match save_fn(key, &jpeg_bytes) {
-                                Ok(_) => log::trace!("Saved 16-bit TIFF result to cache"),
-                                Err(e) => log::warn!("Failed to save 16-bit TIFF result to cache: {}", e),
-                            }
-                        }
-                        Ok(jpeg_bytes)
-                    },
-                    Err(e) => {
-                        log::error!("JPEG encoding failed for 16-bit TIFF {}: {:?}", file_path, e);
-                        Err("JPEG encoding failed for 16-bit TIFF".to_string())
-                    }
+        // Bit 0 of NewSubfileType marks a reduced-resolution version of the main image
+        let subfile_type = decoder.find_tag_unsigned::<u32>(Tag::NewSubfileType).ok().flatten().unwrap_or(0);
+        if subfile_type & 1 == 0 {
+            continue;
+        }
+        let (w, h) = decoder.dimensions()?;
+        // Overviews keep the aspect ratio up to rounding; anything else is a different image
+        let aspect_error = (u64::from(w) * u64::from(height)).abs_diff(u64::from(h) * u64::from(width));
+        if aspect_error > u64::from(width) + u64::from(height) || !within_limit(w, h) {
+            continue;
+        }
+        let pixels = u64::from(w) * u64::from(h);
+        if largest.is_none_or(|(_, largest_pixels)| pixels > largest_pixels) {
+            largest = Some((index, pixels));
+        }
+        if covered(w, h) && best.is_none_or(|(_, best_pixels)| pixels < best_pixels) {
+            best = Some((index, pixels));
+        }
+    }
+    if best.is_none() && !within_limit(width, height) {
+        best = largest;
+    }
+
+    match best {
+        Some((overview, _)) => {
+            log::debug!("Using TIFF overview IFD {} instead of the full resolution image", overview);
+            decoder.seek_to_image(overview)
+        }
+        None if index > 0 => decoder.seek_to_image(0),
+        None => Ok(()),
+    }
+}
+
+// Function to read the image one strip/tile at a time, averaging blocks of step x step pixels,
+// so the full resolution image never has to be held in memory
+fn decode_downsampled<R: Read + Seek>(
+    decoder: &mut Decoder<R>,
+    width: u32,
+    height: u32,
+    color_type: tiff::ColorType,
+    min_dimension: u32,
+    crop_to_square: bool,
+) -> Result<RgbImage, String> {
+    let edge = if crop_to_square { width.min(height) } else { width.max(height) };
+    // Keep about twice the target size so the final resize still has detail to filter
+    let step = (edge / min_dimension.saturating_mul(2).max(1)).max(1);
+    let (out_width, out_height) = (width.div_ceil(step), height.div_ceil(step));
+    log::debug!("Downsampling TIFF by {} to {}x{}", step, out_width, out_height);
+
+    let out_pixels = out_width as usize * out_height as usize;
+    let mut sums = vec![0u64; out_pixels * 3];
+    let mut counts = vec![0u64; out_pixels];
+
+    let chunk_count = match decoder.get_chunk_type() {
+        ChunkType::Strip => decoder.strip_count(),
+        ChunkType::Tile => decoder.tile_count(),
+    }
+    .map_err(|e| format!("Failed to count TIFF chunks: {:?}", e))?;
+    let (chunk_width, chunk_height) = decoder.chunk_dimensions();
+    let chunks_across = width.div_ceil(chunk_width.max(1));
+
+    for chunk_index in 0..chunk_count {
+        let (data_width, data_height) = decoder.chunk_data_dimensions(chunk_index);
+        let chunk = decoder.read_chunk(chunk_index)
+            .map_err(|e| format!("Failed to read TIFF chunk {}: {:?}", chunk_index, e))?;
+        let rgb_data = to_rgb8(chunk, color_type)?;
+        if rgb_data.len() < data_width as usize * data_height as usize * 3 {
+            return Err(format!("TIFF chunk {} is shorter than its dimensions", chunk_index));
+        }
+
+        let x0 = (chunk_index % chunks_across) * chunk_width;
+        let y0 = (chunk_index / chunks_across) * chunk_height;
+        for y in 0..data_height {
+            let out_row = ((y0 + y) / step) as usize * out_width as usize;
+            for x in 0..data_width {
+                let out_index = out_row + ((x0 + x) / step) as usize;
+                let in_index = (y as usize * data_width as usize + x as usize) * 3;
+                for channel in 0..3 {
+                    sums[out_index * 3 + channel] += u64::from(rgb_data[in_index + channel]);
                 }
-            } else {
-                log::error!("Failed to create RGB image from 16-bit TIFF data for {}", file_path);
-                Err("Failed to create RGB image from 16-bit TIFF data".to_string())
+                counts[out_index] += 1;
             }
         }
-        Ok(other_format) => {
-            log::error!("Unsupported TIFF data format for {}: {:?}", file_path, other_format);
-            Err(format!("Unsupported TIFF data format for {}", file_path))
-        },
-        Err(e) => {
-            log::error!("Failed to read TIFF image data for {}: {:?}", file_path, e);
-            Err(format!("Failed to read TIFF image data for {}: {:?}", file_path, e))
-        },
     }
+
+    let rgb_data: Vec<u8> = sums
+        .iter()
+        .enumerate()
+        .map(|(i, sum)| sum.checked_div(counts[i / 3]).unwrap_or(0) as u8)
+        .collect();
+    RgbImage::from_raw(out_width, out_height, rgb_data)
+        .ok_or_else(|| "Failed to create RGB image from downsampled TIFF data".to_string())
+}
+
+// Function to convert decoded TIFF samples (8 or 16 bit gray, RGB or YCbCr) to 8-bit RGB
+fn to_rgb8(data: DecodingResult, color_type: tiff::ColorType) -> Result<Vec<u8>, String> {
+    let samples: Vec<u8> = match data {
+        DecodingResult::U8(data) => data,
+        DecodingResult::U16(data) => data.iter().map(|&x| (x >> 8) as u8).collect(),
+        _ => return Err("Unsupported TIFF data format".to_string()),
+    };
+
+    let rgb_data = match color_type {
+        tiff::ColorType::Gray(nbits) => {
+            log::trace!("TIFF is greyscale ({} bits), converting to RGB", nbits);
+            // Convert grayscale to RGB by duplicating each value
+            samples.iter().flat_map(|v| std::iter::repeat_n(*v, 3)).collect::<Vec<u8>>()
+        }
+        tiff::ColorType::RGB(_) => {
+            samples
+        }
+        tiff::ColorType::YCbCr(_) => {
+            log::trace!("TIFF is YCbCr, converting to RGB");
+            let mut rgb_data = Vec::with_capacity(samples.len());
+            for chunk in samples.chunks_exact(3) {
+                let y = chunk[0] as f32;
+                let cb = chunk[1] as f32 - 128.0;
+                let cr = chunk[2] as f32 - 128.0;
+
+                let r = (y + 1.402 * cr).clamp(0.0, 255.0) as u8;
+                let g = (y - 0.344136 * cb - 0.714136 * cr).clamp(0.0, 255.0) as u8;
+                let b = (y + 1.772 * cb).clamp(0.0, 255.0) as u8;
+
+                rgb_data.push(r);
+                rgb_data.push(g);
+                rgb_data.push(b);
+            }
+            rgb_data
+        }
+        _ => {
+            log::warn!("TIFF color type not handled: {:?}", color_type);
+            samples
+        }
+    };
+    Ok(rgb_data)
 }
 
 pub fn generate_tiff_preview(file_path: &str) -> Option<String>  {
     log::info!("Generating TIFF preview for: {}", file_path);
     
-    let cache_key = super::cache::generate_cache_key(file_path);
+    let cache_key = super::cache::preview_cache_key(file_path);
 
     match convert_tiff_to_rgb_jpeg(
         file_path,
-        1980,
-        60,
+        super::image::PREVIEW_MAX_DIMENSION,
+        super::image::quality_settings(file_path).preview_quality,
         Some(&cache_key),
         Some(super::cache::save_preview_to_cache),
+        false,
+        false,
     ) {
         Ok(jpeg_bytes) => {
             log::debug!("TIFF preview generation successful, encoding as base64");
@@ -232,22 +424,26 @@ pub fn generate_tiff_preview(file_path: &str) -> Option<String>  {
         }
         Err(e) => {
             log::error!("TIFF preview generation failed for {}: {}", file_path, e);
+            crate::failures::report_failure(file_path, crate::failures::FailureStage::Preview, &e);
             None
         }
     }
 }
 
-pub fn generate_tiff_thumbnail(file_path: &str) -> Option<String> {
+pub fn generate_tiff_thumbnail(file_path: &str, scale: u32) -> Option<String> {
     log::info!("Generating TIFF thumbnail for: {}", file_path);
     
-    let cache_key = super::cache::generate_cache_key(file_path);
+    let cache_key = super::cache::scaled_thumbnail_cache_key(file_path, scale);
+    let settings = super::image::quality_settings(file_path);
     
     match convert_tiff_to_rgb_jpeg(
         file_path,
-        200,
-        50,
+        settings.thumbnail_size * scale,
+        settings.thumbnail_quality,
         Some(&cache_key),
         Some(super::cache::save_thumbnail_to_cache),
+        super::cache::thumbnail_fill(),
+        true,
     ) {
         Ok(jpeg_bytes) => {
             log::debug!("TIFF thumbnail generation successful, encoding as base64");
@@ -258,6 +454,7 @@ pub fn generate_tiff_thumbnail(file_path: &str) -> Option<String> {
         }
         Err(e) => {
             log::error!("TIFF thumbnail generation failed for {}: {}", file_path, e);
+            crate::failures::report_failure(file_path, crate::failures::FailureStage::Thumbnail, &e);
             None
         }
     }
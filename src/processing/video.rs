@@ -4,23 +4,151 @@ use image;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use std::fs;
 
-use super::cache::{generate_cache_key};
+use super::cache::{generate_cache_key, thumbnail_fill};
+use crate::cli::DEFAULT_VIDEO_EXTENSIONS;
+use crate::failures::{report_failure, FailureStage};
+
+/// Returns the configured video file extensions (lowercase, without dot), with "default"
+/// expanded to the built-in set.
+pub fn video_extensions() -> Vec<String> {
+    let configured = match crate::cli::CLI_ARGS.get() {
+        Some(args) => args.video_extensions.clone(),
+        None => vec!["default".to_string()],
+    };
+    let mut extensions: Vec<String> = Vec::new();
+    for extension in configured {
+        let expanded: Vec<String> = if extension == "default" {
+            DEFAULT_VIDEO_EXTENSIONS.split(',').map(str::to_string).collect()
+        } else {
+            vec![extension]
+        };
+        for extension in expanded {
+            if !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+        }
+    }
+    extensions
+}
+
+/// Whether files with this (lowercase) extension are videos.
+pub fn is_video_extension(extension: &str) -> bool {
+    video_extensions().iter().any(|video| video == extension)
+}
+
+/// Keys the properties of a video's first video stream are stored under in `key_value`
+pub const VIDEO_CODEC_KEY: &str = "video:Codec";
+pub const VIDEO_WIDTH_KEY: &str = "video:Width";
+pub const VIDEO_HEIGHT_KEY: &str = "video:Height";
+pub const VIDEO_FRAME_RATE_KEY: &str = "video:FrameRate";
+
+/// Codec, size and frame rate of a video's first video stream, as reported by ffprobe
+#[derive(Debug, PartialEq)]
+pub struct VideoInfo {
+    // ffprobe codec name, e.g. "h264", "hevc", "vp9"
+    pub codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // Frames per second, None for variable or unknown frame rates
+    pub fps: Option<f64>,
+}
+
+impl VideoInfo {
+    /// The properties as `key_value` entries; unknown ones are left out.
+    pub fn key_values(&self) -> Vec<(&'static str, String)> {
+        [
+            (VIDEO_CODEC_KEY, self.codec.clone()),
+            (VIDEO_WIDTH_KEY, self.width.map(|w| w.to_string())),
+            (VIDEO_HEIGHT_KEY, self.height.map(|h| h.to_string())),
+            // Rounded, so 29.97 fps is stored as "29.97" and not "29.97002997002997"
+            (VIDEO_FRAME_RATE_KEY, self.fps.map(|fps| format!("{}", (fps * 100.0).round() / 100.0))),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect()
+    }
+}
+
+/// Reads codec, size and frame rate of a video with ffprobe. None when ffprobe is missing or
+/// fails, or the file has no video stream.
+pub fn probe_video(file_path: &str) -> Option<VideoInfo> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-show_entries", "stream=codec_name,width,height,avg_frame_rate,r_frame_rate",
+            "-of", "json",
+            file_path,
+        ])
+        .output();
+    match output {
+        Ok(result) if result.status.success() => {
+            let info = parse_ffprobe_output(&result.stdout);
+            if info.is_none() {
+                log::debug!("ffprobe found no video stream in {}", file_path);
+            }
+            info
+        }
+        Ok(result) => {
+            log::warn!("ffprobe failed for video {}: {}", file_path, String::from_utf8_lossy(&result.stderr).trim());
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to execute ffprobe for video {}: {}", file_path, e);
+            None
+        }
+    }
+}
+
+/// Parses the `-of json` output of ffprobe for the first video stream into a [`VideoInfo`].
+pub fn parse_ffprobe_output(output: &[u8]) -> Option<VideoInfo> {
+    let json: serde_json::Value = serde_json::from_slice(output).ok()?;
+    let stream = json.get("streams")?.as_array()?.first()?;
+    let dimension = |name: &str| stream.get(name)?.as_u64().and_then(|v| u32::try_from(v).ok()).filter(|v| *v > 0);
+    // avg_frame_rate is "0/0" for some containers; r_frame_rate is the base rate then
+    let fps = ["avg_frame_rate", "r_frame_rate"]
+        .iter()
+        .find_map(|name| parse_frame_rate(stream.get(*name)?.as_str()?));
+    Some(VideoInfo {
+        codec: stream.get("codec_name").and_then(|v| v.as_str()).map(str::to_string),
+        width: dimension("width"),
+        height: dimension("height"),
+        fps,
+    })
+}
+
+// Function to parse an ffprobe rational frame rate such as "30000/1001" or "25/1"
+fn parse_frame_rate(value: &str) -> Option<f64> {
+    let (numerator, denominator) = value.split_once('/')?;
+    let fps = numerator.trim().parse::<f64>().ok()? / denominator.trim().parse::<f64>().ok()?;
+    (fps.is_finite() && fps > 0.0).then_some(fps)
+}
 
 // Function to generate a video thumbnail using ffmpeg binary
-pub fn generate_video_thumbnail(file_path: &str) -> Option<String> {
+pub fn generate_video_thumbnail(file_path: &str, scale: u32) -> Option<String> {
     log::info!("Generating video thumbnail for: {}", file_path);
     
     // Create a temporary file for the thumbnail
     let temp_dir = env::temp_dir();
-    let temp_thumbnail = temp_dir.join(format!("thumb_{}.jpg", generate_cache_key(file_path)));
+    let temp_thumbnail = temp_dir.join(format!("thumb_{}_{}x.jpg", generate_cache_key(file_path), scale));
     
     log::debug!("Using temporary file for video thumbnail: {}", temp_thumbnail.display());
+
+    let settings = super::image::quality_settings(file_path);
+    let size = settings.thumbnail_size * scale;
+    let video_thumbnail_filter = if thumbnail_fill() {
+        // Scale to cover the square (200x200 at scale 1) and center-crop the overflow
+        format!("scale={0}:{0}:force_original_aspect_ratio=increase,crop={0}:{0}", size)
+    } else {
+        // Scale and pad to the square
+        format!("scale={0}:{0}:force_original_aspect_ratio=decrease,pad={0}:{0}:(ow-iw)/2:(oh-ih)/2", size)
+    };
     
     // Use ffmpeg to extract the first frame
     let output = Command::new("ffmpeg")
-        .args(&[
+        .args([
             "-i", file_path,           // Input file
-            "-vf", "scale=200:200:force_original_aspect_ratio=decrease,pad=200:200:(ow-iw)/2:(oh-ih)/2", // Scale and pad to 200x200
+            "-vf", &video_thumbnail_filter,
             "-vframes", "1",           // Extract only 1 frame
             "-q:v", "2",              // High quality
             "-y",                     // Overwrite output file
@@ -52,17 +180,15 @@ pub fn generate_video_thumbnail(file_path: &str) -> Option<String> {
                             match image::load_from_memory(&thumbnail_bytes) {
                                 Ok(img) => {
                                     log::trace!("Successfully loaded thumbnail image with image crate");
-                                    // Convert back to JPEG bytes
-                                    let mut jpeg_bytes = Vec::new();
-                                    match img.write_with_encoder(
-                                        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_bytes, 50)
-                                    ) {
-                                        Ok(_) => {
-                                            log::debug!("Successfully processed video thumbnail, final size: {} bytes", jpeg_bytes.len());
-                                            return Some(BASE64.encode(&jpeg_bytes));
+                                    let img = super::image::sharpen_thumbnail(img);
+                                    // Re-encode in the --thumbnail-format
+                                    match super::image::encode_thumbnail(&img, settings.thumbnail_quality) {
+                                        Ok(encoded_bytes) => {
+                                            log::debug!("Successfully processed video thumbnail, final size: {} bytes", encoded_bytes.len());
+                                            return Some(BASE64.encode(&encoded_bytes));
                                         },
                                         Err(e) => {
-                                            log::warn!("Failed to encode video thumbnail as JPEG: {:?}", e);
+                                            log::warn!("Failed to encode video thumbnail: {:?}", e);
                                         }
                                     }
                                 },
@@ -77,13 +203,19 @@ pub fn generate_video_thumbnail(file_path: &str) -> Option<String> {
                         },
                         Err(e) => {
                             log::error!("Failed to read generated thumbnail file {}: {}", temp_thumbnail.display(), e);
+                            report_failure(file_path, FailureStage::Thumbnail, &format!("Failed to read the ffmpeg output: {}", e));
                         }
                     }
                 } else {
                     log::warn!("ffmpeg completed but thumbnail file was not created: {}", temp_thumbnail.display());
+                    report_failure(file_path, FailureStage::Thumbnail, "ffmpeg completed without writing a frame");
                 }
             } else {
-                log::error!("ffmpeg failed for video {}: {}", file_path, String::from_utf8_lossy(&result.stderr));
+                let stderr = String::from_utf8_lossy(&result.stderr);
+                log::error!("ffmpeg failed for video {}: {}", file_path, stderr);
+                // The last line names the error, the ones before are the banner and stream info
+                let reason = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("ffmpeg failed");
+                report_failure(file_path, FailureStage::Thumbnail, reason);
             }
             
             // Clean up temp file if it exists
@@ -95,6 +227,7 @@ pub fn generate_video_thumbnail(file_path: &str) -> Option<String> {
         }
         Err(e) => {
             log::error!("Failed to execute ffmpeg for video {}: {}", file_path, e);
+            report_failure(file_path, FailureStage::Thumbnail, &format!("Failed to execute ffmpeg: {}", e));
             
             // Clean up temp file if it exists
             if temp_thumbnail.exists() {
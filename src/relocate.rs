@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection};
+
+use crate::db::write_transaction;
+use crate::processing::cache::rename_cached_files;
+use crate::sidecar_scan::{sidecar_files_for_image, sidecar_path_condition};
+
+/// An image moved on disk together with its sidecars, index rows and cache files
+#[derive(Debug, serde::Serialize)]
+pub struct MovedFile {
+    pub from: String,
+    pub to: String,
+    // New paths of the sidecars moved along with the image
+    pub sidecars: Vec<String>,
+    // Cached thumbnails, previews and video previews renamed to the new path
+    pub cache_files: usize,
+}
+
+/// Why an image could not be moved
+#[derive(Debug, PartialEq)]
+pub enum MoveError {
+    /// The image does not exist or is not in the index
+    NotFound(String),
+    /// Something already exists at the destination
+    Conflict(String),
+    /// The database update or a file system operation failed; nothing was changed
+    Failed(String),
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::NotFound(e) | MoveError::Conflict(e) | MoveError::Failed(e) => f.write_str(e),
+        }
+    }
+}
+
+/// Moves an indexed image and its sidecars from `from` to `to` (both image paths) and updates
+/// the index to match. The paths of its file rows are changed in a transaction that is only
+/// committed once every file has been moved; if a move fails, the files already moved are moved
+/// back and the transaction is rolled back. Cache files are renamed afterwards, so the image is
+/// served from cache right away; failing to rename one only means it is regenerated.
+pub fn move_image(conn: &Connection, from: &str, to: &str) -> Result<MovedFile, MoveError> {
+    let from_path = Path::new(from);
+    if !from_path.is_file() {
+        return Err(MoveError::NotFound(format!("File not found: {}", from)));
+    }
+    let image_name_len = from_path.file_name().map_or(0, |name| name.len());
+    let mut moves: Vec<(PathBuf, PathBuf)> = vec![(from_path.to_path_buf(), PathBuf::from(to))];
+    for sidecar in sidecar_files_for_image(from_path) {
+        // Keep the sidecar's own extension, e.g. "a.jpg.XMP" to "b.jpg.XMP"
+        let suffix = sidecar.file_name().and_then(|name| name.to_str()).map_or("", |name| &name[image_name_len..]);
+        moves.push((sidecar.clone(), PathBuf::from(format!("{}{}", to, suffix))));
+    }
+    if let Some((_, target)) = moves.iter().find(|(_, target)| fs::symlink_metadata(target).is_ok()) {
+        return Err(MoveError::Conflict(format!("Destination already exists: {}", target.display())));
+    }
+
+    let tx = write_transaction(conn).map_err(|e| MoveError::Failed(format!("Failed to start transaction: {}", e)))?;
+    let rows: Vec<(i64, String)> = tx
+        .prepare(&format!("SELECT file.id, file.path FROM file WHERE {}", sidecar_path_condition(1)))
+        .and_then(|mut stmt| {
+            stmt.query_map(params![from], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(|e| MoveError::Failed(format!("Query error: {}", e)))?;
+    if rows.is_empty() {
+        return Err(MoveError::NotFound(format!("File is not in the index: {}", from)));
+    }
+    // A sidecar named after the image's stem ("photo.xmp" for "photo.NEF") has no suffix to keep
+    if let Some((_, path)) = rows.iter().find(|(_, path)| !path.starts_with(from)) {
+        return Err(MoveError::Conflict(format!("Moving images with a stem-named sidecar is not supported: {}", path)));
+    }
+    for (file_id, path) in &rows {
+        // Sidecar rows keep their extension; a row indexed with exiftool is the image itself
+        let new_path = format!("{}{}", to, &path[from.len()..]);
+        if let Err(e) = tx.execute("UPDATE file SET path = ?1, image_path = ?2 WHERE id = ?3", params![new_path, to, file_id]) {
+            return Err(match e {
+                rusqlite::Error::SqliteFailure(f, _) if f.code == rusqlite::ErrorCode::ConstraintViolation => {
+                    MoveError::Conflict(format!("Destination is already in the index: {}", new_path))
+                }
+                e => MoveError::Failed(format!("Update error: {}", e)),
+            });
+        }
+    }
+
+    if let Some(dir) = Path::new(to).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| MoveError::Failed(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+    for (done, (source, target)) in moves.iter().enumerate() {
+        if let Err(e) = fs::rename(source, target) {
+            log::error!("Failed to move {} to {}: {}", source.display(), target.display(), e);
+            undo_moves(&moves[..done]);
+            return Err(MoveError::Failed(format!("Failed to move {}: {}", source.display(), e)));
+        }
+    }
+    if let Err(e) = tx.commit() {
+        log::error!("Failed to commit the move of {} to {}: {}", from, to, e);
+        undo_moves(&moves);
+        return Err(MoveError::Failed(format!("Commit error: {}", e)));
+    }
+    log::info!("Moved {} to {} with {} sidecars", from, to, moves.len() - 1);
+
+    let cache_files = rename_cached_files(from, to) + usize::from(rename_video_preview(from_path, Path::new(to)));
+    Ok(MovedFile {
+        from: from.to_string(),
+        to: to.to_string(),
+        sidecars: moves[1..].iter().map(|(_, target)| target.display().to_string()).collect(),
+        cache_files,
+    })
+}
+
+// Function to move files back to where they came from, last moved first
+fn undo_moves(moves: &[(PathBuf, PathBuf)]) {
+    for (source, target) in moves.iter().rev() {
+        if let Err(e) = fs::rename(target, source) {
+            log::error!("Failed to move {} back to {}: {}", target.display(), source.display(), e);
+        }
+    }
+}
+
+// Function to rename the transcoded preview of a video ("<stem>_480p.mp4") when its file name
+// changes. A preview already there for another video of that name is left alone.
+fn rename_video_preview(from: &Path, to: &Path) -> bool {
+    let Some(args) = crate::cli::CLI_ARGS.get() else {
+        return false;
+    };
+    let (Some(old_stem), Some(new_stem)) = (from.file_stem(), to.file_stem()) else {
+        return false;
+    };
+    let is_video = from.extension().and_then(|ext| ext.to_str()).is_some_and(crate::processing::video::is_video_extension);
+    if !is_video || old_stem == new_stem {
+        return false;
+    }
+    let preview_cache_dir = Path::new(&args.video_preview_cache);
+    let Some(preview) = crate::routes::find_transcoded_video(preview_cache_dir, old_stem) else {
+        return false;
+    };
+    let mut new_name = new_stem.to_os_string();
+    new_name.push("_480p.mp4");
+    let target = preview_cache_dir.join(new_name);
+    if target.exists() {
+        return false;
+    }
+    match fs::rename(&preview, &target) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("Failed to rename video preview {}: {}", preview.display(), e);
+            false
+        }
+    }
+}
@@ -1,20 +1,116 @@
-use actix_web::{web, HttpResponse, Responder};
-use rusqlite::Connection;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use urlencoding;
-use crate::cli::get_cli_args;
+use crate::background::{mark_viewed, prioritize_previews, regenerate_files, start_regeneration, RegenerateTarget};
+use crate::cli::{get_cli_args, PreviewFormat, PreviewStrategy};
+use crate::albums::{add_to_album, album_files, album_name, create_album, list_albums, remove_from_album};
+use crate::archive::{tar_size, write_tar, ArchiveEntry};
+use crate::failures::{clear_failures, list_failures, FailureStage};
+use crate::history::{clear_history, record_search, recent_searches};
+use crate::relocate::{move_image, MoveError};
+use crate::sidecar_scan::{GPS_LATITUDE_KEY, GPS_LONGITUDE_KEY, image_path_for_sidecar, parse_capture_date, resolve_image_path, sidecar_files_for_image, sidecar_path_condition};
+use crate::db::{fts_search_enabled, open_read_only};
+use crate::feed::{atom_feed, json_feed, recent_feed_items, FeedItem};
+use crate::suggest::{fuzzy_score, register_fuzzy_match, suggest, tag_vocabulary};
+use crate::synonyms::synonyms_of;
 use base64::{Engine as _, engine::{general_purpose}};
 
 use crate::processing::{
-    image::{generate_thumbnail, generate_preview},
+    badge::MAX_RATING_STARS,
+    cache::{
+        cached_image_content_type, scaled_thumbnail_cache_key, thumbnail_cache_key, thumbnail_content_type, thumbnail_exists_in_cache,
+        thumbnail_scale_for_dpr,
+    },
+    color::{color_distance, parse_hex},
+    icon::FileTypeIcon,
+    image::{generate_badged_thumbnail, generate_preview, generate_preview_variant, generate_scaled_thumbnail, generate_sized_preview, generate_thumbnail, oversized_source, preview_width},
+    video::{video_extensions, VIDEO_CODEC_KEY, VIDEO_HEIGHT_KEY, VIDEO_WIDTH_KEY},
 };
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
 use once_cell::sync::Lazy;
 
 #[derive(Deserialize)]
 pub struct IndexQuery {
     pub search: Option<String>,
+    // Substring match on the camera make/model (tiff:Make, tiff:Model, exif:Model)
+    pub camera: Option<String>,
+    // Substring match on the lens model (e.g. exifEX:LensModel, aux:Lens)
+    pub lens: Option<String>,
+    // Capture date range, inclusive (e.g. "2023-05-01", "2023-05", "2023-05-01T10:00:00+02:00")
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // Image file size range, inclusive (e.g. "500KB", "50MB", "1.5GB")
+    pub min_size: Option<String>,
+    pub max_size: Option<String>,
+    // Include sidecars whose image file no longer exists (excluded by default)
+    pub include_missing: Option<bool>,
+    // Directory subtree to search in, absolute or relative to --scan-dir (e.g. "2023/Italy")
+    pub path_prefix: Option<String>,
+    // digiKam color label by name ("red") or number ("1"); "none" matches unlabeled files
+    pub color_label: Option<String>,
+    // digiKam pick label: "none", "rejected", "pending" or "accepted" (or 0-3)
+    pub pick: Option<String>,
+    // Video codec from ffprobe, e.g. "h264", "hevc" (or its alias "h265"), "vp9", "av1"
+    pub video_codec: Option<String>,
+    // Minimum video resolution by the shorter edge: "720p", "1080p", "4k" (2160p), "8k" ...
+    pub video_resolution: Option<String>,
+    // true: only files with a stored GPS position; false: only files without one
+    pub has_gps: Option<bool>,
+    // Result order: "path" (default) or "import_desc" for most recently imported first
+    pub sort: Option<String>,
+    // JSON API only: "inline" (default) embeds thumbnails, "lazy" leaves them to /thumbnail/{path}
+    pub thumbnails: Option<String>,
+    // JSON API only: "v1" (default) field names, or "v2" with `url`/`caption` for `file_path`/`value`
+    pub format: Option<String>,
+    // Bypass --exclude-tag/--exclude-path-glob; needs the --exclude-override-token bearer token
+    pub include_excluded: Option<bool>,
+    // JSON API only: match terms within a few edits (typos, OCR errors) and rank by closeness
+    pub fuzzy: Option<bool>,
+    // 1-based page of matching files; /api only paginates when page or per_page is given
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct RecentQuery {
+    // Lower bound on the import time: a date ("2024-06-01") or a relative age ("7d", "12h", "2w")
+    pub since: Option<String>,
+    pub limit: Option<usize>,
+    pub include_missing: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct BrowseQuery {
+    // Directory to list, absolute or relative to --scan-dir; the scan directory when empty
+    pub path: Option<String>,
+    pub include_missing: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct WindowQuery {
+    // Index of the first file of the window within the whole result set
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    // Requested width in pixels, rounded up to a multiple of 32 and clamped to 64-1980
+    pub w: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct ThumbnailQuery {
+    // "sync" (default) blocks until generated, "async" answers 202 while generating
+    pub mode: Option<String>,
+    // Thumbnail uncached JPEGs from their embedded EXIF thumbnail when it is large enough
+    pub exif: Option<bool>,
+    // Device pixel ratio; served from the closest --thumbnail-scale-factors density covering it
+    pub dpr: Option<f32>,
+    // "rating" draws the stored xmp:Rating as stars onto the thumbnail
+    pub badge: Option<String>,
 }
 
 // Struct to hold each result row
@@ -23,11 +119,271 @@ pub struct SearchResult {
     pub file_path: String,
     pub value: String,
     pub thumbnail_base64: Option<String>,
+    pub dominant_color: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub source_present: bool,
+    // digiKam labels by name, None when unlabeled
+    pub color_label: Option<&'static str>,
+    pub pick_label: Option<&'static str>,
+    // Whether a GPS position is stored for the file
+    pub has_gps: bool,
+    // With fuzzy=true, how closely the value matches the closest search term (1.0 contains it)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+// A SearchResult in the v2 response shape (format=v2), for clients expecting these field names
+#[derive(Serialize)]
+pub struct SearchResultV2<'a> {
+    pub url: &'a str,
+    pub caption: &'a str,
+    pub thumbnail_base64: Option<&'a str>,
+    pub dominant_color: Option<&'a str>,
+    pub size_bytes: Option<i64>,
+    pub source_present: bool,
+    pub color_label: Option<&'static str>,
+    pub pick_label: Option<&'static str>,
+    pub has_gps: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+impl<'a> From<&'a SearchResult> for SearchResultV2<'a> {
+    fn from(result: &'a SearchResult) -> Self {
+        SearchResultV2 {
+            url: &result.file_path,
+            caption: &result.value,
+            thumbnail_base64: result.thumbnail_base64.as_deref(),
+            dominant_color: result.dominant_color.as_deref(),
+            size_bytes: result.size_bytes,
+            source_present: result.source_present,
+            color_label: result.color_label,
+            pick_label: result.pick_label,
+            has_gps: result.has_gps,
+            score: result.score,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RecentResult {
+    pub file_path: String,
+    pub import_time: Option<i64>,
+    pub capture_time: Option<i64>,
+    pub size_bytes: Option<i64>,
+    pub source_present: bool,
+}
+
+/// Immediate contents of a directory of the index, for /api/browse
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BrowseListing {
+    pub path: String,
+    // None at the scan directory, which is the root of the tree
+    pub parent: Option<String>,
+    pub directories: Vec<BrowseDirectory>,
+    pub files: Vec<BrowseFile>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BrowseDirectory {
+    pub name: String,
+    pub path: String,
+    // Indexed files anywhere below the directory
+    pub count: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BrowseFile {
+    pub name: String,
+    pub file_path: String,
+}
+
+// One file of a /api/window response, with what a virtualized grid needs for its placeholder
+#[derive(Debug, Serialize)]
+pub struct WindowRow {
+    pub file_path: String,
+    // Pixel size of the original image, None when its header cannot be read (RAW, video, missing)
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub capture_time: Option<i64>,
+    pub dominant_color: Option<String>,
+    pub source_present: bool,
+    pub thumbnail_base64: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SuggestQuery {
+    // The (possibly mistyped) search term to find known tags for
+    pub q: String,
+}
+
+#[derive(Deserialize)]
+pub struct ColorQuery {
+    pub hex: String,
+    // Maximum RGB distance (0-441) to the requested color
+    pub tolerance: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct ColorSearchResult {
+    pub file_path: String,
+    pub dominant_color: String,
+    pub distance: f64,
+}
+
+#[derive(Deserialize)]
+pub struct BatchMetadataRequest {
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MetadataEntry {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Deserialize)]
+pub struct DetailQuery {
+    // Image path, or its sidecar path
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAlbumRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct AlbumPathsRequest {
+    // Image paths, or their sidecar paths
+    pub paths: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AlbumQuery {
+    // "inline" (default) or "lazy", as for /api
+    pub thumbnails: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AlbumFile {
+    pub file_path: String,
+    pub thumbnail_base64: Option<String>,
+    pub source_present: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+    // Image path, or its sidecar path
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct ThumbnailStatusRequest {
+    pub paths: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct FailuresQuery {
+    // Only failures of this stage: scan, thumbnail or preview
+    pub stage: Option<FailureStage>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateQuery {
+    // Assets to rebuild: thumbnails, previews or all (default)
+    pub target: Option<RegenerateTarget>,
+}
+
+#[derive(Deserialize)]
+pub struct CacheClearQuery {
+    // Caches to clear: thumbnails, previews or all (default)
+    #[serde(rename = "type")]
+    pub cache_type: Option<RegenerateTarget>,
+}
+
+#[derive(Deserialize)]
+pub struct CacheVerifyQuery {
+    // Percentage of cache files to check (1-100), --cache-verify-sample by default
+    pub sample: Option<u8>,
+}
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    // Image path to move, or its sidecar path
+    pub from: String,
+    // New image path; sidecars follow with their own extension
+    pub to: String,
+}
+
+#[derive(Deserialize)]
+pub struct PrioritizePreviewsRequest {
+    // Image paths, most urgent first
+    pub paths: Vec<String>,
 }
 
+// Upper bound on paths per batch metadata request
+const MAX_BATCH_METADATA_PATHS: usize = 500;
+
+// Upper bound on paths per thumbnail status request
+const MAX_THUMBNAIL_STATUS_PATHS: usize = 1000;
+
+// Upper bound on paths per preview priority request; beyond --preview-priority-queue they are dropped anyway
+const MAX_PRIORITIZE_PREVIEW_PATHS: usize = 1000;
+
+// Default and maximum number of files returned by /api/recent
+const DEFAULT_RECENT_LIMIT: usize = 100;
+const MAX_RECENT_LIMIT: usize = 1000;
+
+// Default and maximum number of search terms returned by /api/history
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+const MAX_HISTORY_LIMIT: usize = 100;
+
+// Default and maximum number of failures returned by /api/failures
+const DEFAULT_FAILURES_LIMIT: usize = 100;
+const MAX_FAILURES_LIMIT: usize = 1000;
+
+// Default and maximum number of files per /api/window request
+const DEFAULT_WINDOW_LIMIT: usize = 100;
+const MAX_WINDOW_LIMIT: usize = 500;
+
+// Default and maximum number of files per page of /search and paginated /api requests
+pub const DEFAULT_PER_PAGE: usize = 50;
+pub const MAX_PER_PAGE: usize = 500;
+
+// Number of "did you mean" suggestions returned by /api/suggest
+const MAX_SUGGESTIONS: usize = 5;
+
+// Metadata keys holding the tag lists /api/suggest draws its vocabulary from
+const SUGGEST_TAG_KEY_CONDITION: &str =
+    "(key_value.key LIKE 'digiKam:TagsList%' OR key_value.key LIKE 'dc:subject%' OR key_value.key LIKE 'lr:hierarchicalSubject%')";
+
 // Global flag to indicate if user requests are active
 pub static USER_REQUEST_ACTIVE: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(false)));
 
+// Paths whose thumbnails are currently being generated by an async thumbnail request
+static THUMBNAILS_IN_PROGRESS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// Paths whose async thumbnail generation failed (or panicked), reported once to the next poll
+// instead of queueing them again
+static THUMBNAILS_FAILED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// Images already marked viewed for --preview-strategy viewed, so repeated thumbnail requests
+// do not write again
+static VIEWED_IMAGES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// Set while a /rescan import is running, so overlapping requests do not import twice
+static RESCAN_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+// Files up to this size are thumbnailed synchronously even in async mode
+const SYNC_THUMBNAIL_MAX_BYTES: u64 = 2 * 1024 * 1024;
+
 // Helper to wrap user request handlers and set/unset the busy flag
 async fn with_user_activity<F, Fut, R>(f: F) -> R
 where
@@ -40,6 +396,27 @@ where
     result
 }
 
+/// Builds the Content-Security-Policy header. The templates rely on inline scripts, inline
+/// styles and data: thumbnails, so those are allowed; everything else is limited to this origin.
+pub fn content_security_policy(frame_ancestors: &[String]) -> String {
+    format!(
+        "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; \
+         img-src 'self' data: blob:; media-src 'self' blob:; object-src 'none'; base-uri 'self'; \
+         frame-ancestors {}",
+        frame_ancestors.join(" ")
+    )
+}
+
+/// X-Frame-Options equivalent of the frame ancestors for browsers without CSP support. It cannot
+/// express other origins, so it is left out when any are allowed and CSP alone decides.
+pub fn x_frame_options(frame_ancestors: &[String]) -> Option<&'static str> {
+    match frame_ancestors {
+        [only] if only == "'none'" => Some("DENY"),
+        [only] if only == "'self'" => Some("SAMEORIGIN"),
+        _ => None,
+    }
+}
+
 // Function to escape HTML characters
 fn html_escape(text: &str) -> String {
     text.replace('&', "&amp;")
@@ -49,48 +426,76 @@ fn html_escape(text: &str) -> String {
         .replace('\'', "&#x27;")
 }
 
+// A single parsed search term. Query building and highlighting both work on this
+// structure so they always agree on what the user searched for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchTerm {
+    // The text to match against metadata values
+    pub value: String,
+    // Metadata key the term is restricted to (key:value syntax), None for all keys
+    pub key: Option<String>,
+    // Files matching a negated term are excluded from the results
+    pub negated: bool,
+    // Preceded by OR (or |): starts a group of terms matched as an alternative to the terms before
+    pub alternative: bool,
+}
+
+impl SearchTerm {
+    pub fn new(value: &str) -> SearchTerm {
+        SearchTerm { value: value.to_string(), key: None, negated: false, alternative: false }
+    }
+
+    // Only positive terms with a value are highlighted; key prefixes and
+    // negated terms never appear as highlighted text
+    fn is_highlightable(&self) -> bool {
+        !self.negated && !self.value.is_empty()
+    }
+}
+
 // Function to highlight search terms in text
-fn highlight_search_terms(text: &str, search_term: &str) -> String {
-    if search_term.is_empty() {
+pub fn highlight_search_terms(text: &str, terms: &[SearchTerm]) -> String {
+    // Match on the raw text (ASCII case-insensitive like SQLite LIKE) and escape afterwards,
+    // so terms never match inside HTML entities or previously inserted markup
+    let lower_text = text.to_ascii_lowercase();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for term in terms.iter().filter(|t| t.is_highlightable()) {
+        let needle = term.value.to_ascii_lowercase();
+        let mut start = 0;
+        while let Some(pos) = lower_text[start..].find(&needle) {
+            ranges.push((start + pos, start + pos + needle.len()));
+            start += pos + needle.len();
+        }
+    }
+
+    if ranges.is_empty() {
         return html_escape(text);
     }
-    
-    // Escape the original text first
-    let mut escaped_text = html_escape(text);
-    
-    // Parse search terms using the same logic as the search query
-    let terms_to_highlight = parse_search_terms(search_term);
-    
-    // Highlight each term
-    for term in terms_to_highlight {
-        if !term.is_empty() {
-            let term_lower = term.to_lowercase();
-            let mut result = String::new();
-            let mut remaining = escaped_text.as_str();
-            
-            while let Some(pos) = remaining.to_lowercase().find(&term_lower) {
-                // Add text before the match
-                result.push_str(&remaining[..pos]);
-                
-                // Add highlighted match
-                let match_text = &remaining[pos..pos + term.len()];
-                result.push_str(&format!("<mark style=\"background-color: lightgreen; padding: 1px 2px; border-radius: 2px;\">{}</mark>", match_text));
-                
-                // Move to text after the match
-                remaining = &remaining[pos + term.len()..];
-            }
-            
-            // Add remaining text
-            result.push_str(remaining);
-            escaped_text = result;
+
+    // Merge overlapping matches from different terms
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
         }
     }
-    
-    escaped_text
+
+    let mut result = String::new();
+    let mut position = 0;
+    for (start, end) in merged {
+        result.push_str(&html_escape(&text[position..start]));
+        result.push_str(&format!("<mark style=\"background-color: lightgreen; padding: 1px 2px; border-radius: 2px;\">{}</mark>", html_escape(&text[start..end])));
+        position = end;
+    }
+    result.push_str(&html_escape(&text[position..]));
+    result
 }
 
-// Function to parse search query and handle cross-field search
-fn parse_search_query(search_term: &str) -> (String, Vec<String>) {
+/// Parses a search query and builds the SQL WHERE clause matching it across all metadata
+/// fields, with its `?N` parameters in order. `synonyms` returns the alternatives of a term
+/// (`synonyms_of` for --synonyms-file), keeping the function free of global state.
+pub fn parse_search_query(search_term: &str, synonyms: impl Fn(&str) -> Vec<String>) -> (String, Vec<String>) {
     if search_term.trim().is_empty() {
         return ("WHERE key_value.value LIKE ?1".to_string(), vec![format!("%{}%", search_term)]);
     }
@@ -101,134 +506,776 @@ fn parse_search_query(search_term: &str) -> (String, Vec<String>) {
     if terms.is_empty() {
         return ("WHERE key_value.value LIKE ?1".to_string(), vec![format!("%{}%", search_term)]);
     }
-    
-    if terms.len() == 1 {
+
+    build_search_where(&terms, &synonyms)
+}
+
+/// Like `parse_search_query`, but matching each term with `fuzzy_match` (within a few edits)
+/// instead of LIKE. The connection needs `register_fuzzy_match`. An empty query matches everything.
+pub fn parse_fuzzy_search_query(search_term: &str, synonyms: impl Fn(&str) -> Vec<String>) -> (String, Vec<String>) {
+    let terms = parse_search_terms(search_term);
+    if terms.is_empty() {
+        return parse_search_query(search_term, synonyms);
+    }
+    build_where(&terms, &synonyms, true)
+}
+
+/// Builds the WHERE clause for parsed search terms. Every term has to be found in at least one
+/// metadata field of the same file, and none of a negated term's; a single term matches the
+/// joined key_value row directly.
+/// Terms separated by OR form alternative groups: AND binds tighter, so `a b OR c` finds files
+/// matching both a and b, or c. The groups are parenthesized, so filters can be ANDed after them.
+pub fn build_search_where(terms: &[SearchTerm], synonyms: &dyn Fn(&str) -> Vec<String>) -> (String, Vec<String>) {
+    build_where(terms, synonyms, false)
+}
+
+fn build_where(terms: &[SearchTerm], synonyms: &dyn Fn(&str) -> Vec<String>, fuzzy: bool) -> (String, Vec<String>) {
+    let mut parameters = Vec::new();
+
+    if let [term] = terms {
         // Single term, use original single-term logic
-        return ("WHERE key_value.value LIKE ?1".to_string(), vec![format!("%{}%", terms[0])]);
+        if !term.negated {
+            let condition = search_term_condition("key_value", term, synonyms, fuzzy, &mut parameters);
+            return (format!("WHERE {}", condition), parameters);
+        }
     }
     
     // Build WHERE clause that searches across all metadata fields for each file
     // Each term must be found in at least one metadata field of the same file
-    let mut where_conditions = Vec::new();
-    let mut parameters = Vec::new();
+    let mut group_conditions: Vec<Vec<String>> = Vec::new();
     
     for (i, term) in terms.iter().enumerate() {
-        let param_num = i + 1;
-        where_conditions.push(format!(
-            "file.id IN (SELECT DISTINCT kv{}.file_id FROM key_value kv{} WHERE kv{}.value LIKE ?{})",
-            param_num, param_num, param_num, param_num
-        ));
-        parameters.push(format!("%{}%", term.trim()));
+        let alias = format!("kv{}", i + 1);
+        let condition = search_term_condition(&alias, term, synonyms, fuzzy, &mut parameters);
+        let condition = format!(
+            "file.id {}IN (SELECT DISTINCT {}.file_id FROM key_value {} WHERE {})",
+            if term.negated { "NOT " } else { "" }, alias, alias, condition
+        );
+        match group_conditions.last_mut() {
+            Some(group) if !term.alternative => group.push(condition),
+            _ => group_conditions.push(vec![condition]),
+        }
     }
     
-    let where_clause = format!("WHERE {}", where_conditions.join(" AND "));
+    let where_clause = match group_conditions.as_slice() {
+        [group] => format!("WHERE {}", group.join(" AND ")),
+        groups => {
+            let alternatives: Vec<String> = groups
+                .iter()
+                .map(|group| if group.len() == 1 { group.join("") } else { format!("({})", group.join(" AND ")) })
+                .collect();
+            format!("WHERE ({})", alternatives.join(" OR "))
+        }
+    };
     (where_clause, parameters)
 }
 
-// Function to parse search terms, handling quoted strings and whitespace splitting
-fn parse_search_terms(input: &str) -> Vec<String> {
-    let mut terms = Vec::new();
-    let mut current_term = String::new();
-    let mut in_quotes = false;
-    let mut chars = input.chars().peekable();
-    
-    while let Some(ch) = chars.next() {
-        match ch {
-            '"' => {
-                if in_quotes {
-                    // End of quoted string
-                    if !current_term.trim().is_empty() {
-                        terms.push(current_term.trim().to_string());
-                        current_term.clear();
-                    }
-                    in_quotes = false;
-                } else {
-                    // Start of quoted string
-                    // If we have accumulated non-quoted content, save it first
-                    if !current_term.trim().is_empty() {
-                        terms.push(current_term.trim().to_string());
-                        current_term.clear();
-                    }
-                    in_quotes = true;
-                }
-            }
-            ' ' | '\t' | '\n' | '\r' => {
-                if in_quotes {
-                    // Inside quotes, preserve whitespace
-                    current_term.push(ch);
-                } else {
-                    // Outside quotes, whitespace is a separator
-                    if !current_term.trim().is_empty() {
-                        terms.push(current_term.trim().to_string());
-                        current_term.clear();
-                    }
-                }
-            }
-            _ => {
-                current_term.push(ch);
+/// Key patterns (for LIKE) of the metadata searched by a `field:value` term. `title`, `tags`,
+/// `description` and `date` name the keys they are stored under; any other field matches the
+/// keys containing it, so `make:canon` finds `tiff:Make`.
+pub fn search_field_keys(field: &str) -> Vec<String> {
+    let keys: &[&str] = match field.to_ascii_lowercase().as_str() {
+        "title" => &["dc:title%"],
+        "tags" | "tag" => &["digiKam:TagsList%", "dc:subject%"],
+        "description" => &["dc:description%", "%ImageDescription%"],
+        "date" => &["exif:DateTimeOriginal%", "xmp:CreateDate%"],
+        _ => return vec![format!("%{}%", field)],
+    };
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+// Function to build the condition of one term on a key_value table alias: its value (or a
+// synonym) has to match, under one of the keys of its field for field:value terms
+fn search_term_condition(table: &str, term: &SearchTerm, synonyms: &dyn Fn(&str) -> Vec<String>, fuzzy: bool, parameters: &mut Vec<String>) -> String {
+    let Some(field) = &term.key else {
+        return term_condition(table, term.value.trim(), synonyms, fuzzy, parameters);
+    };
+    let key_conditions: Vec<String> = search_field_keys(field)
+        .into_iter()
+        .map(|key| {
+            parameters.push(key);
+            format!("{}.key LIKE ?{}", table, parameters.len())
+        })
+        .collect();
+    let key_condition = if key_conditions.len() == 1 { key_conditions.join("") } else { format!("({})", key_conditions.join(" OR ")) };
+    let value_condition = term_condition(table, term.value.trim(), synonyms, fuzzy, parameters);
+    format!("{} AND {}", key_condition, value_condition)
+}
+
+// Function to build the condition matching a value (or one of its synonyms) on a key_value table
+// alias: fuzzy_match with fuzzy, else through the full-text index when it is enabled and LIKE
+// otherwise. The trigram index cannot match terms shorter than 3 characters, and LIKE wildcards
+// in a term keep their meaning, so those terms are matched with LIKE either way.
+fn term_condition(table: &str, value: &str, synonyms: &dyn Fn(&str) -> Vec<String>, fuzzy: bool, parameters: &mut Vec<String>) -> String {
+    let fts = !fuzzy && fts_search_enabled();
+    let mut alternatives = vec![value.to_string()];
+    alternatives.extend(synonyms(value));
+    let conditions: Vec<String> = alternatives
+        .iter()
+        .map(|alternative| {
+            if fuzzy {
+                parameters.push(alternative.clone());
+                format!("fuzzy_match({}.value, ?{})", table, parameters.len())
+            } else if fts && alternative.chars().count() >= 3 && !alternative.contains(['%', '_']) {
+                // A quoted FTS5 string is a phrase, which the trigram tokenizer matches as a substring
+                parameters.push(format!("\"{}\"", alternative.replace('"', "\"\"")));
+                format!("{}.id IN (SELECT rowid FROM key_value_fts WHERE key_value_fts MATCH ?{})", table, parameters.len())
+            } else {
+                parameters.push(format!("%{}%", alternative));
+                format!("{}.value LIKE ?{}", table, parameters.len())
             }
-        }
+        })
+        .collect();
+    if conditions.len() == 1 {
+        conditions.join("")
+    } else {
+        format!("({})", conditions.join(" OR "))
     }
-    
-    // Add any remaining term
-    if !current_term.trim().is_empty() {
-        terms.push(current_term.trim().to_string());
+}
+
+// Function to add the synonyms of each positive term, so they are highlighted like the term itself
+pub fn with_synonym_terms(terms: Vec<SearchTerm>) -> Vec<SearchTerm> {
+    let mut expanded = Vec::new();
+    for term in terms {
+        let synonyms = if term.is_highlightable() { synonyms_of(&term.value) } else { Vec::new() };
+        expanded.push(term);
+        expanded.extend(synonyms.iter().map(|synonym| SearchTerm::new(synonym)));
     }
-    
-    // Filter out empty terms
-    terms.into_iter().filter(|t| !t.is_empty()).collect()
+    expanded
 }
 
-pub async fn index(query: web::Query<IndexQuery>) -> HttpResponse {
-    log::debug!("Index endpoint called with query: {:?}", query.search);
-    
-    // If there's a search query, show search results
-    if let Some(search_term) = &query.search {
-        if !search_term.is_empty() {
-            log::info!("Redirecting to search page for term: {}", search_term);
-            return search_page(query).await;
+// Function to narrow a search down by the camera, lens and capture date filters of the query.
+// Files hidden by --exclude-tag/--exclude-path-glob are left out unless include_excluded is set.
+fn append_search_filters(query: &IndexQuery, include_excluded: bool, where_clause: &mut String, parameters: &mut Vec<String>) -> Result<(), String> {
+    let filters = [
+        (query.camera.as_deref(), "key IN ('tiff:Make', 'tiff:Model', 'exif:Model')"),
+        (query.lens.as_deref(), "(key LIKE '%LensModel' OR key = 'aux:Lens')"),
+    ];
+    for (value, key_condition) in filters {
+        let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let param_num = parameters.len() + 1;
+        where_clause.push_str(&format!(
+            " AND file.id IN (SELECT file_id FROM key_value WHERE {} AND value LIKE ?{})",
+            key_condition, param_num
+        ));
+        parameters.push(format!("%{}%", value));
+    }
+
+    let date_filters = [
+        (query.from.as_deref(), "from", ">=", false),
+        (query.to.as_deref(), "to", "<=", true),
+    ];
+    for (value, name, operator, end_of_period) in date_filters {
+        let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let timestamp = parse_date_bound(value, end_of_period)
+            .ok_or_else(|| format!("Invalid '{}' date: {}", name, value))?;
+        let param_num = parameters.len() + 1;
+        where_clause.push_str(&format!(" AND file.capture_time {} CAST(?{} AS INTEGER)", operator, param_num));
+        parameters.push(timestamp.to_string());
+    }
+
+    let size_filters = [
+        (query.min_size.as_deref(), "min_size", ">="),
+        (query.max_size.as_deref(), "max_size", "<="),
+    ];
+    for (value, name, operator) in size_filters {
+        let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let size_bytes = parse_size(value).ok_or_else(|| format!("Invalid '{}' size: {}", name, value))?;
+        let param_num = parameters.len() + 1;
+        where_clause.push_str(&format!(" AND file.size_bytes {} CAST(?{} AS INTEGER)", operator, param_num));
+        parameters.push(size_bytes.to_string());
+    }
+
+    if let Some(prefix) = query.path_prefix.as_deref().filter(|p| !p.trim().is_empty()) {
+        let directory = normalize_path_prefix(prefix, &get_cli_args().scan_dir);
+        let param_num = parameters.len() + 1;
+        // Range on the path instead of LIKE: exact case, no wildcards to escape, and the path index applies
+        where_clause.push_str(&format!(" AND file.path >= ?{0} || '/' AND file.path < ?{0} || '0'", param_num));
+        parameters.push(directory);
+    }
+
+    let label_filters = [
+        (query.color_label.as_deref(), "color_label", "digiKam:ColorLabel", &COLOR_LABELS[..]),
+        (query.pick.as_deref(), "pick", "digiKam:PickLabel", &PICK_LABELS[..]),
+    ];
+    for (value, name, key, names) in label_filters {
+        let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let number = parse_label(value, names).ok_or_else(|| format!("Invalid '{}': {}", name, value))?;
+        let labelled = format!("SELECT file_id FROM key_value WHERE key = '{}' AND CAST(value AS INTEGER)", key);
+        if number == 0 {
+            // Files without the key count as unlabeled too
+            where_clause.push_str(&format!(" AND file.id NOT IN ({} <> 0)", labelled));
+        } else {
+            let param_num = parameters.len() + 1;
+            where_clause.push_str(&format!(" AND file.id IN ({} = CAST(?{} AS INTEGER))", labelled, param_num));
+            parameters.push(number.to_string());
         }
     }
-    
-    log::debug!("Serving index page");
-    let html = include_str!("../templates/index.html");
 
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+    if let Some(value) = query.video_codec.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        let param_num = parameters.len() + 1;
+        where_clause.push_str(&format!(
+            " AND file.id IN (SELECT file_id FROM key_value WHERE key = '{}' AND value = ?{} COLLATE NOCASE)",
+            VIDEO_CODEC_KEY, param_num
+        ));
+        parameters.push(video_codec_name(value));
+    }
+    if let Some(value) = query.video_resolution.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        let min_edge = parse_video_resolution(value).ok_or_else(|| format!("Invalid 'video_resolution': {}", value))?;
+        let param_num = parameters.len() + 1;
+        // Compared on the shorter edge, so portrait videos count like landscape ones
+        where_clause.push_str(&format!(
+            " AND file.id IN (SELECT width.file_id FROM key_value width \
+             JOIN key_value height ON height.file_id = width.file_id AND height.key = '{}' \
+             WHERE width.key = '{}' AND MIN(CAST(width.value AS INTEGER), CAST(height.value AS INTEGER)) >= CAST(?{} AS INTEGER))",
+            VIDEO_HEIGHT_KEY, VIDEO_WIDTH_KEY, param_num
+        ));
+        parameters.push(min_edge.to_string());
+    }
+
+    if let Some(has_gps) = query.has_gps {
+        where_clause.push_str(&format!(" AND {}{}", if has_gps { "" } else { "NOT " }, has_gps_condition()));
+    }
+
+    if !query.include_missing.unwrap_or(false) {
+        where_clause.push_str(" AND file.source_present = 1");
+    }
+    if !include_excluded {
+        let args = get_cli_args();
+        append_exclusion_filters(&args.exclude_tag, &args.exclude_path_glob, where_clause, parameters);
+    }
+    Ok(())
 }
 
-pub async fn health_check() -> impl Responder {
-    log::trace!("Health check endpoint called");
-    HttpResponse::Ok().body("Healthy")
+/// SQL condition on `file` that is true when a GPS position is stored for the file, i.e. it has
+/// an `exif:GPSLatitude` or `exif:GPSLongitude` value. Used by the `has_gps` filter and result field.
+pub fn has_gps_condition() -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM key_value gps WHERE gps.file_id = file.id AND gps.key IN ('{}', '{}'))",
+        GPS_LATITUDE_KEY, GPS_LONGITUDE_KEY
+    )
 }
 
-pub async fn api_search(query: web::Query<IndexQuery>) -> impl Responder {
-    let search_term = query.search.as_deref().unwrap_or("");
-    log::info!("API search called with term: '{}'", search_term);
-    
-    let (where_clause, parameters) = parse_search_query(search_term);
-    log::debug!("Generated SQL where clause: {}", where_clause);
-    log::debug!("Parameters: {:?}", parameters);
+// Metadata keys holding tag lists, checked by --exclude-tag
+const EXCLUDE_TAG_KEY_CONDITION: &str = "(excluded.key LIKE 'digiKam:TagsList%' OR excluded.key LIKE 'dc:subject%')";
 
-    let args = get_cli_args();
-    let conn = match Connection::open(&args.db_path) {
-        Ok(c) => {
-            log::debug!("Successfully opened database: {}", args.db_path);
-            c
-        },
-        Err(e) => {
-            log::error!("Failed to open database {}: {}", args.db_path, e);
-            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+/// Appends conditions hiding files that carry one of `tags` or whose path matches one of `globs`.
+///
+/// A tag matches a whole entry of the semicolon-joined tag list, case-insensitively, and also
+/// hides its subtags ("Private" hides "Private/Family"). Globs use SQLite GLOB syntax and are
+/// matched against both the image path and its sidecar path.
+pub fn append_exclusion_filters(tags: &[String], globs: &[String], where_clause: &mut String, parameters: &mut Vec<String>) {
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        let escaped = tag.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        parameters.push(format!("%;{};%", escaped));
+        parameters.push(format!("%;{}/%", escaped));
+        let (exact, nested) = (parameters.len() - 1, parameters.len());
+        where_clause.push_str(&format!(
+            " AND NOT EXISTS (SELECT 1 FROM key_value excluded WHERE excluded.file_id = file.id AND {} \
+             AND (';' || excluded.value || ';' LIKE ?{} ESCAPE '\\' OR ';' || excluded.value || ';' LIKE ?{} ESCAPE '\\'))",
+            EXCLUDE_TAG_KEY_CONDITION, exact, nested
+        ));
+    }
+    for glob in globs.iter().filter(|g| !g.trim().is_empty()) {
+        parameters.push(glob.clone());
+        let param_num = parameters.len();
+        where_clause.push_str(&format!(
+            " AND NOT (file.path GLOB ?{0} OR file.path GLOB ?{0} || '.*')",
+            param_num
+        ));
+    }
+}
+
+// Function to decide whether a request may see files hidden by the exclusion options. Asking
+// for include_excluded without the configured bearer token is refused rather than ignored.
+fn include_excluded_allowed(req: &HttpRequest, requested: Option<bool>) -> Result<bool, String> {
+    if !requested.unwrap_or(false) {
+        return Ok(false);
+    }
+    let presented = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match (get_cli_args().exclude_override_token.as_deref(), presented) {
+        (Some(expected), Some(presented)) if tokens_match(expected, presented.trim()) => Ok(true),
+        _ => {
+            log::warn!("Refused include_excluded request without a valid override token");
+            Err("include_excluded requires a valid override token".to_string())
+        }
+    }
+}
+
+// Compares tokens without stopping at the first differing byte
+fn tokens_match(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected.bytes().zip(presented.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// digiKam color label names, indexed by the number stored in `digiKam:ColorLabel`
+pub const COLOR_LABELS: [&str; 10] = ["none", "red", "orange", "yellow", "green", "blue", "magenta", "gray", "black", "white"];
+
+/// digiKam pick label names, indexed by the number stored in `digiKam:PickLabel`
+pub const PICK_LABELS: [&str; 4] = ["none", "rejected", "pending", "accepted"];
+
+/// Parses a label filter given by name (case-insensitive) or number into its stored number.
+pub fn parse_label(value: &str, names: &[&str]) -> Option<usize> {
+    let value = value.trim();
+    match value.parse::<usize>() {
+        Ok(number) => (number < names.len()).then_some(number),
+        Err(_) => names.iter().position(|name| name.eq_ignore_ascii_case(value)),
+    }
+}
+
+/// Maps a `video_codec` filter onto the codec name ffprobe reports, e.g. "h265" and "x265"
+/// onto "hevc". Other names are passed on in lowercase.
+pub fn video_codec_name(value: &str) -> String {
+    let value = value.trim().to_ascii_lowercase();
+    match value.as_str() {
+        "h265" | "x265" | "h.265" => "hevc".to_string(),
+        "avc" | "x264" | "h.264" => "h264".to_string(),
+        _ => value,
+    }
+}
+
+/// Parses a `video_resolution` filter into the minimum length of the video's shorter edge:
+/// "1080p" (or any "<lines>p"), "hd" (720), "fhd" (1080), "4k"/"uhd" (2160) or "8k" (4320).
+pub fn parse_video_resolution(value: &str) -> Option<u32> {
+    let value = value.trim().to_ascii_lowercase();
+    match value.as_str() {
+        "hd" => Some(720),
+        "fhd" => Some(1080),
+        "4k" | "uhd" => Some(2160),
+        "8k" => Some(4320),
+        _ => value.strip_suffix('p')?.parse().ok().filter(|lines| *lines > 0),
+    }
+}
+
+/// Maps a stored label number onto its name; unlabeled (0), unknown and malformed values give None.
+pub fn label_name(stored: &str, names: &[&'static str]) -> Option<&'static str> {
+    match stored.trim().parse::<usize>() {
+        Ok(0) | Err(_) => None,
+        Ok(number) => names.get(number).copied(),
+    }
+}
+
+/// Resolves a `path_prefix` search filter into the directory whose subtree is searched.
+///
+/// Relative prefixes are taken relative to the scan directory. Trailing slashes are dropped, so
+/// "2023/Italy" and "2023/Italy/" match the same files, and never files in "2023/Italy2".
+pub fn normalize_path_prefix(prefix: &str, scan_dir: &str) -> String {
+    let prefix = prefix.trim();
+    let directory = if prefix.starts_with('/') {
+        prefix.to_string()
+    } else {
+        Path::new(scan_dir).join(prefix).to_string_lossy().into_owned()
+    };
+    directory.trim_end_matches('/').to_string()
+}
+
+/// Resolves the `path` of /api/browse like a `path_prefix` filter, after turning backslashes into
+/// slashes and collapsing repeated separators, so "2023\Italy" and "2023//Italy/" list "2023/Italy".
+pub fn normalize_browse_path(path: &str, scan_dir: &str) -> String {
+    let mut normalized = String::with_capacity(path.len());
+    for c in path.trim().chars().map(|c| if c == '\\' { '/' } else { c }) {
+        if !(c == '/' && normalized.ends_with('/')) {
+            normalized.push(c);
+        }
+    }
+    normalize_path_prefix(&normalized, scan_dir)
+}
+
+/// Splits the image paths below `directory` into its immediate subdirectories, with the number of
+/// files anywhere below each, and the files directly in it. Both are sorted by name; paths outside
+/// the directory are ignored. `root` is the top of the tree, which has no parent.
+pub fn browse_listing(directory: &str, root: &str, file_paths: impl IntoIterator<Item = String>) -> BrowseListing {
+    let mut directories: BTreeMap<String, usize> = BTreeMap::new();
+    let mut files: Vec<BrowseFile> = Vec::new();
+    let base = format!("{}/", directory);
+    for file_path in file_paths {
+        let Some(relative) = file_path.strip_prefix(&base) else {
+            continue;
+        };
+        match relative.split_once('/') {
+            Some((name, _)) => *directories.entry(name.to_string()).or_default() += 1,
+            None => files.push(BrowseFile { name: relative.to_string(), file_path: file_path.clone() }),
+        }
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let parent = (directory != root.trim_end_matches('/'))
+        .then(|| directory.rsplit_once('/').map(|(parent, _)| parent.to_string()))
+        .flatten();
+    BrowseListing {
+        path: directory.to_string(),
+        parent,
+        directories: directories
+            .into_iter()
+            .map(|(name, count)| BrowseDirectory { path: format!("{}{}", base, name), name, count })
+            .collect(),
+        files,
+    }
+}
+
+// Function to map the sort option of a search onto an ORDER BY clause
+fn search_order_by(query: &IndexQuery) -> Result<&'static str, String> {
+    match query.sort.as_deref().map(str::trim).unwrap_or("") {
+        "" | "path" => Ok("file.path ASC"),
+        // Backfilled rows share one import time; their ids keep the insertion order
+        "import_desc" => Ok("file.import_time DESC, file.id DESC"),
+        other => Err(format!("Invalid 'sort': {}", other)),
+    }
+}
+
+/// Parses the `since` bound of /api/recent into a Unix timestamp.
+///
+/// Accepts a relative age in hours, days or weeks ("12h", "7d", "2w"), counted back from
+/// `now`, or any date accepted by the `from` search filter ("2024-06", "2024-06-01").
+pub fn parse_since(value: &str, now: i64) -> Option<i64> {
+    let value = value.trim();
+    let unit_seconds = match value.chars().last()? {
+        'h' => Some(3600),
+        'd' => Some(86400),
+        'w' => Some(7 * 86400),
+        _ => None,
+    };
+    if let Some(unit_seconds) = unit_seconds {
+        let amount: u32 = value[..value.len() - 1].parse().ok()?;
+        return now.checked_sub(i64::from(amount) * unit_seconds);
+    }
+    parse_date_bound(value, false)
+}
+
+/// Parses a human readable size such as "50MB", "1.5 GB", "500k" or "1024" into bytes.
+///
+/// Units are binary (1 KB = 1024 bytes) and case-insensitive; "KiB" style suffixes are
+/// accepted too. A plain number is taken as bytes.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+    let bytes = number * multiplier as f64;
+    if bytes.is_finite() && bytes <= i64::MAX as f64 {
+        Some(bytes.round() as u64)
+    } else {
+        None
+    }
+}
+
+// Function to turn a date range bound into a timestamp. Truncated dates used as an
+// end bound cover the whole period, so "to=2023-05" includes all of May.
+fn parse_date_bound(value: &str, end_of_period: bool) -> Option<i64> {
+    let start = parse_capture_date(value)?;
+    if !end_of_period || value.contains(['T', ' ']) {
+        return Some(start);
+    }
+    let parts: Vec<&str> = value.split(['-', ':']).collect();
+    let next_period_start = match parts.as_slice() {
+        [year] => parse_capture_date(&format!("{:04}", year.parse::<i64>().ok()? + 1))?,
+        [year, month] => {
+            let (year, month) = (year.parse::<i64>().ok()?, month.parse::<u32>().ok()?);
+            let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+            parse_capture_date(&format!("{:04}-{:02}", year, month))?
+        }
+        _ => start + 86400,
+    };
+    Some(next_period_start - 1)
+}
+
+// Splits `field:value` on the first colon into the field and the value. The field has to be a
+// plain word starting with a letter, so times like 10:30 stay whole terms.
+fn split_search_field(token: &str) -> (Option<String>, String) {
+    match token.split_once(':') {
+        Some((field, value)) if is_search_field(field) && !value.is_empty() => (Some(field.to_string()), value.to_string()),
+        _ => (None, token.to_string()),
+    }
+}
+
+fn is_search_field(field: &str) -> bool {
+    field.starts_with(|c: char| c.is_ascii_alphabetic()) && field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Function to parse search terms, handling quoted strings and whitespace splitting. A bare OR
+// (or |) between terms makes the following terms an alternative group; quoted, it is a term.
+// `field:value` (or `field:"quoted value"`) restricts a term to the keys of that field, and a
+// leading `-` (outside the quotes) excludes the files matching the term.
+pub fn parse_search_terms(input: &str) -> Vec<SearchTerm> {
+    // Tokens with whether they were quoted, and the prefix (-, field: or both) of a quoted value
+    let mut tokens: Vec<(String, bool, Option<String>)> = Vec::new();
+    let mut current_term = String::new();
+    let mut in_quotes = false;
+    let mut quoted_prefix: Option<String> = None;
+    
+    for ch in input.chars() {
+        match ch {
+            '"' => {
+                if in_quotes {
+                    // End of quoted string
+                    if !current_term.trim().is_empty() {
+                        tokens.push((current_term.trim().to_string(), true, quoted_prefix.take()));
+                        current_term.clear();
+                    }
+                    quoted_prefix = None;
+                    in_quotes = false;
+                } else {
+                    // Start of quoted string
+                    // A minus or field prefix right before the quote applies to the quoted value
+                    let prefix = current_term.trim();
+                    let field = prefix.strip_prefix('-').unwrap_or(prefix);
+                    if prefix == "-" || field.strip_suffix(':').is_some_and(is_search_field) {
+                        quoted_prefix = Some(prefix.to_string());
+                        current_term.clear();
+                    }
+                    // If we have accumulated non-quoted content, save it first
+                    if !current_term.trim().is_empty() {
+                        tokens.push((current_term.trim().to_string(), false, None));
+                        current_term.clear();
+                    }
+                    in_quotes = true;
+                }
+            }
+            ' ' | '\t' | '\n' | '\r' => {
+                if in_quotes {
+                    // Inside quotes, preserve whitespace
+                    current_term.push(ch);
+                } else {
+                    // Outside quotes, whitespace is a separator
+                    if !current_term.trim().is_empty() {
+                        tokens.push((current_term.trim().to_string(), false, None));
+                        current_term.clear();
+                    }
+                }
+            }
+            _ => {
+                current_term.push(ch);
+            }
+        }
+    }
+    
+    // Add any remaining term (an unterminated quote counts as quoted)
+    if !current_term.trim().is_empty() {
+        tokens.push((current_term.trim().to_string(), in_quotes, quoted_prefix.take()));
+    }
+    
+    // Turn OR operators into the alternative flag of the next term. Leading, trailing and
+    // repeated operators have no terms to separate and are dropped.
+    let mut terms: Vec<SearchTerm> = Vec::new();
+    let mut alternative = false;
+    for (token, quoted, prefix) in tokens {
+        if !quoted && (token == "OR" || token == "|" || token == "-") {
+            if token != "-" {
+                alternative = !terms.is_empty();
+            }
+            continue;
+        }
+        let (negated, key, value) = if quoted {
+            let prefix = prefix.unwrap_or_default();
+            let field = prefix.strip_prefix('-').unwrap_or(&prefix).strip_suffix(':').map(str::to_string);
+            (prefix.starts_with('-'), field, token)
+        } else {
+            let negated = token.starts_with('-');
+            let (key, value) = split_search_field(token.strip_prefix('-').unwrap_or(&token));
+            (negated, key, value)
+        };
+        let mut term = SearchTerm::new(&value);
+        term.key = key;
+        term.negated = negated;
+        term.alternative = alternative;
+        alternative = false;
+        terms.push(term);
+    }
+    terms
+}
+
+pub async fn index(req: HttpRequest, query: web::Query<IndexQuery>) -> HttpResponse {
+    log::debug!("Index endpoint called with query: {:?}", query.search);
+    
+    // If there's a search query, show search results
+    if let Some(search_term) = &query.search {
+        let has_filters = query.camera.is_some() || query.lens.is_some() || query.from.is_some() || query.to.is_some()
+            || query.min_size.is_some() || query.max_size.is_some() || query.sort.is_some()
+            || query.include_excluded.is_some() || query.color_label.is_some() || query.pick.is_some()
+            || query.path_prefix.is_some() || query.video_codec.is_some() || query.video_resolution.is_some()
+            || query.has_gps.is_some();
+        if !search_term.is_empty() || has_filters {
+            log::info!("Redirecting to search page for term: {}", search_term);
+            return search_page(req, query).await;
+        }
+    }
+    
+    log::debug!("Serving index page");
+    let html = include_str!("../templates/index.html");
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+pub async fn health_check() -> impl Responder {
+    log::trace!("Health check endpoint called");
+    HttpResponse::Ok().body("Healthy")
+}
+
+pub async fn metrics() -> impl Responder {
+    log::trace!("Metrics endpoint called");
+    match crate::metrics::render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4; charset=utf-8")
+            .body(body),
+        Err(e) => {
+            log::error!("{}", e);
+            HttpResponse::InternalServerError().body(e)
+        }
+    }
+}
+
+// Function to re-import the sidecars in the background and then restart the thumbnail/preview
+// workers, which exit once they have caught up. Returns false when a scan is already running.
+fn start_rescan() -> bool {
+    if RESCAN_IN_PROGRESS.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    std::thread::spawn(|| {
+        if let Err(e) = crate::sidecar_scan::scan_and_import_sidecars() {
+            log::error!("Rescan failed to import sidecars: {}", e);
+        }
+        RESCAN_IN_PROGRESS.store(false, Ordering::SeqCst);
+        crate::background::start_background_workers();
+    });
+    true
+}
+
+pub async fn rescan() -> impl Responder {
+    if let Some(response) = reject_if_read_only() {
+        return response;
+    }
+    if !start_rescan() {
+        log::info!("Rescan requested while one is already running");
+        return HttpResponse::Conflict().json(serde_json::json!({ "status": "scan_in_progress" }));
+    }
+    log::info!("Rescan requested");
+    HttpResponse::Accepted().json(serde_json::json!({
+        "status": "started",
+        // Workers still busy with the previous pass will pick up new files in a queued pass
+        "workers_running": crate::background::background_workers_running(),
+    }))
+}
+
+pub async fn api_search(req: HttpRequest, query: web::Query<IndexQuery>) -> impl Responder {
+    let search_term = query.search.as_deref().unwrap_or("");
+    log::info!("API search called with term: '{}'", search_term);
+    let include_excluded = match include_excluded_allowed(&req, query.include_excluded) {
+        Ok(include_excluded) => include_excluded,
+        Err(e) => return HttpResponse::Forbidden().body(e),
+    };
+    
+    let fuzzy = query.fuzzy.unwrap_or(false);
+    let (mut where_clause, mut parameters) = if fuzzy {
+        parse_fuzzy_search_query(search_term, synonyms_of)
+    } else {
+        parse_search_query(search_term, synonyms_of)
+    };
+    if let Err(e) = append_search_filters(&query, include_excluded, &mut where_clause, &mut parameters) {
+        log::warn!("Invalid search filter: {}", e);
+        return HttpResponse::BadRequest().body(e);
+    }
+    let order_by = match search_order_by(&query) {
+        Ok(order_by) => order_by,
+        Err(e) => {
+            log::warn!("Invalid search sort: {}", e);
+            return HttpResponse::BadRequest().body(e);
+        }
+    };
+    let inline_thumbnails = match query.thumbnails.as_deref().map(str::trim).unwrap_or("") {
+        "" | "inline" => true,
+        "lazy" => false,
+        other => {
+            log::warn!("Invalid thumbnails mode: {}", other);
+            return HttpResponse::BadRequest().body(format!("Invalid 'thumbnails': {}", other));
+        }
+    };
+    let v2_format = match query.format.as_deref().map(str::trim).unwrap_or("") {
+        "" | "v1" => false,
+        "v2" => true,
+        other => {
+            log::warn!("Invalid response format: {}", other);
+            return HttpResponse::BadRequest().body(format!("Invalid 'format': {}", other));
+        }
+    };
+    let paginated = query.page.is_some() || query.per_page.is_some();
+    let page = SearchPage::new(query.page, query.per_page);
+
+    let args = get_cli_args();
+    let conn = match open_read_only(&args.db_path) {
+        Ok(c) => {
+            log::debug!("Successfully opened database: {}", args.db_path);
+            c
+        },
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
         },
     };
+    if fuzzy {
+        if let Err(e) = register_fuzzy_match(&conn) {
+            log::error!("Failed to register fuzzy_match: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Fuzzy search error: {}", e));
+        }
+    }
+    let total = if paginated {
+        match count_search_files(&conn, &where_clause, &parameters) {
+            Ok(total) => Some(total),
+            Err(e) => {
+                log::error!("Query execution error counting results: {}", e);
+                return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+            }
+        }
+    } else {
+        None
+    };
+    // Fuzzy results are ordered by score after the query, so their pages are cut out afterwards
+    if paginated && !fuzzy {
+        append_page_filter(&mut where_clause, &mut parameters, order_by, page);
+    }
+    log::debug!("Generated SQL where clause: {}", where_clause);
+    log::debug!("Parameters: {:?}", parameters);
+    // Terms (and their synonyms) each row is scored against, to rank fuzzy matches by closeness
+    let fuzzy_terms: Vec<String> = if fuzzy {
+        with_synonym_terms(parse_search_terms(search_term))
+            .into_iter()
+            .filter(|term| term.is_highlightable())
+            .map(|term| term.value)
+            .collect()
+    } else {
+        Vec::new()
+    };
 
     let mut stmt = match conn.prepare(
-        &format!("SELECT file.path, key_value.value \
+        &format!("SELECT COALESCE(file.image_path, file.path), key_value.value, file.dominant_color, file.size_bytes, file.source_present, \
+         (SELECT value FROM key_value color WHERE color.file_id = file.id AND color.key = 'digiKam:ColorLabel' LIMIT 1), \
+         (SELECT value FROM key_value pick WHERE pick.file_id = file.id AND pick.key = 'digiKam:PickLabel' LIMIT 1), \
+         {} \
          FROM key_value \
          JOIN file ON key_value.file_id = file.id \
          {} \
-         ORDER BY file.path ASC", where_clause)
+         ORDER BY {}", has_gps_condition(), where_clause, order_by)
     ) {
         Ok(s) => s,
         Err(e) => {
@@ -241,14 +1288,32 @@ pub async fn api_search(query: web::Query<IndexQuery>) -> impl Responder {
         .query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
             let file_path: String = row.get(0)?;
             let value: String = row.get(1)?;
-            // Remove ".xmp" suffix if present
-            let file_path = file_path.strip_suffix(".xmp").unwrap_or(&file_path).to_string();
+            let dominant_color: Option<String> = row.get(2)?;
+            let size_bytes: Option<i64> = row.get(3)?;
+            let source_present: bool = row.get(4)?;
+            let color_label = row.get::<_, Option<String>>(5)?.and_then(|v| label_name(&v, &COLOR_LABELS));
+            let pick_label = row.get::<_, Option<String>>(6)?.and_then(|v| label_name(&v, &PICK_LABELS));
+            let has_gps: bool = row.get(7)?;
+            // Rows of a matching file that are close to none of the terms score 0
+            let score = (!fuzzy_terms.is_empty()).then(|| {
+                fuzzy_terms.iter().filter_map(|term| fuzzy_score(&value, term)).fold(0.0, f64::max)
+            });
+            // Remove the sidecar extension (".xmp") if present
+            let file_path = image_path_for_sidecar(&file_path).to_string();
             
             log::trace!("Processing result: {}", file_path);
-            // Generate thumbnail for the image
-            let thumbnail_base64 = generate_thumbnail(&file_path);
-            
-            Ok(SearchResult { file_path, value, thumbnail_base64 })
+            Ok(SearchResult {
+                file_path,
+                value,
+                thumbnail_base64: None,
+                dominant_color,
+                size_bytes,
+                source_present,
+                color_label,
+                pick_label,
+                has_gps,
+                score,
+            })
         });
 
     let mut results = Vec::new();
@@ -265,32 +1330,1096 @@ pub async fn api_search(query: web::Query<IndexQuery>) -> impl Responder {
             }
         }
         Err(e) => {
-            log::error!("Query execution error: {}", e);
-            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
-        },
-    }
-
-    log::info!("API search completed, found {} results", results.len());
-
-    // Return as JSON
-    match serde_json::to_string(&results) {
-        Ok(json) => HttpResponse::Ok().content_type("application/json").body(json),
-        Err(e) => {
-            log::error!("JSON serialization error: {}", e);
-            HttpResponse::InternalServerError().body(format!("Serialization error: {}", e))
-        },
+            log::error!("Query execution error: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        },
+    }
+
+    if fuzzy {
+        // Closest matches first; the sort order only decides between equal scores
+        results.sort_by(|a, b| b.score.unwrap_or(0.0).total_cmp(&a.score.unwrap_or(0.0)));
+        if paginated {
+            let mut files = Vec::new();
+            let mut seen = HashSet::new();
+            for result in &results {
+                if seen.insert(result.file_path.clone()) {
+                    files.push(result.file_path.clone());
+                }
+            }
+            let page_files: HashSet<String> = files.into_iter().skip(page.offset()).take(page.per_page).collect();
+            results.retain(|result| page_files.contains(&result.file_path));
+        }
+    }
+    // Generate thumbnails for the returned images, unless the client fetches them lazily
+    if inline_thumbnails {
+        for result in results.iter_mut() {
+            result.thumbnail_base64 = generate_thumbnail(&result.file_path);
+        }
+    }
+    log::info!("API search completed, found {} results", results.len());
+    let file_count = total.unwrap_or_else(|| results.iter().map(|result| result.file_path.as_str()).collect::<HashSet<_>>().len());
+    record_search_history(search_term, file_count).await;
+
+    // Return as JSON, paginated results with the page they are on
+    let results = if v2_format {
+        serde_json::to_value(results.iter().map(SearchResultV2::from).collect::<Vec<_>>())
+    } else {
+        serde_json::to_value(&results)
+    };
+    let json = results.and_then(|results| match total {
+        Some(total) => serde_json::to_string(&serde_json::json!({
+            "total": total,
+            "page": page.page,
+            "per_page": page.per_page,
+            "pages": page.page_count(total),
+            "results": results,
+        })),
+        None => serde_json::to_string(&results),
+    });
+    match json {
+        Ok(json) => HttpResponse::Ok().content_type("application/json").body(json),
+        Err(e) => {
+            log::error!("JSON serialization error: {}", e);
+            HttpResponse::InternalServerError().body(format!("Serialization error: {}", e))
+        },
+    }
+}
+
+// Number of files /api would return for the same search and filters, without reading any image
+pub async fn api_count(req: HttpRequest, query: web::Query<IndexQuery>) -> impl Responder {
+    let search_term = query.search.as_deref().unwrap_or("");
+    log::info!("Count requested for term: '{}'", search_term);
+    let include_excluded = match include_excluded_allowed(&req, query.include_excluded) {
+        Ok(include_excluded) => include_excluded,
+        Err(e) => return HttpResponse::Forbidden().body(e),
+    };
+
+    let fuzzy = query.fuzzy.unwrap_or(false);
+    let (mut where_clause, mut parameters) = if fuzzy {
+        parse_fuzzy_search_query(search_term, synonyms_of)
+    } else {
+        parse_search_query(search_term, synonyms_of)
+    };
+    if let Err(e) = append_search_filters(&query, include_excluded, &mut where_clause, &mut parameters) {
+        log::warn!("Invalid search filter: {}", e);
+        return HttpResponse::BadRequest().body(e);
+    }
+    log::debug!("Generated SQL where clause: {}", where_clause);
+
+    let args = get_cli_args();
+    let conn = match open_read_only(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    if fuzzy {
+        if let Err(e) = register_fuzzy_match(&conn) {
+            log::error!("Failed to register fuzzy_match: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Fuzzy search error: {}", e));
+        }
+    }
+    match count_search_files(&conn, &where_clause, &parameters) {
+        Ok(count) => {
+            log::info!("Count for '{}': {} files", search_term, count);
+            HttpResponse::Ok().json(serde_json::json!({ "count": count }))
+        }
+        Err(e) => {
+            log::error!("Query execution error counting results: {}", e);
+            HttpResponse::InternalServerError().body(format!("Query error: {}", e))
+        }
+    }
+}
+
+/// Runs a search for one window of its result set: the number of matching files and the files
+/// at `offset..offset + limit` in `order_by` order. Each file is listed once, however many of its
+/// metadata fields match. Rows come without dimensions and thumbnails, which are up to the caller.
+pub fn search_window(
+    conn: &Connection,
+    where_clause: &str,
+    parameters: &[String],
+    order_by: &str,
+    offset: usize,
+    limit: usize,
+) -> rusqlite::Result<(usize, Vec<WindowRow>)> {
+    let total = count_search_files(conn, where_clause, parameters)?;
+
+    let mut parameters = parameters.to_vec();
+    parameters.push(limit.to_string());
+    parameters.push(offset.to_string());
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(file.image_path, file.path), file.capture_time, file.dominant_color, file.source_present \
+         FROM key_value \
+         JOIN file ON key_value.file_id = file.id \
+         {} \
+         GROUP BY file.id \
+         ORDER BY {} \
+         LIMIT CAST(?{} AS INTEGER) OFFSET CAST(?{} AS INTEGER)",
+        where_clause,
+        order_by,
+        parameters.len() - 1,
+        parameters.len()
+    ))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+            let file_path: String = row.get(0)?;
+            Ok(WindowRow {
+                file_path: image_path_for_sidecar(&file_path).to_string(),
+                width: None,
+                height: None,
+                capture_time: row.get(1)?,
+                dominant_color: row.get(2)?,
+                source_present: row.get(3)?,
+                thumbnail_base64: None,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok((total, rows))
+}
+
+/// Counts the files matching a search, each once however many of its metadata fields match
+pub fn count_search_files(conn: &Connection, where_clause: &str, parameters: &[String]) -> rusqlite::Result<usize> {
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(DISTINCT file.id) FROM key_value JOIN file ON key_value.file_id = file.id {}",
+            where_clause
+        ),
+        rusqlite::params_from_iter(parameters.iter()),
+        |row| row.get(0),
+    )
+}
+
+/// A page of search results: its 1-based number and the number of files per page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchPage {
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl SearchPage {
+    /// The requested page, by default the first one of `DEFAULT_PER_PAGE` files. Page 0 counts
+    /// as the first page and `per_page` is kept within 1..=`MAX_PER_PAGE`.
+    pub fn new(page: Option<usize>, per_page: Option<usize>) -> SearchPage {
+        SearchPage {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+        }
+    }
+
+    /// Number of files on the pages before this one
+    pub fn offset(&self) -> usize {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// Number of pages for `total` matching files, at least one
+    pub fn page_count(&self, total: usize) -> usize {
+        total.div_ceil(self.per_page).max(1)
+    }
+}
+
+/// Narrows a search down to the files of one page in `order_by` order, keeping every matching
+/// row of those files. The subquery repeats the WHERE clause, whose `?N` parameters SQLite binds
+/// for both, so call this after all other filters have been appended.
+pub fn append_page_filter(where_clause: &mut String, parameters: &mut Vec<String>, order_by: &str, page: SearchPage) {
+    parameters.push(page.per_page.to_string());
+    parameters.push(page.offset().to_string());
+    let page_condition = format!(
+        " AND file.id IN (SELECT file.id FROM key_value JOIN file ON key_value.file_id = file.id {} \
+         GROUP BY file.id ORDER BY {} LIMIT CAST(?{} AS INTEGER) OFFSET CAST(?{} AS INTEGER))",
+        where_clause,
+        order_by,
+        parameters.len() - 1,
+        parameters.len()
+    );
+    where_clause.push_str(&page_condition);
+}
+
+/// Link to another page of a search, keeping all other parameters of its query string
+pub fn page_link(path: &str, query_string: &str, page: usize) -> String {
+    let mut parameters: Vec<String> = query_string
+        .split('&')
+        .filter(|parameter| !parameter.is_empty() && parameter.split('=').next() != Some("page"))
+        .map(str::to_string)
+        .collect();
+    parameters.push(format!("page={}", page));
+    format!("{}?{}", path, parameters.join("&"))
+}
+
+pub async fn api_window(req: HttpRequest, query: web::Query<IndexQuery>, window: web::Query<WindowQuery>) -> impl Responder {
+    let search_term = query.search.as_deref().unwrap_or("");
+    let offset = window.offset.unwrap_or(0);
+    let limit = window.limit.unwrap_or(DEFAULT_WINDOW_LIMIT).min(MAX_WINDOW_LIMIT);
+    log::info!("Window search called with term: '{}', offset {}, limit {}", search_term, offset, limit);
+    let include_excluded = match include_excluded_allowed(&req, query.include_excluded) {
+        Ok(include_excluded) => include_excluded,
+        Err(e) => return HttpResponse::Forbidden().body(e),
+    };
+
+    let (mut where_clause, mut parameters) = parse_search_query(search_term, synonyms_of);
+    if let Err(e) = append_search_filters(&query, include_excluded, &mut where_clause, &mut parameters) {
+        log::warn!("Invalid search filter: {}", e);
+        return HttpResponse::BadRequest().body(e);
+    }
+    let order_by = match search_order_by(&query) {
+        Ok(order_by) => order_by,
+        Err(e) => {
+            log::warn!("Invalid search sort: {}", e);
+            return HttpResponse::BadRequest().body(e);
+        }
+    };
+    let inline_thumbnails = match query.thumbnails.as_deref().map(str::trim).unwrap_or("") {
+        "" | "inline" => true,
+        "lazy" => false,
+        other => {
+            log::warn!("Invalid thumbnails mode: {}", other);
+            return HttpResponse::BadRequest().body(format!("Invalid 'thumbnails': {}", other));
+        }
+    };
+
+    let args = get_cli_args();
+    let conn = match open_read_only(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    let (total, mut rows) = match search_window(&conn, &where_clause, &parameters, order_by, offset, limit) {
+        Ok(window) => window,
+        Err(e) => {
+            log::error!("Window query error: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        }
+    };
+    drop(conn);
+
+    for row in rows.iter_mut().filter(|row| row.source_present) {
+        if let Ok((width, height)) = image::image_dimensions(&row.file_path) {
+            row.width = Some(width);
+            row.height = Some(height);
+        }
+        if inline_thumbnails {
+            row.thumbnail_base64 = generate_thumbnail(&row.file_path);
+        }
+    }
+
+    log::info!("Window search returned {} of {} files", rows.len(), total);
+    HttpResponse::Ok().json(serde_json::json!({
+        "total": total,
+        "offset": offset,
+        "limit": limit,
+        "rows": rows,
+    }))
+}
+
+pub async fn api_suggest(query: web::Query<SuggestQuery>) -> impl Responder {
+    let term = query.q.trim();
+    log::info!("Suggestions requested for: '{}'", term);
+    if term.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Query 'q' must not be empty" }));
+    }
+
+    // Tags of excluded files are not offered, so suggestions do not reveal them
+    let mut where_clause = format!("WHERE {}", SUGGEST_TAG_KEY_CONDITION);
+    let mut parameters: Vec<String> = Vec::new();
+    let args = get_cli_args();
+    append_exclusion_filters(&args.exclude_tag, &args.exclude_path_glob, &mut where_clause, &mut parameters);
+
+    let conn = match open_read_only(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    let mut stmt = match conn.prepare(&format!(
+        "SELECT DISTINCT key_value.value FROM key_value JOIN file ON key_value.file_id = file.id {}",
+        where_clause
+    )) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("SQL preparation error for suggestions: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Prepare error: {}", e));
+        },
+    };
+    let tag_lists = match stmt
+        .query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get::<_, String>(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+    {
+        Ok(tag_lists) => tag_lists,
+        Err(e) => {
+            log::error!("Query execution error for suggestions: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        }
+    };
+
+    let vocabulary = tag_vocabulary(tag_lists.iter().map(String::as_str));
+    let suggestions = suggest(term, &vocabulary, MAX_SUGGESTIONS);
+    log::info!("Found {} suggestions for '{}' among {} tags", suggestions.len(), term, vocabulary.len());
+    HttpResponse::Ok().json(suggestions)
+}
+
+pub async fn api_by_color(query: web::Query<ColorQuery>) -> impl Responder {
+    log::info!("Color search called with hex: '{}', tolerance: {:?}", query.hex, query.tolerance);
+
+    let target = match parse_hex(&query.hex) {
+        Some(rgb) => rgb,
+        None => {
+            log::warn!("Invalid color in color search: {}", query.hex);
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Invalid hex color, expected rrggbb or #rrggbb"
+            }));
+        }
+    };
+    let tolerance = query.tolerance.unwrap_or(60.0).max(0.0);
+
+    let args = get_cli_args();
+    let conn = match open_read_only(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+
+    let mut where_clause = String::from("WHERE dominant_color IS NOT NULL");
+    let mut parameters = Vec::new();
+    append_exclusion_filters(&args.exclude_tag, &args.exclude_path_glob, &mut where_clause, &mut parameters);
+    let mut stmt = match conn.prepare(&format!("SELECT COALESCE(image_path, path), dominant_color FROM file {}", where_clause)) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("SQL preparation error for color search: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Prepare error: {}", e));
+        },
+    };
+
+    let rows = stmt.query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    });
+
+    // Distance is computed in Rust since SQLite has no notion of colors
+    let mut results = Vec::new();
+    match rows {
+        Ok(mapped) => {
+            for (file_path, dominant_color) in mapped.flatten() {
+                if let Some(rgb) = parse_hex(&dominant_color) {
+                    let distance = color_distance(target, rgb);
+                    if distance <= tolerance {
+                        let file_path = image_path_for_sidecar(&file_path).to_string();
+                        results.push(ColorSearchResult { file_path, dominant_color, distance });
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Query execution error in color search: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        },
+    }
+    results.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    log::info!("Color search completed, found {} results", results.len());
+    HttpResponse::Ok().json(results)
+}
+
+// Lists the immediate subdirectories and files of a directory, from the indexed paths alone
+pub async fn api_browse(query: web::Query<BrowseQuery>) -> impl Responder {
+    let args = get_cli_args();
+    let directory = normalize_browse_path(query.path.as_deref().unwrap_or(""), &args.scan_dir);
+    log::info!("Browse request for {}", directory);
+
+    // Same range on the path as the path_prefix filter, so the listing matches its searches
+    let mut where_clause = String::from("WHERE file.path >= ?1 || '/' AND file.path < ?1 || '0'");
+    let mut parameters: Vec<String> = vec![directory.clone()];
+    if !query.include_missing.unwrap_or(false) {
+        where_clause.push_str(" AND source_present = 1");
+    }
+    append_exclusion_filters(&args.exclude_tag, &args.exclude_path_glob, &mut where_clause, &mut parameters);
+
+    let conn = match open_read_only(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    let mut stmt = match conn.prepare(&format!("SELECT COALESCE(image_path, path) FROM file {}", where_clause)) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("SQL preparation error for browse: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Prepare error: {}", e));
+        },
+    };
+    let file_paths = stmt
+        .query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get::<_, String>(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>());
+    match file_paths {
+        Ok(file_paths) => {
+            let image_paths = file_paths.iter().map(|path| image_path_for_sidecar(path).to_string());
+            let root = normalize_path_prefix("", &args.scan_dir);
+            HttpResponse::Ok().json(browse_listing(&directory, &root, image_paths))
+        }
+        Err(e) => {
+            log::error!("Browse query error: {}", e);
+            HttpResponse::InternalServerError().body(format!("Query error: {}", e))
+        }
+    }
+}
+
+pub async fn api_recent(query: web::Query<RecentQuery>) -> impl Responder {
+    log::info!("Recent files request since {:?}, limit {:?}", query.since, query.limit);
+
+    let limit = query.limit.unwrap_or(DEFAULT_RECENT_LIMIT).min(MAX_RECENT_LIMIT);
+    // Files imported before import_time was recorded are not recent
+    let mut where_clause = String::from("WHERE import_time IS NOT NULL");
+    let mut parameters: Vec<String> = Vec::new();
+    if let Some(since) = query.since.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let Some(timestamp) = parse_since(since, now) else {
+            log::warn!("Invalid 'since' for recent files: {}", since);
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Invalid 'since': {}", since)
+            }));
+        };
+        parameters.push(timestamp.to_string());
+        where_clause.push_str(&format!(" AND import_time >= CAST(?{} AS INTEGER)", parameters.len()));
+    }
+    if !query.include_missing.unwrap_or(false) {
+        where_clause.push_str(" AND source_present = 1");
+    }
+    let args = get_cli_args();
+    append_exclusion_filters(&args.exclude_tag, &args.exclude_path_glob, &mut where_clause, &mut parameters);
+    parameters.push(limit.to_string());
+
+    let conn = match open_read_only(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+
+    let mut stmt = match conn.prepare(&format!(
+        "SELECT COALESCE(image_path, path), import_time, capture_time, size_bytes, source_present \
+         FROM file \
+         {} \
+         ORDER BY import_time DESC, id DESC \
+         LIMIT CAST(?{} AS INTEGER)",
+        where_clause,
+        parameters.len()
+    )) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("SQL preparation error for recent files: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Prepare error: {}", e));
+        },
+    };
+
+    let rows = stmt.query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+        let file_path: String = row.get(0)?;
+        Ok(RecentResult {
+            file_path: image_path_for_sidecar(&file_path).to_string(),
+            import_time: row.get(1)?,
+            capture_time: row.get(2)?,
+            size_bytes: row.get(3)?,
+            source_present: row.get(4)?,
+        })
+    });
+
+    let mut results = Vec::new();
+    match rows {
+        Ok(mapped) => {
+            for row in mapped {
+                match row {
+                    Ok(result) => results.push(result),
+                    Err(e) => {
+                        log::error!("Row processing error in recent files: {}", e);
+                        return HttpResponse::InternalServerError().body(format!("Row error: {}", e));
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Query execution error in recent files: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        },
+    }
+
+    log::info!("Recent files request returned {} files", results.len());
+    HttpResponse::Ok().json(results)
+}
+
+// Function to find the base of absolute links: --public-url, or the scheme and host the request was sent to
+fn public_base_url(req: &HttpRequest) -> String {
+    match get_cli_args().public_url.as_deref().map(str::trim).filter(|url| !url.is_empty()) {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => {
+            let info = req.connection_info();
+            format!("{}://{}", info.scheme(), info.host())
+        }
+    }
+}
+
+// Function to load the items of /feed.json and /feed.xml
+fn load_feed_items() -> Result<Vec<FeedItem>, String> {
+    let args = get_cli_args();
+    let conn = open_read_only(&args.db_path).map_err(|e| format!("DB open error: {}", e))?;
+    recent_feed_items(&conn, args.feed_items, &args.exclude_tag, &args.exclude_path_glob).map_err(|e| format!("Query error: {}", e))
+}
+
+pub async fn feed_json(req: HttpRequest) -> impl Responder {
+    log::info!("JSON feed requested");
+    match load_feed_items() {
+        Ok(items) => HttpResponse::Ok()
+            .content_type("application/feed+json")
+            .body(json_feed(&public_base_url(&req), &items).to_string()),
+        Err(e) => {
+            log::error!("Failed to build JSON feed: {}", e);
+            HttpResponse::InternalServerError().body(e)
+        }
+    }
+}
+
+pub async fn feed_atom(req: HttpRequest) -> impl Responder {
+    log::info!("Atom feed requested");
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    match load_feed_items() {
+        Ok(items) => HttpResponse::Ok()
+            .content_type("application/atom+xml; charset=utf-8")
+            .body(atom_feed(&public_base_url(&req), &items, now)),
+        Err(e) => {
+            log::error!("Failed to build Atom feed: {}", e);
+            HttpResponse::InternalServerError().body(e)
+        }
+    }
+}
+
+// Function to add a search to the history with --track-search-history. The background workers
+// pause meanwhile, as their reads block the write. A failure is only logged, as the search itself
+// succeeded.
+async fn record_search_history(search_term: &str, result_count: usize) {
+    let args = get_cli_args();
+    if !args.track_search_history || search_term.trim().is_empty() {
+        return;
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    with_user_activity(|| async move {
+        // The search itself ran on a read-only connection
+        let recorded = Connection::open(&args.db_path).and_then(|conn| record_search(&conn, search_term, result_count, now));
+        if let Err(e) = recorded {
+            log::warn!("Failed to record search history: {}", e);
+        }
+    }).await
+}
+
+// Function to answer 404 to history requests unless --track-search-history is set
+fn reject_if_history_disabled() -> Option<HttpResponse> {
+    (!get_cli_args().track_search_history).then(|| {
+        HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Search history is disabled, see --track-search-history"
+        }))
+    })
+}
+
+pub async fn api_history(query: web::Query<HistoryQuery>) -> impl Responder {
+    if let Some(response) = reject_if_history_disabled() {
+        return response;
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).min(MAX_HISTORY_LIMIT);
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    match recent_searches(&conn, limit) {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => {
+            log::error!("Failed to read search history: {}", e);
+            HttpResponse::InternalServerError().body(format!("Query error: {}", e))
+        }
+    }
+}
+
+pub async fn api_clear_history() -> impl Responder {
+    if let Some(response) = reject_if_history_disabled().or_else(reject_if_read_only) {
+        return response;
+    }
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    match clear_history(&conn) {
+        Ok(removed) => {
+            log::info!("Cleared {} search history entries", removed);
+            HttpResponse::Ok().json(serde_json::json!({ "removed": removed }))
+        }
+        Err(e) => {
+            log::error!("Failed to clear search history: {}", e);
+            HttpResponse::InternalServerError().body(format!("Delete error: {}", e))
+        }
+    }
+}
+
+pub async fn api_failures(query: web::Query<FailuresQuery>) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_FAILURES_LIMIT).min(MAX_FAILURES_LIMIT);
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    match list_failures(&conn, query.stage, limit) {
+        Ok(failures) => HttpResponse::Ok().json(failures),
+        Err(e) => {
+            log::error!("Failed to read failures: {}", e);
+            HttpResponse::InternalServerError().body(format!("Query error: {}", e))
+        }
+    }
+}
+
+pub async fn api_clear_failures(query: web::Query<FailuresQuery>) -> impl Responder {
+    if let Some(response) = reject_if_read_only() {
+        return response;
+    }
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    match clear_failures(&conn, query.stage) {
+        Ok(removed) => {
+            log::info!("Cleared {} failures", removed);
+            HttpResponse::Ok().json(serde_json::json!({ "removed": removed }))
+        }
+        Err(e) => {
+            log::error!("Failed to clear failures: {}", e);
+            HttpResponse::InternalServerError().body(format!("Delete error: {}", e))
+        }
+    }
+}
+
+/// Clears the failures (of one stage or all) and processes the files again: scan failures with
+/// a rescan, which is followed by a background worker pass regenerating missing thumbnails and
+/// previews. Files failing again are recorded again.
+pub async fn api_retry_failures(query: web::Query<FailuresQuery>) -> impl Responder {
+    if let Some(response) = reject_if_read_only() {
+        return response;
+    }
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    let rescan = query.stage.is_none_or(|stage| stage == FailureStage::Scan);
+    if rescan && RESCAN_IN_PROGRESS.load(Ordering::SeqCst) {
+        log::info!("Failure retry requested while a scan is running");
+        return HttpResponse::Conflict().json(serde_json::json!({ "status": "scan_in_progress" }));
+    }
+    let removed = match clear_failures(&conn, query.stage) {
+        Ok(removed) => removed,
+        Err(e) => {
+            log::error!("Failed to clear failures for retry: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Delete error: {}", e));
+        }
+    };
+    // Without scan failures to retry, the worker pass alone regenerates the missing files
+    if !(rescan && start_rescan()) {
+        crate::background::start_background_workers();
+    }
+    log::info!("Retrying {} failures", removed);
+    HttpResponse::Accepted().json(serde_json::json!({
+        "status": "started",
+        "retried": removed,
+        "rescan": rescan,
+    }))
+}
+
+pub async fn api_cache_verify(query: web::Query<CacheVerifyQuery>) -> impl Responder {
+    let sample_percent = query.sample.unwrap_or(get_cli_args().cache_verify_sample);
+    if !(1..=100).contains(&sample_percent) {
+        return HttpResponse::BadRequest().body(format!("Invalid 'sample': {}", sample_percent));
+    }
+    log::info!("Cache verification requested for {}% of the cache files", sample_percent);
+    // The request runs the check itself, so nothing to wait for
+    let summary = web::block(move || crate::processing::cache::verify_caches(sample_percent, &AtomicBool::new(false))).await;
+    match summary {
+        Ok(Some(summary)) => {
+            if summary.removed > 0 {
+                crate::background::start_background_workers();
+            }
+            HttpResponse::Ok().json(serde_json::json!({
+                "sample": sample_percent,
+                "checked": summary.checked,
+                "corrupt": summary.corrupt,
+                "removed": summary.removed,
+            }))
+        }
+        Ok(None) => HttpResponse::Conflict().json(serde_json::json!({ "status": "verify_in_progress" })),
+        Err(e) => {
+            log::error!("Cache verification failed: {}", e);
+            HttpResponse::InternalServerError().body(format!("Verification error: {}", e))
+        }
+    }
+}
+
+// Empties the thumbnail and/or preview cache directory, then starts a worker pass to fill them again
+pub async fn api_cache_clear(query: web::Query<CacheClearQuery>) -> impl Responder {
+    let cache_type = query.cache_type.unwrap_or(RegenerateTarget::All);
+    log::info!("Clearing the {:?} cache", cache_type);
+    let cleared = web::block(move || {
+        let thumbnails = (cache_type != RegenerateTarget::Previews).then(|| {
+            let summary = crate::processing::cache::clear_cache_dir(&crate::processing::cache::get_cache_dir());
+            crate::processing::cache::clear_memory_thumbnails();
+            summary
+        });
+        let previews = (cache_type != RegenerateTarget::Thumbnails)
+            .then(|| crate::processing::cache::clear_cache_dir(&crate::processing::cache::get_preview_cache_dir()));
+        (thumbnails, previews)
+    })
+    .await;
+    match cleared {
+        Ok((thumbnails, previews)) => {
+            let files: usize = thumbnails.iter().chain(&previews).map(|summary| summary.files).sum();
+            let bytes: u64 = thumbnails.iter().chain(&previews).map(|summary| summary.bytes).sum();
+            log::info!("Cleared {} cache files ({} bytes)", files, bytes);
+            crate::background::start_background_workers();
+            HttpResponse::Ok().json(serde_json::json!({
+                "thumbnails": thumbnails,
+                "previews": previews,
+                "files": files,
+                "bytes": bytes,
+            }))
+        }
+        Err(e) => {
+            log::error!("Clearing the cache failed: {}", e);
+            HttpResponse::InternalServerError().body(format!("Clear error: {}", e))
+        }
+    }
+}
+
+// Reports the file count, size and age range of each cache directory
+pub async fn api_cache_stats() -> impl Responder {
+    let stats = web::block(|| {
+        let video_cache_dir = std::path::PathBuf::from(&get_cli_args().video_preview_cache);
+        serde_json::json!({
+            "thumbnails": crate::processing::cache::cache_dir_stats(&crate::processing::cache::get_cache_dir()),
+            "previews": crate::processing::cache::cache_dir_stats(&crate::processing::cache::get_preview_cache_dir()),
+            "videos": crate::processing::cache::cache_dir_stats(&video_cache_dir),
+        })
+    })
+    .await;
+    match stats {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => {
+            log::error!("Reading cache statistics failed: {}", e);
+            HttpResponse::InternalServerError().body(format!("Stats error: {}", e))
+        }
+    }
+}
+
+// Takes the search and filters of /api, plus `target`. The results are regenerated on a separate
+// thread, each file reported as a line of JSON as it is done, followed by a summary line.
+pub async fn api_regenerate_search(req: HttpRequest, query: web::Query<IndexQuery>, options: web::Query<RegenerateQuery>) -> impl Responder {
+    let Some(search_term) = query.search.as_deref() else {
+        return HttpResponse::BadRequest().body("Missing 'search'; pass search= (it may be empty) to regenerate all matching files");
+    };
+    let include_excluded = match include_excluded_allowed(&req, query.include_excluded) {
+        Ok(include_excluded) => include_excluded,
+        Err(e) => return HttpResponse::Forbidden().body(e),
+    };
+    let target = options.target.unwrap_or(RegenerateTarget::All);
+    let (mut where_clause, mut parameters) = parse_search_query(search_term, synonyms_of);
+    if let Err(e) = append_search_filters(&query, include_excluded, &mut where_clause, &mut parameters) {
+        log::warn!("Invalid search filter: {}", e);
+        return HttpResponse::BadRequest().body(e);
+    }
+
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    let file_paths = conn
+        .prepare(&format!(
+            "SELECT DISTINCT COALESCE(file.image_path, file.path) FROM key_value JOIN file ON key_value.file_id = file.id {} ORDER BY 1",
+            where_clause
+        ))
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        });
+    let file_paths: Vec<String> = match file_paths {
+        Ok(paths) => paths.iter().map(|path| image_path_for_sidecar(path).to_string()).collect(),
+        Err(e) => {
+            log::error!("Query execution error for regeneration: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        }
+    };
+
+    let Some(guard) = start_regeneration() else {
+        log::info!("Regeneration requested while one is already running");
+        return HttpResponse::Conflict().json(serde_json::json!({ "status": "regenerate_in_progress" }));
+    };
+    log::info!("Regenerating {:?} for {} files matching '{}'", target, file_paths.len(), search_term);
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel::<String>();
+    std::thread::spawn(move || {
+        let total = file_paths.len();
+        let mut done = 0;
+        let summary = regenerate_files(guard, &file_paths, target, &USER_REQUEST_ACTIVE, |file_path, regenerated| {
+            done += 1;
+            // A closed connection does not stop the regeneration
+            let _ = sender.send(format!(
+                "{}\n",
+                serde_json::json!({ "file_path": file_path, "regenerated": regenerated, "done": done, "total": total })
+            ));
+        });
+        log::info!("Regeneration done: {} regenerated, {} failed", summary.regenerated, summary.failed);
+        let _ = sender.send(format!("{}\n", serde_json::json!({ "status": "done", "summary": summary })));
+    });
+    let body = futures::stream::unfold(receiver, |mut receiver| async move {
+        let line = receiver.recv().await?;
+        Some((Ok::<_, actix_web::Error>(web::Bytes::from(line)), receiver))
+    });
+    HttpResponse::Ok().content_type("application/x-ndjson").streaming(body)
+}
+
+// Report which thumbnails are already in the disk cache, without generating any
+pub async fn api_thumbnail_status(request: web::Json<ThumbnailStatusRequest>) -> impl Responder {
+    log::debug!("Thumbnail status request for {} paths", request.paths.len());
+
+    if request.paths.len() > MAX_THUMBNAIL_STATUS_PATHS {
+        log::warn!("Thumbnail status request too large: {} paths", request.paths.len());
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Too many paths, maximum is {}", MAX_THUMBNAIL_STATUS_PATHS)
+        }));
+    }
+
+    let mut status: BTreeMap<String, bool> = BTreeMap::new();
+    for path in &request.paths {
+        // Security check - prevent path traversal
+        if path.contains("..") {
+            log::warn!("Path traversal attempt blocked in thumbnail status: {}", path);
+            continue;
+        }
+        // Keyed like /thumbnail/{path}, so "photo.xmp" reports the thumbnail of "photo.NEF"
+        let file_path = resolve_image_path(path);
+        let cached = thumbnail_exists_in_cache(&thumbnail_cache_key(&file_path));
+        status.insert(file_path, cached);
+    }
+
+    log::debug!(
+        "Thumbnail status: {} of {} cached",
+        status.values().filter(|cached| **cached).count(),
+        status.len()
+    );
+    HttpResponse::Ok().json(status)
+}
+
+pub async fn api_prioritize_previews(request: web::Json<PrioritizePreviewsRequest>) -> impl Responder {
+    log::debug!("Preview priority request for {} paths", request.paths.len());
+
+    if request.paths.len() > MAX_PRIORITIZE_PREVIEW_PATHS {
+        log::warn!("Preview priority request too large: {} paths", request.paths.len());
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Too many paths, maximum is {}", MAX_PRIORITIZE_PREVIEW_PATHS)
+        }));
+    }
+
+    // Only existing images are queued; cached previews are skipped by the worker itself
+    let image_paths: Vec<String> = request
+        .paths
+        .iter()
+        .filter(|path| {
+            if path.contains("..") {
+                log::warn!("Path traversal attempt blocked in preview priority: {}", path);
+                return false;
+            }
+            true
+        })
+        .map(|path| resolve_image_path(path))
+        .filter(|path| Path::new(path).is_file())
+        .collect();
+    let queued = prioritize_previews(&image_paths);
+    log::debug!("Prioritized {} previews", queued);
+    // Without a worker pass underway nothing else would pick them up
+    if queued > 0 && crate::background::drain_priority_previews() {
+        log::debug!("Generating prioritized previews outside of a worker pass");
+    }
+    HttpResponse::Accepted().json(serde_json::json!({ "queued": queued }))
+}
+
+pub async fn api_metadata_batch(request: web::Json<BatchMetadataRequest>) -> impl Responder {
+    log::info!("Batch metadata request for {} paths", request.paths.len());
+
+    if request.paths.len() > MAX_BATCH_METADATA_PATHS {
+        log::warn!("Batch metadata request too large: {} paths", request.paths.len());
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Too many paths, maximum is {}", MAX_BATCH_METADATA_PATHS)
+        }));
+    }
+
+    // Requested image paths, with sidecar paths mapped to their image
+    let mut metadata: BTreeMap<String, Vec<MetadataEntry>> = BTreeMap::new();
+    for path in &request.paths {
+        // Security check - prevent path traversal
+        if path.contains("..") {
+            log::warn!("Path traversal attempt blocked in batch metadata: {}", path);
+            continue;
+        }
+        metadata.insert(image_path_for_sidecar(path).to_string(), Vec::new());
+    }
+    if metadata.is_empty() {
+        return HttpResponse::Ok().json(metadata);
+    }
+
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+
+    // One query for all paths instead of a round-trip per path
+    let conditions: Vec<String> = (1..=metadata.len()).map(sidecar_path_condition).collect();
+    let mut stmt = match conn.prepare(&format!(
+        "SELECT COALESCE(file.image_path, file.path), key_value.key, key_value.value \
+         FROM key_value \
+         JOIN file ON key_value.file_id = file.id \
+         WHERE {} \
+         ORDER BY file.path, key_value.key",
+        conditions.join(" OR ")
+    )) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("SQL preparation error for batch metadata: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Prepare error: {}", e));
+        },
+    };
+
+    let requested: Vec<String> = metadata.keys().cloned().collect();
+    let rows = stmt.query_map(rusqlite::params_from_iter(requested.iter()), |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+    });
+    match rows {
+        Ok(mapped) => {
+            for row in mapped {
+                match row {
+                    Ok((db_path, key, value)) => {
+                        if let Some(entries) = metadata.get_mut(image_path_for_sidecar(&db_path)) {
+                            entries.push(MetadataEntry { key, value });
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Row processing error in batch metadata: {}", e);
+                        return HttpResponse::InternalServerError().body(format!("Row error: {}", e));
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Query execution error in batch metadata: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        },
+    }
+
+    HttpResponse::Ok().json(metadata)
+}
+
+// Metadata shown to users for a file: develop settings and empty or binary-looking values are left out
+fn display_metadata(conn: &Connection, file_id: i64) -> Vec<MetadataEntry> {
+    let mut metadata_stmt = match conn.prepare(
+        // Develop settings (crop/orientation numbers) are not meaningful to display
+        "SELECT key, value FROM key_value WHERE file_id = ?1 AND key NOT LIKE 'crs:%' AND key != 'tiff:Orientation' ORDER BY key"
+    ) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("Failed to prepare metadata query: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let metadata_rows = metadata_stmt.query_map(rusqlite::params![file_id], |row| {
+        Ok(MetadataEntry { key: row.get(0)?, value: row.get(1)? })
+    });
+
+    let mut all_metadata = Vec::new();
+    match metadata_rows {
+        Ok(mapped) => {
+            for row in mapped {
+                match row {
+                    Ok(entry) => {
+                        // Skip empty values and very long values that might be binary data
+                        if !entry.value.trim().is_empty() && entry.value.len() < 500 {
+                            all_metadata.push(entry);
+                        }
+                    },
+                    Err(e) => {
+                        log::warn!("Error reading metadata value for file_id {}: {}", file_id, e);
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Metadata query error for file_id {}: {}", file_id, e);
+        }
     }
+    all_metadata
 }
 
-pub async fn search_page(query: web::Query<IndexQuery>) -> HttpResponse {
+pub async fn search_page(req: HttpRequest, query: web::Query<IndexQuery>) -> HttpResponse {
     let search_term = query.search.as_deref().unwrap_or("");
     log::info!("Search page called with term: '{}'", search_term);
+    let include_excluded = match include_excluded_allowed(&req, query.include_excluded) {
+        Ok(include_excluded) => include_excluded,
+        Err(e) => return HttpResponse::Forbidden().body(e),
+    };
     
-    let (where_clause, parameters) = parse_search_query(search_term);
-    log::debug!("Generated SQL where clause: {}", where_clause);
+    let (mut where_clause, mut parameters) = parse_search_query(search_term, synonyms_of);
+    if let Err(e) = append_search_filters(&query, include_excluded, &mut where_clause, &mut parameters) {
+        log::warn!("Invalid search filter: {}", e);
+        return HttpResponse::BadRequest().body(e);
+    }
+    let order_by = match search_order_by(&query) {
+        Ok(order_by) => order_by,
+        Err(e) => {
+            log::warn!("Invalid search sort: {}", e);
+            return HttpResponse::BadRequest().body(e);
+        }
+    };
+    let page = SearchPage::new(query.page, query.per_page);
 
     let args = get_cli_args();
-    let conn = match Connection::open(&args.db_path) {
+    let conn = match open_read_only(&args.db_path) {
         Ok(c) => {
             log::debug!("Successfully opened database for search: {}", args.db_path);
             c
@@ -300,14 +2429,23 @@ pub async fn search_page(query: web::Query<IndexQuery>) -> HttpResponse {
             return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
         },
     };
+    let total = match count_search_files(&conn, &where_clause, &parameters) {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("Query execution error counting search results: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        }
+    };
+    append_page_filter(&mut where_clause, &mut parameters, order_by, page);
+    log::debug!("Generated SQL where clause: {}", where_clause);
 
     // First, get the matching file IDs
     let mut stmt = match conn.prepare(
-        &format!("SELECT DISTINCT file.id, file.path \
+        &format!("SELECT DISTINCT file.id, COALESCE(file.image_path, file.path), file.import_time \
          FROM key_value \
          JOIN file ON key_value.file_id = file.id \
          {} \
-         ORDER BY file.path ASC", where_clause)
+         ORDER BY {}", where_clause, order_by)
     ) {
         Ok(s) => s,
         Err(e) => {
@@ -329,8 +2467,8 @@ pub async fn search_page(query: web::Query<IndexQuery>) -> HttpResponse {
             for row in mapped {
                 match row {
                     Ok((file_id, file_path)) => {
-                        // Remove ".xmp" suffix if present
-                        let clean_path = file_path.strip_suffix(".xmp").unwrap_or(&file_path).to_string();
+                        // Remove the sidecar extension (".xmp") if present
+                        let clean_path = image_path_for_sidecar(&file_path).to_string();
                         file_results.push((file_id, clean_path));
                     },
                     Err(e) => {
@@ -346,52 +2484,24 @@ pub async fn search_page(query: web::Query<IndexQuery>) -> HttpResponse {
         },
     }
 
-    log::info!("Search page found {} unique files", file_results.len());
+    log::info!("Search page found {} unique files, showing {} on page {}", total, file_results.len(), page.page);
+    record_search_history(search_term, total).await;
 
     // Now get all metadata for each file
     let mut results_with_metadata = Vec::new();
     for (file_id, file_path) in file_results {
-        // Get all metadata values for this file
-        let mut metadata_stmt = match conn.prepare(
-            "SELECT value FROM key_value WHERE file_id = ?1 ORDER BY key"
-        ) {
-            Ok(s) => s,
-            Err(e) => {
-                log::error!("Failed to prepare metadata query: {}", e);
-                continue;
-            }
-        };
-
-        let metadata_rows = metadata_stmt.query_map(rusqlite::params![file_id], |row| {
-            let value: String = row.get(0)?;
-            Ok(value)
-        });
-
-        let mut all_metadata = Vec::new();
-        match metadata_rows {
-            Ok(mapped) => {
-                for row in mapped {
-                    match row {
-                        Ok(value) => {
-                            // Skip empty values and very long values that might be binary data
-                            if !value.trim().is_empty() && value.len() < 500 {
-                                all_metadata.push(value);
-                            }
-                        },
-                        Err(e) => {
-                            log::warn!("Error reading metadata value for file_id {}: {}", file_id, e);
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                log::error!("Metadata query error for file_id {}: {}", file_id, e);
-            }
-        }
+        // Get all displayable metadata values for this file
+        let all_metadata: Vec<String> = display_metadata(&conn, file_id)
+            .into_iter()
+            .map(|entry| entry.value)
+            .collect();
 
         results_with_metadata.push((file_path, all_metadata));
     }
 
+    // Parse once with the same parser the query builder used, for highlighting
+    let search_terms = with_synonym_terms(parse_search_terms(search_term));
+
     // Generate HTML efficiently
     let mut html_parts = Vec::new();
     
@@ -412,7 +2522,7 @@ pub async fn search_page(query: web::Query<IndexQuery>) -> HttpResponse {
         // Create highlighted metadata values
         let mut highlighted_metadata = Vec::new();
         for metadata_value in &all_metadata {
-            let highlighted_value = highlight_search_terms(metadata_value, search_term);
+            let highlighted_value = highlight_search_terms(metadata_value, &search_terms);
             highlighted_metadata.push(highlighted_value);
         }
         
@@ -443,15 +2553,158 @@ pub async fn search_page(query: web::Query<IndexQuery>) -> HttpResponse {
     }
 
     // HTML footer
-    html_parts.push(include_str!("../templates/search_footer.html").to_string());
+    // The modal plays these as video; extensions are restricted to ASCII alphanumerics by the CLI parser
+    let video_extensions = video_extensions()
+        .iter()
+        .map(|extension| format!("'{}'", extension))
+        .collect::<Vec<_>>()
+        .join(",");
+    // Links to the neighbouring pages keep the search and its filters
+    let page_count = page.page_count(total);
+    let page_link_html = |label: &str, target: usize| {
+        format!(r#"<a class="page-link" href="{}">{}</a>"#, html_escape(&page_link("/search", req.query_string(), target)), label)
+    };
+    let previous = if page.page > 1 { page_link_html("&laquo; Previous", (page.page - 1).min(page_count)) } else { String::new() };
+    let next = if page.page < page_count { page_link_html("Next &raquo;", page.page + 1) } else { String::new() };
+    let pagination_html = format!(
+        r#"<div class="pagination">{}<span class="page-info">Page {} of {} ({} files)</span>{}</div>"#,
+        previous, page.page, page_count, total, next
+    );
+    html_parts.push(
+        include_str!("../templates/search_footer.html")
+            .replace("<!-- pagination -->", &pagination_html)
+            .replace(
+                "const videoExts = ['mp4','avi','mov','wmv','flv','webm','mkv','m4v','3gp','ogv'];",
+                &format!("const videoExts = [{}];", video_extensions),
+            ),
+    );
 
     HttpResponse::Ok()
         .content_type("text/html; charset=utf-8")
         .body(html_parts.join(""))
 }
 
+// Server-rendered detail view (preview plus metadata table) for clients without JavaScript
+pub async fn detail_fragment(query: web::Query<DetailQuery>) -> HttpResponse {
+    log::info!("Detail request for: {}", query.path);
+
+    // Security check - prevent path traversal
+    if query.path.contains("..") {
+        log::warn!("Path traversal attempt blocked for detail: {}", query.path);
+        return HttpResponse::BadRequest().body("Invalid path: path traversal not allowed");
+    }
+
+    // Accept both the image path and its sidecar path
+    let file_path = image_path_for_sidecar(&query.path).to_string();
+
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+
+    let file_id: Option<i64> = match conn.query_row(
+        &format!("SELECT id FROM file WHERE {} ORDER BY path LIMIT 1", sidecar_path_condition(1)),
+        rusqlite::params![file_path],
+        |row| row.get(0),
+    ) {
+        Ok(id) => Some(id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => {
+            log::error!("Query execution error in detail: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        },
+    };
+    let Some(file_id) = file_id else {
+        log::debug!("Detail requested for unindexed file: {}", file_path);
+        return HttpResponse::NotFound().body("File not found in index");
+    };
+
+    let escaped_file_path = html_escape(&file_path);
+    let metadata_rows: Vec<String> = display_metadata(&conn, file_id)
+        .iter()
+        .map(|entry| format!(
+            "            <tr><th>{}</th><td>{}</td></tr>\n",
+            html_escape(&entry.key),
+            html_escape(&entry.value)
+        ))
+        .collect();
+
+    let html = format!(r#"<div class="detail" data-file-path="{}">
+    <div class="detail-preview">
+        <img class="detail-image" src="/image/{}" alt="{}" />
+    </div>
+    <div class="file-path">{}</div>
+    <table class="detail-metadata">
+        <tbody>
+{}        </tbody>
+    </table>
+</div>
+"#, urlencoding::encode(&file_path), urlencoding::encode(&file_path), escaped_file_path, escaped_file_path, metadata_rows.join(""));
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// Function to read the xmp:Rating stored for an image, for its thumbnail badge. None when the
+// image is not indexed or unrated; ratings of 0 and rejected (-1) draw no stars either.
+fn stored_rating(file_path: &str) -> rusqlite::Result<Option<u8>> {
+    let conn = Connection::open(&get_cli_args().db_path)?;
+    let value: Option<String> = conn
+        .query_row(
+            &format!(
+                "SELECT key_value.value FROM file JOIN key_value ON key_value.file_id = file.id \
+                 WHERE {} AND key_value.key = 'xmp:Rating' ORDER BY file.path LIMIT 1",
+                sidecar_path_condition(1)
+            ),
+            rusqlite::params![file_path],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .filter(|&rating| rating > 0)
+        .map(|rating| rating.min(i64::from(MAX_RATING_STARS)) as u8))
+}
+
+// Function to mark an image viewed for --preview-strategy viewed the first time its thumbnail is
+// requested, unless the server is --read-only. Written in the background so the thumbnail is not
+// held up; a failure is only logged and retried on the next request.
+fn record_view(file_path: &str) {
+    let args = get_cli_args();
+    if args.read_only || args.preview_strategy != PreviewStrategy::Viewed || !VIEWED_IMAGES.lock().unwrap().insert(file_path.to_string()) {
+        return;
+    }
+    let file_path = file_path.to_string();
+    tokio::task::spawn_blocking(move || {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let marked = Connection::open(&get_cli_args().db_path).and_then(|conn| mark_viewed(&conn, &file_path, now));
+        if let Err(e) = marked {
+            log::warn!("Failed to mark {} as viewed: {}", file_path, e);
+            VIEWED_IMAGES.lock().unwrap().remove(&file_path);
+        }
+    });
+}
+
+/// Awaits a blocking generation task for at most --generation-timeout-secs. None when the timeout
+/// passed; the abandoned task cannot be cancelled and keeps running, caching its result when done.
+async fn await_generation<T>(task: tokio::task::JoinHandle<T>) -> Option<Result<T, tokio::task::JoinError>> {
+    let timeout_secs = get_cli_args().generation_timeout_secs;
+    if timeout_secs == 0 {
+        return Some(task.await);
+    }
+    tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), task).await.ok()
+}
+
 // Add a new endpoint for fetching individual thumbnails
-pub async fn get_thumbnail(path: web::Path<String>) -> impl Responder {
+pub async fn get_thumbnail(path: web::Path<String>, query: web::Query<ThumbnailQuery>) -> impl Responder {
     with_user_activity(|| async move {
         let image_path = path.into_inner();
         log::debug!("Thumbnail request for: {}", image_path);
@@ -468,24 +2721,138 @@ pub async fn get_thumbnail(path: web::Path<String>) -> impl Responder {
             }));
         }
         
-        // Remove ".xmp" suffix if present
-        let file_path = clean_path.strip_suffix(".xmp").unwrap_or(&clean_path).to_string();
+        // Map a sidecar path to its image, also when named "photo.xmp" for "photo.NEF"
+        let file_path = resolve_image_path(&clean_path);
         log::trace!("Processing thumbnail for cleaned path: {}", file_path);
+
+        // Sidecars can outlive their images; report that distinctly instead of a failed generation
+        if !std::path::Path::new(&file_path).exists() {
+            log::debug!("Thumbnail source missing: {}", file_path);
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "status": "source_missing",
+                "thumbnail": null,
+                "file_path": clean_path
+            }));
+        }
+
+        record_view(&file_path);
+
+        let scale = match query.dpr {
+            Some(dpr) if !(dpr.is_finite() && dpr > 0.0) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": "dpr must be a positive number"
+                }));
+            }
+            Some(dpr) => thumbnail_scale_for_dpr(dpr),
+            None => 1,
+        };
+        let rating = match query.badge.as_deref() {
+            None => None,
+            Some("rating") => match stored_rating(&file_path) {
+                Ok(rating) => rating,
+                Err(e) => {
+                    log::error!("Failed to read the rating of {}: {}", file_path, e);
+                    return HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Query error: {}", e)
+                    }));
+                }
+            },
+            Some(other) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Unknown badge '{}', expected 'rating'", other)
+                }));
+            }
+        };
+        let prefer_exif = query.exif.unwrap_or(false);
+        let generate = move |file_path: &str| match rating {
+            Some(rating) => generate_badged_thumbnail(file_path, scale, prefer_exif, rating),
+            None => generate_scaled_thumbnail(file_path, scale, prefer_exif),
+        };
+
+        // In async mode, large uncached files are generated in the background and the
+        // client is told to poll again instead of holding the connection open
+        let async_mode = query.mode.as_deref() == Some("async");
+        if async_mode && !thumbnail_exists_in_cache(&scaled_thumbnail_cache_key(&file_path, scale)) {
+            let file_size = std::fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0);
+            if file_size > SYNC_THUMBNAIL_MAX_BYTES {
+                // A failed generation is reported once; the request after that queues it again
+                if THUMBNAILS_FAILED.lock().unwrap().remove(&file_path) {
+                    return HttpResponse::Ok().json(serde_json::json!({
+                        "status": "failed",
+                        "thumbnail": null,
+                        "file_path": clean_path
+                    }));
+                }
+                let newly_queued = THUMBNAILS_IN_PROGRESS.lock().unwrap().insert(file_path.clone());
+                if newly_queued {
+                    log::debug!("Queueing async thumbnail generation for: {}", file_path);
+                    let queued_path = file_path.clone();
+                    tokio::task::spawn_blocking(move || {
+                        // A panicking generation must not leave the path in progress forever
+                        let generated = std::panic::catch_unwind(|| generate(&queued_path)).ok().flatten();
+                        if generated.is_none() {
+                            log::warn!("Async thumbnail generation failed for: {}", queued_path);
+                            THUMBNAILS_FAILED.lock().unwrap().insert(queued_path.clone());
+                        }
+                        THUMBNAILS_IN_PROGRESS.lock().unwrap().remove(&queued_path);
+                    });
+                } else {
+                    log::trace!("Thumbnail generation already in progress for: {}", file_path);
+                }
+                return HttpResponse::Accepted()
+                    .append_header(("Retry-After", "1"))
+                    .json(serde_json::json!({
+                        "status": "generating",
+                        "thumbnail": null,
+                        "file_path": clean_path
+                    }));
+            }
+        }
         
         // Generate thumbnail in a blocking task
-        let thumbnail_result = tokio::task::spawn_blocking(move || {
-            generate_thumbnail(&file_path)
-        }).await;
+        let thumbnail_task = tokio::task::spawn_blocking(move || {
+            generate(&file_path)
+        });
+        let Some(thumbnail_result) = await_generation(thumbnail_task).await else {
+            log::warn!("Thumbnail generation timed out for: {}", clean_path);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Thumbnail generation timed out",
+                "file_path": clean_path
+            }));
+        };
         
         match thumbnail_result {
             Ok(Some(thumbnail_base64)) => {
                 log::debug!("Successfully generated thumbnail for: {}", clean_path);
                 HttpResponse::Ok().json(serde_json::json!({
+                    "content_type": thumbnail_content_type(&thumbnail_base64),
                     "thumbnail": thumbnail_base64,
                     "file_path": clean_path
                 }))
             }
             Ok(None) => {
+                let file_path = resolve_image_path(&clean_path);
+                if let Some((width, height)) = oversized_source(&file_path) {
+                    return HttpResponse::Ok().json(serde_json::json!({
+                        "status": "too_large",
+                        "thumbnail": null,
+                        "file_path": clean_path,
+                        "width": width,
+                        "height": height
+                    }));
+                }
+                // A generic icon keeps the grid readable for types that cannot be thumbnailed
+                let icon = FileTypeIcon::for_path(&file_path).filter(|_| get_cli_args().thumbnail_fallback_icons);
+                if let Some(icon) = icon {
+                    log::debug!("Serving the {} icon for: {}", icon.name(), clean_path);
+                    return HttpResponse::Ok().json(serde_json::json!({
+                        "status": "icon",
+                        "icon": icon.name(),
+                        "thumbnail": general_purpose::STANDARD.encode(icon.svg()),
+                        "content_type": "image/svg+xml",
+                        "file_path": clean_path
+                    }));
+                }
                 log::warn!("Could not generate thumbnail for: {}", clean_path);
                 HttpResponse::Ok().json(serde_json::json!({
                     "thumbnail": null,
@@ -503,7 +2870,28 @@ pub async fn get_thumbnail(path: web::Path<String>) -> impl Responder {
     }).await
 }
 
-pub async fn get_preview(path: web::Path<String>) -> impl Responder {
+/// Picks the first of the --preview-formats the Accept header lists without `q=0`; None means JPEG.
+/// Wildcards such as `image/*` are not taken as support, as browsers send them for any image.
+pub fn negotiate_preview_format(accept: Option<&str>) -> Option<PreviewFormat> {
+    let accepted: Vec<&str> = accept?
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let media_type = parts.next()?;
+            let refused = parts.any(|param| {
+                param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()).is_some_and(|q| q <= 0.0)
+            });
+            (!refused).then_some(media_type)
+        })
+        .collect();
+    get_cli_args()
+        .preview_formats
+        .iter()
+        .copied()
+        .find(|format| accepted.iter().any(|media_type| media_type.eq_ignore_ascii_case(format.mime_type())))
+}
+
+pub async fn get_preview(req: HttpRequest, path: web::Path<String>, query: web::Query<PreviewQuery>) -> impl Responder {
     with_user_activity(|| async move {
         let image_path = path.into_inner();
         log::info!("Image serve request for: {}", image_path);
@@ -532,30 +2920,63 @@ pub async fn get_preview(path: web::Path<String>) -> impl Responder {
             return HttpResponse::BadRequest().body("Path is not a file");
         }
 
+        let width = match query.w {
+            Some(0) => {
+                log::warn!("Invalid preview width 0 for: {}", clean_path);
+                return HttpResponse::BadRequest().body("Invalid 'w': 0");
+            }
+            Some(requested) => preview_width(requested),
+            None => None,
+        };
+
+        let accept = req.headers().get(actix_web::http::header::ACCEPT).and_then(|value| value.to_str().ok());
+        let format = negotiate_preview_format(accept);
         let image_path_for_closure = clean_path.clone();
         
-        // Generate preview in a blocking task
-        let preview_result = tokio::task::spawn_blocking(move || {
-            generate_preview(&image_path_for_closure)
-        }).await;
+        // Generate preview in a blocking task; other formats are encoded from the JPEG preview
+        let preview_task = tokio::task::spawn_blocking(move || match format {
+            Some(format) => generate_preview_variant(&image_path_for_closure, width, format),
+            None => match width {
+                Some(width) => generate_sized_preview(&image_path_for_closure, width),
+                None => generate_preview(&image_path_for_closure),
+            }
+            .and_then(|preview_base64| match general_purpose::STANDARD.decode(&preview_base64) {
+                Ok(jpeg_bytes) => Some(jpeg_bytes),
+                Err(e) => {
+                    log::error!("Failed to decode base64 preview for {}: {:?}", image_path_for_closure, e);
+                    None
+                }
+            }),
+        });
+        let Some(preview_result) = await_generation(preview_task).await else {
+            log::warn!("Preview generation timed out for: {}", clean_path);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Preview generation timed out",
+                "file_path": clean_path
+            }));
+        };
         
         match preview_result {
-            Ok(Some(preview_base64)) => {
+            Ok(Some(preview_bytes)) => {
                 log::debug!("Successfully generated preview for: {}", clean_path);
-                // Decode base64 to bytes before returning as image/jpeg
-                match general_purpose::STANDARD.decode(&preview_base64) {
-                    Ok(jpeg_bytes) => {
-                        HttpResponse::Ok()
-                            .content_type("image/jpeg")
-                            .body(jpeg_bytes)
-                    }
-                    Err(e) => {
-                        log::error!("Failed to decode base64 preview for {}: {:?}", clean_path, e);
-                        HttpResponse::InternalServerError().body("Failed to decode preview image")
-                    }
+                let mut response = HttpResponse::Ok();
+                response.content_type(cached_image_content_type(&preview_bytes));
+                if !get_cli_args().preview_formats.is_empty() {
+                    // The response depends on the Accept header, so caches must not mix the formats
+                    response.append_header((actix_web::http::header::VARY, "Accept"));
                 }
+                response.body(preview_bytes)
             }
             Ok(None) => {
+                if let Some((width, height)) = oversized_source(&clean_path) {
+                    return HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                        "status": "too_large",
+                        "error": "Image is too large to preview",
+                        "file_path": clean_path,
+                        "width": width,
+                        "height": height
+                    }));
+                }
                 log::warn!("Could not generate preview for: {}", clean_path);
                 HttpResponse::Ok().json(serde_json::json!({
                     "preview": null,
@@ -575,7 +2996,360 @@ pub async fn get_preview(path: web::Path<String>) -> impl Responder {
 }
 
 // Add this function near the other endpoints
-pub async fn serve_video(path: web::Path<String>) -> impl Responder {
+/// Finds the transcoded preview `<stem>_480p.mp4` of a video in the preview cache. The stem must
+/// match exactly, the `_480p.mp4` suffix in any case (e.g. "Clip_480P.MP4" for "Clip.MP4").
+pub fn find_transcoded_video(preview_cache_dir: &Path, stem: &std::ffi::OsStr) -> Option<std::path::PathBuf> {
+    const SUFFIX: &str = "_480p.mp4";
+    let mut transcoded_file_name = stem.to_os_string();
+    transcoded_file_name.push(SUFFIX);
+    let exact = preview_cache_dir.join(&transcoded_file_name);
+    if exact.is_file() {
+        return Some(exact);
+    }
+
+    let stem = stem.to_str()?;
+    std::fs::read_dir(preview_cache_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix(stem))
+                    .is_some_and(|suffix| suffix.eq_ignore_ascii_case(SUFFIX))
+        })
+}
+
+// Function to answer 403 to requests changing the database when running with --read-only
+fn reject_if_read_only() -> Option<HttpResponse> {
+    get_cli_args().read_only.then(|| {
+        HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Server is read-only"
+        }))
+    })
+}
+
+// Function to check the image paths of an album edit: traversal-checked, sidecar paths mapped
+// to their image, at most MAX_BATCH_METADATA_PATHS of them
+fn album_image_paths(paths: &[String]) -> Result<Vec<String>, String> {
+    if paths.len() > MAX_BATCH_METADATA_PATHS {
+        return Err(format!("Too many paths, maximum is {}", MAX_BATCH_METADATA_PATHS));
+    }
+    if let Some(path) = paths.iter().find(|path| path.contains("..")) {
+        log::warn!("Path traversal attempt blocked in album edit: {}", path);
+        return Err("Invalid path: path traversal not allowed".to_string());
+    }
+    Ok(paths.iter().map(|path| image_path_for_sidecar(path).to_string()).collect())
+}
+
+pub async fn api_list_albums() -> impl Responder {
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    match list_albums(&conn) {
+        Ok(albums) => HttpResponse::Ok().json(albums),
+        Err(e) => {
+            log::error!("Failed to list albums: {}", e);
+            HttpResponse::InternalServerError().body(format!("Query error: {}", e))
+        }
+    }
+}
+
+pub async fn api_create_album(request: web::Json<CreateAlbumRequest>) -> impl Responder {
+    if let Some(response) = reject_if_read_only() {
+        return response;
+    }
+    let name = request.name.trim();
+    if name.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Album name must not be empty" }));
+    }
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    match create_album(&conn, name) {
+        Ok(id) => {
+            log::info!("Created album {} ({})", name, id);
+            HttpResponse::Created().json(serde_json::json!({ "id": id, "name": name }))
+        }
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+            HttpResponse::Conflict().json(serde_json::json!({ "error": "An album with this name already exists" }))
+        }
+        Err(e) => {
+            log::error!("Failed to create album {}: {}", name, e);
+            HttpResponse::InternalServerError().body(format!("Insert error: {}", e))
+        }
+    }
+}
+
+pub async fn api_album(path: web::Path<i64>, query: web::Query<AlbumQuery>) -> impl Responder {
+    let album_id = path.into_inner();
+    let inline_thumbnails = match query.thumbnails.as_deref().map(str::trim).unwrap_or("") {
+        "" | "inline" => true,
+        "lazy" => false,
+        other => {
+            log::warn!("Invalid thumbnails mode: {}", other);
+            return HttpResponse::BadRequest().body(format!("Invalid 'thumbnails': {}", other));
+        }
+    };
+    let args = get_cli_args();
+    let conn = match Connection::open(&args.db_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Failed to open database {}: {}", args.db_path, e);
+            return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+        },
+    };
+    let name = match album_name(&conn, album_id) {
+        Ok(Some(name)) => name,
+        Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({ "error": "Album not found" })),
+        Err(e) => {
+            log::error!("Failed to read album {}: {}", album_id, e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        }
+    };
+    let files = match album_files(&conn, album_id, &args.exclude_tag, &args.exclude_path_glob) {
+        Ok(files) => files,
+        Err(e) => {
+            log::error!("Failed to read files of album {}: {}", album_id, e);
+            return HttpResponse::InternalServerError().body(format!("Query error: {}", e));
+        }
+    };
+    drop(conn);
+
+    let files: Vec<AlbumFile> = files
+        .into_iter()
+        .map(|(sidecar_path, source_present)| {
+            let file_path = image_path_for_sidecar(&sidecar_path).to_string();
+            let thumbnail_base64 = if inline_thumbnails && source_present { generate_thumbnail(&file_path) } else { None };
+            AlbumFile { file_path, thumbnail_base64, source_present }
+        })
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({ "id": album_id, "name": name, "files": files }))
+}
+
+pub async fn api_album_add(path: web::Path<i64>, request: web::Json<AlbumPathsRequest>) -> impl Responder {
+    with_user_activity(|| async move {
+        if let Some(response) = reject_if_read_only() {
+            return response;
+        }
+        let album_id = path.into_inner();
+        let image_paths = match album_image_paths(&request.paths) {
+            Ok(paths) => paths,
+            Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+        };
+        let args = get_cli_args();
+        let conn = match Connection::open(&args.db_path) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Failed to open database {}: {}", args.db_path, e);
+                return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+            },
+        };
+        match album_name(&conn, album_id) {
+            Ok(Some(_)) => {}
+            Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({ "error": "Album not found" })),
+            Err(e) => return HttpResponse::InternalServerError().body(format!("Query error: {}", e)),
+        }
+        match add_to_album(&conn, album_id, &image_paths) {
+            Ok((added, unknown)) => {
+                log::info!("Added {} files to album {}", added, album_id);
+                HttpResponse::Ok().json(serde_json::json!({ "added": added, "unknown": unknown }))
+            }
+            Err(e) => {
+                log::error!("Failed to add files to album {}: {}", album_id, e);
+                HttpResponse::InternalServerError().body(format!("Insert error: {}", e))
+            }
+        }
+    }).await
+}
+
+pub async fn api_album_remove(path: web::Path<i64>, request: web::Json<AlbumPathsRequest>) -> impl Responder {
+    with_user_activity(|| async move {
+        if let Some(response) = reject_if_read_only() {
+            return response;
+        }
+        let album_id = path.into_inner();
+        let image_paths = match album_image_paths(&request.paths) {
+            Ok(paths) => paths,
+            Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+        };
+        let args = get_cli_args();
+        let conn = match Connection::open(&args.db_path) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Failed to open database {}: {}", args.db_path, e);
+                return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+            },
+        };
+        match album_name(&conn, album_id) {
+            Ok(Some(_)) => {}
+            Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({ "error": "Album not found" })),
+            Err(e) => return HttpResponse::InternalServerError().body(format!("Query error: {}", e)),
+        }
+        match remove_from_album(&conn, album_id, &image_paths) {
+            Ok(removed) => {
+                log::info!("Removed {} files from album {}", removed, album_id);
+                HttpResponse::Ok().json(serde_json::json!({ "removed": removed }))
+            }
+            Err(e) => {
+                log::error!("Failed to remove files from album {}: {}", album_id, e);
+                HttpResponse::InternalServerError().body(format!("Delete error: {}", e))
+            }
+        }
+    }).await
+}
+
+// Function to check a path of a move: traversal-checked and within --scan-dir, so a moved
+// image stays in the part of the file system the index covers
+fn move_path_allowed(path: &str, scan_dir: &str) -> Result<(), String> {
+    if path.contains("..") {
+        log::warn!("Path traversal attempt blocked in move: {}", path);
+        return Err("Invalid path: path traversal not allowed".to_string());
+    }
+    if !Path::new(path).starts_with(scan_dir) {
+        log::warn!("Move outside of the scan directory blocked: {}", path);
+        return Err(format!("Path is outside of the scan directory: {}", path));
+    }
+    Ok(())
+}
+
+pub async fn api_move(request: web::Json<MoveRequest>) -> impl Responder {
+    with_user_activity(|| async move {
+        if let Some(response) = reject_if_read_only() {
+            return response;
+        }
+        let args = get_cli_args();
+        let from = image_path_for_sidecar(request.from.trim()).to_string();
+        let to = request.to.trim().to_string();
+        if let Err(e) = move_path_allowed(&from, &args.scan_dir).and_then(|_| move_path_allowed(&to, &args.scan_dir)) {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": e }));
+        }
+        if image_path_for_sidecar(&to) != to || from == to {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Destination must be a new image path"
+            }));
+        }
+        log::info!("Move requested from {} to {}", from, to);
+
+        let conn = match Connection::open(&args.db_path) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Failed to open database {}: {}", args.db_path, e);
+                return HttpResponse::InternalServerError().body(format!("DB open error: {}", e));
+            },
+        };
+        match move_image(&conn, &from, &to) {
+            Ok(moved) => HttpResponse::Ok().json(moved),
+            Err(MoveError::NotFound(e)) => HttpResponse::NotFound().json(serde_json::json!({ "error": e })),
+            Err(MoveError::Conflict(e)) => HttpResponse::Conflict().json(serde_json::json!({ "error": e })),
+            Err(e) => {
+                log::error!("Failed to move {} to {}: {}", from, to, e);
+                HttpResponse::InternalServerError().json(serde_json::json!({ "error": e.to_string() }))
+            }
+        }
+    }).await
+}
+
+// Size of the chunks a download archive is streamed in
+const ARCHIVE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns a tar archive of an original image together with its sidecar files, so the
+/// metadata travels with the image. Images without a sidecar are archived alone.
+// Function to check that an existing file is within --scan-dir, after resolving symbolic links,
+// so only the indexed part of the file system can be downloaded
+fn file_in_scan_dir(path: &Path, scan_dir: &str) -> bool {
+    match (std::fs::canonicalize(path), std::fs::canonicalize(scan_dir)) {
+        (Ok(path), Ok(scan_dir)) => path.starts_with(scan_dir),
+        _ => false,
+    }
+}
+
+pub async fn download_with_sidecar(query: web::Query<DownloadQuery>) -> impl Responder {
+    with_user_activity(|| async move {
+        let clean_path = query.path.clone();
+        log::info!("Download with sidecar request for: {}", clean_path);
+
+        // Security check - prevent path traversal
+        if clean_path.contains("..") {
+            log::warn!("Path traversal attempt blocked for download: {}", clean_path);
+            return HttpResponse::BadRequest().body("Invalid path: path traversal not allowed");
+        }
+
+        let image_path = std::path::PathBuf::from(resolve_image_path(&clean_path));
+        if !image_path.is_file() {
+            log::warn!("Download source not found: {}", image_path.display());
+            return HttpResponse::NotFound().body("Image file not found");
+        }
+        // Answered like a missing file, so the response does not tell which files exist elsewhere
+        if !file_in_scan_dir(&image_path, &get_cli_args().scan_dir) {
+            log::warn!("Download outside of the scan directory blocked: {}", image_path.display());
+            return HttpResponse::NotFound().body("Image file not found");
+        }
+
+        let archive_name = format!(
+            "{}.tar",
+            image_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+        );
+        // Only the files are opened here; their contents are read while the archive streams
+        let entries = web::block(move || -> std::io::Result<Vec<ArchiveEntry<std::fs::File>>> {
+            let mut entries = vec![ArchiveEntry::open(&image_path)?];
+            for sidecar in sidecar_files_for_image(&image_path) {
+                entries.push(ArchiveEntry::open(&sidecar)?);
+            }
+            log::debug!("Archiving {} files for {}", entries.len(), image_path.display());
+            Ok(entries)
+        })
+        .await;
+
+        let entries = match entries {
+            Ok(Ok(entries)) => entries,
+            Ok(Err(e)) => {
+                log::error!("Failed to read files for download of {}: {}", clean_path, e);
+                return HttpResponse::InternalServerError().body("Failed to read files");
+            }
+            Err(e) => {
+                log::error!("Download task failed for {}: {:?}", clean_path, e);
+                return HttpResponse::InternalServerError().body("Failed to build archive");
+            }
+        };
+
+        let archive_size = tar_size(&entries);
+        let (sender, receiver) = tokio::sync::mpsc::channel::<Vec<u8>>(4);
+        tokio::task::spawn_blocking(move || {
+            // A closed channel means the client went away, which stops the archive early
+            if let Err(e) = write_tar(entries, ARCHIVE_CHUNK_SIZE, |chunk| sender.blocking_send(chunk).is_ok()) {
+                log::error!("Failed to stream archive for {}: {}", clean_path, e);
+            }
+        });
+        let body = futures::stream::unfold(receiver, |mut receiver| async move {
+            let chunk = receiver.recv().await?;
+            Some((Ok::<_, actix_web::Error>(web::Bytes::from(chunk)), receiver))
+        });
+
+        HttpResponse::Ok()
+            .content_type("application/x-tar")
+            .append_header((
+                "Content-Disposition",
+                format!("attachment; filename*=UTF-8''{}", urlencoding::encode(&archive_name)),
+            ))
+            .no_chunking(archive_size)
+            .streaming(body)
+    }).await
+}
+
+pub async fn serve_video(req: HttpRequest, path: web::Path<String>) -> impl Responder {
     with_user_activity(|| async move {
         let video_path = path.into_inner();
         log::info!("Video preview request for: {}", video_path);
@@ -599,36 +3373,34 @@ pub async fn serve_video(path: web::Path<String>) -> impl Responder {
         let stem = orig_path.file_stem();
         let ext = orig_path.extension();
 
-        let transcoded_file_path = if let (Some(stem), Some(_ext)) = (stem, ext) {
-            let mut transcoded_file_name = stem.to_os_string();
-            transcoded_file_name.push("_480p.mp4");
-            preview_cache_dir.join(transcoded_file_name)
-        } else {
+        let Some(stem) = stem.filter(|_| ext.is_some()) else {
             log::warn!("Could not construct _480p filename for: {}", clean_path);
             return HttpResponse::NotFound().body("Invalid video path");
         };
 
-        log::info!("Looking for transcoded video file in preview cache: {}", transcoded_file_path.display());
+        log::info!("Looking for {}_480p.mp4 in preview cache: {}", stem.to_string_lossy(), preview_cache_dir.display());
 
-        if !transcoded_file_path.exists() {
-            log::warn!("Transcoded video file not found: {}", transcoded_file_path.display());
+        let Some(transcoded_file_path) = find_transcoded_video(preview_cache_dir, stem) else {
+            log::warn!("Transcoded video file not found for: {}", clean_path);
             return HttpResponse::NotFound().body("Transcoded video file not found");
-        }
+        };
 
-        match std::fs::File::open(&transcoded_file_path) {
-            Ok(mut file) => {
-                let mut buf = Vec::new();
-                if std::io::Read::read_to_end(&mut file, &mut buf).is_ok() {
-                    return HttpResponse::Ok()
-                        .content_type("video/mp4")
-                        .append_header(("Cache-Control", "public, max-age=3600"))
-                        .body(buf);
-                }
+        // NamedFile streams the file in chunks and answers Range and conditional requests itself
+        match actix_files::NamedFile::open_async(&transcoded_file_path).await {
+            Ok(file) => {
+                let mut response = file
+                    .set_content_type("video/mp4".parse().expect("valid MIME type"))
+                    .into_response(&req);
+                response.headers_mut().insert(
+                    actix_web::http::header::CACHE_CONTROL,
+                    actix_web::http::header::HeaderValue::from_static("public, max-age=3600"),
+                );
+                response
             }
             Err(e) => {
                 log::error!("Failed to open transcoded video file: {}", e);
+                HttpResponse::InternalServerError().body("Failed to read transcoded video")
             }
         }
-        HttpResponse::InternalServerError().body("Failed to read transcoded video")
     }).await
 }
\ No newline at end of file
@@ -1,16 +1,25 @@
-use quick_xml::escape::unescape;
-use quick_xml::events::Event;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
 use rayon::prelude::*;
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 use xxhash_rust::xxh3::xxh3_64;
 
 use crate::cli::get_cli_args;
+use crate::failures::FailureStage;
+use crate::processing::develop::DEVELOP_KEYS;
+
+/// Keys of the GPS position, as written by digiKam, Lightroom and exiftool into XMP sidecars
+pub const GPS_LATITUDE_KEY: &str = "exif:GPSLatitude";
+pub const GPS_LONGITUDE_KEY: &str = "exif:GPSLongitude";
 
 /// Scans the given directory for XMP sidecar files and imports their metadata into the SQLite database.
 pub fn scan_and_import_sidecars() -> Result<()> {
@@ -24,38 +33,16 @@ pub fn scan_and_import_sidecars() -> Result<()> {
     log::debug!("Successfully opened database connection");
 
     {
-        let conn = conn.lock().unwrap();
-        log::debug!("Creating database tables if they don't exist");
-        
-        // Table file contains all sidecar files with their path and hash
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS file (
-                id INTEGER PRIMARY KEY,
-                path TEXT NOT NULL,
-                hash BIGINT NOT NULL,
-                UNIQUE(path, hash)
-            )",
-            [],
-        )?;
-        log::trace!("File table created/verified");
-        
-        // Table key_value contains all key-value pairs extracted from the XMP files
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS key_value (
-                id INTEGER PRIMARY KEY,
-                file_id INTEGER NOT NULL,
-                key TEXT NOT NULL,
-                value TEXT NOT NULL,
-                FOREIGN KEY(file_id) REFERENCES file(id)
-            )",
-            [],
-        )?;
-        log::trace!("Key_value table created/verified");
-    }
-
-    log::info!("Scanning directory for XMP files: {}", scan_dir);
+        let mut conn = conn.lock().unwrap();
+        log::debug!("Applying database migrations");
+        crate::db::run_migrations(&mut conn)?;
+        crate::db::set_fts_search(crate::db::ensure_fts_index(&conn)?);
+    }
+
+    let extensions = sidecar_extensions();
+    log::info!("Scanning directory for sidecar files ({}): {}", extensions.join(", "), scan_dir);
     
-    // Collect all XMP file paths first
+    // Collect all sidecar file paths first
     let xmp_files: Vec<_> = WalkDir::new(&scan_dir)
         .into_iter()
         .filter_map(|e| {
@@ -69,36 +56,66 @@ pub fn scan_and_import_sidecars() -> Result<()> {
         })
         .filter(|entry| {
             let path = entry.path();
-            let is_xmp = path.is_file()
+            let is_sidecar = path.is_file()
                 && path
                     .extension()
-                    .map(|ext| ext.eq_ignore_ascii_case("xmp"))
+                    .map(|ext| extensions.iter().any(|wanted| ext.eq_ignore_ascii_case(wanted)))
                     .unwrap_or(false);
             
-            if is_xmp {
-                log::trace!("Found XMP file: {}", path.display());
+            if is_sidecar {
+                log::trace!("Found sidecar file: {}", path.display());
             }
-            is_xmp
+            is_sidecar
         })
         .map(|entry| entry.path().to_owned())
         .collect();
 
-    log::info!("Found {} XMP files to process", xmp_files.len());
+    log::info!("Found {} sidecar files to process", xmp_files.len());
+
+    if args.use_exiftool {
+        import_with_exiftool(&conn.lock().unwrap(), &scan_dir, &xmp_files);
+    }
 
     if xmp_files.is_empty() {
-        log::warn!("No XMP files found in directory: {}", scan_dir);
-        return Ok(());
+        log::warn!("No sidecar files found in directory: {}", scan_dir);
+        return remove_deleted_sidecars(&conn.lock().unwrap(), &scan_dir);
     }
 
-    let processed_count = Arc::new(Mutex::new(0));
+    let processed_count = AtomicUsize::new(0);
+    // Milliseconds into the scan of the last progress line; threads race for the next one with a
+    // compare-exchange, so the parallel loop never waits on a lock for it
+    let last_progress_ms = AtomicU64::new(0);
+    let progress_interval_ms = args.scan_progress_interval_secs.saturating_mul(1000);
+    let total = xmp_files.len();
+    let started = Instant::now();
     let error_count = Arc::new(Mutex::new(0));
+    let partial_count = Arc::new(Mutex::new(0));
+    let skip_malformed = args.malformed_xmp == crate::cli::MalformedXmp::Skip;
 
     // Process each XMP file in parallel
     xmp_files.par_iter().for_each(|path| {
         if let Some(path_str) = path.to_str() {
-            log::debug!("Processing XMP file: {}", path_str);
+            log::debug!("Processing sidecar file: {}", path_str);
 
-            match extract_key_value(path_str) {
+            let parsed = match extract_sidecar_key_value(path_str) {
+                Ok(kv) => Some(kv),
+                Err(SidecarError::Malformed { position, message, kv }) => {
+                    *partial_count.lock().unwrap() += 1;
+                    if skip_malformed {
+                        log::warn!("Skipping partially parsed sidecar {} (malformed XML at byte {}: {})", path_str, position, message);
+                        record_scan_failure(&conn.lock().unwrap(), path_str, &format!("Malformed XML at byte {}: {}", position, message));
+                        return;
+                    }
+                    log::warn!("Importing {} key-value pairs of partially parsed sidecar {} (malformed XML at byte {})", kv.len(), path_str, position);
+                    Some(kv)
+                }
+                Err(e) => {
+                    log::warn!("Failed to extract key-value pairs from {}: {}", path_str, e);
+                    record_scan_failure(&conn.lock().unwrap(), path_str, &e.to_string());
+                    None
+                }
+            };
+            match parsed {
                 Some(kv) => {
                     log::trace!("Extracted {} key-value pairs from {}", kv.len(), path_str);
 
@@ -128,6 +145,8 @@ pub fn scan_and_import_sidecars() -> Result<()> {
                                                                     if old_hash == hash {
                                                                         // Already up to date, skip
                                                                         log::trace!("File {} is up to date (hash {})", path_str, hash);
+                                                                        // The image may have changed without its sidecar
+                                                                        update_source_info(conn, file_id, path_str);
                                                                         return;
                                                                     } else {
                                                                         log::info!("File {} has changed, updating (old hash: {}, new hash: {})", path_str, old_hash, hash);
@@ -137,6 +156,7 @@ pub fn scan_and_import_sidecars() -> Result<()> {
                                                                             params![hash, file_id],
                                                                         ) {
                                                                             log::error!("Failed to update hash for {}: {}", path_str, e);
+                                                                            record_scan_failure(conn, path_str, &format!("Failed to update hash: {}", e));
                                                                             let mut error_count = error_count.lock().unwrap();
                                                                             *error_count += 1;
                                                                             return;
@@ -145,23 +165,36 @@ pub fn scan_and_import_sidecars() -> Result<()> {
                                                                         // Delete all old key-values
                                                                         if let Err(e) = conn.execute("DELETE FROM key_value WHERE file_id = ?1", params![file_id]) {
                                                                             log::error!("Failed to delete old key-values for {}: {}", path_str, e);
+                                                                            record_scan_failure(conn, path_str, &format!("Failed to delete old key-values: {}", e));
                                                                             let mut error_count = error_count.lock().unwrap();
                                                                             *error_count += 1;
                                                                             return;
                                                                         }
 
                                                                         insert_key_values(conn, file_id, &kv);
+                                                                        update_source_info(conn, file_id, path_str);
+                                                                        clear_scan_failure(conn, path_str);
                                                                         log::info!("Updated file: {} [{}]", path_str, hash);
                                                                     }
                                                                 }
                                                                 Ok(None) => {
+                                                                    if let Some(moved) = find_moved_file(conn, hash, path_str) {
+                                                                        if let Err(e) = move_file_row(conn, &moved, path_str) {
+                                                                            log::error!("Failed to move row of {} to {}: {}", moved.path, path_str, e);
+                                                                            record_scan_failure(conn, path_str, &format!("Failed to move file: {}", e));
+                                                                            let mut error_count = error_count.lock().unwrap();
+                                                                            *error_count += 1;
+                                                                        }
+                                                                        return;
+                                                                    }
                                                                     log::info!("New file detected: {}", path_str);
                                                                     // Insert new row into table file
                                                                     if let Err(e) = conn.execute(
-                                                                        "INSERT INTO file (path, hash) VALUES (?1, ?2)",
+                                                                        "INSERT INTO file (path, hash, import_time) VALUES (?1, ?2, CAST(strftime('%s', 'now') AS INTEGER))",
                                                                         params![path_str, hash],
                                                                     ) {
                                                                         log::error!("Failed to insert new file {}: {}", path_str, e);
+                                                                        record_scan_failure(conn, path_str, &format!("Failed to insert file: {}", e));
                                                                         let mut error_count = error_count.lock().unwrap();
                                                                         *error_count += 1;
                                                                         return;
@@ -169,6 +202,8 @@ pub fn scan_and_import_sidecars() -> Result<()> {
                                                                     let file_id: i64 = conn.last_insert_rowid();
 
                                                                     insert_key_values(conn, file_id, &kv);
+                                                                    update_source_info(conn, file_id, path_str);
+                                                                    clear_scan_failure(conn, path_str);
                                                                     log::info!("Inserted file: {} [{}]", path_str, hash);
                                                                 }
                                                                 Err(e) => {
@@ -201,6 +236,7 @@ pub fn scan_and_import_sidecars() -> Result<()> {
                                 }
                                 Err(e) => {
                                     log::error!("Failed to read file {}: {}", path_str, e);
+                                    record_scan_failure(&conn.lock().unwrap(), path_str, &format!("Failed to read file: {}", e));
                                     let mut error_count = error_count.lock().unwrap();
                                     *error_count += 1;
                                 }
@@ -208,25 +244,28 @@ pub fn scan_and_import_sidecars() -> Result<()> {
                         }
                         Err(e) => {
                             log::error!("Failed to open file {}: {}", path_str, e);
+                            record_scan_failure(&conn.lock().unwrap(), path_str, &format!("Failed to open file: {}", e));
                             let mut error_count = error_count.lock().unwrap();
                             *error_count += 1;
                         }
                     }
                 }
                 None => {
-                    log::warn!("Failed to extract key-value pairs from {}", path_str);
                     let mut error_count = error_count.lock().unwrap();
                     *error_count += 1;
                 }
             }
 
-            // Update processed count
-            let mut processed_count = processed_count.lock().unwrap();
-            *processed_count += 1;
-
-            // Log progress every 100 files
-            if *processed_count % 100 == 0 {
-                log::info!("Processed {} files so far", *processed_count);
+            let processed = processed_count.fetch_add(1, Ordering::Relaxed) + 1;
+            if progress_interval_ms > 0 {
+                let elapsed = started.elapsed();
+                let elapsed_ms = elapsed.as_millis() as u64;
+                let last_ms = last_progress_ms.load(Ordering::Relaxed);
+                if elapsed_ms >= last_ms + progress_interval_ms
+                    && last_progress_ms.compare_exchange(last_ms, elapsed_ms, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+                {
+                    log::info!("{}", scan_progress(processed, total, elapsed));
+                }
             }
         } else {
             log::error!("Invalid UTF-8 in file path: {:?}", path);
@@ -235,20 +274,265 @@ pub fn scan_and_import_sidecars() -> Result<()> {
         }
     });
     
-    let final_processed = *processed_count.lock().unwrap();
+    let final_processed = processed_count.load(Ordering::Relaxed);
     let final_errors = *error_count.lock().unwrap();
+    let final_partial = *partial_count.lock().unwrap();
     
-    log::info!("Sidecar scan completed - Processed: {} files, Errors: {} files", final_processed, final_errors);
+    log::info!(
+        "Sidecar scan completed - Processed: {} files, Errors: {} files, Partially parsed: {} files",
+        final_processed, final_errors, final_partial
+    );
+    if final_partial > 0 {
+        log::warn!(
+            "{} sidecars have malformed XML and were {}; search the log for \"Malformed XML\" to find them",
+            final_partial,
+            if skip_malformed { "skipped" } else { "imported partially" }
+        );
+    }
     
     if final_errors > 0 {
         log::warn!("Scan completed with {} errors", final_errors);
     } else {
         log::info!("Scan completed successfully with no errors");
     }
-    
+
+    remove_deleted_sidecars(&conn.lock().unwrap(), &scan_dir)?;
     Ok(())
 }
 
+/// Formats a progress line of the sidecar import, e.g. "Processed 1200 of 5000 sidecars (24%),
+/// 1m 30s elapsed, about 4m 45s left". The estimate assumes the remaining sidecars take as long
+/// on average as the ones so far, so it is rough while few are done.
+pub fn scan_progress(processed: usize, total: usize, elapsed: Duration) -> String {
+    let percent = (processed * 100).checked_div(total).unwrap_or(100);
+    let remaining = total.saturating_sub(processed);
+    let estimate = if processed == 0 {
+        "estimating time left".to_string()
+    } else {
+        let left = elapsed.mul_f64(remaining as f64 / processed as f64);
+        format!("about {} left", format_duration(left))
+    };
+    format!(
+        "Processed {} of {} sidecars ({}%), {} elapsed, {}",
+        processed, total, percent, format_duration(elapsed), estimate
+    )
+}
+
+// Function to format a duration for progress lines: "45s", "4m 05s" or "2h 10m"
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+// Function to record a sidecar that could not be imported for /api/failures
+fn record_scan_failure(conn: &Connection, path: &str, error: &str) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    if let Err(e) = crate::failures::record_failure(conn, path, FailureStage::Scan, error, now) {
+        log::warn!("Failed to record scan failure of {}: {}", path, e);
+    }
+}
+
+// Function to drop the scan failure of a sidecar that was imported now
+fn clear_scan_failure(conn: &Connection, path: &str) {
+    if let Err(e) = crate::failures::clear_failure(conn, path, FailureStage::Scan) {
+        log::warn!("Failed to clear scan failure of {}: {}", path, e);
+    }
+}
+
+/// Removes the index rows whose file, the sidecar or for exiftool rows the image itself, no
+/// longer exists, together with their key-values and album entries. Every scan does this for the
+/// files under its `scan_dir`; this also drops the rows of other roots. Returns the number of rows
+/// removed.
+pub fn prune_missing_files() -> Result<usize> {
+    let args = get_cli_args();
+    let mut conn = Connection::open(&args.db_path)?;
+    crate::db::run_migrations(&mut conn)?;
+    let removed = remove_missing_files(&conn, None)?;
+    if removed == 0 {
+        log::info!("Prune found no missing files in the index");
+    }
+    Ok(removed)
+}
+
+// Drops the rows of the files under `scan_dir` that no longer exist at the end of a scan. Skipped
+// when the directory itself is gone, e.g. an unmounted drive, which would empty the index.
+fn remove_deleted_sidecars(conn: &Connection, scan_dir: &str) -> Result<()> {
+    if !Path::new(scan_dir).is_dir() {
+        log::warn!("Scan directory {} is missing, keeping the rows of its files", scan_dir);
+        return Ok(());
+    }
+    remove_missing_files(conn, Some(Path::new(scan_dir)))?;
+    Ok(())
+}
+
+// Deletes the rows of missing files, only those below `root` when given, with their key-values
+// and album entries
+fn remove_missing_files(conn: &Connection, root: Option<&Path>) -> Result<usize> {
+    let rows: Vec<(i64, String)> = conn
+        .prepare("SELECT id, path FROM file")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+    let missing: Vec<(i64, String)> = rows
+        .into_iter()
+        .filter(|(_, path)| root.is_none_or(|root| Path::new(path).starts_with(root)))
+        .filter(|(_, path)| !Path::new(path).exists())
+        .collect();
+    if missing.is_empty() {
+        return Ok(0);
+    }
+
+    let tx = crate::db::write_transaction(conn)?;
+    for (file_id, path) in &missing {
+        log::info!("Removing {} from the index, it no longer exists", path);
+        tx.execute("DELETE FROM key_value WHERE file_id = ?1", params![file_id])?;
+        tx.execute("DELETE FROM album_file WHERE file_id = ?1", params![file_id])?;
+        tx.execute("DELETE FROM file WHERE id = ?1", params![file_id])?;
+    }
+    tx.commit()?;
+    log::info!("Removed {} missing files from the index", missing.len());
+    Ok(missing.len())
+}
+
+/// Indexes the media files under `scan_dir` that have no sidecar from their embedded metadata,
+/// read with exiftool (`--use-exiftool`). Their rows are keyed by the image path itself. An image
+/// is re-read when its size or modification time changes; one that got a sidecar since is indexed
+/// from the sidecar instead. Without a working exiftool nothing is imported.
+fn import_with_exiftool(conn: &std::sync::MutexGuard<'_, Connection>, scan_dir: &str, sidecar_files: &[PathBuf]) {
+    let exiftool_path = &get_cli_args().exiftool_path;
+    match crate::exiftool::exiftool_version(exiftool_path) {
+        Ok(version) => log::info!("Importing files without sidecars with exiftool {}", version),
+        Err(e) => {
+            log::warn!("exiftool is not available, indexing sidecars only: {}", e);
+            return;
+        }
+    }
+
+    // Image path -> sidecar path
+    let covered: HashMap<String, &str> = sidecar_files
+        .iter()
+        .filter_map(|path| path.to_str())
+        .map(|path| (resolve_image_path(path), path))
+        .collect();
+    move_exiftool_rows_to_sidecar(conn, &covered);
+
+    // Size and modification time stand in for a content hash, so unchanged images are not read
+    let mut changed: Vec<(PathBuf, i64)> = Vec::new();
+    for entry in WalkDir::new(scan_dir).into_iter().flatten() {
+        let path = entry.path();
+        let Some(path_str) = path.to_str() else {
+            continue;
+        };
+        if !entry.file_type().is_file() || !crate::exiftool::is_media_file(path) || covered.contains_key(path_str) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_nanos())
+            .unwrap_or(0);
+        let hash = xxh3_64(format!("{}:{}", metadata.len(), modified).as_bytes()) as i64;
+        let stored: Option<(i64, i64)> = conn
+            .query_row("SELECT id, hash FROM file WHERE path = ?1", params![path_str], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+            .unwrap_or(None);
+        match stored {
+            Some((file_id, old_hash)) if old_hash == hash => update_source_info(conn, file_id, path_str),
+            _ => changed.push((path.to_path_buf(), hash)),
+        }
+    }
+    log::info!("Found {} new or changed files without sidecars", changed.len());
+    if changed.is_empty() {
+        return;
+    }
+
+    let hashes: HashMap<PathBuf, i64> = changed.iter().cloned().collect();
+    let files: Vec<PathBuf> = changed.into_iter().map(|(path, _)| path).collect();
+    let mut imported = 0;
+    for (path, kv) in crate::exiftool::read_metadata(exiftool_path, &files) {
+        let (Some(path_str), Some(hash)) = (path.to_str(), hashes.get(&path)) else {
+            log::warn!("Unexpected file in exiftool output: {}", path.display());
+            continue;
+        };
+        let stored: Option<i64> = conn
+            .query_row("SELECT id FROM file WHERE path = ?1", params![path_str], |row| row.get(0))
+            .optional()
+            .unwrap_or(None);
+        let file_id = match stored {
+            Some(file_id) => {
+                let result = conn
+                    .execute("UPDATE file SET hash = ?1 WHERE id = ?2", params![hash, file_id])
+                    .and_then(|_| conn.execute("DELETE FROM key_value WHERE file_id = ?1", params![file_id]));
+                if let Err(e) = result {
+                    log::error!("Failed to update {}: {}", path_str, e);
+                    continue;
+                }
+                file_id
+            }
+            None => {
+                if let Err(e) = conn.execute(
+                    "INSERT INTO file (path, hash, import_time) VALUES (?1, ?2, CAST(strftime('%s', 'now') AS INTEGER))",
+                    params![path_str, hash],
+                ) {
+                    log::error!("Failed to insert new file {}: {}", path_str, e);
+                    continue;
+                }
+                conn.last_insert_rowid()
+            }
+        };
+        insert_key_values(conn, file_id, &kv);
+        update_source_info(conn, file_id, path_str);
+        log::debug!("Imported {} with exiftool ({} keys)", path_str, kv.len());
+        imported += 1;
+    }
+    log::info!("Imported {} of {} files with exiftool", imported, files.len());
+}
+
+// Moves the exiftool rows of images that have a sidecar now over to the sidecar. The reset hash
+// makes the sidecar import refresh the row, so it keeps its id and album entries.
+fn move_exiftool_rows_to_sidecar(conn: &Connection, covered: &HashMap<String, &str>) {
+    let rows: Vec<(i64, String)> = match conn
+        .prepare("SELECT id, path FROM file")
+        .and_then(|mut stmt| stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect())
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            log::error!("Failed to list indexed files: {}", e);
+            return;
+        }
+    };
+    for (file_id, path) in rows {
+        // Sidecar rows map to a different image path; exiftool rows are keyed by the image itself
+        let Some(sidecar_path) = covered.get(path.as_str()).filter(|_| image_path_for_sidecar(&path) == path) else {
+            continue;
+        };
+        log::info!("{} has a sidecar now, indexing it from {}", path, sidecar_path);
+        if conn.execute("UPDATE file SET path = ?1, hash = 0 WHERE id = ?2", params![sidecar_path, file_id]).is_ok() {
+            continue;
+        }
+        // The sidecar is indexed already
+        let result = conn
+            .execute("DELETE FROM key_value WHERE file_id = ?1", params![file_id])
+            .and_then(|_| conn.execute("DELETE FROM album_file WHERE file_id = ?1", params![file_id]))
+            .and_then(|_| conn.execute("DELETE FROM file WHERE id = ?1", params![file_id]));
+        if let Err(e) = result {
+            log::error!("Failed to remove exiftool row of {}: {}", path, e);
+        }
+    }
+}
+
 fn insert_key_values(
     conn: &std::sync::MutexGuard<'_, Connection>,
     file_id: i64,
@@ -274,25 +558,576 @@ fn insert_key_values(
 
     let mut inserted_count = 1; // Count the xmp:ModifyDate we just inserted
     
-    // Insert the rest of the key-values
-    for (key, value) in kv {
-        if key.contains("digiKam:TagsList") || key == "dc:title/rdf:Alt" {
-            log::trace!("Inserting key: {} = {}", key, value);
+    // Insert the rest of the key-values allowed by --import-keys
+    for (key, value) in select_import_key_values(kv, &get_cli_args().import_keys) {
+        log::trace!("Inserting key: {} = {}", key, value);
+        if let Err(e) = conn.execute(
+            "INSERT INTO key_value (file_id, key, value) VALUES (?1, ?2, ?3)",
+            params![file_id, key, value],
+        ) {
+            log::error!("Failed to insert key-value {}='{}' for file_id {}: {}", key, value, file_id, e);
+        } else {
+            inserted_count += 1;
+        }
+    }
+    
+    // Store develop settings (crop/orientation) under normalized key names so previews can apply them
+    for develop_key in DEVELOP_KEYS {
+        let found = kv
+            .iter()
+            .find(|(k, _)| k.as_str() == *develop_key || k.ends_with(&format!(":{}", develop_key)) || k.ends_with(&format!("/{}", develop_key)));
+        if let Some((_, value)) = found {
+            log::trace!("Inserting develop setting: {} = {}", develop_key, value);
             if let Err(e) = conn.execute(
                 "INSERT INTO key_value (file_id, key, value) VALUES (?1, ?2, ?3)",
-                params![file_id, key, value],
+                params![file_id, develop_key, value],
             ) {
-                log::error!("Failed to insert key-value {}='{}' for file_id {}: {}", key, value, file_id, e);
+                log::error!("Failed to insert develop setting {}='{}' for file_id {}: {}", develop_key, value, file_id, e);
             } else {
                 inserted_count += 1;
             }
         }
     }
     
+    // Normalized capture time for date range queries; NULL when missing or malformed
+    let capture_time = capture_timestamp(kv);
+    log::trace!("Capture time for file_id {}: {:?}", file_id, capture_time);
+    if let Err(e) = conn.execute(
+        "UPDATE file SET capture_time = ?1 WHERE id = ?2",
+        params![capture_time, file_id],
+    ) {
+        log::error!("Failed to store capture time for file_id {}: {}", file_id, e);
+    }
+    
     log::debug!("Successfully inserted {} key-value pairs for file_id {}", inserted_count, file_id);
 }
 
-fn extract_key_value(path: &str) -> Option<HashMap<String, String>> {
+// Store whether the image a sidecar belongs to still exists, and its on-disk size (not
+// the size of the sidecar itself). Size is NULL when the image cannot be read.
+fn update_source_info(conn: &Connection, file_id: i64, sidecar_path: &str) {
+    let image_path = resolve_image_path(sidecar_path);
+    let image_path = image_path.as_str();
+    let size_bytes = match fs::metadata(image_path) {
+        Ok(metadata) => Some(metadata.len() as i64),
+        Err(e) => {
+            log::debug!("Could not stat image {}: {}", image_path, e);
+            None
+        }
+    };
+    let source_present = Path::new(image_path).exists();
+    if !source_present {
+        log::debug!("Sidecar {} has no image", sidecar_path);
+    }
+    // Only write when something changed, so unchanged files cost no writes on rescans
+    if let Err(e) = conn.execute(
+        "UPDATE file SET size_bytes = ?1, source_present = ?2, image_path = ?3 \
+         WHERE id = ?4 AND (size_bytes IS NOT ?1 OR source_present IS NOT ?2 OR image_path IS NOT ?3)",
+        params![size_bytes, source_present, image_path, file_id],
+    ) {
+        log::error!("Failed to store size for {}: {}", image_path, e);
+    }
+}
+
+// The row of a file that may have moved to a new sidecar path
+struct MovedRow {
+    id: i64,
+    path: String,
+    image_path: String,
+}
+
+// Finds the row a new sidecar moved from: one with the same sidecar hash and image size whose
+// sidecar no longer exists. The size tells apart identical sidecars of different images.
+fn find_moved_file(conn: &Connection, hash: i64, sidecar_path: &str) -> Option<MovedRow> {
+    let size_bytes = fs::metadata(resolve_image_path(sidecar_path)).ok().map(|metadata| metadata.len() as i64);
+    let rows = conn
+        .prepare("SELECT id, path, image_path FROM file WHERE hash = ?1 AND size_bytes IS ?2")
+        .and_then(|mut stmt| {
+            stmt.query_map(params![hash, size_bytes], |row| {
+                let path: String = row.get(1)?;
+                let image_path = row.get::<_, Option<String>>(2)?.unwrap_or_else(|| image_path_for_sidecar(&path).to_string());
+                Ok(MovedRow { id: row.get(0)?, path, image_path })
+            })?
+            .collect::<Result<Vec<_>>>()
+        });
+    match rows {
+        Ok(rows) => rows.into_iter().find(|row| !Path::new(&row.path).exists()),
+        Err(e) => {
+            log::error!("Failed to look up moved files for {}: {}", sidecar_path, e);
+            None
+        }
+    }
+}
+
+// Points a moved file's row at its new sidecar, keeping its key-values, and renames its cache
+// files so the thumbnails and previews are not generated again
+fn move_file_row(conn: &Connection, moved: &MovedRow, sidecar_path: &str) -> Result<()> {
+    conn.execute("UPDATE file SET path = ?1 WHERE id = ?2", params![sidecar_path, moved.id])?;
+    update_source_info(conn, moved.id, sidecar_path);
+    clear_scan_failure(conn, sidecar_path);
+    let image_path = resolve_image_path(sidecar_path);
+    let cache_files = if image_path == moved.image_path {
+        0
+    } else {
+        crate::processing::cache::rename_cached_files(&moved.image_path, &image_path)
+    };
+    log::info!("Moved file: {} -> {} ({} cache files renamed)", moved.path, sidecar_path, cache_files);
+    Ok(())
+}
+
+// Capture date keys in order of preference
+const CAPTURE_DATE_KEYS: &[&str] = &["exif:DateTimeOriginal", "xmp:CreateDate"];
+
+// Find the capture date of a sidecar and normalize it to a UTC epoch timestamp
+pub fn capture_timestamp(kv: &HashMap<String, String>) -> Option<i64> {
+    CAPTURE_DATE_KEYS.iter().find_map(|wanted| {
+        kv.iter()
+            .filter(|(key, _)| short_key(key) == *wanted)
+            .find_map(|(_, value)| {
+                let parsed = parse_capture_date(value);
+                if parsed.is_none() {
+                    log::warn!("Ignoring malformed capture date {} = '{}'", wanted, value);
+                }
+                parsed
+            })
+    })
+}
+
+/// Parses an XMP or EXIF date into seconds since the Unix epoch (UTC).
+///
+/// Accepts XMP dates ("2023-05-01T10:00:00+02:00", "...Z", fractional seconds and the
+/// truncated forms "2023-05-01T10:00", "2023-05-01", "2023-05", "2023") and EXIF dates
+/// ("2023:05:01 10:00:00"). Dates without a timezone are taken as UTC, since the camera's
+/// offset is unknown. Returns None for malformed dates.
+pub fn parse_capture_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (date, time) = match value.find(['T', ' ']) {
+        Some(pos) => (&value[..pos], Some(value[pos + 1..].trim())),
+        None => (value, None),
+    };
+
+    let mut date_parts = date.split(['-', ':']);
+    let year: i64 = parse_digits(date_parts.next()?, 4)?;
+    let month: u32 = date_parts.next().map(|m| parse_digits(m, 2)).unwrap_or(Some(1))?;
+    let day: u32 = date_parts.next().map(|d| parse_digits(d, 2)).unwrap_or(Some(1))?;
+    if date_parts.next().is_some() || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let (mut seconds, mut offset) = (0i64, 0i64);
+    if let Some(time) = time.filter(|t| !t.is_empty()) {
+        // Split off the timezone designator
+        let (clock, zone) = match time.find(['Z', '+', '-']) {
+            Some(pos) => (&time[..pos], &time[pos..]),
+            None => (time, ""),
+        };
+        let mut clock_parts = clock.split(':');
+        let hour: i64 = parse_digits(clock_parts.next()?, 2)?;
+        let minute: i64 = parse_digits(clock_parts.next()?, 2)?;
+        let second: i64 = match clock_parts.next() {
+            // Fractional seconds are dropped
+            Some(sec) => parse_digits(sec.split('.').next()?, 2)?,
+            None => 0,
+        };
+        if clock_parts.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        seconds = hour * 3600 + minute * 60 + second;
+
+        offset = match zone {
+            "" | "Z" => 0,
+            _ => {
+                let sign = if zone.starts_with('-') { -1 } else { 1 };
+                let zone = zone[1..].replace(':', "");
+                if zone.len() != 4 {
+                    return None;
+                }
+                let zone_hours: i64 = parse_digits(&zone[..2], 2)?;
+                let zone_minutes: i64 = parse_digits(&zone[2..], 2)?;
+                if zone_hours > 14 || zone_minutes > 59 {
+                    return None;
+                }
+                sign * (zone_hours * 3600 + zone_minutes * 60)
+            }
+        };
+    }
+
+    Some(days_from_civil(year, month, day) * 86400 + seconds - offset)
+}
+
+// Parse a fixed-width run of ASCII digits
+fn parse_digits<T: std::str::FromStr>(s: &str, width: usize) -> Option<T> {
+    if s.len() == width && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Strip the XML path leading up to the rdf:Description, so that both attribute style
+// (".../rdf:Description:tiff:Model") and element style (".../rdf:Description/exif:Model")
+// keys are stored under the property name ("tiff:Model", "exif:Model")
+pub fn short_key(key: &str) -> &str {
+    match key.rfind("rdf:Description") {
+        Some(pos) => {
+            let rest = &key[pos + "rdf:Description".len()..];
+            rest.strip_prefix(':').or_else(|| rest.strip_prefix('/')).unwrap_or(key)
+        }
+        None => key,
+    }
+}
+
+// RDF/XML bookkeeping attributes (namespace declarations, rdf:about, language qualifiers)
+// that are parsed like any other attribute but carry no metadata worth indexing
+fn is_markup_key(key: &str) -> bool {
+    key.starts_with("xmlns") || key.contains(":xmlns:") || key.ends_with("rdf:about") || key.ends_with("xml:lang")
+}
+
+// Select the parsed key-values to persist: keys whose short name contains one of the
+// configured import key substrings, or every key when "all" is configured.
+// xmp:ModifyDate and develop settings are stored separately and skipped here.
+// Sorted by key for deterministic inserts.
+pub fn select_import_key_values(kv: &HashMap<String, String>, import_keys: &[String]) -> Vec<(String, String)> {
+    let import_all = import_keys.iter().any(|wanted| wanted.trim().eq_ignore_ascii_case("all"));
+    let mut selected: Vec<(String, String)> = kv
+        .iter()
+        .map(|(key, value)| (short_key(key), value))
+        .filter(|(key, value)| !value.trim().is_empty() && !is_markup_key(key))
+        .filter(|(key, _)| *key != "xmp:ModifyDate" && !DEVELOP_KEYS.contains(key))
+        .filter(|(key, _)| {
+            import_all
+                || import_keys
+                    .iter()
+                    .map(|wanted| wanted.trim())
+                    .any(|wanted| !wanted.is_empty() && key.contains(wanted))
+        })
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+    selected.sort();
+    selected.dedup_by(|a, b| a.0 == b.0);
+    selected
+}
+
+// Store the attributes of an element keyed by its tag path. Repeated sibling elements
+// (e.g. several rdf:li) produce the same key, so their values are collected joined by
+// semicolon like TagsList does instead of overwriting each other.
+fn collect_attributes(kv: &mut HashMap<String, String>, element_path: &str, e: &BytesStart) {
+    for attr in e.attributes().flatten() {
+        let key = format!(
+            "{}:{}",
+            element_path,
+            String::from_utf8_lossy(attr.key.as_ref())
+        );
+        let value = attr.unescape_value().unwrap_or_default().to_string();
+        log::trace!("Found attribute: {} = {}", key, value);
+        match kv.get_mut(&key) {
+            Some(existing) => {
+                existing.push(';');
+                existing.push_str(&value);
+            }
+            None => {
+                kv.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Returns the configured sidecar file extensions (without dot), "xmp" by default.
+pub fn sidecar_extensions() -> Vec<String> {
+    match crate::cli::CLI_ARGS.get() {
+        Some(args) => args.sidecar_extensions.clone(),
+        None => vec!["xmp".to_string()],
+    }
+}
+
+/// Maps a sidecar path to the path of its image by stripping the sidecar extension,
+/// e.g. "photo.jpg.xmp" or "photo.jpg.json" to "photo.jpg". Other paths are returned as-is.
+pub fn image_path_for_sidecar(path: &str) -> &str {
+    for extension in sidecar_extensions() {
+        let suffix_start = path.len().saturating_sub(extension.len() + 1);
+        if let Some(suffix) = path.get(suffix_start..) {
+            if suffix_start > 0 && suffix.starts_with('.') && suffix[1..].eq_ignore_ascii_case(&extension) {
+                return &path[..suffix_start];
+            }
+        }
+    }
+    path
+}
+
+// RAW formats, preferred when a "photo.xmp" sidecar matches several images (RAW+JPEG shooting),
+// as that naming is used by RAW developers for the RAW file
+pub const RAW_EXTENSIONS: &[&str] = &["nef", "cr2", "cr3", "arw", "orf", "rw2", "raf", "dng", "pef", "srw"];
+
+/// Resolves the image a sidecar belongs to. "photo.jpg.xmp" belongs to "photo.jpg"; when that
+/// does not exist, the sidecar may follow the "photo.xmp" naming of Lightroom, darktable and
+/// other RAW developers, and the image is looked up by name in the same directory ("photo.NEF",
+/// "photo.jpg"), preferring RAW files. Without a match the stripped path is returned, as for an
+/// image that was deleted. Paths that are not sidecars are returned as-is.
+///
+/// Scans store the result in `file.image_path`. Queries read `COALESCE(file.image_path, file.path)`
+/// and strip the sidecar extension from it, which covers rows not scanned since the column was added.
+pub fn resolve_image_path(sidecar_path: &str) -> String {
+    let image_path = image_path_for_sidecar(sidecar_path);
+    if image_path == sidecar_path || Path::new(image_path).exists() {
+        return image_path.to_string();
+    }
+    match find_image_by_stem(Path::new(image_path)) {
+        Some(found) => {
+            log::trace!("Resolved sidecar {} to {}", sidecar_path, found.display());
+            found.to_string_lossy().into_owned()
+        }
+        None => image_path.to_string(),
+    }
+}
+
+// Function to find the media file named "<stem>.<extension>" for a stem path such as "dir/photo"
+fn find_image_by_stem(stem_path: &Path) -> Option<PathBuf> {
+    let (dir, stem) = (stem_path.parent()?, stem_path.file_name()?.to_str()?);
+    let mut candidates: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_stem().and_then(|name| name.to_str()) == Some(stem)
+                && path.is_file()
+                && crate::exiftool::is_media_file(path)
+        })
+        .collect();
+    candidates.sort();
+    let is_raw = |path: &PathBuf| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| RAW_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+    };
+    let raw = candidates.iter().position(is_raw).unwrap_or(0);
+    (!candidates.is_empty()).then(|| candidates.swap_remove(raw))
+}
+
+/// Finds the sidecar files of an image on disk, e.g. "photo.jpg.xmp" or "photo.jpg.XMP" for
+/// "photo.jpg", for each configured sidecar extension. The image name has to match exactly.
+pub fn sidecar_files_for_image(image_path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(image_name)) = (image_path.parent(), image_path.file_name().and_then(|n| n.to_str())) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let extensions = sidecar_extensions();
+    let mut sidecars: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                return false;
+            };
+            name.strip_prefix(image_name)
+                .and_then(|suffix| suffix.strip_prefix('.'))
+                .is_some_and(|extension| extensions.iter().any(|wanted| extension.eq_ignore_ascii_case(wanted)))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    sidecars.sort();
+    sidecars
+}
+
+/// SQL condition on `file.path` matching the sidecars of the image path bound to parameter
+/// `?{param}`, whatever the case of the sidecar extension ("photo.JPG.xmp" and "photo.JPG.XMP",
+/// but not "photo.jpg.xmp"), or the image itself when it was indexed with exiftool. Sidecars
+/// resolved to the image by name ("photo.xmp" for "photo.NEF") match on `file.image_path`. The
+/// range on the path keeps the lookup on the path index; "/" is the character sorting right after ".".
+pub fn sidecar_path_condition(param: usize) -> String {
+    let extensions: Vec<String> = sidecar_extensions()
+        .iter()
+        .map(|extension| format!("'{}'", extension.to_ascii_lowercase().replace('\'', "''")))
+        .collect();
+    format!(
+        "(file.path = ?{0} OR file.image_path = ?{0} OR file.path >= ?{0} || '.' AND file.path < ?{0} || '/' \
+         AND lower(substr(file.path, length(?{0}) + 2)) IN ({1}))",
+        param,
+        extensions.join(", ")
+    )
+}
+
+/// Why a sidecar could not be read completely
+#[derive(Debug)]
+pub enum SidecarError {
+    /// The file could not be read or parsed at all
+    Unreadable(String),
+    /// The XML is malformed at byte offset `position` in a way the parser cannot recover from;
+    /// `kv` holds the key/value pairs read up to there
+    Malformed { position: u64, message: String, kv: HashMap<String, String> },
+}
+
+impl std::fmt::Display for SidecarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SidecarError::Unreadable(e) => f.write_str(e),
+            SidecarError::Malformed { position, message, .. } => write!(f, "malformed XML at byte {}: {}", position, message),
+        }
+    }
+}
+
+/// Parses a sidecar file into a map of keys to values, dispatching on its extension:
+/// ".json" files are read as Google Takeout style JSON, everything else as XMP.
+pub fn extract_sidecar_key_value(path: &str) -> Result<HashMap<String, String>, SidecarError> {
+    let is_json = Path::new(path)
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+    if is_json {
+        extract_json_key_value(path).ok_or_else(|| SidecarError::Unreadable(format!("Failed to read JSON sidecar {}", path)))
+    } else {
+        extract_key_value(path)
+    }
+}
+
+/// Parses a Google Takeout style JSON sidecar, mapping its known fields onto the keys
+/// used for XMP metadata so they are imported, searched and displayed the same way:
+///
+/// - `title` to `dc:title/rdf:Alt`, `description` to `dc:description/rdf:Alt`
+/// - `photoTakenTime.timestamp` to `exif:DateTimeOriginal`
+/// - `photoLastModifiedTime.timestamp` (or `creationTime.timestamp`) to `xmp:ModifyDate`
+/// - `geoData` latitude/longitude/altitude to `exif:GPSLatitude`/`exif:GPSLongitude`/`exif:GPSAltitude`
+pub fn extract_json_key_value(path: &str) -> Option<HashMap<String, String>> {
+    log::trace!("Extracting key-value pairs from JSON sidecar: {}", path);
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            log::error!("Failed to read JSON sidecar {}: {}", path, e);
+            return None;
+        }
+    };
+    let json: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(json) => json,
+        Err(e) => {
+            log::error!("Failed to parse JSON sidecar {}: {}", path, e);
+            return None;
+        }
+    };
+
+    let mut kv = HashMap::new();
+    let text = |pointer: &str| {
+        json.pointer(pointer)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+    };
+    // Takeout stores timestamps as strings of epoch seconds
+    let timestamp = |pointer: &str| {
+        json.pointer(pointer).and_then(|v| match v {
+            serde_json::Value::String(s) => s.trim().parse::<i64>().ok(),
+            other => other.as_i64(),
+        })
+    };
+
+    if let Some(title) = text("/title") {
+        kv.insert("dc:title/rdf:Alt".to_string(), title);
+    }
+    if let Some(description) = text("/description") {
+        kv.insert("dc:description/rdf:Alt".to_string(), description);
+    }
+    if let Some(taken) = timestamp("/photoTakenTime/timestamp") {
+        kv.insert("exif:DateTimeOriginal".to_string(), format_timestamp(taken));
+    }
+    if let Some(modified) = timestamp("/photoLastModifiedTime/timestamp").or_else(|| timestamp("/creationTime/timestamp")) {
+        kv.insert("xmp:ModifyDate".to_string(), format_timestamp(modified));
+    }
+
+    // Takeout writes 0.0/0.0 when the location is unknown
+    let coordinate = |key: &str| json.pointer(&format!("/geoData/{}", key)).and_then(|v| v.as_f64());
+    if let (Some(latitude), Some(longitude)) = (coordinate("latitude"), coordinate("longitude")) {
+        if latitude != 0.0 || longitude != 0.0 {
+            kv.insert(GPS_LATITUDE_KEY.to_string(), latitude.to_string());
+            kv.insert(GPS_LONGITUDE_KEY.to_string(), longitude.to_string());
+            if let Some(altitude) = coordinate("altitude") {
+                kv.insert("exif:GPSAltitude".to_string(), altitude.to_string());
+            }
+        }
+    }
+
+    log::debug!("JSON parsing completed for {} - Key-value pairs: {}", path, kv.len());
+    if kv.is_empty() {
+        log::warn!("No key-value pairs extracted from {}", path);
+    }
+    Some(kv)
+}
+
+/// Formats a Unix timestamp as an XMP (RFC 3339) date in UTC ("2023-05-01T10:00:00Z")
+pub fn format_timestamp(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86400);
+    let seconds = timestamp.rem_euclid(86400);
+    // Inverse of days_from_civil (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60
+    )
+}
+
+// RDF container elements holding the rdf:li items of a multi-valued property
+const RDF_CONTAINERS: &[&str] = &["rdf:Seq", "rdf:Bag", "rdf:Alt"];
+
+// A multi-valued property whose rdf:li items are being collected
+struct ListCollector {
+    tag: String,
+    depth: usize,
+    container: Option<String>,
+    items: Vec<String>,
+}
+
+/// Parses an XMP sidecar file into a map of tag paths to values.
+///
+/// The list keys configured with --list-keys are collected into a single value per
+/// property, see [`extract_key_value_with_list_keys`].
+pub fn extract_key_value(path: &str) -> Result<HashMap<String, String>, SidecarError> {
+    let list_keys: Vec<String> = match crate::cli::CLI_ARGS.get() {
+        Some(args) => args.list_keys.clone(),
+        None => crate::cli::DEFAULT_LIST_KEYS.split(',').map(String::from).collect(),
+    };
+    extract_key_value_with_list_keys(path, &list_keys)
+}
+
+/// Parses an XMP sidecar file into a map of tag paths to values.
+///
+/// Properties whose tag ends with one of `list_keys` are multi-valued: the text of the
+/// rdf:li items of their rdf:Seq, rdf:Bag or rdf:Alt container is joined by semicolon and
+/// stored under "<property>/<container>", e.g. "digiKam:TagsList/rdf:Seq" or
+/// "dc:subject/rdf:Bag".
+///
+/// Recoverable mistakes are tolerated and logged with their byte offset: a bare "&", an end
+/// tag that does not match its start tag (it closes the innermost open element of that name)
+/// and an end tag without a start tag (ignored). Anything else stops the parse, as does a file
+/// that ends inside an element; the error then carries the pairs read so far.
+pub fn extract_key_value_with_list_keys(path: &str, list_keys: &[String]) -> Result<HashMap<String, String>, SidecarError> {
     log::trace!("Extracting key-value pairs from XMP file: {}", path);
     
     let xml = match fs::read_to_string(path) {
@@ -302,136 +1137,127 @@ fn extract_key_value(path: &str) -> Option<HashMap<String, String>> {
         }
         Err(e) => {
             log::error!("Failed to read XMP file {}: {}", path, e);
-            return None;
+            return Err(SidecarError::Unreadable(format!("Failed to read XMP file {}: {}", path, e)));
         }
     };
     
     let mut reader = Reader::from_str(&xml);
-    reader.config_mut().trim_text(true);
+    let config = reader.config_mut();
+    config.allow_dangling_amp = true;
+    // End tags are matched against the open elements below instead
+    config.check_end_names = false;
+    config.allow_unmatched_ends = true;
 
     let mut buf: Vec<u8> = Vec::new();
     let mut kv = HashMap::new();
     let mut tag_stack: Vec<String> = Vec::new();
-    let mut in_tagslist = false;
-    let mut in_seq = false;
-    let mut in_title = false;
-    let mut in_alt = false;
-    let mut tagslist_items: Vec<String> = Vec::new();
-    let mut title_items: Vec<String> = Vec::new();
+    let mut current_list: Option<ListCollector> = None;
+    // Byte offset and message of the error that stopped the parse
+    let mut malformed: Option<(u64, String)> = None;
+    // Text is read in pieces around entity references ("Fish ", "&amp;", " Chips") and stored
+    // once the next markup is reached
+    let mut text = String::new();
 
     let mut element_count = 0;
     let mut text_count = 0;
 
     loop {
-        match reader.read_event() {
+        let event = reader.read_event();
+        if !text.is_empty() && !matches!(event, Ok(Event::Text(_)) | Ok(Event::GeneralRef(_))) {
+            store_text(&mut kv, &tag_stack, current_list.as_mut(), &text);
+            text.clear();
+        }
+        match event {
             Ok(Event::Start(ref e)) => {
                 element_count += 1;
                 let tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
                 tag_stack.push(tag.clone());
                 
-                if tag.ends_with("digiKam:TagsList") {
-                    in_tagslist = true;
-                    log::trace!("Entering digiKam:TagsList section");
-                }
-                if in_tagslist && tag.ends_with("rdf:Seq") {
-                    in_seq = true;
-                    log::trace!("Entering rdf:Seq section within TagsList");
-                }
-                if tag.ends_with("dc:title") {
-                    in_title = true;
-                    log::trace!("Entering dc:title section");
-                }
-                if in_title && tag.ends_with("rdf:Alt") {
-                    in_alt = true;
-                    log::trace!("Entering rdf:Alt section within title");
+                match current_list.as_mut() {
+                    None => {
+                        if list_keys.iter().any(|key| !key.is_empty() && tag.ends_with(key.as_str())) {
+                            log::trace!("Entering list property {}", tag);
+                            current_list = Some(ListCollector {
+                                tag: tag.clone(),
+                                depth: tag_stack.len(),
+                                container: None,
+                                items: Vec::new(),
+                            });
+                        }
+                    }
+                    Some(list) => {
+                        if list.container.is_none()
+                            && tag_stack.len() == list.depth + 1
+                            && RDF_CONTAINERS.contains(&tag.as_str())
+                        {
+                            log::trace!("Entering {} section within {}", tag, list.tag);
+                            list.container = Some(tag.clone());
+                        }
+                    }
                 }
                 
-                for attr in e.attributes().flatten() {
-                    let key = format!(
-                        "{}:{}",
-                        tag_stack.join("/"),
-                        String::from_utf8_lossy(attr.key.as_ref())
-                    );
-                    let value = attr.unescape_value().unwrap_or_default().to_string();
-                    log::trace!("Found attribute: {} = {}", key, value);
-                    kv.insert(key, value);
-                }
+                collect_attributes(&mut kv, &tag_stack.join("/"), e);
+            }
+            Ok(Event::Empty(ref e)) => {
+                // Self-closing elements (e.g. <rdf:li stArea:x="..."/>) only carry attributes
+                element_count += 1;
+                let tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                let element_path = if tag_stack.is_empty() {
+                    tag
+                } else {
+                    format!("{}/{}", tag_stack.join("/"), tag)
+                };
+                collect_attributes(&mut kv, &element_path, e);
             }
             Ok(Event::Text(e)) => {
                 text_count += 1;
-                let lossy = String::from_utf8_lossy(e.as_ref()).into_owned();
-                let text = unescape(&lossy).unwrap_or_else(|_| lossy.clone().into());
-                if !tag_stack.is_empty() && !text.trim().is_empty() {
-                    let key = tag_stack.join("/");
-                    // Collect rdf:li items under digiKam:TagsList/rdf:Seq
-                    if in_tagslist
-                        && in_seq
-                        && tag_stack
-                            .last()
-                            .map(|t| t.ends_with("rdf:li"))
-                            .unwrap_or(false)
-                    {
-                        log::trace!("Found TagsList item: {}", text);
-                        tagslist_items.push(text.to_string());
-                    // Collect rdf:li items under dc:title/rdf:Alt
-                    } else if in_title
-                        && in_alt
-                        && tag_stack
-                            .last()
-                            .map(|t| t.ends_with("rdf:li"))
-                            .unwrap_or(false)
-                    {
-                        log::trace!("Found title item: {}", text);
-                        title_items.push(text.to_string());
-                    } else {
-                        log::trace!("Found text content: {} = {}", key, text);
-                        kv.insert(key, text.to_string());
-                    }
+                text.push_str(&String::from_utf8_lossy(e.as_ref()));
+            }
+            Ok(Event::GeneralRef(e)) => {
+                let name = String::from_utf8_lossy(e.as_ref()).into_owned();
+                match (e.resolve_char_ref(), resolve_predefined_entity(&name)) {
+                    (Ok(Some(c)), _) => text.push(c),
+                    (_, Some(value)) => text.push_str(value),
+                    // Entities declared in a DTD are not expanded
+                    _ => text.push_str(&format!("&{};", name)),
                 }
             }
             Ok(Event::End(ref e)) => {
                 let tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                if in_seq && tag.ends_with("rdf:Seq") {
-                    in_seq = false;
-                    log::trace!("Exiting rdf:Seq section");
-                }
-                if in_tagslist && tag.ends_with("digiKam:TagsList") {
-                    in_tagslist = false;
-                    log::trace!("Exiting digiKam:TagsList section");
-                    // Store all collected tagslist_items as a single value (joined by semicolon)
-                    if !tagslist_items.is_empty() {
-                        let combined_tags = tagslist_items.join(";");
-                        log::debug!("Collected {} TagsList items: {}", tagslist_items.len(), combined_tags);
-                        kv.insert(
-                            "digiKam:TagsList/rdf:Seq".to_string(),
-                            combined_tags,
-                        );
-                        tagslist_items.clear();
-                    }
-                }
-                if in_alt && tag.ends_with("rdf:Alt") {
-                    in_alt = false;
-                    log::trace!("Exiting rdf:Alt section");
+                let Some(index) = tag_stack.iter().rposition(|open| *open == tag) else {
+                    log::warn!("Ignoring unmatched end tag </{}> in {} at byte {}", tag, path, reader.buffer_position());
+                    continue;
+                };
+                if index + 1 < tag_stack.len() {
+                    log::warn!(
+                        "End tag </{}> in {} at byte {} closes unclosed elements: {}",
+                        tag, path, reader.buffer_position(), tag_stack[index + 1..].join(", ")
+                    );
                 }
-                if in_title && tag.ends_with("dc:title") {
-                    in_title = false;
-                    log::trace!("Exiting dc:title section");
-                    // Store all collected title_items as a single value (joined by semicolon)
-                    if !title_items.is_empty() {
-                        let combined_titles = title_items.join(";");
-                        log::debug!("Collected {} title items: {}", title_items.len(), combined_titles);
-                        kv.insert("dc:title/rdf:Alt".to_string(), combined_titles);
-                        title_items.clear();
-                    }
+                if current_list.as_ref().is_some_and(|list| list.depth > index) {
+                    let list = current_list.take().unwrap();
+                    log::trace!("Exiting list property {}", list.tag);
+                    store_list_items(&mut kv, list);
                 }
-                tag_stack.pop();
+                tag_stack.truncate(index);
             }
             Ok(Event::Eof) => {
                 log::trace!("Reached end of XML file");
+                if !tag_stack.is_empty() {
+                    let message = format!("file ends inside {}", tag_stack.join("/"));
+                    log::warn!("Malformed XML in {} at byte {}: {}", path, reader.buffer_position(), message);
+                    malformed = Some((reader.buffer_position(), message));
+                    // Keep the list items read before the file ended
+                    if let Some(list) = current_list.take() {
+                        store_list_items(&mut kv, list);
+                    }
+                }
                 break;
             }
             Err(e) => {
-                log::error!("XML parsing error in {}: {}", path, e);
+                let position = reader.error_position();
+                log::warn!("Malformed XML in {} at byte {}: {}", path, position, e);
+                malformed = Some((position, e.to_string()));
                 break;
             }
             _ => {}
@@ -446,5 +1272,52 @@ fn extract_key_value(path: &str) -> Option<HashMap<String, String>> {
         log::warn!("No key-value pairs extracted from {}", path);
     }
     
-    Some(kv)
+    match malformed {
+        Some((position, message)) => Err(SidecarError::Malformed { position, message, kv }),
+        None => Ok(kv),
+    }
+}
+
+// Function to store the text of the innermost open element: as an item when it is an rdf:li
+// directly under the container of the list property being collected, otherwise under its tag path
+fn store_text(kv: &mut HashMap<String, String>, tag_stack: &[String], current_list: Option<&mut ListCollector>, text: &str) {
+    let text = text.trim();
+    if tag_stack.is_empty() || text.is_empty() {
+        return;
+    }
+    let key = tag_stack.join("/");
+    let list_item = current_list.filter(|list| {
+        list.container.is_some()
+            && tag_stack.len() == list.depth + 2
+            && tag_stack.last().map(|t| t.ends_with("rdf:li")).unwrap_or(false)
+    });
+    if let Some(list) = list_item {
+        log::trace!("Found {} item: {}", list.tag, text);
+        list.items.push(text.to_string());
+    } else {
+        log::trace!("Found text content: {} = {}", key, text);
+        kv.insert(key, text.to_string());
+    }
+}
+
+// Function to store the items collected for a list property as a single value (joined by
+// semicolon). A property repeated in another rdf:Description adds its new items to the earlier ones.
+fn store_list_items(kv: &mut HashMap<String, String>, list: ListCollector) {
+    let (Some(container), false) = (list.container, list.items.is_empty()) else {
+        return;
+    };
+    let key = format!("{}/{}", list.tag, container);
+    let mut items: Vec<String> = kv
+        .remove(&key)
+        .map(|existing| existing.split(';').map(String::from).collect())
+        .unwrap_or_default();
+    let earlier = items.len();
+    for item in list.items {
+        if !items[..earlier].contains(&item) {
+            items.push(item);
+        }
+    }
+    let combined = items.join(";");
+    log::debug!("Collected {} {} items: {}", items.len(), list.tag, combined);
+    kv.insert(key, combined);
 }
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+
+// Suggestions less similar than this to the query are not worth offering
+const MIN_SIMILARITY: f64 = 0.7;
+
+/// A known tag close to a mistyped search term
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct Suggestion {
+    pub value: String,
+    /// Normalized Damerau-Levenshtein similarity, 1.0 for an exact (case-insensitive) match
+    pub similarity: f64,
+}
+
+/// Builds the vocabulary suggestions are drawn from out of stored tag lists: every
+/// semicolon-separated tag, plus each level of hierarchical tags (`Places/Italy` also yields
+/// `Places` and `Italy`, `|` separates levels in `lr:hierarchicalSubject`). Terms are deduplicated
+/// case-insensitively, keeping the first spelling seen.
+pub fn tag_vocabulary<'a>(tag_lists: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut vocabulary = BTreeMap::new();
+    for tag in tag_lists.into_iter().flat_map(|list| list.split(';')).map(str::trim).filter(|tag| !tag.is_empty()) {
+        let levels = tag.split(['/', '|']).map(str::trim).filter(|level| !level.is_empty());
+        for term in std::iter::once(tag).chain(levels) {
+            vocabulary.entry(term.to_lowercase()).or_insert_with(|| term.to_string());
+        }
+    }
+    vocabulary.into_values().collect()
+}
+
+/// Returns up to `limit` terms of the vocabulary closest to the query by edit distance, most
+/// similar first. Case is ignored and swapped letters count as a single edit.
+pub fn suggest(query: &str, vocabulary: &[String], limit: usize) -> Vec<Suggestion> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut suggestions: Vec<Suggestion> = vocabulary
+        .iter()
+        .map(|term| Suggestion {
+            value: term.clone(),
+            similarity: strsim::normalized_damerau_levenshtein(&query, &term.to_lowercase()),
+        })
+        .filter(|suggestion| suggestion.similarity >= MIN_SIMILARITY)
+        .collect();
+    suggestions.sort_by(|a, b| b.similarity.total_cmp(&a.similarity).then_with(|| a.value.cmp(&b.value)));
+    suggestions.truncate(limit);
+    suggestions
+}
+
+// Edits a fuzzy search term may be away from the text it matches, by the term's length: none
+// for up to two characters, one for up to five and two beyond
+fn max_fuzzy_edits(term_chars: usize) -> usize {
+    match term_chars {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+// Words of a text, split at anything but letters and digits
+fn fuzzy_words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).collect()
+}
+
+/// Scores how closely a metadata value matches a fuzzy search term: 1.0 when it contains the term
+/// (case-insensitive), otherwise the normalized Damerau-Levenshtein similarity of the closest run
+/// of as many words as the term has. None when even that run is more edits away than the term's
+/// length allows (one edit for 3-5 characters, two for longer terms).
+pub fn fuzzy_score(text: &str, term: &str) -> Option<f64> {
+    let term = term.trim().to_lowercase();
+    if term.is_empty() {
+        return None;
+    }
+    let text = text.to_lowercase();
+    if text.contains(&term) {
+        return Some(1.0);
+    }
+    let term_words = fuzzy_words(&term);
+    let text_words = fuzzy_words(&text);
+    if term_words.is_empty() || text_words.len() < term_words.len() {
+        return None;
+    }
+    let term = term_words.join(" ");
+    let term_chars = term.chars().count();
+    text_words
+        .windows(term_words.len())
+        .map(|window| {
+            let candidate = window.join(" ");
+            let distance = strsim::damerau_levenshtein(&candidate, &term);
+            (distance, candidate.chars().count().max(term_chars))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(distance, _)| *distance <= max_fuzzy_edits(term_chars))
+        .map(|(distance, chars)| 1.0 - distance as f64 / chars as f64)
+}
+
+/// Registers `fuzzy_match(value, term)` on the connection, true when `fuzzy_score` matches the
+/// value to the term. Used by searches with fuzzy=true instead of LIKE.
+pub fn register_fuzzy_match(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "fuzzy_match",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            // Values that are not text (e.g. NULL) never match
+            let text = ctx.get_raw(0).as_str().ok();
+            let term = ctx.get::<String>(1)?;
+            Ok(text.is_some_and(|text| fuzzy_score(text, &term).is_some()))
+        },
+    )
+}
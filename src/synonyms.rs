@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Lowercased term -> its synonyms, loaded once from --synonyms-file at startup
+static SYNONYMS: OnceLock<HashMap<String, Vec<String>>> = OnceLock::new();
+
+/// Parses a synonyms file: one group of equivalent terms per line, separated by `=`
+/// (e.g. `NYC = New York City`). Blank lines and lines starting with `#` are ignored.
+/// Terms are matched case-insensitively; a term listed in several groups gets the
+/// synonyms of all of them.
+pub fn parse_synonyms(text: &str) -> HashMap<String, Vec<String>> {
+    let mut synonyms: HashMap<String, Vec<String>> = HashMap::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let group: Vec<&str> = line.split('=').map(str::trim).filter(|term| !term.is_empty()).collect();
+        for term in &group {
+            let entry = synonyms.entry(term.to_ascii_lowercase()).or_default();
+            for synonym in &group {
+                let known = entry.iter().any(|existing| existing.eq_ignore_ascii_case(synonym));
+                if !synonym.eq_ignore_ascii_case(term) && !known {
+                    entry.push(synonym.to_string());
+                }
+            }
+        }
+    }
+    synonyms.retain(|_, list| !list.is_empty());
+    synonyms
+}
+
+/// Reads the synonyms file and makes it available to searches. Returns the number of terms with synonyms.
+pub fn load_synonyms(path: &str) -> Result<usize, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read synonyms file {}: {}", path, e))?;
+    let synonyms = parse_synonyms(&text);
+    let count = synonyms.len();
+    SYNONYMS.set(synonyms).map_err(|_| "Synonyms already loaded".to_string())?;
+    log::info!("Loaded synonyms for {} terms from {}", count, path);
+    Ok(count)
+}
+
+/// Returns the synonyms of a search term, empty when it has none or no synonyms file is loaded.
+pub fn synonyms_of(term: &str) -> Vec<String> {
+    SYNONYMS
+        .get()
+        .and_then(|synonyms| synonyms.get(&term.trim().to_ascii_lowercase()))
+        .cloned()
+        .unwrap_or_default()
+}
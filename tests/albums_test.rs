@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use image_find::albums::{add_to_album, album_files, album_name, create_album, list_albums, remove_from_album};
+    use image_find::db::run_migrations;
+    use rusqlite::Connection;
+
+    fn indexed_db() -> Connection {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        let files = [
+            ("/photos/a.jpg.xmp", "Family"),
+            ("/photos/b.jpg.XMP", "Private"),
+            ("/photos/c.jpg.xmp", "Trips"),
+        ];
+        for (path, tags) in files {
+            conn.execute("INSERT INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+            conn.execute(
+                "INSERT INTO key_value (file_id, key, value) VALUES (last_insert_rowid(), 'digiKam:TagsList/rdf:Seq', ?1)",
+                [tags],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn paths(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn member_paths(conn: &Connection, album_id: i64, exclude_tags: &[String]) -> Vec<String> {
+        album_files(conn, album_id, exclude_tags, &[]).unwrap().into_iter().map(|(path, _)| path).collect()
+    }
+
+    #[test]
+    fn test_album_membership() {
+        let conn = indexed_db();
+        let album = create_album(&conn, "Summer").unwrap();
+        assert_eq!(album_name(&conn, album).unwrap().as_deref(), Some("Summer"));
+        assert_eq!(album_name(&conn, album + 1).unwrap(), None);
+        assert!(create_album(&conn, "Summer").is_err(), "Album names are unique");
+
+        // Images are found by their sidecar in any extension case; unindexed paths are reported back
+        let (added, unknown) = add_to_album(&conn, album, &paths(&["/photos/c.jpg", "/photos/a.jpg", "/photos/x.jpg"])).unwrap();
+        assert_eq!(added, 2);
+        assert_eq!(unknown, ["/photos/x.jpg"]);
+        let (added, _) = add_to_album(&conn, album, &paths(&["/photos/c.jpg", "/photos/b.jpg"])).unwrap();
+        assert_eq!(added, 1, "Files already in the album are not added twice");
+
+        assert_eq!(member_paths(&conn, album, &[]), ["/photos/c.jpg.xmp", "/photos/a.jpg.xmp", "/photos/b.jpg.XMP"]);
+        assert_eq!(member_paths(&conn, album, &paths(&["Private"])), ["/photos/c.jpg.xmp", "/photos/a.jpg.xmp"]);
+
+        // Albums are independent of each other
+        let other = create_album(&conn, "autumn").unwrap();
+        add_to_album(&conn, other, &paths(&["/photos/a.jpg"])).unwrap();
+        let albums = list_albums(&conn).unwrap();
+        let listed: Vec<(&str, i64)> = albums.iter().map(|a| (a.name.as_str(), a.file_count)).collect();
+        assert_eq!(listed, [("autumn", 1), ("Summer", 3)]);
+
+        assert_eq!(remove_from_album(&conn, album, &paths(&["/photos/a.jpg", "/photos/x.jpg"])).unwrap(), 1);
+        assert_eq!(member_paths(&conn, album, &[]), ["/photos/c.jpg.xmp", "/photos/b.jpg.XMP"]);
+        assert_eq!(member_paths(&conn, other, &[]), ["/photos/a.jpg.xmp"]);
+    }
+}
@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use image_find::archive::{tar_size, write_tar, ArchiveEntry};
+    use std::io::Write;
+
+    fn entry<'a>(name: &str, contents: &'a [u8], mtime: u64) -> ArchiveEntry<&'a [u8]> {
+        ArchiveEntry { name: name.to_string(), size: contents.len() as u64, mtime, contents }
+    }
+
+    // Writes the archive in chunks and joins them again
+    fn tar_archive(entries: Vec<ArchiveEntry<&[u8]>>) -> Vec<u8> {
+        let mut archive = Vec::new();
+        write_tar(entries, 1000, |chunk| {
+            archive.extend_from_slice(&chunk);
+            true
+        })
+        .unwrap();
+        archive
+    }
+
+    fn octal_field(field: &[u8]) -> u64 {
+        let digits = std::str::from_utf8(field).unwrap().trim_end_matches(['\0', ' ']);
+        u64::from_str_radix(digits, 8).unwrap()
+    }
+
+    fn name_field(header: &[u8]) -> &str {
+        std::str::from_utf8(&header[..100]).unwrap().trim_end_matches('\0')
+    }
+
+    fn checksum_is_valid(header: &[u8]) -> bool {
+        let sum: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| if (148..156).contains(&i) { u64::from(b' ') } else { u64::from(byte) })
+            .sum();
+        sum == octal_field(&header[148..156])
+    }
+
+    #[test]
+    fn test_tar_archive_layout() {
+        let image = [7; 600];
+        let entries = vec![entry("photo.jpg", &image, 1_700_000_000), entry("photo.jpg.xmp", b"<x:xmpmeta/>", 0)];
+        assert_eq!(tar_size(&entries), 512 * 7);
+        let archive = tar_archive(entries);
+        // Header + two data blocks, header + one data block, two end blocks
+        assert_eq!(archive.len(), 512 * 7);
+
+        let first = &archive[..512];
+        assert_eq!(name_field(first), "photo.jpg");
+        assert_eq!(octal_field(&first[124..136]), 600);
+        assert_eq!(octal_field(&first[136..148]), 1_700_000_000);
+        assert_eq!(first[156], b'0');
+        assert_eq!(&first[257..263], b"ustar\0");
+        assert!(checksum_is_valid(first));
+        assert_eq!(&archive[512..1112], &[7; 600][..]);
+
+        let second = &archive[512 * 3..512 * 4];
+        assert_eq!(name_field(second), "photo.jpg.xmp");
+        assert!(checksum_is_valid(second));
+        assert_eq!(&archive[512 * 4..512 * 4 + 12], b"<x:xmpmeta/>");
+        assert!(archive[512 * 5..].iter().all(|&byte| byte == 0));
+    }
+
+    // Names that do not fit the ustar header are carried in a PAX extended header
+    #[test]
+    fn test_tar_archive_long_name() {
+        let name = format!("{}.jpg.xmp", "x".repeat(120));
+        let entries = vec![entry(&name, b"", 0)];
+        let size = tar_size(&entries);
+        let archive = tar_archive(entries);
+        assert_eq!(archive.len() as u64, size);
+
+        let pax = &archive[..512];
+        assert_eq!(pax[156], b'x');
+        assert!(checksum_is_valid(pax));
+        let record_len = octal_field(&pax[124..136]) as usize;
+        let record = std::str::from_utf8(&archive[512..512 + record_len]).unwrap();
+        assert_eq!(record, format!("{} path={}\n", record_len, name));
+
+        let file = &archive[1024..1536];
+        assert_eq!(file[156], b'0');
+        assert_eq!(name_field(file).len(), 100);
+    }
+
+    // A file is read and handed on in chunks, never held whole
+    #[test]
+    fn test_write_tar_streams_file() {
+        let dir = std::path::Path::new("tests/tmp/archive_stream");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("large.jpg");
+        let contents: Vec<u8> = (0..5_000_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::File::create(&path).unwrap().write_all(&contents).unwrap();
+
+        let entries = vec![ArchiveEntry::open(&path).unwrap()];
+        assert_eq!(entries[0].name, "large.jpg");
+        assert_eq!(entries[0].size, contents.len() as u64);
+        let size = tar_size(&entries);
+
+        let chunk_size = 64 * 1024;
+        let mut archive = Vec::new();
+        let mut chunks = 0;
+        write_tar(entries, chunk_size, |chunk| {
+            assert!(chunk.len() <= chunk_size);
+            chunks += 1;
+            archive.extend_from_slice(&chunk);
+            true
+        })
+        .unwrap();
+        assert_eq!(archive.len() as u64, size);
+        assert!(chunks > contents.len() / chunk_size);
+        assert_eq!(&archive[512..512 + contents.len()], &contents[..]);
+
+        // Refusing a chunk stops the archive
+        let mut chunks = 0;
+        write_tar(vec![ArchiveEntry::open(&path).unwrap()], chunk_size, |_| {
+            chunks += 1;
+            chunks < 3
+        })
+        .unwrap();
+        assert_eq!(chunks, 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
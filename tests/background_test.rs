@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::background::{background_workers_running, start_background_workers};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::routes::USER_REQUEST_ACTIVE;
+    use image_find::sidecar_scan::scan_and_import_sidecars;
+
+    const ROOT: &str = "tests/tmp/background_test";
+
+    fn count_files(dir: &str) -> usize {
+        walkdir::WalkDir::new(dir).into_iter().flatten().filter(|entry| entry.file_type().is_file()).count()
+    }
+
+    fn wait_for_workers() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while background_workers_running() {
+            assert!(Instant::now() < deadline, "Background workers did not finish");
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    // Both workers exit after one pass, and a pass requested meanwhile runs once more before they do
+    #[test]
+    fn test_workers_exit_when_done() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        RgbImage::from_pixel(320, 240, Rgb([40, 120, 200])).save(format!("{}/lib/photo.png", ROOT)).unwrap();
+        fs::write(
+            format!("{}/lib/photo.png.xmp", ROOT),
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="2"/>
+</rdf:RDF></x:xmpmeta>"#,
+        )
+        .unwrap();
+
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/background_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/background_test/thumbs",
+            "--full-image-cache", "tests/tmp/background_test/full",
+            "--video-preview-cache", "tests/tmp/background_test/video",
+            "--scan-dir", "tests/tmp/background_test/lib",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        scan_and_import_sidecars().expect("Sidecar import failed");
+
+        // Hold the workers back as if a user request was being served
+        USER_REQUEST_ACTIVE.store(true, Ordering::SeqCst);
+        assert!(start_background_workers());
+        assert!(!start_background_workers(), "A second start while running should only queue a pass");
+        assert!(background_workers_running());
+        USER_REQUEST_ACTIVE.store(false, Ordering::SeqCst);
+
+        wait_for_workers();
+        assert_eq!(count_files(&format!("{}/thumbs", ROOT)), 1);
+        assert_eq!(count_files(&format!("{}/full", ROOT)), 1);
+
+        // Idle workers start right away again
+        assert!(start_background_workers());
+        wait_for_workers();
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use image_find::routes::{browse_listing, normalize_browse_path, BrowseDirectory, BrowseFile};
+
+    fn paths() -> Vec<String> {
+        [
+            "/photos/2023/Italy/rome.jpg",
+            "/photos/2023/Italy/Venice/canal.jpg",
+            "/photos/2023/Italy/Venice/bridge.jpg",
+            "/photos/2023/Sweden/lake.jpg",
+            "/photos/2023/cover.png",
+            "/photos/2024/snow.jpg",
+            "/photos/2023x/other.jpg",
+        ]
+        .iter()
+        .map(|path| path.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_normalize_browse_path() {
+        assert_eq!(normalize_browse_path("", "/photos"), "/photos");
+        assert_eq!(normalize_browse_path("2023\\Italy", "/photos"), "/photos/2023/Italy");
+        assert_eq!(normalize_browse_path("2023//Italy/", "/photos/"), "/photos/2023/Italy");
+        assert_eq!(normalize_browse_path("/photos/2023/", "/elsewhere"), "/photos/2023");
+    }
+
+    // Subdirectories count every file below them; files are only those directly in the directory
+    #[test]
+    fn test_browse_listing() {
+        let listing = browse_listing("/photos/2023", "/photos", paths());
+        assert_eq!(listing.path, "/photos/2023");
+        assert_eq!(listing.parent.as_deref(), Some("/photos"));
+        assert_eq!(
+            listing.directories,
+            [
+                BrowseDirectory { name: "Italy".to_string(), path: "/photos/2023/Italy".to_string(), count: 3 },
+                BrowseDirectory { name: "Sweden".to_string(), path: "/photos/2023/Sweden".to_string(), count: 1 },
+            ]
+        );
+        assert_eq!(
+            listing.files,
+            [BrowseFile { name: "cover.png".to_string(), file_path: "/photos/2023/cover.png".to_string() }]
+        );
+
+        // The scan directory is the root of the tree
+        let root = browse_listing("/photos", "/photos/", paths());
+        assert_eq!(root.parent, None);
+        let names: Vec<(&str, usize)> = root.directories.iter().map(|d| (d.name.as_str(), d.count)).collect();
+        assert_eq!(names, [("2023", 5), ("2023x", 1), ("2024", 1)]);
+        assert!(root.files.is_empty());
+    }
+}
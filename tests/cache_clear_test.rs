@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use actix_web::web;
+    use image_find::background::RegenerateTarget;
+    use image_find::processing::cache::{clear_cache_dir, CacheClearSummary};
+    use image_find::routes::CacheClearQuery;
+
+    const ROOT: &str = "tests/tmp/cache_clear_test";
+
+    // Everything below the cache directory goes, but links are not followed out of it
+    #[test]
+    fn test_clear_cache_dir() {
+        let _ = fs::remove_dir_all(ROOT);
+        let cache_dir = std::path::PathBuf::from(format!("{}/thumbs", ROOT));
+        fs::create_dir_all(cache_dir.join("ab")).unwrap();
+        fs::create_dir_all(cache_dir.join("by-content/cd")).unwrap();
+        fs::create_dir_all(format!("{}/outside", ROOT)).unwrap();
+        fs::write(cache_dir.join("ab/abcd.jpg"), [0; 100]).unwrap();
+        fs::write(cache_dir.join("by-content/cd/cdef.jpg"), [0; 50]).unwrap();
+        fs::write(cache_dir.join("flat.jpg"), [0; 10]).unwrap();
+        let outside = format!("{}/outside/photo.jpg", ROOT);
+        fs::write(&outside, b"original").unwrap();
+        std::os::unix::fs::symlink(fs::canonicalize(&outside).unwrap(), cache_dir.join("ab/link.jpg")).unwrap();
+        std::os::unix::fs::symlink(fs::canonicalize(format!("{}/outside", ROOT)).unwrap(), cache_dir.join("linked-dir")).unwrap();
+
+        let summary = clear_cache_dir(&cache_dir);
+        assert_eq!(summary, CacheClearSummary { files: 5, bytes: 160 });
+        assert!(cache_dir.is_dir(), "The cache directory itself is kept");
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 0);
+        assert_eq!(fs::read(&outside).unwrap(), b"original");
+
+        assert_eq!(clear_cache_dir(&cache_dir), CacheClearSummary::default());
+        let _ = fs::remove_dir_all(ROOT);
+    }
+
+    // ?type= takes the singular names, their plurals, or nothing for all caches
+    #[test]
+    fn test_cache_clear_query_type() {
+        let cache_type = |query: &str| web::Query::<CacheClearQuery>::from_query(query).map(|query| query.cache_type);
+        assert_eq!(cache_type("type=thumbnail").unwrap(), Some(RegenerateTarget::Thumbnails));
+        assert_eq!(cache_type("type=preview").unwrap(), Some(RegenerateTarget::Previews));
+        assert_eq!(cache_type("type=all").unwrap(), Some(RegenerateTarget::All));
+        assert_eq!(cache_type("type=thumbnails").unwrap(), Some(RegenerateTarget::Thumbnails));
+        assert_eq!(cache_type("").unwrap(), None);
+        assert!(cache_type("type=videos").is_err());
+    }
+}
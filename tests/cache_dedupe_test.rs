@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{cache_file_path, get_cache_dir, save_thumbnail_to_cache};
+
+    fn init_cli_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/cache_dedupe_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/cache_dedupe_test_thumbs",
+            "--full-image-cache", "tests/tmp/cache_dedupe_test_full",
+            "--video-preview-cache", "tests/tmp/cache_dedupe_test_video",
+            "--scan-dir", "tests/data",
+            "--dedupe-cache",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    // Identical thumbnails share one file, and rewriting one key leaves the others intact
+    #[test]
+    fn test_identical_thumbnails_are_linked() {
+        init_cli_args();
+        let _ = fs::remove_dir_all("tests/tmp/cache_dedupe_test_thumbs");
+        let cache_dir = get_cache_dir();
+
+        save_thumbnail_to_cache("first", b"same jpeg bytes").unwrap();
+        save_thumbnail_to_cache("second", b"same jpeg bytes").unwrap();
+        save_thumbnail_to_cache("other", b"different jpeg bytes").unwrap();
+
+        assert_eq!(fs::read(cache_file_path(&cache_dir, "second", "jpg")).unwrap(), b"same jpeg bytes");
+        let content_files: usize = fs::read_dir(cache_dir.join("by-content"))
+            .unwrap()
+            .map(|shard| fs::read_dir(shard.unwrap().path()).unwrap().count())
+            .sum();
+        assert_eq!(content_files, 2);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let first = fs::metadata(cache_file_path(&cache_dir, "first", "jpg")).unwrap();
+            let second = fs::metadata(cache_file_path(&cache_dir, "second", "jpg")).unwrap();
+            assert_eq!(first.ino(), second.ino());
+            assert_eq!(first.nlink(), 3);
+        }
+
+        save_thumbnail_to_cache("first", b"regenerated jpeg bytes").unwrap();
+        assert_eq!(fs::read(cache_file_path(&cache_dir, "first", "jpg")).unwrap(), b"regenerated jpeg bytes");
+        assert_eq!(fs::read(cache_file_path(&cache_dir, "second", "jpg")).unwrap(), b"same jpeg bytes");
+
+        let _ = fs::remove_dir_all(cache_dir);
+    }
+}
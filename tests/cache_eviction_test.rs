@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, FileTimes};
+    use std::time::{Duration, SystemTime};
+
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{cache_file_path, get_cache_dir, save_thumbnail_to_cache};
+
+    const ROOT: &str = "tests/tmp/cache_eviction_test";
+
+    // Writing past --max-thumbnail-cache-size evicts the least recently used thumbnails
+    #[test]
+    fn test_cache_eviction() {
+        let _ = fs::remove_dir_all(ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/cache_eviction_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/cache_eviction_test/thumbs",
+            "--full-image-cache", "tests/tmp/cache_eviction_test/full",
+            "--video-preview-cache", "tests/tmp/cache_eviction_test/video",
+            "--scan-dir", "tests/tmp/cache_eviction_test",
+            "--max-thumbnail-cache-size", "3KB",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let cache_dir = get_cache_dir();
+
+        // Used an hour, two hours and three hours after the oldest, in an order unlike their keys
+        let now = SystemTime::now();
+        for (key, age_hours) in [("cc03", 1), ("aa01", 3), ("bb02", 2)] {
+            save_thumbnail_to_cache(key, &[0; 1000]).unwrap();
+            let used = now - Duration::from_secs(age_hours * 3600);
+            fs::File::options()
+                .write(true)
+                .open(cache_file_path(&cache_dir, key, "jpg"))
+                .unwrap()
+                .set_times(FileTimes::new().set_accessed(used).set_modified(used))
+                .unwrap();
+        }
+        assert!(cache_file_path(&cache_dir, "aa01", "jpg").exists(), "Nothing is evicted below the cap");
+
+        save_thumbnail_to_cache("dd04", &[0; 1000]).unwrap();
+        assert!(!cache_file_path(&cache_dir, "aa01", "jpg").exists(), "The least recently used file is evicted");
+        assert!(cache_file_path(&cache_dir, "cc03", "jpg").exists());
+        assert!(cache_file_path(&cache_dir, "dd04", "jpg").exists(), "The new file is kept");
+        let total: u64 = walkdir::WalkDir::new(&cache_dir)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.metadata().unwrap().len())
+            .sum();
+        assert!(total <= 3 * 1024, "Cache holds {} bytes", total);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{generate_cache_key, preview_cache_key, thumbnail_cache_key};
+
+    const ROOT: &str = "tests/tmp/cache_key_test";
+
+    // With --cache-key content, keys follow the file's size and mtime instead of its path
+    #[test]
+    fn test_content_cache_key() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/cache_key_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/cache_key_test/thumbs",
+            "--full-image-cache", "tests/tmp/cache_key_test/full",
+            "--video-preview-cache", "tests/tmp/cache_key_test/video",
+            "--scan-dir", "tests/tmp/cache_key_test",
+            "--cache-key", "content",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/moved", ROOT)).unwrap();
+
+        // Files that cannot be stat'd keep the path key
+        let missing = "tests/tmp/cache_key_test/missing.jpg";
+        assert_eq!(thumbnail_cache_key(missing), generate_cache_key(missing));
+        assert_eq!(preview_cache_key(missing), generate_cache_key(missing));
+
+        let photo = "tests/tmp/cache_key_test/photo.jpg";
+        fs::write(photo, b"original").unwrap();
+        let thumbnail_key = thumbnail_cache_key(photo);
+        let preview_key = preview_cache_key(photo);
+        assert_ne!(thumbnail_key, generate_cache_key(photo));
+
+        // Moving the file keeps its keys
+        let moved = "tests/tmp/cache_key_test/moved/photo.jpg";
+        fs::rename(photo, moved).unwrap();
+        assert_eq!(thumbnail_cache_key(moved), thumbnail_key);
+        assert_eq!(preview_cache_key(moved), preview_key);
+
+        // Editing it in place changes them, even at the same size
+        fs::write(moved, b"modified").unwrap();
+        let file = fs::File::options().write(true).open(moved).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        assert_ne!(thumbnail_cache_key(moved), thumbnail_key);
+        assert_ne!(preview_cache_key(moved), preview_key);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, FileTimes};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use image_find::processing::cache::{cache_dir_stats, CacheStats};
+
+    const ROOT: &str = "tests/tmp/cache_stats_test";
+
+    // Files in subdirectories count, links do not
+    #[test]
+    fn test_cache_dir_stats() {
+        let _ = fs::remove_dir_all(ROOT);
+        let cache_dir = std::path::PathBuf::from(format!("{}/thumbs", ROOT));
+        let empty = cache_dir_stats(&cache_dir);
+        assert_eq!(empty, CacheStats { path: cache_dir.display().to_string(), ..CacheStats::default() });
+
+        fs::create_dir_all(cache_dir.join("ab")).unwrap();
+        for (name, len, modified) in [("ab/abcd.jpg", 100, 1_700_000_000), ("flat.jpg", 20, 1_600_000_000)] {
+            let path = cache_dir.join(name);
+            fs::write(&path, vec![0; len]).unwrap();
+            let time = UNIX_EPOCH + Duration::from_secs(modified);
+            fs::File::options().write(true).open(&path).unwrap().set_times(FileTimes::new().set_modified(time)).unwrap();
+        }
+        std::os::unix::fs::symlink(fs::canonicalize(cache_dir.join("flat.jpg")).unwrap(), cache_dir.join("link.jpg")).unwrap();
+
+        let stats = cache_dir_stats(&cache_dir);
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.bytes, 120);
+        assert_eq!(stats.oldest, Some(1_600_000_000));
+        assert_eq!(stats.newest, Some(1_700_000_000));
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{
+        cache_file_path, get_cache_dir, get_cached_thumbnail, migrate_cache_layout, save_thumbnail_to_cache,
+    };
+    use image_find::processing::image::generate_thumbnail;
+
+    // All tests in this binary share one set of CLI args
+    fn init_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/cache_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/cache_test_thumbs",
+            "--full-image-cache", "tests/tmp/cache_test_full",
+            "--video-preview-cache", "tests/tmp/cache_test_video",
+            "--scan-dir", "tests/data",
+            "--memory-cache-entries", "2",
+            "--revalidate-cache",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    // Hot thumbnails are served from memory, and regenerating one replaces the memory entry
+    #[test]
+    fn test_memory_cache_in_front_of_disk() {
+        init_args();
+
+        let cache_key = "memory_cache_test";
+        let source_path = "tests/tmp/memory_cache_test_missing_source.jpg";
+        let cache_file = cache_file_path(&get_cache_dir(), cache_key, "jpg");
+        save_thumbnail_to_cache(cache_key, b"first").expect("Failed to write thumbnail");
+        assert_eq!(get_cached_thumbnail(cache_key, source_path).as_deref(), Some("Zmlyc3Q="));
+
+        // Served from memory even when the disk file is gone
+        fs::remove_file(&cache_file).unwrap();
+        assert_eq!(get_cached_thumbnail(cache_key, source_path).as_deref(), Some("Zmlyc3Q="));
+
+        // Regeneration invalidates the stale memory entry
+        save_thumbnail_to_cache(cache_key, b"second").expect("Failed to write thumbnail");
+        assert_eq!(get_cached_thumbnail(cache_key, source_path).as_deref(), Some("c2Vjb25k"));
+
+        let _ = fs::remove_file(&cache_file);
+    }
+
+    // With --revalidate-cache an edited source image gets a fresh thumbnail
+    #[test]
+    fn test_revalidate_regenerates_when_source_is_newer() {
+        init_args();
+
+        let source_path = "tests/tmp/revalidate_source.png";
+        fs::create_dir_all("tests/tmp").unwrap();
+        RgbImage::from_pixel(64, 64, Rgb([255, 0, 0])).save(source_path).unwrap();
+        let first = generate_thumbnail(source_path).expect("Failed to generate thumbnail");
+        assert_eq!(generate_thumbnail(source_path).as_deref(), Some(first.as_str()));
+
+        // Edit the image in place and make sure its mtime is after the cache file's
+        RgbImage::from_pixel(64, 64, Rgb([0, 0, 255])).save(source_path).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(source_path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let second = generate_thumbnail(source_path).expect("Failed to regenerate thumbnail");
+        assert_ne!(first, second, "Stale thumbnail was served after the source changed");
+
+        let _ = fs::remove_file(source_path);
+    }
+
+    // Files are sharded by the first two key characters, and files of another layout are moved there once
+    #[test]
+    fn test_flat_cache_is_migrated() {
+        init_args();
+        let cache_dir = std::path::Path::new("tests/tmp/cache_migrate_test");
+        let _ = fs::remove_dir_all(cache_dir);
+        fs::create_dir_all(cache_dir.join("xy")).unwrap();
+        fs::create_dir_all(cache_dir.join("by-content")).unwrap();
+        fs::write(cache_dir.join("abcdef.jpg"), b"flat").unwrap();
+        fs::write(cache_dir.join("xy").join("ab1234.jpg"), b"misplaced").unwrap();
+        fs::write(cache_dir.join("cd5678.avif"), b"avif thumbnail").unwrap();
+        fs::write(cache_dir.join("by-content").join("ffee.jpg"), b"content").unwrap();
+        fs::write(cache_dir.join("notes.txt"), b"not a cache file").unwrap();
+
+        assert_eq!(cache_file_path(cache_dir, "abcdef", "jpg"), cache_dir.join("ab").join("abcdef.jpg"));
+        assert_eq!(cache_file_path(cache_dir, "cd5678", "avif"), cache_dir.join("cd").join("cd5678.avif"));
+        assert_eq!(migrate_cache_layout(cache_dir), 4);
+        assert_eq!(fs::read(cache_dir.join("ab").join("abcdef.jpg")).unwrap(), b"flat");
+        assert_eq!(fs::read(cache_dir.join("ab").join("ab1234.jpg")).unwrap(), b"misplaced");
+        assert_eq!(fs::read(cache_dir.join("cd").join("cd5678.avif")).unwrap(), b"avif thumbnail");
+        assert_eq!(fs::read(cache_dir.join("by-content").join("ff").join("ffee.jpg")).unwrap(), b"content");
+        assert!(cache_dir.join("notes.txt").exists());
+        assert!(!cache_dir.join("xy").exists());
+
+        assert_eq!(migrate_cache_layout(cache_dir), 0);
+
+        let _ = fs::remove_dir_all(cache_dir);
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::atomic::AtomicBool;
+
+    use image_find::processing::cache::{verify_cache_dir, CacheVerifySummary};
+
+    const ROOT: &str = "tests/tmp/cache_verify_test";
+
+    fn jpeg_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::RgbImage::from_pixel(8, 8, image::Rgb([200, 100, 50]))
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Jpeg)
+            .unwrap();
+        bytes
+    }
+
+    fn write(dir: &Path, name: &str, bytes: &[u8]) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(name), bytes).unwrap();
+    }
+
+    // Empty and truncated cache files are removed, intact ones and other files are kept
+    #[test]
+    fn test_verify_removes_corrupt_files() {
+        let cache_dir = Path::new(ROOT).join("full");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let jpeg = jpeg_bytes();
+        write(&cache_dir.join("aa"), "aa01.jpg", &jpeg);
+        write(&cache_dir.join("ab"), "ab02.jpg", &jpeg[..jpeg.len() / 3]);
+        write(&cache_dir.join("by-content").join("ac"), "ac03.jpg", b"");
+        write(&cache_dir, "notes.txt", b"not a cache file");
+
+        let summary = verify_cache_dir(&cache_dir, 100, 0, &AtomicBool::new(false));
+        assert_eq!(summary, CacheVerifySummary { checked: 3, corrupt: 2, removed: 2 });
+        assert!(cache_dir.join("aa/aa01.jpg").exists());
+        assert!(!cache_dir.join("ab/ab02.jpg").exists());
+        assert!(!cache_dir.join("by-content/ac/ac03.jpg").exists());
+        assert!(cache_dir.join("notes.txt").exists());
+
+        let _ = fs::remove_dir_all(&cache_dir);
+    }
+
+    // A sample checks the keys in its buckets, wrapping around at 100
+    #[test]
+    fn test_verify_samples_by_key() {
+        let cache_dir = Path::new(ROOT).join("sample");
+        let _ = fs::remove_dir_all(&cache_dir);
+        // Keys "0000..." and "0032..." fall into buckets 0 and 50
+        write(&cache_dir, "0000aa.jpg", b"corrupt");
+        write(&cache_dir, "0032aa.jpg", b"corrupt");
+
+        let checked = |sample_percent: u8, offset: u8| verify_cache_dir(&cache_dir, sample_percent, offset, &AtomicBool::new(false)).checked;
+        assert_eq!(checked(10, 60), 0);
+        assert_eq!(checked(30, 80), 1, "Buckets 80-99 and 0-9");
+        assert!(!cache_dir.join("0000aa.jpg").exists());
+        assert!(cache_dir.join("0032aa.jpg").exists());
+        assert_eq!(checked(50, 50), 1);
+        assert!(!cache_dir.join("0032aa.jpg").exists());
+
+        let _ = fs::remove_dir_all(&cache_dir);
+    }
+}
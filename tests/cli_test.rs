@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use image_find::cli::{json_log_line, parse_frame_ancestor, parse_video_extension, CliArgs, Command, LogFormat, CLI_ARGS};
+    use image_find::processing::video::{is_video_extension, video_extensions};
+    use image_find::routes::{content_security_policy, x_frame_options};
+
+    // Every JSON log line is a parseable object, even with quotes and newlines in the message
+    #[test]
+    fn test_json_log_line_is_parseable() {
+        let line = json_log_line(
+            "2024-01-01T10:00:00.123Z",
+            log::Level::Warn,
+            "image_find::routes",
+            "Failed to open \"a.jpg\"\nsecond line",
+        );
+        assert!(!line.contains('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&line).expect("Log line is not valid JSON");
+        assert_eq!(value["timestamp"], "2024-01-01T10:00:00.123Z");
+        assert_eq!(value["level"], "WARN");
+        assert_eq!(value["target"], "image_find::routes");
+        assert_eq!(value["message"], "Failed to open \"a.jpg\"\nsecond line");
+    }
+
+    #[test]
+    fn test_log_format_defaults_to_text() {
+        let base = [
+            "image_find",
+            "--db-path", "db.sqlite",
+            "--thumbnail-cache", "thumb",
+            "--full-image-cache", "full",
+            "--video-preview-cache", "video",
+            "--scan-dir", "photos",
+        ];
+        let args = CliArgs::parse_from(base);
+        assert!(matches!(args.log_format, LogFormat::Text));
+
+        let args = CliArgs::parse_from(base.iter().copied().chain(["--log-format", "json"]));
+        assert!(matches!(args.log_format, LogFormat::Json));
+    }
+
+    // Without a subcommand the server is started, as before subcommands existed
+    #[test]
+    fn test_subcommand_defaults_to_serve() {
+        let base = [
+            "image_find",
+            "--db-path", "db.sqlite",
+            "--thumbnail-cache", "thumb",
+            "--full-image-cache", "full",
+            "--video-preview-cache", "video",
+            "--scan-dir", "photos",
+        ];
+        assert_eq!(CliArgs::parse_from(base).command(), Command::Serve);
+        for (name, command) in [("serve", Command::Serve), ("index", Command::Index), ("prune", Command::Prune), ("verify", Command::Verify)] {
+            let args = CliArgs::parse_from(base.iter().copied().chain([name]));
+            assert_eq!(args.command(), command);
+        }
+        assert!(CliArgs::try_parse_from(base.iter().copied().chain(["reindex"])).is_err());
+    }
+
+    // Keywords get their CSP quotes; values that could break out of the directive are rejected
+    #[test]
+    fn test_frame_ancestors() {
+        assert_eq!(parse_frame_ancestor("self").unwrap(), "'self'");
+        assert_eq!(parse_frame_ancestor("'none'").unwrap(), "'none'");
+        assert_eq!(parse_frame_ancestor("https://portal.example.com").unwrap(), "https://portal.example.com");
+        for value in ["", "a;b", "https://a.example https://b.example", "'unsafe-inline'", "a\r\nX-Injected: 1"] {
+            assert!(parse_frame_ancestor(value).is_err(), "'{}' should be rejected", value);
+        }
+
+        let same_origin = vec!["'self'".to_string()];
+        assert!(content_security_policy(&same_origin).ends_with("frame-ancestors 'self'"));
+        assert_eq!(x_frame_options(&same_origin), Some("SAMEORIGIN"));
+        assert_eq!(x_frame_options(&["'none'".to_string()]), Some("DENY"));
+
+        let portal = vec!["'self'".to_string(), "https://portal.example.com".to_string()];
+        assert!(content_security_policy(&portal).ends_with("frame-ancestors 'self' https://portal.example.com"));
+        assert_eq!(x_frame_options(&portal), None);
+    }
+
+    // "default" keeps the built-in set, further entries extend it
+    #[test]
+    fn test_video_extensions() {
+        assert_eq!(parse_video_extension(".MTS").unwrap(), "mts");
+        for value in ["", ".", "m'ts", "m2ts]", "mp 4"] {
+            assert!(parse_video_extension(value).is_err(), "'{}' should be rejected", value);
+        }
+
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "db.sqlite",
+            "--thumbnail-cache", "thumb",
+            "--full-image-cache", "full",
+            "--video-preview-cache", "video",
+            "--scan-dir", "photos",
+            "--video-extensions", "default,.MTS,m2ts,mp4",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let extensions = video_extensions();
+        assert_eq!(extensions.len(), 12);
+        assert_eq!(&extensions[10..], ["mts", "m2ts"]);
+        assert!(is_video_extension("mov"));
+        assert!(is_video_extension("m2ts"));
+        assert!(!is_video_extension("jpg"));
+    }
+}
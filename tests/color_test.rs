@@ -0,0 +1,24 @@
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb, RgbImage};
+    use image_find::processing::color::{color_distance, dominant_color, parse_hex, to_hex};
+
+    #[test]
+    fn test_hex_round_trip() {
+        assert_eq!(parse_hex("#3366ff"), Some([0x33, 0x66, 0xff]));
+        assert_eq!(parse_hex("3366FF"), Some([0x33, 0x66, 0xff]));
+        assert_eq!(parse_hex("#36f"), None);
+        assert_eq!(parse_hex("zzzzzz"), None);
+        assert_eq!(to_hex([0x33, 0x66, 0xff]), "#3366ff");
+    }
+
+    // A mostly blue image with a small red patch is dominated by blue
+    #[test]
+    fn test_dominant_color_picks_majority() {
+        let img = RgbImage::from_fn(100, 100, |x, y| {
+            if x < 20 && y < 20 { Rgb([220, 20, 20]) } else { Rgb([20, 40, 200]) }
+        });
+        let color = dominant_color(&DynamicImage::ImageRgb8(img));
+        assert!(color_distance(color, [20, 40, 200]) < 10.0, "Unexpected dominant color {:?}", color);
+    }
+}
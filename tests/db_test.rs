@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use image_find::db::{current_schema_version, open_read_only, run_migrations};
+    use rusqlite::Connection;
+
+    // Migrations are applied once and re-running them is a no-op
+    #[test]
+    fn test_migrations_are_idempotent() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        assert_eq!(current_schema_version(&conn).unwrap(), 0);
+
+        run_migrations(&mut conn).expect("First migration run failed");
+        let version = current_schema_version(&conn).unwrap();
+        assert!(version > 0, "No migrations were recorded");
+
+        run_migrations(&mut conn).expect("Second migration run failed");
+        assert_eq!(current_schema_version(&conn).unwrap(), version);
+
+        let applied: i64 = conn
+            .query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(applied, version);
+    }
+
+    // Databases created before schema_version existed are adopted without data loss
+    #[test]
+    fn test_migrations_adopt_existing_database() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        conn.execute_batch(
+            "CREATE TABLE file (id INTEGER PRIMARY KEY, path TEXT NOT NULL, hash BIGINT NOT NULL, UNIQUE(path, hash));
+             CREATE TABLE key_value (id INTEGER PRIMARY KEY, file_id INTEGER NOT NULL, key TEXT NOT NULL, value TEXT NOT NULL);
+             INSERT INTO file (path, hash) VALUES ('/photos/a.jpg.xmp', 1);",
+        )
+        .unwrap();
+
+        run_migrations(&mut conn).expect("Migrating legacy database failed");
+
+        let files: i64 = conn
+            .query_row("SELECT COUNT(*) FROM file", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(files, 1);
+
+        // Existing files are assumed to still have their image until a scan says otherwise
+        let present: bool = conn
+            .query_row("SELECT source_present FROM file", [], |row| row.get(0))
+            .unwrap();
+        assert!(present);
+
+        // Files imported before import_time existed are not stamped with the upgrade time, which
+        // would list the whole library as just imported
+        let import_time: Option<i64> = conn
+            .query_row("SELECT import_time FROM file", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(import_time, None);
+    }
+
+    // Read-only connections see the data but cannot change it, nor create a missing database
+    #[test]
+    fn test_open_read_only() {
+        let _ = std::fs::create_dir_all("tests/tmp");
+        let db_path = "tests/tmp/db_test_read_only.sqlite";
+        let _ = std::fs::remove_file(db_path);
+        let mut conn = Connection::open(db_path).expect("Failed to create DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        conn.execute("INSERT INTO file (path, hash) VALUES ('/photos/a.jpg.xmp', 0)", []).unwrap();
+
+        let read_only = open_read_only(db_path).expect("Failed to open read-only");
+        let files: i64 = read_only.query_row("SELECT COUNT(*) FROM file", [], |row| row.get(0)).unwrap();
+        assert_eq!(files, 1);
+        let write = read_only.execute("DELETE FROM file", []);
+        assert!(
+            matches!(write, Err(rusqlite::Error::SqliteFailure(ref e, _)) if e.code == rusqlite::ErrorCode::ReadOnly),
+            "write was not refused: {:?}",
+            write
+        );
+
+        assert!(open_read_only("tests/tmp/db_test_missing.sqlite").is_err());
+        assert!(!std::path::Path::new("tests/tmp/db_test_missing.sqlite").exists());
+    }
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use image::{DynamicImage, RgbImage};
+    use image_find::processing::develop::DevelopSettings;
+
+    fn kv(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_crop_and_orientation_are_applied() {
+        let settings = DevelopSettings::from_key_values(&kv(&[
+            ("crs:HasCrop", "True"),
+            ("crs:CropTop", "0.25"),
+            ("crs:CropLeft", "0"),
+            ("crs:CropBottom", "0.75"),
+            ("crs:CropRight", "0.5"),
+            ("crs:Orientation", "6"),
+        ]))
+        .expect("Expected develop settings");
+
+        let img = DynamicImage::ImageRgb8(RgbImage::new(400, 200));
+        let result = settings.apply(img);
+        // Cropped to 200x100, then rotated 90 degrees
+        assert_eq!((result.width(), result.height()), (100, 200));
+    }
+
+    #[test]
+    fn test_disabled_or_missing_crop_is_ignored() {
+        let settings = DevelopSettings::from_key_values(&kv(&[
+            ("crs:HasCrop", "False"),
+            ("crs:CropTop", "0.25"),
+            ("crs:CropLeft", "0"),
+            ("crs:CropBottom", "0.75"),
+            ("crs:CropRight", "0.5"),
+        ]));
+        assert_eq!(settings, None);
+        assert_eq!(DevelopSettings::from_key_values(&kv(&[("crs:Orientation", "1")])).unwrap().crop, None);
+    }
+}
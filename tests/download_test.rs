@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App};
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::routes::download_with_sidecar;
+
+    const ROOT: &str = "tests/tmp/download_test";
+
+    // Only files within --scan-dir are downloaded; anything else is answered like a missing file
+    #[actix_web::test]
+    async fn test_download_outside_scan_dir_is_refused() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/download_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/download_test/thumbs",
+            "--full-image-cache", "tests/tmp/download_test/full",
+            "--video-preview-cache", "tests/tmp/download_test/video",
+            "--scan-dir", "tests/tmp/download_test/lib",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        fs::create_dir_all(format!("{}/outside", ROOT)).unwrap();
+        fs::write(format!("{}/lib/a.jpg", ROOT), b"image").unwrap();
+        fs::write(format!("{}/lib/a.jpg.xmp", ROOT), b"sidecar").unwrap();
+        let secret = format!("{}/outside/secret.txt", ROOT);
+        fs::write(&secret, b"secret").unwrap();
+        std::os::unix::fs::symlink(fs::canonicalize(&secret).unwrap(), format!("{}/lib/link.jpg", ROOT)).unwrap();
+
+        let app = test::init_service(
+            App::new().route("/api/download-with-sidecar", web::get().to(download_with_sidecar)),
+        )
+        .await;
+        let status = |path: String| {
+            let request = test::TestRequest::get()
+                .uri(&format!("/api/download-with-sidecar?path={}", urlencoding::encode(&path)))
+                .to_request();
+            let app = &app;
+            async move { test::call_service(app, request).await.status() }
+        };
+
+        assert_eq!(status(format!("{}/lib/a.jpg.xmp", ROOT)).await, StatusCode::OK);
+        assert_eq!(status(secret.clone()).await, StatusCode::NOT_FOUND);
+        assert_eq!(status(fs::canonicalize(&secret).unwrap().display().to_string()).await, StatusCode::NOT_FOUND);
+        assert_eq!(status(format!("{}/lib/link.jpg", ROOT)).await, StatusCode::NOT_FOUND, "Links are resolved");
+        assert_eq!(status("/etc/hostname".to_string()).await, StatusCode::NOT_FOUND);
+        assert_eq!(status(format!("{}/lib/../outside/secret.txt", ROOT)).await, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image_find::processing::embedded_preview::extract_embedded_preview;
+    use image_find::processing::exif::parse_jpeg_header;
+
+    const ROOT: &str = "tests/tmp/embedded_preview_test";
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(width, height, image::Rgb([90, 120, 150])));
+        let mut bytes = Vec::new();
+        img.write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 80)).unwrap();
+        bytes
+    }
+
+    fn dimensions(jpeg: &[u8]) -> (u32, u32) {
+        let header = parse_jpeg_header(jpeg).expect("extracted bytes are a JPEG");
+        (header.width, header.height)
+    }
+
+    // A little-endian TIFF whose IFD0 holds `preview` as a JPEG compressed strip and whose IFD1
+    // points to `thumbnail` through JPEGInterchangeFormat, as in CR2 files
+    fn tiff_raw(preview: &[u8], thumbnail: &[u8]) -> Vec<u8> {
+        let entry = |tag: u16, kind: u16, value: u32| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&kind.to_le_bytes());
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            bytes.extend_from_slice(&value.to_le_bytes());
+            bytes
+        };
+        let ifd0 = 8u32;
+        let ifd1 = ifd0 + 2 + 3 * 12 + 4;
+        let preview_offset = ifd1 + 2 + 2 * 12 + 4;
+        let thumbnail_offset = preview_offset + preview.len() as u32;
+
+        let mut bytes = b"II*\0".to_vec();
+        bytes.extend_from_slice(&ifd0.to_le_bytes());
+        bytes.extend_from_slice(&3u16.to_le_bytes());
+        bytes.extend(entry(0x0103, 3, 6));
+        bytes.extend(entry(0x0111, 4, preview_offset));
+        bytes.extend(entry(0x0117, 4, preview.len() as u32));
+        bytes.extend_from_slice(&ifd1.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend(entry(0x0201, 4, thumbnail_offset));
+        bytes.extend(entry(0x0202, 4, thumbnail.len() as u32));
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(preview);
+        bytes.extend_from_slice(thumbnail);
+        bytes
+    }
+
+    // The preview with the most pixels, or the smallest covering the thumbnail size
+    #[test]
+    fn test_tiff_based_raw() {
+        fs::create_dir_all(ROOT).unwrap();
+        let path = format!("{}/photo.cr2", ROOT);
+        fs::write(&path, tiff_raw(&jpeg(600, 400), &jpeg(160, 120))).unwrap();
+
+        assert_eq!(dimensions(&extract_embedded_preview(&path, None).unwrap()), (600, 400));
+        assert_eq!(dimensions(&extract_embedded_preview(&path, Some(100)).unwrap()), (160, 120));
+        assert_eq!(dimensions(&extract_embedded_preview(&path, Some(200)).unwrap()), (600, 400));
+        // None large enough: the largest
+        assert_eq!(dimensions(&extract_embedded_preview(&path, Some(1000)).unwrap()), (600, 400));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_raf() {
+        fs::create_dir_all(ROOT).unwrap();
+        let path = format!("{}/photo.raf", ROOT);
+        let preview = jpeg(320, 240);
+        let mut bytes = b"FUJIFILMCCD-RAW 0201FF383501".to_vec();
+        bytes.resize(84, 0);
+        bytes.extend_from_slice(&100u32.to_be_bytes());
+        bytes.extend_from_slice(&(preview.len() as u32).to_be_bytes());
+        bytes.resize(100, 0);
+        bytes.extend_from_slice(&preview);
+        fs::write(&path, bytes).unwrap();
+
+        assert_eq!(extract_embedded_preview(&path, None).unwrap(), preview);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    // Nikon keeps a medium-sized preview in its maker note besides the full-size one
+    #[test]
+    fn test_nef() {
+        let path = "tests/data/2009-07-14_115409.NEF";
+        assert_eq!(dimensions(&extract_embedded_preview(path, None).unwrap()), (3008, 2000));
+        assert_eq!(dimensions(&extract_embedded_preview(path, Some(200)).unwrap()), (564, 372));
+    }
+
+    // Other containers and truncated files fail, so exiv2 gets its turn
+    #[test]
+    fn test_unsupported_files() {
+        fs::create_dir_all(ROOT).unwrap();
+        let path = format!("{}/clip.cr3", ROOT);
+        fs::write(&path, b"\0\0\0\x18ftypcrx \0\0\0\x01crx isom").unwrap();
+        assert!(extract_embedded_preview(&path, None).is_err());
+
+        let mut truncated = tiff_raw(&jpeg(600, 400), &jpeg(160, 120));
+        truncated.truncate(200);
+        fs::write(&path, truncated).unwrap();
+        assert!(extract_embedded_preview(&path, None).is_err());
+
+        assert!(extract_embedded_preview(&format!("{}/missing.nef", ROOT), None).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
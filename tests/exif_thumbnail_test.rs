@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Cursor;
+
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use clap::Parser;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::exif::{embedded_thumbnail, read_jpeg_header};
+    use image_find::processing::image::generate_scaled_thumbnail;
+
+    fn init_cli_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/exif_thumbnail_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/exif_thumbnail_test_thumbs",
+            "--full-image-cache", "tests/tmp/exif_thumbnail_test_full",
+            "--video-preview-cache", "tests/tmp/exif_thumbnail_test_video",
+            "--scan-dir", "tests/data",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    fn encode_jpeg(img: &RgbImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg).unwrap();
+        bytes
+    }
+
+    // Little-endian EXIF block with an empty IFD0 and an IFD1 pointing at the thumbnail
+    fn exif_block(thumbnail: &[u8]) -> Vec<u8> {
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(b"II");
+        tiff.extend_from_slice(&42u16.to_le_bytes());
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        // IFD0: no entries, IFD1 follows at offset 14
+        tiff.extend_from_slice(&0u16.to_le_bytes());
+        tiff.extend_from_slice(&14u32.to_le_bytes());
+        // IFD1: two LONG entries, thumbnail data after the next-IFD offset
+        let data_offset = 14 + 2 + 2 * 12 + 4;
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        for (tag, value) in [(0x0201u16, data_offset as u32), (0x0202, thumbnail.len() as u32)] {
+            tiff.extend_from_slice(&tag.to_le_bytes());
+            tiff.extend_from_slice(&4u16.to_le_bytes());
+            tiff.extend_from_slice(&1u32.to_le_bytes());
+            tiff.extend_from_slice(&value.to_le_bytes());
+        }
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(thumbnail);
+        tiff
+    }
+
+    // Writes a blue main image carrying a red embedded thumbnail, so the path taken shows in the result
+    fn write_jpeg_with_exif_thumbnail(path: &str, size: (u32, u32), thumbnail_size: (u32, u32)) {
+        let main = encode_jpeg(&RgbImage::from_pixel(size.0, size.1, Rgb([20, 40, 220])));
+        let thumbnail = encode_jpeg(&RgbImage::from_pixel(thumbnail_size.0, thumbnail_size.1, Rgb([220, 30, 30])));
+
+        let mut segment = b"Exif\0\0".to_vec();
+        segment.extend_from_slice(&exif_block(&thumbnail));
+        let mut file = main[..2].to_vec();
+        file.extend_from_slice(&[0xFF, 0xE1]);
+        file.extend_from_slice(&(segment.len() as u16 + 2).to_be_bytes());
+        file.extend_from_slice(&segment);
+        file.extend_from_slice(&main[2..]);
+        fs::write(path, file).unwrap();
+    }
+
+    fn thumbnail_of(path: &str) -> RgbImage {
+        let thumbnail = generate_scaled_thumbnail(path, 1, true).expect("Thumbnail generation failed");
+        image::load_from_memory(&BASE64.decode(thumbnail).unwrap()).unwrap().to_rgb8()
+    }
+
+    fn is_red(pixel: &Rgb<u8>) -> bool {
+        pixel[0] > 150 && pixel[2] < 100
+    }
+
+    #[test]
+    fn test_read_embedded_thumbnail() {
+        fs::create_dir_all("tests/tmp").unwrap();
+        let path = "tests/tmp/exif_thumbnail_test_read.jpg";
+        write_jpeg_with_exif_thumbnail(path, (1200, 800), (300, 200));
+
+        let header = read_jpeg_header(path).expect("Header not read");
+        assert_eq!((header.width, header.height), (1200, 800));
+        let thumbnail = embedded_thumbnail(header.exif.as_deref().expect("No EXIF block")).expect("No thumbnail");
+        let decoded = image::load_from_memory(thumbnail).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (300, 200));
+
+        assert!(embedded_thumbnail(b"II*\0\x08\0\0\0\0\0\0\0\0\0").is_none());
+        assert!(embedded_thumbnail(b"garbage").is_none());
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_large_enough_exif_thumbnail_is_used() {
+        init_cli_args();
+        fs::create_dir_all("tests/tmp").unwrap();
+        let path = "tests/tmp/exif_thumbnail_test_used.jpg";
+        write_jpeg_with_exif_thumbnail(path, (1200, 800), (300, 200));
+
+        let thumbnail = thumbnail_of(path);
+        assert_eq!(thumbnail.dimensions(), (200, 133));
+        assert!(is_red(thumbnail.get_pixel(100, 66)));
+
+        let _ = fs::remove_file(path);
+    }
+
+    // Too small and letterboxed embedded thumbnails fall back to decoding the image itself
+    #[test]
+    fn test_unusable_exif_thumbnail_falls_back() {
+        init_cli_args();
+        fs::create_dir_all("tests/tmp").unwrap();
+        for (name, thumbnail_size) in [("small", (160, 107)), ("letterboxed", (320, 240))] {
+            let path = format!("tests/tmp/exif_thumbnail_test_{}.jpg", name);
+            write_jpeg_with_exif_thumbnail(&path, (1200, 800), thumbnail_size);
+
+            let thumbnail = thumbnail_of(&path);
+            assert_eq!(thumbnail.dimensions(), (200, 133), "{}", name);
+            assert!(!is_red(thumbnail.get_pixel(100, 66)), "{} thumbnail was used", name);
+
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::albums::{add_to_album, album_files, create_album};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::exiftool::{exiftool_key_values, exiftool_version, is_media_file};
+    use image_find::sidecar_scan::{scan_and_import_sidecars, sidecar_path_condition};
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/exiftool_test";
+
+    // Stands in for exiftool: answers -ver and prints the same metadata for every file
+    const FAKE_EXIFTOOL: &str = r#"#!/bin/sh
+if [ "$1" = "-ver" ]; then echo 12.76; exit 0; fi
+while [ "$1" != "--" ]; do shift; done; shift
+printf '['
+sep=''
+for f in "$@"; do
+  printf '%s{"SourceFile":"%s","IFD0:Model":"X-T4","ExifIFD:DateTimeOriginal":"2023:05:01 10:00:00","XMP-dc:Subject":["Beach","Sunset"],"System:FileName":"x"}' "$sep" "$f"
+  sep=','
+done
+printf ']'
+"#;
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="2"/>
+</rdf:RDF></x:xmpmeta>"#;
+
+    #[test]
+    fn test_exiftool_key_values() {
+        let entry: serde_json::Map<String, serde_json::Value> = serde_json::from_str(
+            r#"{"SourceFile":"/photos/a.jpg","IFD0:Make":"FUJIFILM","IFD0:ModifyDate":"2023:05:02 08:00:00",
+                "XMP-xmp:ModifyDate":"2023:05:03 08:00:00","ExifIFD:ISO":160,"XMP-dc:Subject":["Beach","Sunset"],
+                "XMP-crs:CropTop":0.1,"IFD1:ThumbnailImage":"(Binary data 5120 bytes, use -b option to extract)",
+                "System:FileSize":12345,"ExifTool:ExifToolVersion":12.76,"IPTC:Keywords":"Holiday"}"#,
+        )
+        .unwrap();
+        let kv = exiftool_key_values(&entry);
+        let mut keys: Vec<&str> = kv.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["ExifIFD:ISO", "crs:CropTop", "dc:subject/rdf:Bag", "tiff:Make", "xmp:ModifyDate"]);
+        assert_eq!(kv["tiff:Make"], "FUJIFILM");
+        assert_eq!(kv["xmp:ModifyDate"], "2023:05:02 08:00:00", "The first group in name order wins");
+        assert_eq!(kv["dc:subject/rdf:Bag"], "Beach;Sunset", "Subject is preferred over Keywords");
+        assert_eq!(kv["ExifIFD:ISO"], "160");
+        assert_eq!(kv["crs:CropTop"], "0.1");
+    }
+
+    #[test]
+    fn test_missing_exiftool_is_reported() {
+        assert!(exiftool_version("tests/tmp/no-such-exiftool").is_err());
+        assert!(is_media_file(std::path::Path::new("/photos/a.JPG")));
+        assert!(is_media_file(std::path::Path::new("/photos/clip.mov")));
+        assert!(!is_media_file(std::path::Path::new("/photos/a.jpg.xmp")));
+    }
+
+    // Files without sidecars are indexed under their own path and move over to a sidecar added later
+    #[test]
+    fn test_scan_imports_files_without_sidecars() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let exiftool = format!("{}/exiftool", ROOT);
+        fs::write(&exiftool, FAKE_EXIFTOOL).unwrap();
+        std::process::Command::new("chmod").args(["+x", &exiftool]).status().unwrap();
+        fs::write(format!("{}/lib/a.jpg", ROOT), b"not decoded").unwrap();
+        fs::write(format!("{}/lib/b.jpg", ROOT), b"not decoded").unwrap();
+        fs::write(format!("{}/lib/b.jpg.xmp", ROOT), SIDECAR).unwrap();
+        fs::write(format!("{}/lib/notes.txt", ROOT), b"not media").unwrap();
+
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/exiftool_test/thumbs",
+            "--full-image-cache", "tests/tmp/exiftool_test/full",
+            "--video-preview-cache", "tests/tmp/exiftool_test/video",
+            "--scan-dir", "tests/tmp/exiftool_test/lib",
+            "--use-exiftool",
+            "--exiftool-path", &exiftool,
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        scan_and_import_sidecars().unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        let image_path = format!("{}/lib/a.jpg", ROOT);
+        let (file_id, capture_time): (i64, Option<i64>) = conn
+            .query_row(
+                &format!("SELECT file.id, file.capture_time FROM file WHERE {}", sidecar_path_condition(1)),
+                [&image_path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(capture_time, Some(1682935200));
+        let value = |key: &str| -> String {
+            conn.query_row("SELECT value FROM key_value WHERE file_id = ?1 AND key = ?2", rusqlite::params![file_id, key], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(value("tiff:Model"), "X-T4");
+        assert_eq!(value("dc:subject/rdf:Bag"), "Beach;Sunset");
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM file", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2, "b.jpg is indexed from its sidecar only");
+
+        let album = create_album(&conn, "Trip").unwrap();
+        add_to_album(&conn, album, std::slice::from_ref(&image_path)).unwrap();
+        fs::write(format!("{}/lib/a.jpg.xmp", ROOT), SIDECAR).unwrap();
+        scan_and_import_sidecars().unwrap();
+
+        let sidecar_path: String = conn.query_row("SELECT path FROM file WHERE id = ?1", [file_id], |row| row.get(0)).unwrap();
+        assert_eq!(sidecar_path, format!("{}.xmp", image_path));
+        assert_eq!(value("xmp:Rating"), "2");
+        assert!(conn.query_row("SELECT 1 FROM key_value WHERE file_id = ?1 AND key = 'tiff:Model'", [file_id], |_| Ok(())).is_err());
+        let members: Vec<String> = album_files(&conn, album, &[], &[]).unwrap().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(members, std::slice::from_ref(&image_path));
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::path::Path;
+
+    use image::{Rgb, RgbImage};
+    use image_find::processing::image::generate_thumbnail;
+    use image_find::routes::find_transcoded_video;
+    use image_find::sidecar_scan::{image_path_for_sidecar, sidecar_path_condition};
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_mixed_case_sidecar_maps_to_image() {
+        assert_eq!(image_path_for_sidecar("/photos/IMG_0001.JPG.Xmp"), "/photos/IMG_0001.JPG");
+        assert_eq!(image_path_for_sidecar("/photos/IMG_0001.JPG.xMP"), "/photos/IMG_0001.JPG");
+    }
+
+    // Image to sidecar lookups ignore the case of the sidecar extension, not of the image path
+    #[test]
+    fn test_sidecar_lookup_ignores_extension_case() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE file (id INTEGER PRIMARY KEY, path TEXT NOT NULL, hash BIGINT NOT NULL, image_path TEXT, UNIQUE(path, hash));
+             INSERT INTO file (path, hash) VALUES ('/photos/IMG_0001.JPG.Xmp', 1);
+             INSERT INTO file (path, hash) VALUES ('/photos/IMG_0001.jpg.xmp', 2);
+             INSERT INTO file (path, hash) VALUES ('/photos/IMG_0001.JPG.xmp.bak', 3);
+             INSERT INTO file (path, hash) VALUES ('/photos/IMG_0001.JPGX.xmp', 4);",
+        )
+        .unwrap();
+
+        let mut stmt = conn
+            .prepare(&format!("SELECT path FROM file WHERE {}", sidecar_path_condition(1)))
+            .unwrap();
+        let matches: Vec<String> = stmt
+            .query_map(["/photos/IMG_0001.JPG"], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(matches, vec!["/photos/IMG_0001.JPG.Xmp".to_string()]);
+    }
+
+    #[test]
+    fn test_uppercase_image_extension_is_thumbnailed() {
+        let source_path = "tests/tmp/extension_case_test.JPG";
+        fs::create_dir_all("tests/tmp").unwrap();
+        RgbImage::from_pixel(64, 48, Rgb([10, 20, 30])).save(source_path).unwrap();
+
+        assert!(generate_thumbnail(source_path).is_some());
+
+        let _ = fs::remove_file(source_path);
+    }
+
+    // "Clip.MP4" finds its preview whatever the case of the _480p.mp4 suffix, but only for the same stem
+    #[test]
+    fn test_transcoded_video_suffix_ignores_case() {
+        let cache_dir = Path::new("tests/tmp/extension_case_videos");
+        fs::create_dir_all(cache_dir).unwrap();
+        fs::write(cache_dir.join("Clip_480P.MP4"), b"not really a video").unwrap();
+
+        assert_eq!(
+            find_transcoded_video(cache_dir, OsStr::new("Clip")),
+            Some(cache_dir.join("Clip_480P.MP4"))
+        );
+        assert_eq!(find_transcoded_video(cache_dir, OsStr::new("clip")), None);
+        assert_eq!(find_transcoded_video(cache_dir, OsStr::new("Other")), None);
+
+        let _ = fs::remove_dir_all(cache_dir);
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::db::run_migrations;
+    use image_find::failures::{clear_failure, clear_failures, list_failures, record_failure, FailureStage};
+    use image_find::sidecar_scan::scan_and_import_sidecars;
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/failures_test";
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="4"/>
+</rdf:RDF></x:xmpmeta>"#;
+
+    // A file failing a stage again keeps one entry; entries are listed newest first and cleared by stage
+    #[test]
+    fn test_record_and_clear_failures() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run_migrations(&mut conn).unwrap();
+
+        record_failure(&conn, "/lib/a.jpg", FailureStage::Thumbnail, "Decode error", 100).unwrap();
+        record_failure(&conn, "/lib/a.jpg", FailureStage::Preview, "Decode error", 101).unwrap();
+        record_failure(&conn, "/lib/b.mp4", FailureStage::Thumbnail, "ffmpeg failed", 102).unwrap();
+        record_failure(&conn, "/lib/a.jpg", FailureStage::Thumbnail, "  Still broken\n", 103).unwrap();
+
+        let failures = list_failures(&conn, None, 10).unwrap();
+        let paths: Vec<(&str, &str)> = failures.iter().map(|f| (f.path.as_str(), f.stage.as_str())).collect();
+        assert_eq!(paths, [("/lib/a.jpg", "thumbnail"), ("/lib/b.mp4", "thumbnail"), ("/lib/a.jpg", "preview")]);
+        assert_eq!(failures[0].error, "Still broken");
+        assert_eq!(failures[0].failed_at, 103);
+        assert_eq!(failures[0].attempts, 2);
+
+        assert_eq!(list_failures(&conn, Some(FailureStage::Preview), 10).unwrap().len(), 1);
+        assert_eq!(list_failures(&conn, None, 1).unwrap().len(), 1);
+
+        assert_eq!(clear_failure(&conn, "/lib/b.mp4", FailureStage::Thumbnail).unwrap(), 1);
+        assert_eq!(clear_failures(&conn, Some(FailureStage::Scan)).unwrap(), 0);
+        assert_eq!(clear_failures(&conn, Some(FailureStage::Thumbnail)).unwrap(), 1);
+        assert_eq!(clear_failures(&conn, None).unwrap(), 1);
+        assert!(list_failures(&conn, None, 10).unwrap().is_empty());
+    }
+
+    // A skipped malformed sidecar is listed until a later scan imports it
+    #[test]
+    fn test_scan_failures() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/failures_test/thumbs",
+            "--full-image-cache", "tests/tmp/failures_test/full",
+            "--video-preview-cache", "tests/tmp/failures_test/video",
+            "--scan-dir", "tests/tmp/failures_test/lib",
+            "--malformed-xmp", "skip",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let sidecar = format!("{}/lib/broken.jpg.xmp", ROOT);
+        fs::write(format!("{}/lib/good.jpg.xmp", ROOT), SIDECAR).unwrap();
+        fs::write(&sidecar, &SIDECAR[..SIDECAR.len() - 30]).unwrap();
+        scan_and_import_sidecars().unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let failures = list_failures(&conn, Some(FailureStage::Scan), 10).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].path, sidecar);
+        assert!(failures[0].error.starts_with("Malformed XML"), "{}", failures[0].error);
+
+        fs::write(&sidecar, SIDECAR).unwrap();
+        scan_and_import_sidecars().unwrap();
+        assert!(list_failures(&conn, None, 10).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
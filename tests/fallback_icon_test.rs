@@ -0,0 +1,24 @@
+#[cfg(test)]
+mod tests {
+    use image_find::processing::icon::FileTypeIcon;
+
+    // Icons are picked by extension, whatever its case; unknown types get none
+    #[test]
+    fn test_icon_for_path() {
+        assert_eq!(FileTypeIcon::for_path("/docs/scan.pdf"), Some(FileTypeIcon::Document));
+        assert_eq!(FileTypeIcon::for_path("/docs/notes.TXT"), Some(FileTypeIcon::Document));
+        assert_eq!(FileTypeIcon::for_path("/videos/clip.MP4"), Some(FileTypeIcon::Video));
+        assert_eq!(FileTypeIcon::for_path("/photos/raw/DSC_0001.NEF"), Some(FileTypeIcon::Raw));
+        assert_eq!(FileTypeIcon::for_path("/photos/IMG_0001.heic"), Some(FileTypeIcon::Image));
+        assert_eq!(FileTypeIcon::for_path("/photos/archive.zip"), None);
+        assert_eq!(FileTypeIcon::for_path("/photos/no_extension"), None);
+    }
+
+    #[test]
+    fn test_icons_are_bundled_svgs() {
+        for icon in [FileTypeIcon::Document, FileTypeIcon::Video, FileTypeIcon::Raw, FileTypeIcon::Image] {
+            assert!(icon.svg().starts_with("<svg"), "{} icon is not an SVG", icon.name());
+            assert!(icon.svg().contains(r#"width="200" height="200""#), "{} icon is not thumbnail sized", icon.name());
+        }
+    }
+}
@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use image_find::db::run_migrations;
+    use image_find::feed::{atom_feed, feed_file_url, json_feed, recent_feed_items, FeedItem};
+    use rusqlite::Connection;
+
+    fn item(file_path: &str, title: &str, import_time: i64) -> FeedItem {
+        FeedItem { file_path: file_path.to_string(), title: title.to_string(), import_time }
+    }
+
+    // Newest first, titled by dc:title or the file name; missing and excluded files are left out
+    #[test]
+    fn test_recent_feed_items() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        for (path, import_time, source_present) in [
+            ("/photos/old.jpg.xmp", 1700000000, 1),
+            ("/photos/titled.jpg.xmp", 1700000200, 1),
+            ("/photos/Private/secret.jpg.xmp", 1700000300, 1),
+            ("/photos/gone.jpg.xmp", 1700000400, 0),
+            ("/photos/new.jpg.xmp", 1700000500, 1),
+        ] {
+            conn.execute(
+                "INSERT INTO file (path, hash, import_time, source_present) VALUES (?1, 0, ?2, ?3)",
+                rusqlite::params![path, import_time, source_present],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO key_value (file_id, key, value) SELECT id, 'dc:title/rdf:Alt', 'Harbour at dusk' FROM file WHERE path = '/photos/titled.jpg.xmp'",
+            [],
+        )
+        .unwrap();
+
+        let items = recent_feed_items(&conn, 10, &[], &["*/Private/*".to_string()]).unwrap();
+        assert_eq!(
+            items,
+            [
+                item("/photos/new.jpg", "new.jpg", 1700000500),
+                item("/photos/titled.jpg", "Harbour at dusk", 1700000200),
+                item("/photos/old.jpg", "old.jpg", 1700000000),
+            ]
+        );
+        assert_eq!(recent_feed_items(&conn, 1, &[], &[]).unwrap(), [item("/photos/new.jpg", "new.jpg", 1700000500)]);
+    }
+
+    #[test]
+    fn test_feed_file_url() {
+        assert_eq!(
+            feed_file_url("https://photos.example.com/", "image", "/photos/2023/a b&c.jpg"),
+            "https://photos.example.com/image//photos/2023/a%20b%26c.jpg"
+        );
+    }
+
+    #[test]
+    fn test_json_feed() {
+        let feed = json_feed("https://photos.example.com", &[item("/photos/a b.jpg", "Beach", 1700000000)]);
+        assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(feed["feed_url"], "https://photos.example.com/feed.json");
+        let entry = &feed["items"][0];
+        assert_eq!(entry["id"], "https://photos.example.com/image//photos/a%20b.jpg");
+        assert_eq!(entry["url"], "https://photos.example.com/detail?path=%2Fphotos%2Fa%20b.jpg");
+        assert_eq!(entry["image"], "https://photos.example.com/image//photos/a%20b.jpg?w=640");
+        assert_eq!(entry["title"], "Beach");
+        assert_eq!(entry["date_published"], "2023-11-14T22:13:20Z");
+    }
+
+    // Titles and links are escaped; the feed is as recent as its newest entry
+    #[test]
+    fn test_atom_feed() {
+        let items = [item("/photos/new.jpg", "Fish & <chips>", 1700000500), item("/photos/old.jpg", "old.jpg", 1700000000)];
+        let xml = atom_feed("https://photos.example.com", &items, 1800000000);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+        assert!(xml.contains("  <updated>2023-11-14T22:21:40Z</updated>\n"));
+        assert!(xml.contains("<title>Fish &amp; &lt;chips&gt;</title>"));
+        assert!(xml.contains("<link rel=\"enclosure\" type=\"image/jpeg\" href=\"https://photos.example.com/image//photos/new.jpg?w=640\" />"));
+        assert_eq!(xml.matches("<entry>").count(), 2);
+        assert!(xml.ends_with("</feed>\n"));
+
+        let empty = atom_feed("https://photos.example.com", &[], 1800000000);
+        assert!(empty.contains("<updated>2027-01-15T08:00:00Z</updated>"));
+        assert!(!empty.contains("<entry>"));
+    }
+}
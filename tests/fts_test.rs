@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use image_find::db::{ensure_fts_index, run_migrations, set_fts_search};
+    use image_find::routes::parse_search_query;
+    use rusqlite::Connection;
+
+    fn matching(conn: &Connection, search: &str) -> (String, Vec<String>) {
+        let (where_clause, parameters) = parse_search_query(search, |term| {
+            if term.eq_ignore_ascii_case("nyc") { vec!["New York City".to_string()] } else { Vec::new() }
+        });
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT DISTINCT file.path FROM key_value JOIN file ON key_value.file_id = file.id {} ORDER BY file.path",
+                where_clause
+            ))
+            .unwrap();
+        let paths = stmt
+            .query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, _>>()
+            .unwrap();
+        (where_clause, paths)
+    }
+
+    // The index built on startup and kept up to date by triggers finds the same files as LIKE.
+    // One test, as the search mode is process-wide.
+    #[test]
+    fn test_fts_matches_like() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        let files = [
+            ("/photos/a.jpg.xmp", "dc:title", "Skyline of New York City"),
+            ("/photos/a.jpg.xmp", "digiKam:TagsList", "Trips/2023;Family vacation"),
+            ("/photos/b.jpg.xmp", "dc:title", "NYC at night"),
+            ("/photos/b.jpg.xmp", "dc:description", "A \"quoted\" caption, 50% off"),
+            ("/photos/c.jpg.xmp", "dc:title", "Skyline of Paris"),
+            ("/photos/c.jpg.xmp", "digiKam:TagsList", "Trips/2024;family_vacation"),
+        ];
+        let insert = |conn: &Connection, (path, key, value): (&str, &str, &str)| {
+            conn.execute("INSERT OR IGNORE INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+            conn.execute(
+                "INSERT INTO key_value (file_id, key, value) SELECT id, ?2, ?3 FROM file WHERE path = ?1",
+                [path, key, value],
+            )
+            .unwrap();
+        };
+        // Rows from before the index existed are indexed when it is built
+        for file in &files[..3] {
+            insert(&conn, *file);
+        }
+        assert!(ensure_fts_index(&conn).unwrap());
+        assert!(ensure_fts_index(&conn).unwrap());
+        for file in &files[3..] {
+            insert(&conn, *file);
+        }
+        conn.execute("UPDATE key_value SET value = 'Skyline of Paris, France' WHERE value = 'Skyline of Paris'", []).unwrap();
+
+        let searches = [
+            "skyline",
+            "skyline 2023",
+            "\"new york\" skyline",
+            "family vacation",
+            "\"family vacation\"",
+            "nyc night",
+            "\"quoted\" caption",
+            "50% caption",
+            "of paris OR nyc",
+            "skyline -france",
+            "title:skyline trips",
+            "ty 2024",
+            "tokyo skyline",
+        ];
+        for search in searches {
+            set_fts_search(false);
+            let (like_where, like_paths) = matching(&conn, search);
+            set_fts_search(true);
+            let (fts_where, fts_paths) = matching(&conn, search);
+            assert_eq!(fts_paths, like_paths, "results of '{}'", search);
+            assert!(!like_where.contains("MATCH"));
+            if search == "skyline 2023" {
+                assert!(fts_where.contains("kv1.id IN (SELECT rowid FROM key_value_fts WHERE key_value_fts MATCH ?1)"), "{}", fts_where);
+            }
+        }
+
+        // Deleted rows leave the index
+        conn.execute("DELETE FROM key_value WHERE value LIKE 'Skyline of New York%'", []).unwrap();
+        assert_eq!(matching(&conn, "skyline").1, ["/photos/c.jpg.xmp"]);
+        set_fts_search(false);
+    }
+}
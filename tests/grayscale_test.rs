@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use clap::Parser;
+    use image::{ColorType, DynamicImage, GrayImage, Luma};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::image::generate_thumbnail;
+    use image_find::processing::jpeg::{encode_baseline, encode_progressive};
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    fn init_cli_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/grayscale_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/grayscale_test_thumbs",
+            "--full-image-cache", "tests/tmp/grayscale_test_full",
+            "--video-preview-cache", "tests/tmp/grayscale_test_video",
+            "--scan-dir", "tests/data",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    // Text-like stripes, as on a scanned page
+    fn scanned_page(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| Luma([if (x / 7 + y / 11) % 3 == 0 { 30 } else { 235 }]))
+    }
+
+    fn thumbnail_color(file_path: &str) -> ColorType {
+        let thumbnail = generate_thumbnail(file_path).expect("Thumbnail generation failed");
+        let bytes = BASE64.decode(thumbnail).unwrap();
+        image::load_from_memory(&bytes).expect("Thumbnail does not decode").color()
+    }
+
+    #[test]
+    fn test_grayscale_jpeg_thumbnail_stays_grayscale() {
+        init_cli_args();
+        fs::create_dir_all("tests/tmp").unwrap();
+        let path = "tests/tmp/grayscale_test_scan.jpg";
+        scanned_page(1200, 1600).save(path).unwrap();
+
+        assert_eq!(thumbnail_color(path), ColorType::L8);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_grayscale_tiff_thumbnail_stays_grayscale() {
+        init_cli_args();
+        fs::create_dir_all("tests/tmp").unwrap();
+        let path = "tests/tmp/grayscale_test_scan.tif";
+        let page = scanned_page(1200, 1600);
+        TiffEncoder::new(File::create(path).unwrap())
+            .unwrap()
+            .write_image::<colortype::Gray8>(page.width(), page.height(), page.as_raw())
+            .unwrap();
+
+        assert_eq!(thumbnail_color(path), ColorType::L8);
+
+        let _ = fs::remove_file(path);
+    }
+
+    // Both encoders write one channel for grayscale input, which is smaller than the RGB equivalent
+    #[test]
+    fn test_grayscale_encoding_is_single_channel() {
+        let gray = DynamicImage::ImageLuma8(scanned_page(400, 300));
+        let rgb = DynamicImage::ImageRgb8(gray.to_rgb8());
+
+        let baseline = encode_baseline(&gray, 50).unwrap();
+        assert_eq!(image::load_from_memory(&baseline).unwrap().color(), ColorType::L8);
+        assert!(baseline.len() < encode_baseline(&rgb, 50).unwrap().len());
+
+        if let Ok(progressive) = encode_progressive(&gray, 50) {
+            let decoded = image::load_from_memory(&progressive).unwrap();
+            assert_eq!(decoded.color(), ColorType::L8);
+            assert_eq!((decoded.width(), decoded.height()), (400, 300));
+            assert!(progressive.len() < encode_progressive(&rgb, 50).unwrap().len());
+        }
+    }
+}
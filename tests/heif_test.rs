@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::db::run_migrations;
+    use image_find::failures::list_failures;
+    use image_find::processing::heif::{heif_decode_to_jpeg, is_heif_extension, HeifError};
+    use image_find::processing::image::{generate_preview, generate_thumbnail};
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/heif_test";
+
+    #[test]
+    fn test_is_heif_extension() {
+        for extension in ["heic", "heif", "hif"] {
+            assert!(is_heif_extension(extension), "{} is HEIF", extension);
+        }
+        assert!(!is_heif_extension("jpg"));
+        assert!(!is_heif_extension("avif"));
+    }
+
+    // A file libheif cannot decode, or any HEIC file without libheif installed, gets no
+    // thumbnail or preview instead of an error. Only a real decode failure is recorded, the
+    // missing decoder is not reported again for every file
+    #[test]
+    fn test_undecodable_heic() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/heif_test/thumbs",
+            "--full-image-cache", "tests/tmp/heif_test/full",
+            "--video-preview-cache", "tests/tmp/heif_test/video",
+            "--scan-dir", ROOT,
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let mut conn = Connection::open(&db_path).unwrap();
+        run_migrations(&mut conn).unwrap();
+        let photo = "tests/tmp/heif_test/IMG_0001.HEIC";
+        fs::write(photo, b"not a heif file").unwrap();
+
+        let decoded = heif_decode_to_jpeg(photo);
+        assert!(decoded.is_err());
+        assert_eq!(generate_thumbnail(photo), None);
+        assert_eq!(generate_preview(photo), None);
+
+        let failures = list_failures(&conn, None, 10).unwrap();
+        if decoded == Err(HeifError::DecoderMissing) {
+            assert!(failures.is_empty(), "A missing decoder is not a failure of the file");
+        } else {
+            assert_eq!(failures.len(), 2, "Thumbnail and preview failures are recorded");
+        }
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,46 @@
+#[cfg(test)]
+mod tests {
+    use image_find::db::run_migrations;
+    use image_find::history::{clear_history, record_search, recent_searches, HistoryEntry};
+    use rusqlite::Connection;
+
+    fn queries(conn: &Connection, limit: usize) -> Vec<String> {
+        recent_searches(conn, limit).unwrap().into_iter().map(|entry| entry.query).collect()
+    }
+
+    // Repeated terms are listed once, at the time of their latest search
+    #[test]
+    fn test_recent_searches_are_distinct() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run_migrations(&mut conn).unwrap();
+
+        record_search(&conn, "beach", 12, 1000).unwrap();
+        record_search(&conn, "  ", 0, 1001).unwrap();
+        record_search(&conn, "rome", 3, 1002).unwrap();
+        record_search(&conn, " beach ", 10, 1003).unwrap();
+
+        assert_eq!(queries(&conn, 10), ["beach", "rome"]);
+        assert_eq!(queries(&conn, 1), ["beach"]);
+        assert_eq!(
+            recent_searches(&conn, 1).unwrap()[0],
+            HistoryEntry { query: "beach".to_string(), last_searched: 1003, result_count: 10, search_count: 2 }
+        );
+
+        assert_eq!(clear_history(&conn).unwrap(), 2);
+        assert!(queries(&conn, 10).is_empty());
+    }
+
+    // Only the most recently searched terms are kept
+    #[test]
+    fn test_history_is_bounded() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run_migrations(&mut conn).unwrap();
+        for i in 0..1005 {
+            record_search(&conn, &format!("term {}", i), i, i as i64).unwrap();
+        }
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM search_history", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1000);
+        assert_eq!(queries(&conn, 1), ["term 1004"]);
+        assert!(!queries(&conn, 1000).contains(&"term 4".to_string()));
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{generate_cache_key, preview_cache_key, sized_preview_cache_key, thumbnail_cache_key};
+    use image_find::processing::image::{quality_settings, QualitySettings};
+
+    const ROOT: &str = "tests/tmp/jpeg_quality_test";
+
+    // --thumbnail-quality and --preview-quality replace the defaults everywhere but in
+    // --quality-override directories, and their files are cached apart from default ones
+    #[test]
+    fn test_jpeg_quality() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/jpeg_quality_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/jpeg_quality_test/thumbs",
+            "--full-image-cache", "tests/tmp/jpeg_quality_test/full",
+            "--video-preview-cache", "tests/tmp/jpeg_quality_test/video",
+            "--scan-dir", ROOT,
+            "--thumbnail-quality", "90",
+            "--preview-quality", "85",
+            "--quality-override", "tests/tmp/jpeg_quality_test/docs:preview_quality=40",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let parses = |option: &str, quality: &str| {
+            CliArgs::try_parse_from([
+                "image_find", "--db-path", "db.sqlite", "--thumbnail-cache", "thumbs", "--full-image-cache", "full",
+                "--video-preview-cache", "video", "--scan-dir", ROOT, option, quality,
+            ])
+            .is_ok()
+        };
+        for option in ["--thumbnail-quality", "--preview-quality"] {
+            assert!(parses(option, "1") && parses(option, "100"));
+            for quality in ["0", "101", "high"] {
+                assert!(!parses(option, quality), "{} {} should be rejected", option, quality);
+            }
+        }
+
+        let photo = "tests/tmp/jpeg_quality_test/photo.jpg";
+        assert_eq!(quality_settings(photo), QualitySettings { thumbnail_quality: 90, thumbnail_size: 200, preview_quality: 85 });
+        assert_eq!(
+            quality_settings("tests/tmp/jpeg_quality_test/docs/scan.jpg"),
+            QualitySettings { thumbnail_quality: 90, thumbnail_size: 200, preview_quality: 40 }
+        );
+        assert_ne!(thumbnail_cache_key(photo), generate_cache_key(photo));
+        assert_ne!(preview_cache_key(photo), generate_cache_key(photo));
+        assert_ne!(sized_preview_cache_key(photo, 640), generate_cache_key(&format!("{}#w640", photo)));
+    }
+}
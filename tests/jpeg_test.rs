@@ -0,0 +1,39 @@
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbImage};
+    use image_find::processing::jpeg::{encode_baseline, encode_progressive};
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(320, 240, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8])
+        }))
+    }
+
+    // Start-of-frame marker: 0xC0 baseline, 0xC2 progressive
+    fn has_marker(jpeg: &[u8], marker: u8) -> bool {
+        jpeg.windows(2).any(|w| w == [0xFF, marker])
+    }
+
+    #[test]
+    fn test_baseline_jpeg() {
+        let jpeg = encode_baseline(&gradient(), 60).expect("Baseline encoding failed");
+        assert!(has_marker(&jpeg, 0xC0));
+        assert!(!has_marker(&jpeg, 0xC2));
+    }
+
+    #[cfg(feature = "progressive-jpeg")]
+    #[test]
+    fn test_progressive_jpeg_decodes() {
+        let jpeg = encode_progressive(&gradient(), 60).expect("Progressive encoding failed");
+        assert!(has_marker(&jpeg, 0xC2));
+
+        let decoded = image::load_from_memory(&jpeg).expect("Progressive JPEG did not decode");
+        assert_eq!((decoded.width(), decoded.height()), (320, 240));
+    }
+
+    #[cfg(not(feature = "progressive-jpeg"))]
+    #[test]
+    fn test_progressive_jpeg_unavailable() {
+        assert!(encode_progressive(&gradient(), 60).is_err());
+    }
+}
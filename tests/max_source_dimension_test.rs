@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::image::{generate_preview, generate_thumbnail, oversized_source};
+    use image_find::processing::tiff::decode_tiff_rgb;
+    use tiff::encoder::{colortype, TiffEncoder};
+    use tiff::tags::Tag;
+
+    const ROOT: &str = "tests/tmp/max_source_dimension_test";
+
+    // Sources beyond --max-source-dimension are refused from their header; TIFFs fall back to an overview
+    #[test]
+    fn test_max_source_dimension() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/max_source_dimension_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/max_source_dimension_test/thumbs",
+            "--full-image-cache", "tests/tmp/max_source_dimension_test/full",
+            "--video-preview-cache", "tests/tmp/max_source_dimension_test/video",
+            "--scan-dir", "tests/tmp/max_source_dimension_test",
+            "--max-source-dimension", "1000",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+
+        let wide = format!("{}/wide.png", ROOT);
+        RgbImage::from_pixel(1200, 300, Rgb([90, 90, 90])).save(&wide).unwrap();
+        assert_eq!(oversized_source(&wide), Some((1200, 300)));
+        assert!(generate_preview(&wide).is_none());
+        assert!(generate_thumbnail(&wide).is_none());
+
+        let small = format!("{}/small.png", ROOT);
+        RgbImage::from_pixel(1000, 800, Rgb([90, 90, 90])).save(&small).unwrap();
+        assert_eq!(oversized_source(&small), None);
+        assert!(generate_preview(&small).is_some());
+
+        // Only the 600x450 overview is within the limit, so it is used even for previews
+        let pyramid = format!("{}/pyramid.tif", ROOT);
+        {
+            let mut encoder = TiffEncoder::new(File::create(&pyramid).unwrap()).unwrap();
+            encoder.write_image::<colortype::RGB8>(1600, 1200, &[200u8, 0, 0].repeat(1600 * 1200)).unwrap();
+            let mut overview = encoder.new_image::<colortype::RGB8>(600, 450).unwrap();
+            overview.encoder().write_tag(Tag::NewSubfileType, 1u32).unwrap();
+            overview.write_data(&[0u8, 200, 0].repeat(600 * 450)).unwrap();
+        }
+        assert_eq!(oversized_source(&pyramid), None);
+        let (source, _) = decode_tiff_rgb(&pyramid, 1980, false, u64::MAX).expect("Decode failed");
+        assert_eq!(source.dimensions(), (600, 450));
+
+        let flat = format!("{}/flat.tif", ROOT);
+        {
+            let mut encoder = TiffEncoder::new(File::create(&flat).unwrap()).unwrap();
+            encoder.write_image::<colortype::RGB8>(1600, 1200, &[200u8, 0, 0].repeat(1600 * 1200)).unwrap();
+        }
+        assert_eq!(oversized_source(&flat), Some((1600, 1200)));
+        assert!(decode_tiff_rgb(&flat, 200, false, u64::MAX).is_err());
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
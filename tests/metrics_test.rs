@@ -0,0 +1,22 @@
+#[cfg(test)]
+mod tests {
+    use image_find::metrics::{record_cache_lookup, render, time_generation, METRICS};
+
+    #[test]
+    fn test_generation_outcomes_are_counted() {
+        assert_eq!(time_generation("test", || Some(1)), Some(1));
+        assert_eq!(time_generation::<()>("test", || None), None);
+
+        assert_eq!(METRICS.generated.with_label_values(&["test", "success"]).get(), 1);
+        assert_eq!(METRICS.generated.with_label_values(&["test", "failure"]).get(), 1);
+        assert_eq!(METRICS.generation_seconds.with_label_values(&["test"]).get_sample_count(), 2);
+    }
+
+    #[test]
+    fn test_render_text_format() {
+        record_cache_lookup("thumbnail", true);
+        let body = render().expect("Failed to render metrics");
+        assert!(body.contains("# TYPE imagefind_cache_lookups_total counter"));
+        assert!(body.contains(r#"imagefind_cache_lookups_total{cache="thumbnail",result="hit"}"#));
+    }
+}
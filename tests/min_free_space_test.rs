@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use clap::Parser;
+    use image_find::cli::{parse_min_free_bytes, CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{
+        available_space, cache_file_path, cache_has_free_space, get_cache_dir, has_free_space, save_thumbnail_to_cache,
+    };
+
+    fn init_cli_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/min_free_space_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/min_free_space_test_thumbs",
+            "--full-image-cache", "tests/tmp/min_free_space_test_full",
+            "--video-preview-cache", "tests/tmp/min_free_space_test_video",
+            "--scan-dir", "tests/data",
+            "--min-free-bytes", "1000000TB",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    #[test]
+    fn test_parse_min_free_bytes() {
+        assert_eq!(parse_min_free_bytes("0"), Ok(0));
+        assert_eq!(parse_min_free_bytes("500MB"), Ok(500 << 20));
+        assert_eq!(parse_min_free_bytes("2gb"), Ok(2 << 30));
+        assert!(parse_min_free_bytes("lots").is_err());
+    }
+
+    // Paths that do not exist yet are measured on their closest existing ancestor
+    #[cfg(unix)]
+    #[test]
+    fn test_available_space() {
+        let available = available_space(Path::new("tests/tmp/min_free_space_test_missing/a/b")).unwrap();
+        assert!(available > 0);
+        assert!(has_free_space(Path::new("tests"), 0));
+        assert!(has_free_space(Path::new("tests"), 1));
+        assert!(!has_free_space(Path::new("tests"), u64::MAX));
+    }
+
+    // Below the threshold generated thumbnails are not written, and an outdated entry is dropped
+    #[cfg(unix)]
+    #[test]
+    fn test_low_space_skips_cache_writes() {
+        init_cli_args();
+        let cache_dir = get_cache_dir();
+        let cache_file = cache_file_path(&cache_dir, "low_space", "jpg");
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(&cache_file, b"outdated jpeg bytes").unwrap();
+
+        assert!(!cache_has_free_space(&cache_dir));
+        save_thumbnail_to_cache("low_space", b"new jpeg bytes").unwrap();
+        assert!(!cache_file.exists());
+    }
+}
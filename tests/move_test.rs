@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::albums::{add_to_album, album_files, create_album};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{cache_file_path, generate_cache_key, get_cache_dir};
+    use image_find::relocate::{move_image, MoveError};
+    use image_find::sidecar_scan::scan_and_import_sidecars;
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/move_test";
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="4"/>
+</rdf:RDF></x:xmpmeta>"#;
+
+    fn file_path(conn: &Connection, file_id: i64) -> String {
+        conn.query_row("SELECT path FROM file WHERE id = ?1", [file_id], |row| row.get(0)).unwrap()
+    }
+
+    // The image, its sidecar, its row and its cached thumbnail move together; failed moves change nothing
+    #[test]
+    fn test_move_image() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/move_test/thumbs",
+            "--full-image-cache", "tests/tmp/move_test/full",
+            "--video-preview-cache", "tests/tmp/move_test/video",
+            "--scan-dir", "tests/tmp/move_test/lib",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let from = format!("{}/lib/a.jpg", ROOT);
+        let to = format!("{}/lib/2024/b.jpg", ROOT);
+        fs::write(&from, b"image").unwrap();
+        fs::write(format!("{}.XMP", from), SIDECAR).unwrap();
+        fs::write(format!("{}/lib/c.jpg", ROOT), b"other image").unwrap();
+        fs::write(format!("{}/lib/blocker", ROOT), b"not a directory").unwrap();
+        scan_and_import_sidecars().unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let file_id: i64 = conn.query_row("SELECT id FROM file", [], |row| row.get(0)).unwrap();
+        let album = create_album(&conn, "Trip").unwrap();
+        add_to_album(&conn, album, std::slice::from_ref(&from)).unwrap();
+        let thumbnail = cache_file_path(&get_cache_dir(), &generate_cache_key(&from), "jpg");
+        fs::create_dir_all(thumbnail.parent().unwrap()).unwrap();
+        fs::write(&thumbnail, b"thumbnail").unwrap();
+
+        let conflict = move_image(&conn, &from, &format!("{}/lib/c.jpg", ROOT));
+        assert!(matches!(conflict, Err(MoveError::Conflict(_))));
+        let failed = move_image(&conn, &from, &format!("{}/lib/blocker/b.jpg", ROOT));
+        assert!(matches!(failed, Err(MoveError::Failed(_))));
+        assert_eq!(file_path(&conn, file_id), format!("{}.XMP", from), "The failed move is rolled back");
+        assert!(fs::metadata(&from).is_ok());
+        assert!(matches!(move_image(&conn, &format!("{}/lib/x.jpg", ROOT), &to), Err(MoveError::NotFound(_))));
+
+        let moved = move_image(&conn, &from, &to).unwrap();
+        assert_eq!(moved.sidecars, [format!("{}.XMP", to)]);
+        assert_eq!(moved.cache_files, 1);
+        assert!(fs::metadata(&from).is_err() && fs::metadata(format!("{}.XMP", from)).is_err());
+        assert_eq!(fs::read(&to).unwrap(), b"image");
+        assert_eq!(fs::read_to_string(format!("{}.XMP", to)).unwrap(), SIDECAR);
+        assert_eq!(file_path(&conn, file_id), format!("{}.XMP", to));
+        let rating: String = conn
+            .query_row("SELECT value FROM key_value WHERE file_id = ?1 AND key LIKE '%xmp:Rating'", [file_id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rating, "4");
+        let members: Vec<String> = album_files(&conn, album, &[], &[]).unwrap().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(members, std::slice::from_ref(&to));
+        assert!(!thumbnail.exists());
+        assert_eq!(fs::read(cache_file_path(&get_cache_dir(), &generate_cache_key(&to), "jpg")).unwrap(), b"thumbnail");
+
+        // The next scan finds the sidecar where the index already has it
+        scan_and_import_sidecars().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM file", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, PreviewFormat, CLI_ARGS};
+    use image_find::processing::cache::{get_cached_preview, preview_variant_cache_key};
+    use image_find::processing::image::generate_preview_variant;
+    use image_find::routes::negotiate_preview_format;
+
+    const ROOT: &str = "tests/tmp/preview_format_test";
+
+    // --preview-formats avif,webp serves the first format the client accepts, each cached under its own key
+    #[test]
+    fn test_preview_formats() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/preview_format_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/preview_format_test/thumbs",
+            "--full-image-cache", "tests/tmp/preview_format_test/full",
+            "--video-preview-cache", "tests/tmp/preview_format_test/video",
+            "--scan-dir", "tests/tmp/preview_format_test",
+            "--preview-formats", "avif,webp",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        assert_eq!(negotiate_preview_format(None), None);
+        assert_eq!(negotiate_preview_format(Some("image/jpeg,*/*;q=0.8")), None);
+        assert_eq!(negotiate_preview_format(Some("image/*")), None, "Wildcards do not count as support");
+        assert_eq!(negotiate_preview_format(Some("image/webp,image/avif")), Some(PreviewFormat::Avif), "Server order wins");
+        assert_eq!(negotiate_preview_format(Some("image/avif;q=0, image/webp")), Some(PreviewFormat::Webp));
+
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+        let source_path = "tests/tmp/preview_format_test/gradient.png";
+        RgbImage::from_fn(96, 64, |x, y| Rgb([(x % 256) as u8, (y % 256) as u8, 128])).save(source_path).unwrap();
+
+        // A sized variant keeps the AVIF encoding quick in debug builds
+        let avif = generate_preview_variant(source_path, Some(128), PreviewFormat::Avif).expect("Failed to generate AVIF preview");
+        assert_eq!(&avif[4..12], b"ftypavif");
+        assert!(get_cached_preview(&preview_variant_cache_key(source_path, Some(128), PreviewFormat::Avif), source_path).is_some());
+        assert!(
+            get_cached_preview(&preview_variant_cache_key(source_path, None, PreviewFormat::Avif), source_path).is_none(),
+            "Widths are cached separately"
+        );
+
+        // Lossless WebP is only kept when it is smaller than the JPEG
+        let webp = generate_preview_variant(source_path, Some(128), PreviewFormat::Webp).expect("Failed to generate WebP preview");
+        assert!(webp.starts_with(&[0xFF, 0xD8]) || &webp[8..12] == b"WEBP");
+        assert_eq!(generate_preview_variant(source_path, Some(128), PreviewFormat::Webp), Some(webp), "Served from the cache");
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
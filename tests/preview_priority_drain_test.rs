@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App};
+    use clap::Parser;
+    use image_find::background::background_workers_running;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{get_cached_preview, preview_cache_key};
+    use image_find::routes::api_prioritize_previews;
+
+    const ROOT: &str = "tests/tmp/preview_priority_drain_test";
+
+    // With no worker pass running, a prioritized path still gets its preview
+    #[actix_web::test]
+    async fn test_prioritized_preview_generated_while_workers_idle() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/preview_priority_drain_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/preview_priority_drain_test/thumbs",
+            "--full-image-cache", "tests/tmp/preview_priority_drain_test/full",
+            "--video-preview-cache", "tests/tmp/preview_priority_drain_test/video",
+            "--scan-dir", "tests/tmp/preview_priority_drain_test/lib",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let photo = format!("{}/lib/photo.png", ROOT);
+        image::RgbImage::from_fn(320, 240, |x, y| image::Rgb([x as u8, y as u8, 64])).save(&photo).unwrap();
+        assert!(!background_workers_running());
+
+        let app = test::init_service(
+            App::new().route("/api/prioritize-previews", web::post().to(api_prioritize_previews)),
+        )
+        .await;
+        let request = test::TestRequest::post()
+            .uri("/api/prioritize-previews")
+            .set_json(serde_json::json!({ "paths": [format!("{}.xmp", photo)] }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let started = Instant::now();
+        while get_cached_preview(&preview_cache_key(&photo), &photo).is_none() {
+            assert!(started.elapsed() < Duration::from_secs(30), "Prioritized preview was never generated");
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use image_find::background::{next_priority_preview, prioritize_previews};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+
+    fn paths(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn drain() -> Vec<String> {
+        std::iter::from_fn(next_priority_preview).collect()
+    }
+
+    // Newly prioritized paths go first in their given order; the oldest fall off when the queue is full
+    #[test]
+    fn test_preview_priority_queue() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/preview_priority_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/preview_priority_test/thumbs",
+            "--full-image-cache", "tests/tmp/preview_priority_test/full",
+            "--video-preview-cache", "tests/tmp/preview_priority_test/video",
+            "--scan-dir", "tests/tmp/preview_priority_test/lib",
+            "--preview-priority-queue", "3",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        assert_eq!(prioritize_previews(&paths(&["/photos/b.jpg", "/photos/c.jpg"])), 2);
+        assert_eq!(prioritize_previews(&paths(&["/photos/a.jpg"])), 1);
+        assert_eq!(drain(), ["/photos/a.jpg", "/photos/b.jpg", "/photos/c.jpg"]);
+        assert_eq!(next_priority_preview(), None);
+
+        // Requeueing moves a path up instead of duplicating it
+        prioritize_previews(&paths(&["/photos/a.jpg", "/photos/b.jpg", "/photos/c.jpg"]));
+        prioritize_previews(&paths(&["/photos/c.jpg"]));
+        assert_eq!(drain(), ["/photos/c.jpg", "/photos/a.jpg", "/photos/b.jpg"]);
+
+        prioritize_previews(&paths(&["/photos/a.jpg", "/photos/b.jpg", "/photos/c.jpg"]));
+        assert_eq!(prioritize_previews(&paths(&["/photos/d.jpg", "/photos/e.jpg", "/photos/f.jpg", "/photos/g.jpg"])), 3);
+        assert_eq!(drain(), ["/photos/d.jpg", "/photos/e.jpg", "/photos/f.jpg"]);
+    }
+}
@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use image_find::background::{mark_viewed, preview_candidates};
+    use image_find::cli::PreviewStrategy;
+    use image_find::db::run_migrations;
+    use rusqlite::Connection;
+
+    // Only viewed files are previewed with "viewed", most recently viewed first, and none with "on-demand"
+    #[test]
+    fn test_preview_candidates() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        for path in ["/photos/a.jpg.xmp", "/photos/b.jpg.xmp", "/photos/c.jpg.xmp"] {
+            conn.execute("INSERT INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+        }
+
+        assert_eq!(
+            preview_candidates(&conn, PreviewStrategy::All).unwrap(),
+            ["/photos/a.jpg.xmp", "/photos/b.jpg.xmp", "/photos/c.jpg.xmp"]
+        );
+        assert!(preview_candidates(&conn, PreviewStrategy::Viewed).unwrap().is_empty());
+
+        assert_eq!(mark_viewed(&conn, "/photos/c.jpg", 1700000000).unwrap(), 1);
+        assert_eq!(mark_viewed(&conn, "/photos/a.jpg", 1700000100).unwrap(), 1);
+        // The first view is kept
+        assert_eq!(mark_viewed(&conn, "/photos/c.jpg", 1700000200).unwrap(), 0);
+        assert_eq!(mark_viewed(&conn, "/photos/missing.jpg", 1700000300).unwrap(), 0);
+
+        assert_eq!(
+            preview_candidates(&conn, PreviewStrategy::Viewed).unwrap(),
+            ["/photos/a.jpg.xmp", "/photos/c.jpg.xmp"]
+        );
+        assert!(preview_candidates(&conn, PreviewStrategy::OnDemand).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::albums::{add_to_album, album_files, create_album};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::sidecar_scan::{prune_missing_files, scan_and_import_sidecars};
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/prune_test";
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="4"/>
+</rdf:RDF></x:xmpmeta>"#;
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    // Rows of deleted sidecars go with their key-values and album entries; the others stay
+    #[test]
+    fn test_prune_missing_files() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/prune_test/thumbs",
+            "--full-image-cache", "tests/tmp/prune_test/full",
+            "--video-preview-cache", "tests/tmp/prune_test/video",
+            "--scan-dir", "tests/tmp/prune_test/lib",
+            "prune",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let kept = format!("{}/lib/kept.jpg", ROOT);
+        let deleted = format!("{}/lib/deleted.jpg", ROOT);
+        for image in [&kept, &deleted] {
+            fs::write(image, b"image").unwrap();
+            fs::write(format!("{}.xmp", image), SIDECAR).unwrap();
+        }
+        scan_and_import_sidecars().unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        let album = create_album(&conn, "Trip").unwrap();
+        add_to_album(&conn, album, &[kept.clone(), deleted.clone()]).unwrap();
+        let key_values = count(&conn, "key_value");
+
+        assert_eq!(prune_missing_files().unwrap(), 0, "Nothing is missing yet");
+        fs::remove_file(format!("{}.xmp", deleted)).unwrap();
+        assert_eq!(prune_missing_files().unwrap(), 1);
+
+        assert_eq!(count(&conn, "file"), 1);
+        assert_eq!(count(&conn, "key_value"), key_values / 2);
+        let files = album_files(&conn, album, &[], &[]).unwrap();
+        assert_eq!(files.len(), 1);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
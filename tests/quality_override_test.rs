@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{parse_quality_override, CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{generate_cache_key, preview_cache_key, thumbnail_cache_key};
+    use image_find::processing::image::{generate_thumbnail, quality_settings, QualitySettings};
+
+    const ROOT: &str = "tests/tmp/quality_override_test";
+
+    #[test]
+    fn test_parse_quality_override() {
+        let parsed = parse_quality_override("C:\\Photos:thumbnail_quality=85, preview_quality=90").unwrap();
+        assert_eq!(parsed.prefix, "C:\\Photos");
+        assert_eq!((parsed.thumbnail_quality, parsed.thumbnail_size, parsed.preview_quality), (Some(85), None, Some(90)));
+        for value in ["/photos", ":thumbnail_quality=85", "/photos:thumbnail_quality=0", "/photos:thumbnail_size=5000", "/photos:sharpness=2"] {
+            assert!(parse_quality_override(value).is_err(), "'{}' should be rejected", value);
+        }
+    }
+
+    // The longest matching prefix decides the settings, and the cache key includes them
+    #[test]
+    fn test_quality_overrides() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/quality_override_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/quality_override_test/thumbs",
+            "--full-image-cache", "tests/tmp/quality_override_test/full",
+            "--video-preview-cache", "tests/tmp/quality_override_test/video",
+            "--scan-dir", "tests/tmp/quality_override_test",
+            "--quality-override", "tests/tmp/quality_override_test/docs:thumbnail_quality=90,thumbnail_size=120",
+            "--quality-override", "tests/tmp/quality_override_test/docs/drafts/:thumbnail_quality=20",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let defaults = QualitySettings { thumbnail_quality: 50, thumbnail_size: 200, preview_quality: 60 };
+        assert_eq!(quality_settings("tests/tmp/quality_override_test/docs/a.png").thumbnail_size, 120);
+        assert_eq!(
+            quality_settings("tests/tmp/quality_override_test/docs/drafts/a.png"),
+            QualitySettings { thumbnail_quality: 20, ..defaults },
+            "Settings the longest prefix leaves out keep their defaults"
+        );
+        assert_eq!(quality_settings("tests/tmp/quality_override_test/docs2/a.png"), defaults, "Prefixes match whole components");
+
+        let other = "tests/tmp/quality_override_test/other.png";
+        assert_eq!(thumbnail_cache_key(other), generate_cache_key(other), "Keys without an override are unchanged");
+        let doc = "tests/tmp/quality_override_test/docs/a.png";
+        assert_ne!(thumbnail_cache_key(doc), generate_cache_key(doc));
+        assert_eq!(preview_cache_key(doc), generate_cache_key(doc), "The preview quality is not overridden");
+
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/docs", ROOT)).unwrap();
+        RgbImage::from_pixel(800, 600, Rgb([20, 60, 200])).save(doc).unwrap();
+        let thumbnail = generate_thumbnail(doc).expect("Failed to generate thumbnail");
+        let decoded = image::load_from_memory(&BASE64.decode(thumbnail).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (120, 90));
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::badge::draw_rating_stars;
+    use image_find::processing::cache::{badged_thumbnail_cache_key, thumbnail_cache_key, thumbnail_exists_in_cache};
+    use image_find::processing::image::{generate_badged_thumbnail, generate_thumbnail};
+
+    const ROOT: &str = "tests/tmp/rating_badge_test";
+
+    // Number of pixels in a row that are clearly gold (the star fill)
+    fn gold_pixels(img: &RgbImage, y: u32) -> usize {
+        (0..img.width())
+            .filter(|&x| {
+                let [r, g, b] = img.get_pixel(x, y).0;
+                r > 200 && g > 150 && b < 100
+            })
+            .count()
+    }
+
+    // Stars are drawn in the bottom-left corner, one per rating point, on a darkened strip
+    #[test]
+    fn test_draw_rating_stars() {
+        let row = 200 - 4 - 7;
+        let mut unrated = RgbImage::from_pixel(200, 200, Rgb([255, 255, 255]));
+        draw_rating_stars(&mut unrated, 0, 1);
+        assert_eq!(unrated, RgbImage::from_pixel(200, 200, Rgb([255, 255, 255])));
+
+        let mut one = RgbImage::from_pixel(200, 200, Rgb([255, 255, 255]));
+        draw_rating_stars(&mut one, 1, 1);
+        let mut three = RgbImage::from_pixel(200, 200, Rgb([255, 255, 255]));
+        draw_rating_stars(&mut three, 3, 1);
+        assert!(gold_pixels(&one, row) > 0);
+        assert!(gold_pixels(&three, row) >= 3 * gold_pixels(&one, row));
+        assert!(three.get_pixel(1, 199).0[0] < 200, "The strip behind the stars is darkened");
+        assert_eq!(three.get_pixel(199, 0).0, [255, 255, 255], "The rest of the thumbnail is untouched");
+
+        // Too small to show stars
+        let mut tiny = RgbImage::from_pixel(12, 12, Rgb([255, 255, 255]));
+        draw_rating_stars(&mut tiny, 5, 1);
+        assert_eq!(tiny, RgbImage::from_pixel(12, 12, Rgb([255, 255, 255])));
+    }
+
+    // The badged thumbnail is cached per rating next to the plain one
+    #[test]
+    fn test_badged_thumbnail() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/rating_badge_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/rating_badge_test/thumbs",
+            "--full-image-cache", "tests/tmp/rating_badge_test/full",
+            "--video-preview-cache", "tests/tmp/rating_badge_test/video",
+            "--scan-dir", "tests/tmp/rating_badge_test",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+        let source_path = format!("{}/blue.png", ROOT);
+        RgbImage::from_pixel(600, 400, Rgb([0, 0, 160])).save(&source_path).unwrap();
+
+        assert_ne!(badged_thumbnail_cache_key(&source_path, 1, 4), thumbnail_cache_key(&source_path));
+        assert_ne!(badged_thumbnail_cache_key(&source_path, 1, 4), badged_thumbnail_cache_key(&source_path, 1, 3));
+
+        let badged = generate_badged_thumbnail(&source_path, 1, false, 4).expect("Failed to generate badged thumbnail");
+        assert!(thumbnail_exists_in_cache(&badged_thumbnail_cache_key(&source_path, 1, 4)));
+        assert!(thumbnail_exists_in_cache(&thumbnail_cache_key(&source_path)), "The plain thumbnail is cached too");
+
+        let plain = image::load_from_memory(&BASE64.decode(generate_thumbnail(&source_path).unwrap()).unwrap()).unwrap().to_rgb8();
+        let badged = image::load_from_memory(&BASE64.decode(badged).unwrap()).unwrap().to_rgb8();
+        assert_eq!(badged.dimensions(), plain.dimensions());
+        let row = badged.height() - 4 - 7;
+        assert_eq!(gold_pixels(&plain, row), 0);
+        assert!(gold_pixels(&badged, row) > 0);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
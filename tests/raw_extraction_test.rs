@@ -1,28 +1,33 @@
 #[cfg(test)]
 mod tests {
-    use image;
     use std::fs;
     use std::path::Path;
     use walkdir::WalkDir;
 
     // Import the actual processing functions from our codebase
-    use image_find::cli::{init_logging, CliArgs, LogLevel, CLI_ARGS};
-    use image_find::processing::raw::{generate_raw_preview, generate_raw_thumbnail};
+    use clap::Parser;
+    use image_find::cli::{init_logging, CliArgs, CLI_ARGS};
+    use image_find::processing::raw::{
+        best_preview_file, generate_raw_preview, generate_raw_thumbnail, parse_preview_list, scale_jpeg_bytes,
+        smallest_preview_covering, EmbeddedPreview,
+    };
 
     // Test the problematic NEF file specifically
     #[test]
     fn test_jpeg_extraction() {
         // Initialize app logging via CliArgs at TRACE level, and set test cache paths
-        let _ = (|| {
-            let args = CliArgs {
-                db_path: "tests/tmp/test.sqlite".to_string(),
-                thumbnail_cache: "tests/tmp/thumb_cache".to_string(),
-                full_image_cache: "tests/tmp/full_cache".to_string(),
-                video_preview_cache: "tests/tmp/video_preview_cache".to_string(),
-                scan_dir: "tests/data".to_string(),
-                log_level: LogLevel::Trace,
-                port: 8080,
-            };
+        let _ = {
+            // Parse like the real binary so newly added options pick up their defaults
+            let args = CliArgs::parse_from([
+                "image_find",
+                "--db-path", "tests/tmp/test.sqlite",
+                "--thumbnail-cache", "tests/tmp/thumb_cache",
+                "--full-image-cache", "tests/tmp/full_cache",
+                "--video-preview-cache", "tests/tmp/video_preview_cache",
+                "--scan-dir", "tests/data",
+                "--log-level", "trace",
+                "--port", "8080",
+            ]);
 
             // Ensure directories exist
             let _ = fs::create_dir_all(&args.thumbnail_cache);
@@ -34,7 +39,7 @@ mod tests {
             let _ = CLI_ARGS.set(args.clone());
             init_logging(&args);
             Ok::<(), ()>(())
-        })();
+        };
 
         log::trace!("TRACE logging initialized for tests via CliArgs");
 
@@ -70,7 +75,7 @@ mod tests {
             println!("Testing JPEG extraction from: {}", test_file);
 
             // Thumbnail generation
-            match generate_raw_thumbnail(&test_file) {
+            match generate_raw_thumbnail(&test_file, 1) {
                 Some(thumbnail_base64) => {
                     println!(
                         "Successfully generated thumbnail, base64 length: {}",
@@ -185,4 +190,96 @@ mod tests {
         assert!(tested > 0, "No RAW files found in tests/data to test");
         println!("All RAW extraction tests passed for {} files!", tested);
     }
+
+    fn jpeg_dimensions(bytes: &[u8]) -> (u32, u32) {
+        let img = image::load_from_memory(bytes).expect("Scaled bytes are not an image");
+        (img.width(), img.height())
+    }
+
+    // Extracted previews are scaled down to the target but never up
+    #[test]
+    fn test_extracted_previews_are_not_upscaled() {
+        let mut embedded = Vec::new();
+        image::RgbImage::from_pixel(1620, 1080, image::Rgb([120, 80, 40]))
+            .write_to(&mut std::io::Cursor::new(&mut embedded), image::ImageFormat::Jpeg)
+            .unwrap();
+
+        assert_eq!(jpeg_dimensions(&scale_jpeg_bytes(&embedded, 1980, 60, false, false).unwrap()), (1620, 1080));
+        assert_eq!(jpeg_dimensions(&scale_jpeg_bytes(&embedded, 1024, 60, false, false).unwrap()), (1024, 683));
+        assert_eq!(jpeg_dimensions(&scale_jpeg_bytes(&embedded, 200, 50, false, true).unwrap()), (200, 133));
+        assert_eq!(jpeg_dimensions(&scale_jpeg_bytes(&embedded, 200, 50, true, true).unwrap()), (200, 200));
+        assert_eq!(jpeg_dimensions(&scale_jpeg_bytes(&embedded, 2000, 50, true, true).unwrap()), (1080, 1080));
+
+        // Thumbnail-sized embedded previews are re-encoded as they are, like small standard images
+        let mut small = Vec::new();
+        image::RgbImage::from_pixel(320, 240, image::Rgb([120, 80, 40]))
+            .write_to(&mut std::io::Cursor::new(&mut small), image::ImageFormat::Jpeg)
+            .unwrap();
+        assert_eq!(jpeg_dimensions(&scale_jpeg_bytes(&small, 200, 50, false, true).unwrap()), (320, 240));
+    }
+
+    fn write_jpeg(path: &Path, image: &image::RgbImage, quality: u8) {
+        let mut bytes = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(image).unwrap();
+        fs::write(path, bytes).unwrap();
+    }
+
+    // The preview with the most pixels wins, even when a smaller one takes more bytes
+    #[test]
+    fn test_best_preview_is_largest_dimension() {
+        let dir = Path::new("tests/tmp/best_preview_test");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        // Flat color compresses to a few KB at full size; noise at high quality does not
+        let large = dir.join("raw-preview3.jpg");
+        write_jpeg(&large, &image::RgbImage::from_pixel(1600, 1200, image::Rgb([90, 120, 150])), 30);
+        let noisy = image::RgbImage::from_fn(640, 480, |x, y| {
+            let v = (x.wrapping_mul(7919) ^ y.wrapping_mul(104729)).wrapping_mul(2654435761) >> 24;
+            image::Rgb([v as u8, (v >> 1) as u8, (v >> 2) as u8])
+        });
+        let medium = dir.join("raw-preview2.jpg");
+        write_jpeg(&medium, &noisy, 100);
+        let small = dir.join("raw-preview1.jpg");
+        write_jpeg(&small, &image::RgbImage::from_pixel(160, 120, image::Rgb([90, 120, 150])), 90);
+        assert!(fs::metadata(&medium).unwrap().len() > fs::metadata(&large).unwrap().len());
+
+        let candidates = vec![small.clone(), medium.clone(), large.clone()];
+        assert_eq!(best_preview_file(candidates), Some(large.clone()));
+
+        // Files without a readable header only count by their size
+        let broken = dir.join("raw-preview4.jpg");
+        fs::write(&broken, vec![0u8; 4 * 1024 * 1024]).unwrap();
+        assert_eq!(best_preview_file(vec![broken.clone(), small.clone()]), Some(small.clone()));
+        let other_broken = dir.join("raw-preview5.jpg");
+        fs::write(&other_broken, vec![0u8; 1024]).unwrap();
+        assert_eq!(best_preview_file(vec![other_broken, broken.clone()]), Some(broken));
+        assert_eq!(best_preview_file(Vec::new()), None);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    // Thumbnails take the smallest preview whose shorter side covers them; non-JPEG previews are skipped
+    #[test]
+    fn test_smallest_preview_covering() {
+        let output = "Preview 1: image/jpeg, 160x120 pixels, 11248 bytes\n\
+                      Preview 2: image/tiff, 320x212 pixels, 203520 bytes\n\
+                      Preview 3: image/jpeg, 570x375 pixels, 46098 bytes\n\
+                      Preview 4: image/jpeg, 6016x4016 pixels, 2393568 bytes\n";
+        let previews = parse_preview_list(output);
+        assert_eq!(
+            previews,
+            [
+                EmbeddedPreview { number: 1, width: 160, height: 120 },
+                EmbeddedPreview { number: 3, width: 570, height: 375 },
+                EmbeddedPreview { number: 4, width: 6016, height: 4016 },
+            ]
+        );
+
+        assert_eq!(smallest_preview_covering(&previews, 100).map(|p| p.number), Some(1));
+        assert_eq!(smallest_preview_covering(&previews, 200).map(|p| p.number), Some(3));
+        assert_eq!(smallest_preview_covering(&previews, 400).map(|p| p.number), Some(4));
+        assert_eq!(smallest_preview_covering(&previews, 5000), None);
+        assert!(parse_preview_list("Exif data not found\n").is_empty());
+    }
 }
@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App};
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::db::run_migrations;
+    use image_find::routes::{get_thumbnail, rescan};
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/read_only_test";
+
+    // With --read-only, /rescan is refused and thumbnail requests do not mark files viewed
+    #[actix_web::test]
+    async fn test_read_only_leaves_database_alone() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/read_only_test/thumbs",
+            "--full-image-cache", "tests/tmp/read_only_test/full",
+            "--video-preview-cache", "tests/tmp/read_only_test/video",
+            "--scan-dir", "tests/tmp/read_only_test/lib",
+            "--preview-strategy", "viewed",
+            "--read-only",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let photo = format!("{}/lib/photo.png", ROOT);
+        image::RgbImage::from_pixel(64, 48, image::Rgb([10, 20, 30])).save(&photo).unwrap();
+        let mut conn = Connection::open(&db_path).unwrap();
+        run_migrations(&mut conn).unwrap();
+        conn.execute("INSERT INTO file (path, hash, image_path) VALUES (?1, 0, ?2)", [format!("{}.xmp", photo), photo.clone()])
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .route("/rescan", web::post().to(rescan))
+                .route("/thumbnail/{path:.*}", web::get().to(get_thumbnail)),
+        )
+        .await;
+
+        let response = test::call_service(&app, test::TestRequest::post().uri("/rescan").to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::get().uri(&format!("/thumbnail/{}", photo)).to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // Views are recorded in the background
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let viewed_at: Option<i64> = conn.query_row("SELECT viewed_at FROM file", [], |row| row.get(0)).unwrap();
+        assert_eq!(viewed_at, None);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::AtomicBool;
+
+    use clap::Parser;
+    use image_find::background::{regenerate_files, start_regeneration, RegenerateSummary, RegenerateTarget};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{
+        cache_file_path, get_cache_dir, get_preview_cache_dir, preview_cache_key, remove_cached_files, thumbnail_cache_key,
+    };
+    use image_find::processing::image::{generate_preview, generate_thumbnail};
+
+    fn init_cli_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/regenerate_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/regenerate_test_thumbs",
+            "--full-image-cache", "tests/tmp/regenerate_test_full",
+            "--video-preview-cache", "tests/tmp/regenerate_test_video",
+            "--scan-dir", "tests/data",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    // Cached files are replaced by freshly generated ones; files that cannot be generated count as failed
+    #[test]
+    fn test_regenerate_files() {
+        init_cli_args();
+        fs::create_dir_all("tests/tmp").unwrap();
+        let image_path = std::path::absolute("tests/tmp/regenerate_test.png").unwrap().to_string_lossy().to_string();
+        image::RgbImage::from_pixel(600, 400, image::Rgb([30, 120, 200])).save(&image_path).unwrap();
+        assert!(generate_thumbnail(&image_path).is_some());
+        assert!(generate_preview(&image_path).is_some());
+        let thumbnail_file = cache_file_path(&get_cache_dir(), &thumbnail_cache_key(&image_path), "jpg");
+        let preview_file = cache_file_path(&get_preview_cache_dir(), &preview_cache_key(&image_path), "jpg");
+        fs::write(&thumbnail_file, b"stale").unwrap();
+        fs::write(&preview_file, b"stale").unwrap();
+
+        // Only one regeneration at a time
+        let guard = start_regeneration().unwrap();
+        assert!(start_regeneration().is_none());
+
+        let missing = "/photos/missing.png".to_string();
+        let mut reported = Vec::new();
+        let summary = regenerate_files(
+            guard,
+            &[image_path.clone(), missing.clone()],
+            RegenerateTarget::Thumbnails,
+            &AtomicBool::new(false),
+            |file_path, regenerated| reported.push((file_path.to_string(), regenerated)),
+        );
+        assert_eq!(summary, RegenerateSummary { total: 2, regenerated: 1, failed: 1 });
+        assert_eq!(reported, [(image_path.clone(), true), (missing, false)]);
+        assert!(image::load_from_memory(&fs::read(&thumbnail_file).unwrap()).is_ok());
+        // Previews were not targeted
+        assert_eq!(fs::read(&preview_file).unwrap(), b"stale");
+
+        let guard = start_regeneration().unwrap();
+        let summary = regenerate_files(guard, std::slice::from_ref(&image_path), RegenerateTarget::Previews, &AtomicBool::new(false), |_, _| {});
+        assert_eq!(summary, RegenerateSummary { total: 1, regenerated: 1, failed: 0 });
+        assert!(image::load_from_memory(&fs::read(&preview_file).unwrap()).is_ok());
+
+        assert_eq!(remove_cached_files(&image_path, true, true), 2);
+        assert!(!thumbnail_file.exists() && !preview_file.exists());
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{cache_file_path, get_cache_dir, thumbnail_cache_key};
+    use image_find::sidecar_scan::scan_and_import_sidecars;
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/scan_move_test";
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="4"/>
+</rdf:RDF></x:xmpmeta>"#;
+
+    fn rows(conn: &Connection) -> Vec<(i64, String)> {
+        conn.prepare("SELECT id, path FROM file ORDER BY path")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn key_value_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM key_value", [], |row| row.get(0)).unwrap()
+    }
+
+    // A moved image and sidecar keep their row, key-values and cached thumbnail; a copy gets a new row
+    #[test]
+    fn test_scan_detects_moved_files() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib/2023", ROOT)).unwrap();
+        fs::create_dir_all(format!("{}/lib/Italy", ROOT)).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/scan_move_test/thumbs",
+            "--full-image-cache", "tests/tmp/scan_move_test/full",
+            "--video-preview-cache", "tests/tmp/scan_move_test/video",
+            "--scan-dir", "tests/tmp/scan_move_test/lib",
+            "index",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let old_image = format!("{}/lib/2023/rome.jpg", ROOT);
+        let new_image = format!("{}/lib/Italy/rome.jpg", ROOT);
+        fs::write(&old_image, b"rome image").unwrap();
+        fs::write(format!("{}.xmp", old_image), SIDECAR).unwrap();
+        // Same sidecar, different image: never taken for the moved file
+        let other_image = format!("{}/lib/2023/other.jpg", ROOT);
+        fs::write(&other_image, b"other").unwrap();
+        fs::write(format!("{}.xmp", other_image), SIDECAR).unwrap();
+        scan_and_import_sidecars().unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let before = rows(&conn);
+        let key_values = key_value_count(&conn);
+        let old_cache = cache_file_path(&get_cache_dir(), &thumbnail_cache_key(&old_image), "jpg");
+        fs::create_dir_all(old_cache.parent().unwrap()).unwrap();
+        fs::write(&old_cache, b"thumbnail").unwrap();
+
+        fs::rename(&old_image, &new_image).unwrap();
+        fs::rename(format!("{}.xmp", old_image), format!("{}.xmp", new_image)).unwrap();
+        fs::remove_file(format!("{}.xmp", other_image)).unwrap();
+        scan_and_import_sidecars().unwrap();
+
+        let moved_id = before.iter().find(|(_, path)| path.contains("rome")).unwrap().0;
+        assert_eq!(rows(&conn), [(moved_id, format!("{}.xmp", new_image))]);
+        assert_eq!(key_value_count(&conn), key_values / 2);
+        let image_path: String = conn.query_row("SELECT image_path FROM file", [], |row| row.get(0)).unwrap();
+        assert_eq!(image_path, new_image);
+        assert!(!old_cache.exists());
+        let new_cache = cache_file_path(&get_cache_dir(), &thumbnail_cache_key(&new_image), "jpg");
+        assert_eq!(fs::read(new_cache).unwrap(), b"thumbnail");
+
+        // A copy leaves the original in place, so it is a new file
+        let copy = format!("{}/lib/2023/rome.jpg", ROOT);
+        fs::copy(&new_image, &copy).unwrap();
+        fs::copy(format!("{}.xmp", new_image), format!("{}.xmp", copy)).unwrap();
+        scan_and_import_sidecars().unwrap();
+        let after = rows(&conn);
+        assert_eq!(after.len(), 2);
+        assert!(after.contains(&(moved_id, format!("{}.xmp", new_image))));
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
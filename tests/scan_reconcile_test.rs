@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::sidecar_scan::scan_and_import_sidecars;
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/scan_reconcile_test";
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="4"/>
+</rdf:RDF></x:xmpmeta>"#;
+
+    fn paths(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT path FROM file ORDER BY path")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    // A rescan drops the rows of deleted sidecars under the scan directory, but not those of other roots
+    #[test]
+    fn test_scan_removes_deleted_sidecars() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/scan_reconcile_test/thumbs",
+            "--full-image-cache", "tests/tmp/scan_reconcile_test/full",
+            "--video-preview-cache", "tests/tmp/scan_reconcile_test/video",
+            "--scan-dir", "tests/tmp/scan_reconcile_test/lib",
+            "index",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let kept = format!("{}/lib/kept.jpg", ROOT);
+        let deleted = format!("{}/lib/deleted.jpg", ROOT);
+        for image in [&kept, &deleted] {
+            fs::write(image, b"image").unwrap();
+            fs::write(format!("{}.xmp", image), SIDECAR).unwrap();
+        }
+        scan_and_import_sidecars().unwrap();
+
+        // Rows of another scan directory, including one whose name only shares the prefix
+        let conn = Connection::open(&db_path).unwrap();
+        let others = ["/elsewhere/other.jpg.xmp".to_string(), format!("{}/lib2/other.jpg.xmp", ROOT)];
+        for path in &others {
+            conn.execute("INSERT INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+            conn.execute(
+                "INSERT INTO key_value (file_id, key, value) SELECT id, 'xmp:Rating', '1' FROM file WHERE path = ?1",
+                [path],
+            )
+            .unwrap();
+        }
+        let key_values = |path: &str| -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM key_value JOIN file ON file.id = key_value.file_id WHERE file.path = ?1",
+                [path],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        let deleted_sidecar = format!("{}.xmp", deleted);
+        assert!(key_values(&deleted_sidecar) > 0);
+
+        fs::remove_file(&deleted_sidecar).unwrap();
+        scan_and_import_sidecars().unwrap();
+        let mut expected = vec![format!("{}.xmp", kept), others[0].clone(), others[1].clone()];
+        expected.sort();
+        assert_eq!(paths(&conn), expected);
+        let orphans: i64 = conn
+            .query_row("SELECT COUNT(*) FROM key_value WHERE file_id NOT IN (SELECT id FROM file)", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(orphans, 0);
+        assert_eq!(key_values(&others[0]), 1);
+
+        // With no sidecar left the last row goes too
+        fs::remove_file(format!("{}.xmp", kept)).unwrap();
+        scan_and_import_sidecars().unwrap();
+        assert_eq!(paths(&conn).len(), 2);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,657 @@
+#[cfg(test)]
+mod tests {
+    use image_find::cli::DEFAULT_IMPORT_KEYS;
+    use image_find::db::run_migrations;
+    use image_find::routes::{
+        append_exclusion_filters, build_search_where, has_gps_condition, highlight_search_terms, label_name, normalize_path_prefix, parse_label,
+        append_page_filter, count_search_files, page_link, parse_fuzzy_search_query, parse_search_query, parse_search_terms, search_field_keys, parse_since, parse_size, parse_video_resolution, search_window, SearchPage, SearchResult, SearchResultV2, SearchTerm,
+        video_codec_name, COLOR_LABELS, PICK_LABELS,
+    };
+    use image_find::sidecar_scan::{extract_key_value, select_import_key_values};
+    use image_find::suggest::register_fuzzy_match;
+    use rusqlite::Connection;
+
+    const MARK_OPEN: &str = "<mark style=\"background-color: lightgreen; padding: 1px 2px; border-radius: 2px;\">";
+
+    fn mark(text: &str) -> String {
+        format!("{}{}</mark>", MARK_OPEN, text)
+    }
+
+    #[test]
+    fn test_highlight_uses_parsed_terms() {
+        let terms = parse_search_terms(r#"beach "new york""#);
+        let result = highlight_search_terms("Beach trip to New York", &terms);
+        assert_eq!(result, format!("{} trip to {}", mark("Beach"), mark("New York")));
+    }
+
+    #[test]
+    fn test_highlight_skips_negated_terms() {
+        let terms = vec![
+            SearchTerm::new("sunset"),
+            SearchTerm { value: "beach".to_string(), key: None, negated: true, alternative: false },
+        ];
+        let result = highlight_search_terms("sunset over the beach", &terms);
+        assert_eq!(result, format!("{} over the beach", mark("sunset")));
+    }
+
+    #[test]
+    fn test_highlight_qualified_term_only_marks_value() {
+        let terms = vec![SearchTerm {
+            value: "sunset".to_string(),
+            key: Some("title".to_string()),
+            negated: false,
+            alternative: false,
+        }];
+        let result = highlight_search_terms("title: sunset", &terms);
+        assert_eq!(result, format!("title: {}", mark("sunset")));
+    }
+
+    // Terms must never match inside escaped entities or inserted markup
+    #[test]
+    fn test_highlight_does_not_break_markup() {
+        let terms = parse_search_terms("amp mark style");
+        let result = highlight_search_terms("a & b", &terms);
+        assert_eq!(result, "a &amp; b");
+
+        let terms = parse_search_terms("cat at");
+        let result = highlight_search_terms("<cat>", &terms);
+        assert_eq!(result, format!("&lt;{}&gt;", mark("cat")));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("500k"), Some(500 * 1024));
+        assert_eq!(parse_size("50MB"), Some(50 * 1024 * 1024));
+        assert_eq!(parse_size("1.5 GB"), Some(1536 * 1024 * 1024));
+        assert_eq!(parse_size("2MiB"), Some(2 * 1024 * 1024));
+
+        for value in ["", "MB", "-5MB", "50XB", "1.2.3MB"] {
+            assert_eq!(parse_size(value), None, "'{}' should not parse", value);
+        }
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_since("12h", now), Some(now - 12 * 3600));
+        assert_eq!(parse_since("7d", now), Some(now - 7 * 86400));
+        assert_eq!(parse_since("2w", now), Some(now - 14 * 86400));
+        assert_eq!(parse_since("2024-06-01", now), Some(1_717_200_000));
+
+        for value in ["", "d", "-7d", "7y", "soon"] {
+            assert_eq!(parse_since(value, now), None, "'{}' should not parse", value);
+        }
+    }
+
+    #[test]
+    fn test_normalize_path_prefix() {
+        assert_eq!(normalize_path_prefix("2023/Italy", "/photos"), "/photos/2023/Italy");
+        assert_eq!(normalize_path_prefix("2023/Italy/", "/photos/"), "/photos/2023/Italy");
+        assert_eq!(normalize_path_prefix(" /archive/2023// ", "/photos"), "/archive/2023");
+        assert_eq!(normalize_path_prefix("/", "/photos"), "");
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(parse_label("green", &COLOR_LABELS), Some(4));
+        assert_eq!(parse_label(" Red ", &COLOR_LABELS), Some(1));
+        assert_eq!(parse_label("9", &COLOR_LABELS), Some(9));
+        assert_eq!(parse_label("none", &PICK_LABELS), Some(0));
+        assert_eq!(parse_label("accepted", &PICK_LABELS), Some(3));
+        for value in ["", "10", "-1", "purple"] {
+            assert_eq!(parse_label(value, &COLOR_LABELS), None, "'{}' should not parse", value);
+        }
+        assert_eq!(parse_label("4", &PICK_LABELS), None);
+
+        assert_eq!(label_name("4", &COLOR_LABELS), Some("green"));
+        assert_eq!(label_name("2", &PICK_LABELS), Some("pending"));
+        for stored in ["0", "12", "Red", ""] {
+            assert_eq!(label_name(stored, &COLOR_LABELS), None, "'{}' should not map", stored);
+        }
+    }
+
+    // Paths left after applying the exclusion conditions to a small index
+    fn visible_paths(tags: &[&str], globs: &[&str]) -> Vec<String> {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        let files = [
+            ("/photos/beach.jpg.xmp", "Places/Beach;Family"),
+            ("/photos/secret.jpg.xmp", "Family;Private"),
+            ("/photos/nested.jpg.xmp", "private/Medical"),
+            ("/photos/similar.jpg.xmp", "PrivateBeach"),
+            ("/photos/Private/scan.jpg.xmp", "Documents"),
+        ];
+        for (path, tags) in files {
+            conn.execute("INSERT INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+            conn.execute(
+                "INSERT INTO key_value (file_id, key, value) VALUES (last_insert_rowid(), 'digiKam:TagsList/rdf:Seq', ?1)",
+                [tags],
+            )
+            .unwrap();
+        }
+
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        let globs: Vec<String> = globs.iter().map(|g| g.to_string()).collect();
+        let mut where_clause = String::from("WHERE 1 = 1");
+        let mut parameters = Vec::new();
+        append_exclusion_filters(&tags, &globs, &mut where_clause, &mut parameters);
+        let mut stmt = conn.prepare(&format!("SELECT path FROM file {} ORDER BY path", where_clause)).unwrap();
+        let paths = stmt
+            .query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, _>>()
+            .unwrap();
+        paths
+    }
+
+    #[test]
+    fn test_exclusion_filters() {
+        assert_eq!(visible_paths(&[], &[]).len(), 5);
+
+        // Whole tags and their subtags, case-insensitively, but not tags that merely share a prefix
+        assert_eq!(
+            visible_paths(&["Private"], &[]),
+            vec!["/photos/Private/scan.jpg.xmp", "/photos/beach.jpg.xmp", "/photos/similar.jpg.xmp"]
+        );
+
+        // Globs apply to the image path as well as the sidecar path
+        assert_eq!(visible_paths(&[], &["*/Private/*"]).len(), 4);
+        assert_eq!(visible_paths(&[], &["*/secret.jpg"]).len(), 4);
+        assert_eq!(visible_paths(&["Family"], &["*/Private/*"]), vec!["/photos/nested.jpg.xmp", "/photos/similar.jpg.xmp"]);
+
+        // LIKE wildcards in a tag are taken literally
+        assert_eq!(visible_paths(&["%"], &[]).len(), 5);
+    }
+
+    fn no_synonyms(_: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn nyc_synonyms(term: &str) -> Vec<String> {
+        if term.eq_ignore_ascii_case("nyc") { vec!["New York City".to_string()] } else { Vec::new() }
+    }
+
+    fn params(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_search_query_empty_input() {
+        assert_eq!(parse_search_query("", no_synonyms), ("WHERE key_value.value LIKE ?1".to_string(), params(&["%%"])));
+        let (where_clause, parameters) = parse_search_query("   ", no_synonyms);
+        assert_eq!(where_clause, "WHERE key_value.value LIKE ?1");
+        assert_eq!(parameters, params(&["%   %"]));
+        // Only quotes: no terms are parsed and the input is matched as it is
+        assert_eq!(parse_search_query("\"\"", no_synonyms).1, params(&["%\"\"%"]));
+    }
+
+    #[test]
+    fn test_search_query_single_term() {
+        assert_eq!(
+            parse_search_query("  sunset ", no_synonyms),
+            ("WHERE key_value.value LIKE ?1".to_string(), params(&["%sunset%"]))
+        );
+        assert_eq!(
+            parse_search_query("\"new york\"", no_synonyms),
+            ("WHERE key_value.value LIKE ?1".to_string(), params(&["%new york%"]))
+        );
+    }
+
+    // Each term has to match some metadata value of the same file, each in its own subquery
+    #[test]
+    fn test_search_query_multiple_terms() {
+        let (where_clause, parameters) = parse_search_query("beach \"family vacation\" 2023", no_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE kv1.value LIKE ?1) \
+             AND file.id IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?2) \
+             AND file.id IN (SELECT DISTINCT kv3.file_id FROM key_value kv3 WHERE kv3.value LIKE ?3)"
+        );
+        assert_eq!(parameters, params(&["%beach%", "%family vacation%", "%2023%"]));
+    }
+
+    // A bare OR or | starts an alternative group; quoted, or inside a word, it is searched for
+    #[test]
+    fn test_parse_search_terms_or() {
+        let parsed = |search: &str| -> Vec<(String, bool)> {
+            parse_search_terms(search).into_iter().map(|term| (term.value, term.alternative)).collect()
+        };
+        let terms = |expected: &[(&str, bool)]| -> Vec<(String, bool)> {
+            expected.iter().map(|(value, alternative)| (value.to_string(), *alternative)).collect()
+        };
+        assert_eq!(parsed("a OR b"), terms(&[("a", false), ("b", true)]));
+        assert_eq!(parsed("a b OR c"), terms(&[("a", false), ("b", false), ("c", true)]));
+        assert_eq!(parsed(r#""new york" OR paris"#), terms(&[("new york", false), ("paris", true)]));
+        assert_eq!(parsed("a | b"), terms(&[("a", false), ("b", true)]));
+        // Dangling or repeated operators have nothing to separate
+        assert_eq!(parsed("OR a OR OR b OR"), terms(&[("a", false), ("b", true)]));
+        assert_eq!(parsed(r#"a "OR" b or c Places|Italy"#), terms(&[("a", false), ("OR", false), ("b", false), ("or", false), ("c", false), ("Places|Italy", false)]));
+    }
+
+    // AND binds tighter than OR, and the groups are parenthesized for the filters ANDed after them
+    #[test]
+    fn test_search_query_or() {
+        let (where_clause, parameters) = parse_search_query("a OR b", no_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE (file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE kv1.value LIKE ?1) \
+             OR file.id IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?2))"
+        );
+        assert_eq!(parameters, params(&["%a%", "%b%"]));
+
+        let (where_clause, parameters) = parse_search_query("a b OR c", no_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE ((file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE kv1.value LIKE ?1) \
+             AND file.id IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?2)) \
+             OR file.id IN (SELECT DISTINCT kv3.file_id FROM key_value kv3 WHERE kv3.value LIKE ?3))"
+        );
+        assert_eq!(parameters, params(&["%a%", "%b%", "%c%"]));
+
+        let (where_clause, parameters) = parse_search_query(r#""new york" OR paris"#, no_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE (file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE kv1.value LIKE ?1) \
+             OR file.id IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?2))"
+        );
+        assert_eq!(parameters, params(&["%new york%", "%paris%"]));
+    }
+
+    // field:value splits on the first colon; values that only look like one stay whole terms
+    #[test]
+    fn test_parse_search_terms_fields() {
+        let parsed = |search: &str| -> Vec<(Option<String>, String)> {
+            parse_search_terms(search).into_iter().map(|term| (term.key, term.value)).collect()
+        };
+        let field = |key: &str, value: &str| (Some(key.to_string()), value.to_string());
+        let bare = |value: &str| (None, value.to_string());
+        assert_eq!(parsed("title:sunset beach"), [field("title", "sunset"), bare("beach")]);
+        assert_eq!(parsed(r#"title:"new york" tags:Places/Italy"#), [field("title", "new york"), field("tags", "Places/Italy")]);
+        assert_eq!(parsed("date:2023-05-01T10:30"), [field("date", "2023-05-01T10:30")]);
+        assert_eq!(parsed(r#"10:30 title: "a:b" :x"#), [bare("10:30"), bare("title:"), bare("a:b"), bare(":x")]);
+        assert_eq!(parsed("title:a OR tags:b")[1], field("tags", "b"));
+    }
+
+    #[test]
+    fn test_search_field_keys() {
+        assert_eq!(search_field_keys("title"), ["dc:title%"]);
+        assert_eq!(search_field_keys("Tags"), ["digiKam:TagsList%", "dc:subject%"]);
+        assert_eq!(search_field_keys("date"), ["exif:DateTimeOriginal%", "xmp:CreateDate%"]);
+        assert_eq!(search_field_keys("make"), ["%make%"]);
+    }
+
+    // A field:value term constrains the key of the matched row as well as its value
+    #[test]
+    fn test_search_query_fields() {
+        assert_eq!(
+            parse_search_query("title:sunset", no_synonyms),
+            (
+                "WHERE key_value.key LIKE ?1 AND key_value.value LIKE ?2".to_string(),
+                params(&["dc:title%", "%sunset%"])
+            )
+        );
+
+        let (where_clause, parameters) = parse_search_query("tags:vacation 2023", no_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE (kv1.key LIKE ?1 OR kv1.key LIKE ?2) AND kv1.value LIKE ?3) \
+             AND file.id IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?4)"
+        );
+        assert_eq!(parameters, params(&["digiKam:TagsList%", "dc:subject%", "%vacation%", "%2023%"]));
+    }
+
+    // A leading minus negates a term, unless it is quoted; a lone minus is ignored
+    #[test]
+    fn test_parse_search_terms_negated() {
+        let parsed = |search: &str| -> Vec<(String, bool)> {
+            parse_search_terms(search).into_iter().map(|term| (term.value, term.negated)).collect()
+        };
+        let terms = |expected: &[(&str, bool)]| -> Vec<(String, bool)> {
+            expected.iter().map(|(value, negated)| (value.to_string(), *negated)).collect()
+        };
+        assert_eq!(parsed("cat -dog"), terms(&[("cat", false), ("dog", true)]));
+        assert_eq!(parsed("-draft"), terms(&[("draft", true)]));
+        assert_eq!(parsed("a -b c"), terms(&[("a", false), ("b", true), ("c", false)]));
+        assert_eq!(parsed(r#""-literal" - x-ray"#), terms(&[("-literal", false), ("x-ray", false)]));
+        assert_eq!(parsed(r#"-"new york""#), terms(&[("new york", true)]));
+
+        let term = &parse_search_terms(r#"-title:"of paris""#)[0];
+        assert_eq!((term.key.as_deref(), term.value.as_str(), term.negated), (Some("title"), "of paris", true));
+    }
+
+    // Negated terms exclude the files having a matching value in any field
+    #[test]
+    fn test_search_query_negated() {
+        let (where_clause, parameters) = parse_search_query("cat -dog", no_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE kv1.value LIKE ?1) \
+             AND file.id NOT IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?2)"
+        );
+        assert_eq!(parameters, params(&["%cat%", "%dog%"]));
+
+        assert_eq!(
+            parse_search_query("-draft", no_synonyms),
+            (
+                "WHERE file.id NOT IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE kv1.value LIKE ?1)".to_string(),
+                params(&["%draft%"])
+            )
+        );
+
+        let (where_clause, parameters) = parse_search_query("a -b c", no_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE kv1.value LIKE ?1) \
+             AND file.id NOT IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?2) \
+             AND file.id IN (SELECT DISTINCT kv3.file_id FROM key_value kv3 WHERE kv3.value LIKE ?3)"
+        );
+        assert_eq!(parameters, params(&["%a%", "%b%", "%c%"]));
+    }
+
+    // Synonyms become OR alternatives of their term, with parameters numbered on across terms
+    #[test]
+    fn test_search_query_synonyms() {
+        assert_eq!(
+            parse_search_query("nyc", nyc_synonyms),
+            (
+                "WHERE (key_value.value LIKE ?1 OR key_value.value LIKE ?2)".to_string(),
+                params(&["%nyc%", "%New York City%"])
+            )
+        );
+
+        let terms = vec![SearchTerm::new("NYC"), SearchTerm::new("skyline")];
+        let (where_clause, parameters) = build_search_where(&terms, &nyc_synonyms);
+        assert_eq!(
+            where_clause,
+            "WHERE file.id IN (SELECT DISTINCT kv1.file_id FROM key_value kv1 WHERE (kv1.value LIKE ?1 OR kv1.value LIKE ?2)) \
+             AND file.id IN (SELECT DISTINCT kv2.file_id FROM key_value kv2 WHERE kv2.value LIKE ?3)"
+        );
+        assert_eq!(parameters, params(&["%NYC%", "%New York City%", "%skyline%"]));
+    }
+
+    // The built clause runs against the real schema and finds terms spread over different fields
+    #[test]
+    fn test_search_query_matches_files() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        let files = [
+            ("/photos/a.jpg.xmp", "dc:title", "Skyline of New York City"),
+            ("/photos/a.jpg.xmp", "digiKam:TagsList", "Trips/2023"),
+            ("/photos/b.jpg.xmp", "dc:title", "NYC at night"),
+            ("/photos/c.jpg.xmp", "dc:title", "Skyline of Paris"),
+        ];
+        for (path, key, value) in files {
+            conn.execute("INSERT OR IGNORE INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+            conn.execute(
+                "INSERT INTO key_value (file_id, key, value) SELECT id, ?2, ?3 FROM file WHERE path = ?1",
+                [path, key, value],
+            )
+            .unwrap();
+        }
+        let matching = |search: &str| -> Vec<String> {
+            let (where_clause, parameters) = parse_search_query(search, nyc_synonyms);
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT DISTINCT file.path FROM key_value JOIN file ON key_value.file_id = file.id {} ORDER BY file.path",
+                    where_clause
+                ))
+                .unwrap();
+            let paths = stmt
+                .query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get(0))
+                .unwrap()
+                .collect::<Result<Vec<String>, _>>()
+                .unwrap();
+            // /api/count agrees with the files the search returns
+            assert_eq!(count_search_files(&conn, &where_clause, &parameters).unwrap(), paths.len(), "count of '{}'", search);
+            paths
+        };
+
+        assert_eq!(matching("skyline"), ["/photos/a.jpg.xmp", "/photos/c.jpg.xmp"]);
+        assert_eq!(matching("skyline 2023"), ["/photos/a.jpg.xmp"]);
+        assert_eq!(matching("nyc"), ["/photos/a.jpg.xmp", "/photos/b.jpg.xmp"]);
+        assert_eq!(matching("\"of paris\""), ["/photos/c.jpg.xmp"]);
+        assert!(matching("skyline tokyo").is_empty());
+        assert_eq!(matching("nyc OR paris"), ["/photos/a.jpg.xmp", "/photos/b.jpg.xmp", "/photos/c.jpg.xmp"]);
+        assert_eq!(matching("skyline 2023 | night"), ["/photos/a.jpg.xmp", "/photos/b.jpg.xmp"]);
+        assert_eq!(matching("tokyo OR \"of paris\""), ["/photos/c.jpg.xmp"]);
+        assert_eq!(matching("title:2023"), Vec::<String>::new());
+        assert_eq!(matching("tags:2023"), ["/photos/a.jpg.xmp"]);
+        assert_eq!(matching("title:skyline tags:trips"), ["/photos/a.jpg.xmp"]);
+        assert_eq!(matching("title:\"of paris\" OR tags:trips"), ["/photos/a.jpg.xmp", "/photos/c.jpg.xmp"]);
+        assert_eq!(matching("skyline -2023"), ["/photos/c.jpg.xmp"]);
+        assert_eq!(matching("-nyc"), ["/photos/c.jpg.xmp"]);
+        assert_eq!(matching("-title:skyline"), ["/photos/b.jpg.xmp"]);
+    }
+
+    // Captions imported from a sidecar are found by search terms and highlighted
+    #[test]
+    fn test_description_is_searchable() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        let kv = extract_key_value("tests/data/xmp/description.jpg.xmp").expect("Failed to parse description fixture");
+        let import_keys: Vec<String> = DEFAULT_IMPORT_KEYS.split(',').map(String::from).collect();
+        conn.execute("INSERT INTO file (path, hash) VALUES ('/photos/harbour.jpg.xmp', 0)", []).unwrap();
+        for (key, value) in select_import_key_values(&kv, &import_keys) {
+            conn.execute("INSERT INTO key_value (file_id, key, value) VALUES (1, ?1, ?2)", [key, value]).unwrap();
+        }
+        let matching_keys = |search: &str| -> Vec<String> {
+            let (where_clause, parameters) = parse_search_query(search, |_| Vec::new());
+            let mut stmt = conn
+                .prepare(&format!("SELECT key_value.key FROM key_value JOIN file ON key_value.file_id = file.id {} ORDER BY key", where_clause))
+                .unwrap();
+            let keys = stmt
+                .query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get(0))
+                .unwrap()
+                .collect::<Result<Vec<String>, _>>()
+                .unwrap();
+            keys
+        };
+
+        assert_eq!(matching_keys("dusk"), ["dc:description/rdf:Alt"]);
+        assert_eq!(matching_keys("\"family album\""), ["exif:ImageDescription"]);
+        assert_eq!(
+            highlight_search_terms("Fishing boats returning at dusk", &parse_search_terms("boats dusk")),
+            format!("Fishing {} returning at {}", mark("boats"), mark("dusk"))
+        );
+    }
+
+    // With fuzzy=true a typo still finds the file, and every term has to match some field of it
+    #[test]
+    fn test_fuzzy_search_query() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        register_fuzzy_match(&conn).expect("Failed to register fuzzy_match");
+        for (path, title, description) in [
+            ("/photos/a.jpg.xmp", "Harbour", "Fishing boats returning at dusk"),
+            ("/photos/b.jpg.xmp", "Lake", "Sailing boats"),
+        ] {
+            conn.execute("INSERT INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+            for (key, value) in [("dc:title", title), ("dc:description", description)] {
+                conn.execute(
+                    "INSERT INTO key_value (file_id, key, value) SELECT id, ?2, ?3 FROM file WHERE path = ?1",
+                    [path, key, value],
+                )
+                .unwrap();
+            }
+        }
+        let matching = |search: &str| -> Vec<String> {
+            let (where_clause, parameters) = parse_fuzzy_search_query(search, |_| Vec::new());
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT DISTINCT file.path FROM key_value JOIN file ON key_value.file_id = file.id {} ORDER BY file.path",
+                    where_clause
+                ))
+                .unwrap();
+            let paths = stmt
+                .query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get(0))
+                .unwrap()
+                .collect::<Result<Vec<String>, _>>()
+                .unwrap();
+            paths
+        };
+
+        assert_eq!(matching("harbor"), ["/photos/a.jpg.xmp"]);
+        assert_eq!(matching("baots"), ["/photos/a.jpg.xmp", "/photos/b.jpg.xmp"]);
+        assert_eq!(matching("saling baots"), ["/photos/b.jpg.xmp"]);
+        assert!(matching("mountain").is_empty());
+        assert_eq!(matching("").len(), 2);
+        // Without fuzzy matching the typo finds nothing
+        let (where_clause, _) = parse_search_query("harbor", |_| Vec::new());
+        assert!(where_clause.contains("LIKE"));
+    }
+
+    // Files matching in several fields are counted and listed once; the window pages through them
+    #[test]
+    fn test_search_window() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        for name in ["a", "b", "c", "d"] {
+            let path = format!("/photos/{}.jpg.xmp", name);
+            conn.execute("INSERT INTO file (path, hash, capture_time) VALUES (?1, 0, 1700000000)", [&path]).unwrap();
+            for key in ["dc:title", "dc:description"] {
+                conn.execute(
+                    "INSERT INTO key_value (file_id, key, value) VALUES (last_insert_rowid(), ?1, 'Beach')",
+                    [key],
+                )
+                .unwrap();
+            }
+        }
+        let (where_clause, parameters) = parse_search_query("beach", |_| Vec::new());
+        let paths = |offset: usize, limit: usize| {
+            let (total, rows) = search_window(&conn, &where_clause, &parameters, "file.path ASC", offset, limit).unwrap();
+            (total, rows.into_iter().map(|row| row.file_path).collect::<Vec<_>>())
+        };
+
+        assert_eq!(paths(0, 3), (4, vec!["/photos/a.jpg".to_string(), "/photos/b.jpg".to_string(), "/photos/c.jpg".to_string()]));
+        assert_eq!(paths(3, 3), (4, vec!["/photos/d.jpg".to_string()]));
+        assert_eq!(paths(10, 3), (4, Vec::new()));
+
+        let (_, rows) = search_window(&conn, &where_clause, &parameters, "file.path ASC", 0, 1).unwrap();
+        assert_eq!(rows[0].capture_time, Some(1700000000));
+        assert!(rows[0].thumbnail_base64.is_none() && rows[0].width.is_none());
+    }
+
+    #[test]
+    fn test_search_page_bounds() {
+        assert_eq!(SearchPage::new(None, None), SearchPage { page: 1, per_page: 50 });
+        assert_eq!(SearchPage::new(Some(0), Some(0)), SearchPage { page: 1, per_page: 1 });
+        assert_eq!(SearchPage::new(Some(3), Some(10_000)), SearchPage { page: 3, per_page: 500 });
+        let page = SearchPage::new(Some(3), Some(20));
+        assert_eq!(page.offset(), 40);
+        assert_eq!(page.page_count(41), 3);
+        assert_eq!(page.page_count(0), 1);
+    }
+
+    // A page keeps every matching row of its files, and the total counts each file once
+    #[test]
+    fn test_search_page_filter() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        for name in ["a", "b", "c", "d", "e"] {
+            let path = format!("/photos/{}.jpg.xmp", name);
+            conn.execute("INSERT INTO file (path, hash) VALUES (?1, 0)", [&path]).unwrap();
+            for key in ["dc:title", "dc:description"] {
+                conn.execute(
+                    "INSERT INTO key_value (file_id, key, value) SELECT id, ?2, 'Beach' FROM file WHERE path = ?1",
+                    [&path, key],
+                )
+                .unwrap();
+            }
+        }
+        let (where_clause, parameters) = parse_search_query("beach", |_| Vec::new());
+        assert_eq!(count_search_files(&conn, &where_clause, &parameters).unwrap(), 5);
+
+        let rows = |page: SearchPage| -> Vec<String> {
+            let (mut where_clause, mut parameters) = (where_clause.clone(), parameters.clone());
+            append_page_filter(&mut where_clause, &mut parameters, "file.path DESC", page);
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT file.path FROM key_value JOIN file ON key_value.file_id = file.id {} ORDER BY file.path DESC",
+                    where_clause
+                ))
+                .unwrap();
+            stmt.query_map(rusqlite::params_from_iter(parameters.iter()), |row| row.get(0))
+                .unwrap()
+                .collect::<Result<Vec<String>, _>>()
+                .unwrap()
+        };
+        assert_eq!(rows(SearchPage::new(Some(1), Some(2))), ["/photos/e.jpg.xmp", "/photos/e.jpg.xmp", "/photos/d.jpg.xmp", "/photos/d.jpg.xmp"]);
+        assert_eq!(rows(SearchPage::new(Some(3), Some(2))), ["/photos/a.jpg.xmp", "/photos/a.jpg.xmp"]);
+        assert!(rows(SearchPage::new(Some(4), Some(2))).is_empty());
+    }
+
+    #[test]
+    fn test_page_link() {
+        assert_eq!(page_link("/search", "search=new%20york&page=2&sort=import_desc", 3), "/search?search=new%20york&sort=import_desc&page=3");
+        assert_eq!(page_link("/search", "", 2), "/search?page=2");
+        assert_eq!(page_link("/search", "per_page=10&pages=x", 1), "/search?per_page=10&pages=x&page=1");
+    }
+
+    // Files with either GPS key count as geotagged; other keys do not
+    #[test]
+    fn test_has_gps_condition() {
+        let mut conn = Connection::open_in_memory().expect("Failed to open in-memory DB");
+        run_migrations(&mut conn).expect("Migration failed");
+        let files = [
+            ("/photos/tagged.jpg.xmp", "exif:GPSLatitude"),
+            ("/photos/longitude.jpg.xmp", "exif:GPSLongitude"),
+            ("/photos/altitude.jpg.xmp", "exif:GPSAltitude"),
+            ("/photos/plain.jpg.xmp", "xmp:Rating"),
+        ];
+        for (path, key) in files {
+            conn.execute("INSERT INTO file (path, hash) VALUES (?1, 0)", [path]).unwrap();
+            conn.execute("INSERT INTO key_value (file_id, key, value) VALUES (last_insert_rowid(), ?1, '52.5')", [key]).unwrap();
+        }
+        let paths = |negate: &str| -> Vec<String> {
+            let mut stmt = conn
+                .prepare(&format!("SELECT path FROM file WHERE {}{} ORDER BY path", negate, has_gps_condition()))
+                .unwrap();
+            let paths = stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<Vec<String>, _>>().unwrap();
+            paths
+        };
+
+        assert_eq!(paths(""), ["/photos/longitude.jpg.xmp", "/photos/tagged.jpg.xmp"]);
+        assert_eq!(paths("NOT "), ["/photos/altitude.jpg.xmp", "/photos/plain.jpg.xmp"]);
+    }
+
+    #[test]
+    fn test_video_filters() {
+        assert_eq!(video_codec_name("H265"), "hevc");
+        assert_eq!(video_codec_name(" x264 "), "h264");
+        assert_eq!(video_codec_name("VP9"), "vp9");
+        assert_eq!(parse_video_resolution("4K"), Some(2160));
+        assert_eq!(parse_video_resolution("1080p"), Some(1080));
+        assert_eq!(parse_video_resolution("hd"), Some(720));
+        assert_eq!(parse_video_resolution("0p"), None);
+        assert_eq!(parse_video_resolution("big"), None);
+    }
+
+    // format=v2 renames file_path/value and keeps every other field as is
+    #[test]
+    fn test_search_result_v2_field_names() {
+        let result = SearchResult {
+            file_path: "/photos/a.jpg".to_string(),
+            value: "Beach".to_string(),
+            thumbnail_base64: None,
+            dominant_color: Some("#336699".to_string()),
+            size_bytes: Some(1024),
+            source_present: true,
+            color_label: Some("red"),
+            pick_label: None,
+            has_gps: true,
+            score: None,
+        };
+        let v1 = serde_json::to_value(&result).unwrap();
+        let v2 = serde_json::to_value(SearchResultV2::from(&result)).unwrap();
+        assert_eq!(v2["url"], v1["file_path"]);
+        assert_eq!(v2["caption"], v1["value"]);
+        assert!(v2.get("file_path").is_none() && v2.get("value").is_none());
+        let mut v1_keys: Vec<&String> = v1.as_object().unwrap().keys().filter(|k| *k != "file_path" && *k != "value").collect();
+        let mut v2_keys: Vec<&String> = v2.as_object().unwrap().keys().filter(|k| *k != "url" && *k != "caption").collect();
+        v1_keys.sort();
+        v2_keys.sort();
+        assert_eq!(v1_keys, v2_keys);
+        assert_eq!(v2["dominant_color"], "#336699");
+    }
+}
@@ -0,0 +1,264 @@
+#[cfg(test)]
+mod tests {
+    use image_find::cli::DEFAULT_IMPORT_KEYS;
+    use image_find::sidecar_scan::{
+        capture_timestamp, extract_key_value, extract_key_value_with_list_keys, extract_sidecar_key_value,
+        image_path_for_sidecar, parse_capture_date, scan_progress, select_import_key_values, sidecar_files_for_image,
+        SidecarError,
+    };
+
+    // Repeated sibling elements with the same attributes must not overwrite each other
+    #[test]
+    fn test_repeated_attributes_are_collected() {
+        let kv = extract_key_value("tests/data/xmp/repeated_attributes.jpg.xmp")
+            .expect("Failed to parse repeated_attributes fixture");
+
+        let names = kv
+            .iter()
+            .find(|(k, _)| k.ends_with("rdf:li:mwg-rs:Name"))
+            .map(|(_, v)| v.as_str());
+        assert_eq!(names, Some("Alice;Bob;Carol"));
+
+        let types = kv
+            .iter()
+            .find(|(k, _)| k.ends_with("rdf:li:mwg-rs:Type"))
+            .map(|(_, v)| v.as_str());
+        assert_eq!(types, Some("Face;Face;Face"));
+
+        // Attributes on a single element are still stored as-is
+        let modify_date = kv
+            .iter()
+            .find(|(k, _)| k.ends_with(":xmp:ModifyDate"))
+            .map(|(_, v)| v.as_str());
+        assert_eq!(modify_date, Some("2023-06-01T12:00:00+02:00"));
+    }
+
+    // Camera and lens are imported by default under their short key names
+    #[test]
+    fn test_default_import_keys_include_camera_and_lens() {
+        let kv = extract_key_value("tests/data/xmp/camera_lens.jpg.xmp")
+            .expect("Failed to parse camera_lens fixture");
+        let import_keys: Vec<String> = DEFAULT_IMPORT_KEYS.split(',').map(String::from).collect();
+
+        let selected = select_import_key_values(&kv, &import_keys);
+        let keys: Vec<&str> = selected.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec!["dc:title/rdf:Alt", "exifEX:LensModel", "tiff:Make", "tiff:Model", "xmp:Rating"]
+        );
+        assert!(selected.contains(&("tiff:Model".to_string(), "X-T4".to_string())));
+        assert!(selected.contains(&("exifEX:LensModel".to_string(), "XF16-55mmF2.8 R LM WR".to_string())));
+    }
+
+    // digiKam color and pick labels are imported by default alongside the rating
+    #[test]
+    fn test_default_import_keys_include_labels() {
+        let kv = extract_key_value("tests/data/xmp/labels.jpg.xmp").expect("Failed to parse labels fixture");
+        let import_keys: Vec<String> = DEFAULT_IMPORT_KEYS.split(',').map(String::from).collect();
+
+        let selected = select_import_key_values(&kv, &import_keys);
+        assert_eq!(
+            selected,
+            vec![
+                ("digiKam:ColorLabel".to_string(), "4".to_string()),
+                ("digiKam:PickLabel".to_string(), "3".to_string()),
+                ("xmp:Rating".to_string(), "4".to_string()),
+            ]
+        );
+    }
+
+    // Description captions are imported by default, whether rdf:Alt or plain text
+    #[test]
+    fn test_default_import_keys_include_descriptions() {
+        let kv = extract_key_value("tests/data/xmp/description.jpg.xmp").expect("Failed to parse description fixture");
+        let import_keys: Vec<String> = DEFAULT_IMPORT_KEYS.split(',').map(String::from).collect();
+
+        let selected = select_import_key_values(&kv, &import_keys);
+        assert_eq!(
+            selected,
+            vec![
+                ("dc:description/rdf:Alt".to_string(), "Fishing boats returning at dusk".to_string()),
+                ("dc:title/rdf:Alt".to_string(), "Harbour".to_string()),
+                ("exif:ImageDescription".to_string(), "Scanned from the family album".to_string()),
+            ]
+        );
+    }
+
+    // Only the configured keys are imported
+    #[test]
+    fn test_import_keys_restrict_selection() {
+        let kv = extract_key_value("tests/data/xmp/camera_lens.jpg.xmp")
+            .expect("Failed to parse camera_lens fixture");
+
+        let selected = select_import_key_values(&kv, &["exif:FNumber".to_string()]);
+        assert_eq!(selected, vec![("exif:FNumber".to_string(), "28/10".to_string())]);
+    }
+
+    // "all" imports every parsed key but skips RDF bookkeeping attributes
+    #[test]
+    fn test_import_all_keys() {
+        let kv = extract_key_value("tests/data/xmp/camera_lens.jpg.xmp")
+            .expect("Failed to parse camera_lens fixture");
+
+        let selected = select_import_key_values(&kv, &["all".to_string()]);
+        let keys: Vec<&str> = selected.iter().map(|(k, _)| k.as_str()).collect();
+        assert!(keys.contains(&"exif:FNumber"));
+        assert!(keys.contains(&"tiff:Model"));
+        assert!(!keys.contains(&"xmp:ModifyDate"));
+        assert!(keys.iter().all(|k| !k.contains("xmlns") && !k.ends_with("xml:lang") && !k.ends_with("rdf:about")));
+    }
+
+    // XMP offsets are normalized to UTC; EXIF dates without a timezone are taken as UTC
+    #[test]
+    fn test_parse_capture_date() {
+        assert_eq!(parse_capture_date("2023-05-01T10:00:00Z"), Some(1682935200));
+        assert_eq!(parse_capture_date("2023-05-01T12:00:00+02:00"), Some(1682935200));
+        assert_eq!(parse_capture_date("2023-05-01T05:30:00.25-04:30"), Some(1682935200));
+        assert_eq!(parse_capture_date("2023:05:01 10:00:00"), Some(1682935200));
+        assert_eq!(parse_capture_date("2023-05-01T10:00"), Some(1682935200));
+        assert_eq!(parse_capture_date("2023-05-01"), Some(1682899200));
+        assert_eq!(parse_capture_date("1970"), Some(0));
+        assert_eq!(parse_capture_date("2024-02-29"), Some(1709164800));
+    }
+
+    #[test]
+    fn test_parse_capture_date_malformed() {
+        for value in ["", "yesterday", "0000:00:00 00:00:00", "2023-02-29", "2023-13-01", "2023-05-01T25:00:00", "2023-05-01T10:00:00+2"] {
+            assert_eq!(parse_capture_date(value), None, "'{}' should not parse", value);
+        }
+    }
+
+    // exif:DateTimeOriginal is preferred over xmp:CreateDate
+    #[test]
+    fn test_capture_timestamp_prefers_date_time_original() {
+        let kv = std::collections::HashMap::from([
+            ("x:xmpmeta/rdf:RDF/rdf:Description:xmp:CreateDate".to_string(), "2020-01-01T00:00:00Z".to_string()),
+            ("x:xmpmeta/rdf:RDF/rdf:Description:exif:DateTimeOriginal".to_string(), "2023-05-01T12:00:00+02:00".to_string()),
+        ]);
+        assert_eq!(capture_timestamp(&kv), Some(1682935200));
+    }
+
+    // Configured list keys collect their rdf:li items whatever the container type
+    #[test]
+    fn test_list_keys_collect_any_container() {
+        let kv = extract_key_value("tests/data/xmp/list_containers.jpg.xmp")
+            .expect("Failed to parse list_containers fixture");
+
+        assert_eq!(kv.get("digiKam:TagsList/rdf:Seq").map(String::as_str), Some("Places/Sweden;People/Anna"));
+        assert_eq!(kv.get("dc:title/rdf:Alt").map(String::as_str), Some("Lake"));
+        assert_eq!(kv.get("dc:subject/rdf:Bag").map(String::as_str), Some("lake;summer;swimming"));
+        assert_eq!(kv.get("lr:hierarchicalSubject/rdf:Bag").map(String::as_str), Some("Places|Sweden|Vättern"));
+    }
+
+    // Lists split across rdf:Description blocks are merged in document order, without duplicates
+    #[test]
+    fn test_list_keys_merge_across_descriptions() {
+        let kv = extract_key_value("tests/data/xmp/multiple_descriptions.jpg.xmp")
+            .expect("Failed to parse multiple_descriptions fixture");
+
+        assert_eq!(
+            kv.get("digiKam:TagsList/rdf:Seq").map(String::as_str),
+            Some("Places/Sweden;People/Anna;Events/Midsummer")
+        );
+        assert_eq!(kv.get("dc:title/rdf:Alt").map(String::as_str), Some("Lake"));
+        assert_eq!(kv.get("dc:subject/rdf:Bag").map(String::as_str), Some("lake"));
+        assert!(!kv.keys().any(|k| k.ends_with("rdf:li")), "No list item leaks out as a plain value");
+    }
+
+    // Properties that are not list keys keep their per-element tag path keys
+    #[test]
+    fn test_unlisted_properties_are_not_collected() {
+        let kv = extract_key_value_with_list_keys("tests/data/xmp/list_containers.jpg.xmp", &["dc:title".to_string()])
+            .expect("Failed to parse list_containers fixture");
+
+        assert_eq!(kv.get("dc:title/rdf:Alt").map(String::as_str), Some("Lake"));
+        assert!(!kv.contains_key("dc:subject/rdf:Bag"));
+        assert!(kv.keys().any(|k| k.ends_with("dc:subject/rdf:Bag/rdf:li")));
+    }
+
+    // Entity references, a bare "&" and misnested end tags do not cut the parse short
+    #[test]
+    fn test_recoverable_xml_errors() {
+        let kv = extract_key_value("tests/data/xmp/recoverable_errors.jpg.xmp")
+            .expect("Recoverable errors should parse completely");
+
+        assert_eq!(kv.get("dc:title/rdf:Alt").map(String::as_str), Some("Fish & Chips & Peas \u{2764}"));
+        assert_eq!(kv.get("dc:subject/rdf:Bag").map(String::as_str), Some("harbour;dinner"));
+        assert_eq!(kv.get("digiKam:TagsList/rdf:Seq").map(String::as_str), Some("Places/Grimsby"));
+    }
+
+    // A truncated file or an unrecoverable error reports its byte offset with what was read before it
+    #[test]
+    fn test_malformed_xml_is_partial() {
+        let Err(SidecarError::Malformed { position, kv, .. }) = extract_key_value("tests/data/xmp/truncated.jpg.xmp") else {
+            panic!("A truncated sidecar should be reported as malformed");
+        };
+        assert_eq!(position, std::fs::metadata("tests/data/xmp/truncated.jpg.xmp").unwrap().len());
+        assert_eq!(kv.get("digiKam:TagsList/rdf:Seq").map(String::as_str), Some("Places/Sweden;People/Anna"));
+        assert!(kv.keys().any(|k| k.ends_with(":xmp:Rating")));
+
+        let path = "tests/data/xmp/unclosed_comment.jpg.xmp";
+        let Err(SidecarError::Malformed { position, kv, .. }) = extract_key_value(path) else {
+            panic!("An unclosed comment should be reported as malformed");
+        };
+        let comment_start = std::fs::read_to_string(path).unwrap().find("<!--").unwrap() as u64;
+        assert_eq!(position, comment_start);
+        assert!(kv.keys().any(|k| k.ends_with(":xmp:Rating")));
+        assert!(!kv.contains_key("dc:title/rdf:Alt"));
+    }
+
+    // Takeout JSON fields are mapped onto the XMP keys
+    #[test]
+    fn test_json_sidecar_fields() {
+        let kv = extract_sidecar_key_value("tests/data/json/IMG_0001.jpg.json")
+            .expect("Failed to parse JSON sidecar fixture");
+
+        assert_eq!(kv.get("dc:title/rdf:Alt").map(String::as_str), Some("IMG_0001.jpg"));
+        assert_eq!(kv.get("dc:description/rdf:Alt").map(String::as_str), Some("Picnic by the lake"));
+        assert_eq!(kv.get("exif:DateTimeOriginal").map(String::as_str), Some("2023-05-01T10:00:00Z"));
+        assert_eq!(kv.get("xmp:ModifyDate").map(String::as_str), Some("2023-05-02T04:00:00Z"));
+        assert_eq!(kv.get("exif:GPSLatitude").map(String::as_str), Some("58.3925"));
+        assert_eq!(kv.get("exif:GPSLongitude").map(String::as_str), Some("14.5153"));
+        assert_eq!(capture_timestamp(&kv), Some(1682935200));
+    }
+
+    #[test]
+    fn test_image_path_for_sidecar() {
+        assert_eq!(image_path_for_sidecar("/photos/a.jpg.xmp"), "/photos/a.jpg");
+        assert_eq!(image_path_for_sidecar("/photos/a.jpg.XMP"), "/photos/a.jpg");
+        assert_eq!(image_path_for_sidecar("/photos/a.jpg"), "/photos/a.jpg");
+        assert_eq!(image_path_for_sidecar(".xmp"), ".xmp");
+    }
+
+    // Sidecars are found whatever the case of their extension, but only for the exact image name
+    #[test]
+    fn test_sidecar_files_for_image() {
+        let dir = std::path::Path::new("tests/tmp/sidecar_files_test");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        for name in ["a.jpg", "a.jpg.XMP", "a.jpg.json", "a.JPG.xmp", "ab.jpg.xmp", "b.jpg"] {
+            std::fs::write(dir.join(name), b"x").unwrap();
+        }
+
+        assert_eq!(sidecar_files_for_image(&dir.join("a.jpg")), [dir.join("a.jpg.XMP")]);
+        assert!(sidecar_files_for_image(&dir.join("b.jpg")).is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    // Progress lines estimate the time left from the average time per sidecar so far
+    #[test]
+    fn test_scan_progress() {
+        use std::time::Duration;
+        assert_eq!(
+            scan_progress(1200, 5000, Duration::from_secs(90)),
+            "Processed 1200 of 5000 sidecars (24%), 1m 30s elapsed, about 4m 45s left"
+        );
+        assert_eq!(
+            scan_progress(10, 100_000, Duration::from_secs(12)),
+            "Processed 10 of 100000 sidecars (0%), 12s elapsed, about 33h 19m left"
+        );
+        assert_eq!(scan_progress(0, 10, Duration::ZERO), "Processed 0 of 10 sidecars (0%), 0s elapsed, estimating time left");
+        assert_eq!(scan_progress(10, 10, Duration::from_secs(5)), "Processed 10 of 10 sidecars (100%), 5s elapsed, about 0s left");
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::image::{generate_sized_preview, preview_width};
+
+    fn init_cli_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/sized_preview_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/sized_preview_test_thumbs",
+            "--full-image-cache", "tests/tmp/sized_preview_test_full",
+            "--video-preview-cache", "tests/tmp/sized_preview_test_video",
+            "--scan-dir", "tests/data",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    fn count_cache_files(dir: &str) -> usize {
+        walkdir::WalkDir::new(dir).into_iter().flatten().filter(|entry| entry.file_type().is_file()).count()
+    }
+
+    fn decoded_dimensions(preview_base64: &str) -> (u32, u32) {
+        let bytes = BASE64.decode(preview_base64).expect("Preview is not base64");
+        let img = image::load_from_memory(&bytes).expect("Preview is not an image");
+        (img.width(), img.height())
+    }
+
+    #[test]
+    fn test_preview_width() {
+        assert_eq!(preview_width(1024), Some(1024));
+        assert_eq!(preview_width(480), Some(480));
+        assert_eq!(preview_width(1000), Some(1024));
+        assert_eq!(preview_width(1), Some(64));
+        assert_eq!(preview_width(1950), Some(1952));
+        assert_eq!(preview_width(1960), None);
+        assert_eq!(preview_width(1980), None);
+        assert_eq!(preview_width(u32::MAX), None);
+    }
+
+    // Sized previews are scaled from the full-size preview, each width cached on its own
+    #[test]
+    fn test_sized_preview_is_generated_and_cached() {
+        init_cli_args();
+        fs::create_dir_all("tests/tmp").unwrap();
+        let source_path = "tests/tmp/sized_preview_test.png";
+        RgbImage::from_pixel(3000, 2000, Rgb([90, 160, 30])).save(source_path).unwrap();
+
+        let cache_dir = "tests/tmp/sized_preview_test_full";
+        let _ = fs::remove_dir_all(cache_dir);
+
+        let preview = generate_sized_preview(source_path, 1024).expect("Sized preview failed");
+        assert_eq!(decoded_dimensions(&preview), (1024, 683));
+        // The full-size preview and the 1024px variant
+        assert_eq!(count_cache_files(cache_dir), 2);
+
+        let cached = generate_sized_preview(source_path, 1024).expect("Cached preview failed");
+        assert_eq!(cached, preview);
+
+        let small = generate_sized_preview(source_path, 480).expect("Sized preview failed");
+        assert_eq!(decoded_dimensions(&small), (480, 320));
+        assert_eq!(count_cache_files(cache_dir), 3);
+
+        let _ = fs::remove_file(source_path);
+        let _ = fs::remove_dir_all(cache_dir);
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use image_find::albums::{add_to_album, album_files, create_album};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::sidecar_scan::{resolve_image_path, scan_and_import_sidecars};
+    use rusqlite::Connection;
+
+    const ROOT: &str = "tests/tmp/stem_sidecar_test";
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:ModifyDate="2024-01-01T00:00:00" xmp:Rating="4"/>
+</rdf:RDF></x:xmpmeta>"#;
+
+    // "photo.xmp" sidecars are paired with "photo.<ext>" images; "photo.jpg.xmp" keeps working
+    #[test]
+    fn test_stem_named_sidecars() {
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(format!("{}/lib", ROOT)).unwrap();
+        let db_path = format!("{}/db.sqlite", ROOT);
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", &db_path,
+            "--thumbnail-cache", "tests/tmp/stem_sidecar_test/thumbs",
+            "--full-image-cache", "tests/tmp/stem_sidecar_test/full",
+            "--video-preview-cache", "tests/tmp/stem_sidecar_test/video",
+            "--scan-dir", "tests/tmp/stem_sidecar_test/lib",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let lib = format!("{}/lib", ROOT);
+        for image in ["classic.jpg", "stem.png", "pair.jpg", "pair.NEF"] {
+            fs::write(format!("{}/{}", lib, image), b"image").unwrap();
+        }
+        for sidecar in ["classic.jpg.xmp", "stem.xmp", "pair.xmp", "orphan.xmp"] {
+            fs::write(format!("{}/{}", lib, sidecar), SIDECAR).unwrap();
+        }
+
+        assert_eq!(resolve_image_path(&format!("{}/classic.jpg.xmp", lib)), format!("{}/classic.jpg", lib));
+        assert_eq!(resolve_image_path(&format!("{}/stem.xmp", lib)), format!("{}/stem.png", lib));
+        assert_eq!(resolve_image_path(&format!("{}/pair.xmp", lib)), format!("{}/pair.NEF", lib), "RAW files are preferred");
+        assert_eq!(resolve_image_path(&format!("{}/orphan.xmp", lib)), format!("{}/orphan", lib));
+        assert_eq!(resolve_image_path(&format!("{}/stem.png", lib)), format!("{}/stem.png", lib));
+
+        scan_and_import_sidecars().unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        let (image_path, source_present): (String, bool) = conn
+            .query_row("SELECT image_path, source_present FROM file WHERE path = ?1", [format!("{}/stem.xmp", lib)], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(image_path, format!("{}/stem.png", lib));
+        assert!(source_present);
+
+        // Image path lookups find the stem-named sidecar's row
+        let album = create_album(&conn, "Picks").unwrap();
+        let (added, unknown) = add_to_album(&conn, album, &[format!("{}/stem.png", lib), format!("{}/classic.jpg", lib)]).unwrap();
+        assert_eq!((added, unknown.len()), (2, 0));
+        let files: Vec<String> = album_files(&conn, album, &[], &[]).unwrap().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(files, [format!("{}/stem.png", lib), format!("{}/classic.jpg", lib)]);
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
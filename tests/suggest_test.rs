@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod tests {
+    use image_find::suggest::{fuzzy_score, suggest, tag_vocabulary};
+
+    #[test]
+    fn test_tag_vocabulary() {
+        let vocabulary = tag_vocabulary(["Places/Italy/Rome;Beach", "beach; Family ;", "Places|Italy"]);
+        assert_eq!(vocabulary, ["Beach", "Family", "Italy", "Places", "Places/Italy/Rome", "Places|Italy", "Rome"]);
+    }
+
+    // Typos, including swapped letters, find the tag; unrelated terms find nothing
+    #[test]
+    fn test_suggest_closest_tags() {
+        let vocabulary = tag_vocabulary(["Places/Italy;Beach;Beaches;Family;Bench"]);
+        let values = |query: &str, limit: usize| -> Vec<String> {
+            suggest(query, &vocabulary, limit).into_iter().map(|s| s.value).collect()
+        };
+
+        assert_eq!(values("itlay", 5), ["Italy"]);
+        assert_eq!(values("FAMLY", 5), ["Family"]);
+        assert_eq!(values("beahc", 5), ["Beach"]);
+        assert_eq!(values("beache", 5), ["Beaches", "Beach"]);
+        assert_eq!(values("beache", 1), ["Beaches"]);
+        assert!(values("zebra", 5).is_empty());
+        assert!(values("  ", 5).is_empty());
+
+        let exact = suggest("beach", &vocabulary, 1);
+        assert_eq!(exact[0].similarity, 1.0);
+    }
+
+    // Terms match within a few edits of whole words (or runs of words), contained terms score 1.0
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("Fishing boats at dusk", "BOATS"), Some(1.0));
+        assert_eq!(fuzzy_score("Fishing boats at dusk", "boast"), Some(0.8));
+        assert_eq!(fuzzy_score("Vacation in Stockholm", "stokholm"), Some(1.0 - 1.0 / 9.0));
+        assert_eq!(fuzzy_score("Scanned from the farnily album", "family album"), Some(1.0 - 2.0 / 13.0));
+        // Short terms allow fewer edits
+        assert_eq!(fuzzy_score("A red car", "cat"), Some(1.0 - 1.0 / 3.0));
+        assert_eq!(fuzzy_score("A red car", "ox"), None);
+        assert_eq!(fuzzy_score("Fishing boats at dusk", "harbour"), None);
+        assert_eq!(fuzzy_score("Fishing boats at dusk", " "), None);
+    }
+}
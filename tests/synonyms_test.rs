@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image_find::routes::{highlight_search_terms, parse_search_terms, with_synonym_terms};
+    use image_find::synonyms::{load_synonyms, parse_synonyms, synonyms_of};
+
+    #[test]
+    fn test_parse_synonyms() {
+        let synonyms = parse_synonyms(
+            "# Places\nNYC = New York City\n\nnyc = Big Apple\nCar=Automobile=  \n= lonely\n",
+        );
+        assert_eq!(synonyms["nyc"], ["New York City", "Big Apple"]);
+        assert_eq!(synonyms["new york city"], ["NYC"]);
+        assert_eq!(synonyms["big apple"], ["nyc"]);
+        assert_eq!(synonyms["automobile"], ["Car"]);
+        assert!(!synonyms.contains_key("lonely"));
+        assert!(!synonyms.contains_key("# places"));
+    }
+
+    // Loaded synonyms are matched case-insensitively and highlighted along with the term
+    #[test]
+    fn test_loaded_synonyms_expand_terms() {
+        fs::create_dir_all("tests/tmp").unwrap();
+        let path = "tests/tmp/synonyms_test.txt";
+        fs::write(path, "NYC = New York City\n").unwrap();
+        assert_eq!(load_synonyms(path).unwrap(), 2);
+        let _ = fs::remove_file(path);
+
+        assert_eq!(synonyms_of(" nyc "), ["New York City"]);
+        assert!(synonyms_of("Paris").is_empty());
+
+        let terms = with_synonym_terms(parse_search_terms("NYC skyline"));
+        assert_eq!(terms.len(), 3);
+        let result = highlight_search_terms("Skyline of New York City", &terms);
+        assert!(result.contains("<mark style=\"background-color: lightgreen; padding: 1px 2px; border-radius: 2px;\">New York City</mark>"));
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::AtomicBool;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{
+        cache_file_path, get_cache_dir, get_cached_thumbnail, thumbnail_cache_key, verify_cache_dir, CacheVerifySummary,
+    };
+    use image_find::processing::image::generate_thumbnail;
+
+    const ROOT: &str = "tests/tmp/thumbnail_cache_format_test";
+
+    // --thumbnail-cache-format base64 stores the served text; JPEG files from before are still read
+    #[test]
+    fn test_base64_thumbnail_cache() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/thumbnail_cache_format_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/thumbnail_cache_format_test/thumbs",
+            "--full-image-cache", "tests/tmp/thumbnail_cache_format_test/full",
+            "--video-preview-cache", "tests/tmp/thumbnail_cache_format_test/video",
+            "--scan-dir", "tests/tmp/thumbnail_cache_format_test",
+            "--thumbnail-cache-format", "base64",
+            "--memory-cache-entries", "0",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+
+        let generated_path = format!("{}/generated.png", ROOT);
+        RgbImage::from_pixel(400, 300, Rgb([10, 120, 30])).save(&generated_path).unwrap();
+        let thumbnail = generate_thumbnail(&generated_path).expect("Failed to generate thumbnail");
+        let cache_file = cache_file_path(&get_cache_dir(), &thumbnail_cache_key(&generated_path), "jpg");
+        assert_eq!(fs::read_to_string(&cache_file).unwrap(), thumbnail);
+        assert_eq!(get_cached_thumbnail(&thumbnail_cache_key(&generated_path), &generated_path), Some(thumbnail));
+
+        // A thumbnail cached as JPEG bytes before the format was changed
+        let old_path = format!("{}/old.png", ROOT);
+        let mut jpeg = Vec::new();
+        RgbImage::from_pixel(8, 8, Rgb([200, 100, 50]))
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        let old_file = cache_file_path(&get_cache_dir(), &thumbnail_cache_key(&old_path), "jpg");
+        fs::create_dir_all(old_file.parent().unwrap()).unwrap();
+        fs::write(&old_file, &jpeg).unwrap();
+        assert_eq!(get_cached_thumbnail(&thumbnail_cache_key(&old_path), &old_path), Some(BASE64.encode(&jpeg)));
+
+        let summary = verify_cache_dir(&get_cache_dir(), 100, 0, &AtomicBool::new(false));
+        assert_eq!(summary, CacheVerifySummary { checked: 2, corrupt: 0, removed: 0 }, "Both formats verify as intact");
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
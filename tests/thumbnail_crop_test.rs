@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{generate_cache_key, thumbnail_cache_key};
+    use image_find::processing::image::generate_thumbnail;
+
+    // --thumbnail-crop fill center-crops to an exact square cached under its own key
+    #[test]
+    fn test_fill_thumbnails_are_square() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/crop_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/crop_test_thumbs",
+            "--full-image-cache", "tests/tmp/crop_test_full",
+            "--video-preview-cache", "tests/tmp/crop_test_video",
+            "--scan-dir", "tests/data",
+            "--thumbnail-crop", "fill",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let source_path = "tests/tmp/crop_test_wide.png";
+        fs::create_dir_all("tests/tmp").unwrap();
+        RgbImage::from_pixel(300, 100, Rgb([0, 128, 0])).save(source_path).unwrap();
+
+        assert_ne!(thumbnail_cache_key(source_path), generate_cache_key(source_path));
+
+        let thumbnail = generate_thumbnail(source_path).expect("Failed to generate thumbnail");
+        let decoded = image::load_from_memory(&BASE64.decode(thumbnail).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (200, 200));
+
+        let _ = fs::remove_file(source_path);
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{
+        cache_file_path, get_cache_dir, get_cached_thumbnail, thumbnail_cache_key, thumbnail_content_type,
+        thumbnail_exists_in_cache,
+    };
+    use image_find::processing::image::{generate_preview, generate_thumbnail};
+
+    const ROOT: &str = "tests/tmp/thumbnail_format_test";
+
+    // With --thumbnail-format avif, thumbnails are AVIF, cached as "<key>.avif", while previews stay
+    // JPEG; stored as base64 text, the cached AVIF is read back as it was generated
+    #[test]
+    fn test_avif_thumbnails() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/thumbnail_format_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/thumbnail_format_test/thumbs",
+            "--full-image-cache", "tests/tmp/thumbnail_format_test/full",
+            "--video-preview-cache", "tests/tmp/thumbnail_format_test/video",
+            "--scan-dir", "tests/tmp/thumbnail_format_test",
+            "--thumbnail-format", "avif",
+            "--thumbnail-cache-format", "base64",
+            "--memory-cache-entries", "0",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+
+        let photo = "tests/tmp/thumbnail_format_test/photo.png";
+        image::RgbImage::from_fn(640, 480, |x, y| image::Rgb([(x / 3) as u8, (y / 2) as u8, 128]))
+            .save(photo)
+            .unwrap();
+
+        let thumbnail = generate_thumbnail(photo).expect("thumbnail generated");
+        assert_eq!(thumbnail_content_type(&thumbnail), "image/avif");
+        assert_eq!(&BASE64.decode(&thumbnail).unwrap()[4..12], b"ftypavif");
+        let cache_key = thumbnail_cache_key(photo);
+        assert!(cache_file_path(&get_cache_dir(), &cache_key, "avif").is_file());
+        assert!(!cache_file_path(&get_cache_dir(), &cache_key, "jpg").exists());
+        assert!(thumbnail_exists_in_cache(&cache_key));
+        assert_eq!(get_cached_thumbnail(&cache_key, photo).as_deref(), Some(thumbnail.as_str()));
+
+        let preview = generate_preview(photo).expect("preview generated");
+        assert_eq!(thumbnail_content_type(&preview), "image/jpeg");
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+
+    #[test]
+    fn test_thumbnail_content_type() {
+        assert_eq!(thumbnail_content_type(&BASE64.encode(b"\xFF\xD8\xFF\xE0\0\x10JFIF\0\x01\x01")), "image/jpeg");
+        assert_eq!(thumbnail_content_type(&BASE64.encode(b"RIFF\x24\0\0\0WEBPVP8L")), "image/webp");
+        assert_eq!(thumbnail_content_type(&BASE64.encode(b"\0\0\0\x1CftypavifMA1B")), "image/avif");
+        assert_eq!(thumbnail_content_type(""), "image/jpeg");
+    }
+}
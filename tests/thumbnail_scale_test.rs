@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{
+        scaled_thumbnail_cache_key, thumbnail_cache_key, thumbnail_exists_in_cache, thumbnail_scale_factors, thumbnail_scale_for_dpr,
+    };
+    use image_find::processing::image::{generate_scaled_thumbnail, generate_thumbnail};
+
+    fn dimensions(thumbnail: String) -> (u32, u32) {
+        let decoded = image::load_from_memory(&BASE64.decode(thumbnail).unwrap()).unwrap();
+        (decoded.width(), decoded.height())
+    }
+
+    // --thumbnail-scale-factors 2 adds a 400px thumbnail cached next to the base one
+    #[test]
+    fn test_scaled_thumbnails() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/thumbnail_scale_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/thumbnail_scale_test/thumbs",
+            "--full-image-cache", "tests/tmp/thumbnail_scale_test/full",
+            "--video-preview-cache", "tests/tmp/thumbnail_scale_test/video",
+            "--scan-dir", "tests/tmp/thumbnail_scale_test",
+            "--thumbnail-scale-factors", "2",
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        assert_eq!(thumbnail_scale_factors(), [1, 2], "The base density is always included");
+        assert_eq!(thumbnail_scale_for_dpr(1.0), 1);
+        assert_eq!(thumbnail_scale_for_dpr(1.5), 2);
+        assert_eq!(thumbnail_scale_for_dpr(3.0), 2, "Falls back to the highest density");
+
+        let _ = fs::remove_dir_all("tests/tmp/thumbnail_scale_test");
+        fs::create_dir_all("tests/tmp/thumbnail_scale_test").unwrap();
+        let source_path = "tests/tmp/thumbnail_scale_test/wide.png";
+        RgbImage::from_pixel(1000, 500, Rgb([0, 0, 160])).save(source_path).unwrap();
+
+        assert_eq!(scaled_thumbnail_cache_key(source_path, 1), thumbnail_cache_key(source_path));
+        assert_ne!(scaled_thumbnail_cache_key(source_path, 2), thumbnail_cache_key(source_path));
+
+        let scaled = generate_scaled_thumbnail(source_path, 2, false).expect("Failed to generate 2x thumbnail");
+        assert_eq!(dimensions(scaled), (400, 200));
+        assert!(thumbnail_exists_in_cache(&scaled_thumbnail_cache_key(source_path, 2)));
+        assert!(!thumbnail_exists_in_cache(&thumbnail_cache_key(source_path)), "Densities are cached separately");
+
+        let base = generate_thumbnail(source_path).expect("Failed to generate thumbnail");
+        assert_eq!(dimensions(base), (200, 100));
+
+        let _ = fs::remove_dir_all("tests/tmp/thumbnail_scale_test");
+    }
+}
@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+    use image_find::cli::{parse_sharpen_sigma, CliArgs, CLI_ARGS};
+    use image_find::processing::image::{generate_thumbnail, sharpen_thumbnail};
+
+    fn set_cli_args() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/sharpen_test.sqlite",
+            "--thumbnail-cache", "tests/tmp/sharpen_test_thumbs",
+            "--full-image-cache", "tests/tmp/sharpen_test_full",
+            "--video-preview-cache", "tests/tmp/sharpen_test_video",
+            "--scan-dir", "tests/data",
+            "--thumbnail-sharpen",
+            "--thumbnail-sharpen-sigma", "1.0",
+        ]);
+        let _ = CLI_ARGS.set(args);
+    }
+
+    // The unsharp mask steepens edges: the dark side gets darker and the light side lighter
+    #[test]
+    fn test_sharpen_increases_edge_contrast() {
+        set_cli_args();
+
+        let soft_edge = RgbImage::from_fn(40, 10, |x, _| {
+            let value = match x {
+                0..=18 => 100,
+                19 => 117,
+                20 => 133,
+                _ => 150,
+            };
+            Rgb([value, value, value])
+        });
+        let sharpened = sharpen_thumbnail(DynamicImage::ImageRgb8(soft_edge));
+
+        assert!(sharpened.get_pixel(18, 5)[0] < 100, "dark side was not darkened");
+        assert!(sharpened.get_pixel(21, 5)[0] > 150, "light side was not lightened");
+        // Flat areas are left alone
+        assert_eq!(sharpened.get_pixel(5, 5)[0], 100);
+        assert_eq!(sharpened.get_pixel(35, 5)[0], 150);
+    }
+
+    #[test]
+    fn test_sharpened_thumbnail_is_generated() {
+        set_cli_args();
+
+        let source_path = "tests/tmp/sharpen_test_source.png";
+        fs::create_dir_all("tests/tmp").unwrap();
+        RgbImage::from_fn(600, 400, |x, y| Rgb([(x % 256) as u8, (y % 256) as u8, 128]))
+            .save(source_path)
+            .unwrap();
+
+        let thumbnail = generate_thumbnail(source_path).expect("Failed to generate thumbnail");
+        let decoded = image::load_from_memory(&BASE64.decode(thumbnail).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (200, 133));
+
+        let _ = fs::remove_file(source_path);
+    }
+
+    #[test]
+    fn test_sharpen_sigma_must_be_positive() {
+        assert_eq!(parse_sharpen_sigma("0.8"), Ok(0.8));
+        for value in ["0", "-1", "NaN", "inf", "soft"] {
+            assert!(parse_sharpen_sigma(value).is_err(), "'{}' should be rejected", value);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image::{Rgb, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{generate_cache_key, thumbnail_cache_key};
+    use image_find::processing::image::{generate_thumbnail, quality_settings};
+
+    const ROOT: &str = "tests/tmp/thumbnail_size_test";
+
+    // Thumbnails are generated at --thumbnail-size, cached apart from those of the default size,
+    // and a --quality-override size still wins for its directory
+    #[test]
+    fn test_thumbnail_size() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/thumbnail_size_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/thumbnail_size_test/thumbs",
+            "--full-image-cache", "tests/tmp/thumbnail_size_test/full",
+            "--video-preview-cache", "tests/tmp/thumbnail_size_test/video",
+            "--scan-dir", "tests/tmp/thumbnail_size_test",
+            "--thumbnail-size", "320",
+            "--quality-override", "tests/tmp/thumbnail_size_test/docs:thumbnail_size=120",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let parses = |size: &str| {
+            CliArgs::try_parse_from([
+                "image_find", "--db-path", "db.sqlite", "--thumbnail-cache", "thumbs", "--full-image-cache", "full",
+                "--video-preview-cache", "video", "--scan-dir", ROOT, "--thumbnail-size", size,
+            ])
+            .is_ok()
+        };
+        assert!(parses("16"));
+        for size in ["8", "5000", "large"] {
+            assert!(!parses(size), "'{}' should be rejected", size);
+        }
+
+        let photo = "tests/tmp/thumbnail_size_test/photo.png";
+        assert_eq!(quality_settings(photo).thumbnail_size, 320);
+        assert_eq!(quality_settings("tests/tmp/thumbnail_size_test/docs/a.png").thumbnail_size, 120);
+        assert_ne!(thumbnail_cache_key(photo), generate_cache_key(photo), "Thumbnails of the default size are not served");
+
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+        RgbImage::from_pixel(800, 600, Rgb([200, 60, 20])).save(photo).unwrap();
+        let thumbnail = generate_thumbnail(photo).expect("Failed to generate thumbnail");
+        let decoded = image::load_from_memory(&BASE64.decode(thumbnail).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (320, 240));
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use actix_web::{test, web, App};
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{save_thumbnail_to_cache, thumbnail_cache_key};
+    use image_find::routes::api_thumbnail_status;
+
+    const ROOT: &str = "tests/tmp/thumbnail_status_test";
+
+    // Sidecar paths report the thumbnail of their image, also for stem-named "photo.xmp" sidecars
+    #[actix_web::test]
+    async fn test_thumbnail_status_resolves_sidecars() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/thumbnail_status_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/thumbnail_status_test/thumbs",
+            "--full-image-cache", "tests/tmp/thumbnail_status_test/full",
+            "--video-preview-cache", "tests/tmp/thumbnail_status_test/video",
+            "--scan-dir", "tests/tmp/thumbnail_status_test/lib",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        let lib = format!("{}/lib", ROOT);
+        fs::create_dir_all(&lib).unwrap();
+        for image in ["stem.png", "classic.jpg", "uncached.jpg"] {
+            fs::write(format!("{}/{}", lib, image), b"image").unwrap();
+        }
+        for image in ["stem.png", "classic.jpg"] {
+            save_thumbnail_to_cache(&thumbnail_cache_key(&format!("{}/{}", lib, image)), b"thumbnail").unwrap();
+        }
+
+        let app = test::init_service(
+            App::new().route("/api/thumbnail-status", web::post().to(api_thumbnail_status)),
+        )
+        .await;
+        let request = test::TestRequest::post()
+            .uri("/api/thumbnail-status")
+            .set_json(serde_json::json!({ "paths": [
+                format!("{}/stem.xmp", lib),
+                format!("{}/classic.jpg.xmp", lib),
+                format!("{}/uncached.jpg", lib),
+                format!("{}/../secret.jpg", lib),
+            ] }))
+            .to_request();
+        let status: BTreeMap<String, bool> = test::call_and_read_body_json(&app, request).await;
+
+        assert_eq!(
+            status,
+            BTreeMap::from([
+                (format!("{}/classic.jpg", lib), true),
+                (format!("{}/stem.png", lib), true),
+                (format!("{}/uncached.jpg", lib), false),
+            ])
+        );
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,184 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use clap::Parser;
+    use image::{DynamicImage, RgbImage};
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::image::generate_thumbnail;
+    use image_find::processing::jpeg::encode_baseline;
+    use image_find::processing::tiff::decode_tiff_rgb;
+    use tiff::encoder::{colortype, TiffEncoder};
+    use tiff::tags::Tag;
+
+    // Horizontal red and vertical green gradients, so downsampled blocks can be checked by position
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::with_capacity((width * height * 3) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[(x * 255 / width) as u8, (y * 255 / height) as u8, 64]);
+            }
+        }
+        data
+    }
+
+    // Images above the pixel budget are read strip by strip and box-downsampled
+    #[test]
+    fn test_large_tiff_is_downsampled_by_chunks() {
+        let path = "tests/tmp/tiff_test_strips.tif";
+        fs::create_dir_all("tests/tmp").unwrap();
+        let (width, height) = (640, 480);
+        {
+            let mut encoder = TiffEncoder::new(File::create(path).unwrap()).unwrap();
+            let mut image = encoder.new_image::<colortype::RGB8>(width, height).unwrap();
+            image.rows_per_strip(16).unwrap();
+            image.write_data(&gradient(width, height)).unwrap();
+        }
+
+        let (full, _) = decode_tiff_rgb(path, 100, false, u64::MAX).expect("Full decode failed");
+        assert_eq!(full.dimensions(), (width, height));
+
+        // 640 / (2 * 100) = 3 pixel blocks
+        let (streamed, _) = decode_tiff_rgb(path, 100, false, 0).expect("Chunked decode failed");
+        assert_eq!(streamed.dimensions(), (214, 160));
+        for (x, y) in [(0, 0), (100, 80), (200, 150)] {
+            let block = streamed.get_pixel(x, y);
+            let source = full.get_pixel(x * 3 + 1, y * 3 + 1);
+            for channel in 0..3 {
+                assert!(
+                    block[channel].abs_diff(source[channel]) <= 2,
+                    "Block ({}, {}) is {:?}, expected about {:?}", x, y, block, source
+                );
+            }
+        }
+
+        let _ = fs::remove_file(path);
+    }
+
+    // The smallest reduced-resolution IFD that still covers the target size is decoded instead
+    #[test]
+    fn test_tiff_overview_is_preferred() {
+        let path = "tests/tmp/tiff_test_pyramid.tif";
+        fs::create_dir_all("tests/tmp").unwrap();
+        {
+            let mut encoder = TiffEncoder::new(File::create(path).unwrap()).unwrap();
+            let red: Vec<u8> = [200u8, 0, 0].repeat(1600 * 1200);
+            encoder.write_image::<colortype::RGB8>(1600, 1200, &red).unwrap();
+
+            let green: Vec<u8> = [0u8, 200, 0].repeat(400 * 300);
+            let mut overview = encoder.new_image::<colortype::RGB8>(400, 300).unwrap();
+            overview.encoder().write_tag(Tag::NewSubfileType, 1u32).unwrap();
+            overview.write_data(&green).unwrap();
+
+            // A second page of the same shape is not an overview
+            let blue: Vec<u8> = [0u8, 0, 200].repeat(320 * 240);
+            encoder.write_image::<colortype::RGB8>(320, 240, &blue).unwrap();
+        }
+
+        let (thumbnail_source, _) = decode_tiff_rgb(path, 200, false, u64::MAX).expect("Decode failed");
+        assert_eq!(thumbnail_source.dimensions(), (400, 300));
+        assert_eq!(thumbnail_source.get_pixel(10, 10).0, [0, 200, 0]);
+
+        // Too small for the requested size, so the full resolution image is used
+        let (preview_source, _) = decode_tiff_rgb(path, 1000, false, u64::MAX).expect("Decode failed");
+        assert_eq!(preview_source.dimensions(), (1600, 1200));
+        assert_eq!(preview_source.get_pixel(10, 10).0, [200, 0, 0]);
+
+        let _ = fs::remove_file(path);
+    }
+
+    // Single-strip little-endian TIFF whose strip is a JPEG stream: compression 7 ("new style",
+    // YCbCr as written by JPEG encoders) or 6 ("old style", pointed to by JPEGInterchangeFormat
+    // as in many DNG previews and scanner output)
+    fn write_jpeg_tiff(path: &str, width: u32, height: u32, jpeg: &[u8], compression: u16) {
+        let mut entries: Vec<(u16, u16, u32, u32)> = vec![
+            (256, 4, 1, width),
+            (257, 4, 1, height),
+            // Offset of the three BitsPerSample values, written after the IFD
+            (258, 3, 3, 0),
+            (259, 3, 1, u32::from(compression)),
+            (262, 3, 1, 6),
+            (273, 4, 1, 0),
+            (277, 3, 1, 3),
+            (278, 4, 1, height),
+            (279, 4, 1, jpeg.len() as u32),
+            (530, 3, 2, 1 | (1 << 16)),
+        ];
+        if compression == 6 {
+            entries.push((513, 4, 1, 0));
+            entries.push((514, 4, 1, jpeg.len() as u32));
+        }
+        entries.sort();
+        let bits_offset = 8 + 2 + entries.len() as u32 * 12 + 4;
+        let data_offset = bits_offset + 6;
+        for entry in entries.iter_mut() {
+            match entry.0 {
+                258 => entry.3 = bits_offset,
+                273 | 513 => entry.3 = data_offset,
+                _ => {}
+            }
+        }
+
+        let mut bytes = b"II*\0".to_vec();
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, field_type, count, value) in entries {
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&field_type.to_le_bytes());
+            bytes.extend_from_slice(&count.to_le_bytes());
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        for bits in [8u16; 3] {
+            bytes.extend_from_slice(&bits.to_le_bytes());
+        }
+        bytes.extend_from_slice(jpeg);
+        fs::write(path, bytes).unwrap();
+    }
+
+    // JPEG-compressed TIFFs of both styles decode to the colors of the embedded JPEG
+    #[test]
+    fn test_jpeg_compressed_tiff() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/tiff_test_jpeg/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/tiff_test_jpeg/thumbs",
+            "--full-image-cache", "tests/tmp/tiff_test_jpeg/full",
+            "--video-preview-cache", "tests/tmp/tiff_test_jpeg/video",
+            "--scan-dir", "tests/tmp/tiff_test_jpeg",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all("tests/tmp/tiff_test_jpeg");
+        fs::create_dir_all("tests/tmp/tiff_test_jpeg").unwrap();
+        let (width, height) = (320, 240);
+        let source = DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, _| {
+            if x < width / 2 { image::Rgb([200, 40, 40]) } else { image::Rgb([40, 40, 200]) }
+        }));
+        let jpeg = encode_baseline(&source, 90).unwrap();
+
+        for compression in [7, 6] {
+            let path = format!("tests/tmp/tiff_test_jpeg/compression_{}.tif", compression);
+            write_jpeg_tiff(&path, width, height, &jpeg, compression);
+
+            let (decoded, _) = decode_tiff_rgb(&path, 200, false, u64::MAX)
+                .unwrap_or_else(|e| panic!("Decoding compression {} failed: {}", compression, e));
+            assert_eq!(decoded.dimensions(), (width, height));
+            for (x, expected) in [(40, [200, 40, 40]), (280, [40, 40, 200])] {
+                let pixel = decoded.get_pixel(x, 120).0;
+                for channel in 0..3 {
+                    assert!(
+                        pixel[channel].abs_diff(expected[channel]) <= 8,
+                        "Compression {}: pixel at {} is {:?}, expected about {:?}", compression, x, pixel, expected
+                    );
+                }
+            }
+
+            let thumbnail = generate_thumbnail(&path).unwrap_or_else(|| panic!("No thumbnail for compression {}", compression));
+            let thumbnail = image::load_from_memory(&BASE64.decode(thumbnail).unwrap()).unwrap();
+            assert_eq!((thumbnail.width(), thumbnail.height()), (200, 150));
+        }
+
+        let _ = fs::remove_dir_all("tests/tmp/tiff_test_jpeg");
+    }
+}
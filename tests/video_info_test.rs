@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use image_find::processing::video::{parse_ffprobe_output, VideoInfo};
+
+    #[test]
+    fn test_parse_ffprobe_output() {
+        let output = br#"{"programs": [], "streams": [
+            {"codec_name": "hevc", "width": 3840, "height": 2160, "r_frame_rate": "30000/1001", "avg_frame_rate": "30000/1001"}
+        ]}"#;
+        let info = parse_ffprobe_output(output).unwrap();
+        assert_eq!(info.codec.as_deref(), Some("hevc"));
+        assert_eq!((info.width, info.height), (Some(3840), Some(2160)));
+        assert_eq!(
+            info.key_values(),
+            [
+                ("video:Codec", "hevc".to_string()),
+                ("video:Width", "3840".to_string()),
+                ("video:Height", "2160".to_string()),
+                ("video:FrameRate", "29.97".to_string()),
+            ]
+        );
+    }
+
+    // Unknown frame rates fall back to r_frame_rate; missing properties stay None
+    #[test]
+    fn test_parse_ffprobe_output_partial() {
+        let output = br#"{"streams": [{"codec_name": "h264", "r_frame_rate": "25/1", "avg_frame_rate": "0/0"}]}"#;
+        assert_eq!(
+            parse_ffprobe_output(output),
+            Some(VideoInfo { codec: Some("h264".to_string()), width: None, height: None, fps: Some(25.0) })
+        );
+        assert_eq!(parse_ffprobe_output(br#"{"streams": []}"#), None, "No video stream");
+        assert_eq!(parse_ffprobe_output(b"not json"), None);
+    }
+}
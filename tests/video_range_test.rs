@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::routes::serve_video;
+
+    const ROOT: &str = "tests/tmp/video_range_test";
+
+    // Video previews answer Range requests with the requested bytes, so players can seek
+    #[actix_web::test]
+    async fn test_serve_video_range_request() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/video_range_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/video_range_test/thumbs",
+            "--full-image-cache", "tests/tmp/video_range_test/full",
+            "--video-preview-cache", "tests/tmp/video_range_test/video",
+            "--scan-dir", ROOT,
+        ]);
+        let _ = CLI_ARGS.set(args);
+
+        let video_dir = format!("{}/video", ROOT);
+        fs::create_dir_all(&video_dir).unwrap();
+        let content: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        fs::write(format!("{}/clip_480p.mp4", video_dir), &content).unwrap();
+
+        let app = test::init_service(App::new().route("/video/{path:.*}", web::get().to(serve_video))).await;
+
+        let request = test::TestRequest::get()
+            .uri("/video/photos/clip.mov")
+            .insert_header((header::RANGE, "bytes=100-199"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get(header::CONTENT_RANGE).unwrap(), "bytes 100-199/4096");
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "video/mp4");
+        let body = test::read_body(response).await;
+        assert_eq!(&body[..], &content[100..200]);
+
+        // Without a Range header the whole preview is sent
+        let request = test::TestRequest::get().uri("/video/photos/clip.mov").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await.len(), content.len());
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use clap::Parser;
+    use image_find::cli::{CliArgs, CLI_ARGS};
+    use image_find::processing::cache::{cache_file_path, get_cache_dir, thumbnail_cache_key, thumbnail_content_type};
+    use image_find::processing::image::{generate_thumbnail, THUMBNAIL_QUALITY};
+    use image_find::processing::jpeg::encode_jpeg;
+
+    const ROOT: &str = "tests/tmp/webp_thumbnail_test";
+
+    // With --thumbnail-format webp, thumbnails are lossless WebP cached as "<key>.webp". On a
+    // photo-like image that makes them larger than the JPEG of the same pixels, the trade-off
+    // documented for the option
+    #[test]
+    fn test_webp_thumbnails_are_lossless() {
+        let args = CliArgs::parse_from([
+            "image_find",
+            "--db-path", "tests/tmp/webp_thumbnail_test/db.sqlite",
+            "--thumbnail-cache", "tests/tmp/webp_thumbnail_test/thumbs",
+            "--full-image-cache", "tests/tmp/webp_thumbnail_test/full",
+            "--video-preview-cache", "tests/tmp/webp_thumbnail_test/video",
+            "--scan-dir", ROOT,
+            "--thumbnail-format", "webp",
+        ]);
+        let _ = CLI_ARGS.set(args);
+        let _ = fs::remove_dir_all(ROOT);
+        fs::create_dir_all(ROOT).unwrap();
+
+        // Gradients with some noise, which compress like a photo rather than like flat colors
+        let photo = "tests/tmp/webp_thumbnail_test/photo.png";
+        image::RgbImage::from_fn(640, 480, |x, y| {
+            let noise = ((x * 7919 + y * 104729) % 23) as u8;
+            image::Rgb([(x / 3) as u8 ^ noise, (y / 2) as u8, 128u8.wrapping_add(noise)])
+        })
+        .save(photo)
+        .unwrap();
+
+        let thumbnail = generate_thumbnail(photo).expect("thumbnail generated");
+        assert_eq!(thumbnail_content_type(&thumbnail), "image/webp");
+        let webp = BASE64.decode(&thumbnail).unwrap();
+        assert_eq!(&webp[12..16], b"VP8L", "Lossless WebP bitstream");
+        assert!(cache_file_path(&get_cache_dir(), &thumbnail_cache_key(photo), "webp").is_file());
+
+        let jpeg = encode_jpeg(&image::load_from_memory(&webp).unwrap(), THUMBNAIL_QUALITY).unwrap();
+        assert!(webp.len() > jpeg.len(), "WebP {} bytes, JPEG {} bytes", webp.len(), jpeg.len());
+
+        let _ = fs::remove_dir_all(ROOT);
+    }
+}